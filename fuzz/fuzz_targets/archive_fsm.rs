@@ -0,0 +1,43 @@
+#![no_main]
+
+//! Drives the archive state machine directly, the first byte picking how
+//! much is read at a time, so records get split across reads in every
+//! possible way.
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::fsm::{ArchiveFsm, FsmResult};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&read_len, data)) = data.split_first() else {
+        return;
+    };
+    let read_len = read_len as usize + 1;
+
+    let mut fsm = ArchiveFsm::new(data.len() as u64);
+    loop {
+        if let Some(offset) = fsm.wants_read() {
+            let Some(slice) = data.get(offset as usize..) else {
+                return;
+            };
+            let len = slice.len().min(fsm.space().len()).min(read_len);
+            if len == 0 {
+                // past the end of the input, which is what the I/O wrappers
+                // report as an error
+                return;
+            }
+            fsm.space()[..len].copy_from_slice(&slice[..len]);
+            fsm.fill(len);
+        }
+
+        fsm = match fsm.process() {
+            Ok(FsmResult::Continue(fsm)) => fsm,
+            Ok(FsmResult::Done(archive)) => {
+                for entry in archive.entries() {
+                    let _ = entry.sanitized_name();
+                }
+                return;
+            }
+            Err(_) => return,
+        }
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+//! Archives that are valid apart from their quirks, see
+//! [rc_zip::corpus::ZipBuilder::from_bytes]: they should always be read,
+//! and so should all of their entries, save for the ones whose CRC-32 is
+//! wrong on purpose.
+
+use std::io::Read;
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::corpus::ZipBuilder;
+use rc_zip_sync::ReadZip;
+
+fuzz_target!(|data: &[u8]| {
+    let bytes = ZipBuilder::from_bytes(data).build();
+    let archive = bytes.read_zip().unwrap();
+    for entry in archive.entries() {
+        let mut contents = Vec::new();
+        let _ = entry.reader().read_to_end(&mut contents);
+    }
+});
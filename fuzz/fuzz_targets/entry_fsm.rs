@@ -0,0 +1,46 @@
+#![no_main]
+
+//! Drives the entry state machine without a central directory entry, like
+//! streaming readers do: a local header, then data and maybe a data
+//! descriptor. The first byte picks how much is read at a time, the second
+//! how much room there is for output.
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::fsm::{DecompressionLimits, EntryFsm, FsmResult};
+
+fuzz_target!(|data: &[u8]| {
+    let [read_len, out_len, data @ ..] = data else {
+        return;
+    };
+    let read_len = *read_len as usize + 1;
+    let mut out = vec![0u8; *out_len as usize * 256 + 1];
+
+    // small inputs can claim to decompress to a lot
+    let limits = DecompressionLimits {
+        max_output: Some(16 * 1024 * 1024),
+        ..Default::default()
+    };
+    let mut fsm = EntryFsm::new(None, None).with_limits(limits);
+    let mut pos = 0;
+    loop {
+        let mut filled = 0;
+        if fsm.wants_read() {
+            let len = (data.len() - pos).min(fsm.space().len()).min(read_len);
+            fsm.space()[..len].copy_from_slice(&data[pos..pos + len]);
+            fsm.fill(len);
+            pos += len;
+            filled = len;
+        }
+
+        fsm = match fsm.process(&mut out) {
+            Ok(FsmResult::Continue((fsm, outcome))) => {
+                if filled == 0 && outcome.bytes_read == 0 && outcome.bytes_written == 0 {
+                    // no progress, which the I/O wrappers report as an error
+                    return;
+                }
+                fsm
+            }
+            Ok(FsmResult::Done(_)) | Err(_) => return,
+        }
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+//! A central directory record, and the entry made from it. The first 8
+//! bytes are the global offset, which is negative for archives that were
+//! cut out of a larger file.
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::{
+    encoding::{Encoding, EncodingSource, NameDecoding},
+    parse::CentralDirectoryFileHeader,
+};
+use winnow::{Parser, Partial};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((global_offset, data)) = data.split_first_chunk::<8>() else {
+        return;
+    };
+    let global_offset = i64::from_le_bytes(*global_offset);
+
+    let Ok((_, header)) = CentralDirectoryFileHeader::parser.parse_peek(Partial::new(data)) else {
+        return;
+    };
+    let _ = header.record_len();
+    let decoding = NameDecoding {
+        encoding: Encoding::Cp437,
+        source: EncodingSource::Fallback,
+        confident: false,
+    };
+    let _ = header.as_entry(decoding, global_offset);
+});
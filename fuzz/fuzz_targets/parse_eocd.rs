@@ -0,0 +1,17 @@
+#![no_main]
+
+//! The end of central directory record search, and the zip64 records it
+//! may lead to.
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::parse::{
+    EndOfCentralDirectory64Locator, EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord,
+};
+use winnow::{Parser, Partial};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = EndOfCentralDirectoryRecord::find_in_block(data);
+    let _ = EndOfCentralDirectoryRecord::parser.parse_peek(Partial::new(data));
+    let _ = EndOfCentralDirectory64Locator::parser.parse_peek(Partial::new(data));
+    let _ = EndOfCentralDirectory64Record::parser.parse_peek(Partial::new(data));
+});
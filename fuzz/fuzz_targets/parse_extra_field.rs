@@ -0,0 +1,22 @@
+#![no_main]
+
+//! Extra fields, with every zip64 field expected or none of them: the first
+//! byte picks which.
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::parse::{ExtraFieldSettings, ExtraFields};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&zip64, data)) = data.split_first() else {
+        return;
+    };
+    let masked = if zip64 & 1 != 0 { u32::MAX } else { 0 };
+    let settings = ExtraFieldSettings {
+        uncompressed_size_u32: masked,
+        compressed_size_u32: masked,
+        header_offset_u32: masked,
+    };
+    for field in ExtraFields::new(data, settings) {
+        let _ = field;
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+//! A local file header and the entry made from it, or a data descriptor.
+
+use libfuzzer_sys::fuzz_target;
+use rc_zip::parse::{DataDescriptorRecord, LocalFileHeader};
+use winnow::{Parser, Partial};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, header)) = LocalFileHeader::parser.parse_peek(Partial::new(data)) {
+        let _ = header.as_entry();
+    }
+    for is_zip64 in [false, true] {
+        let _ = DataDescriptorRecord::mk_parser(is_zip64).parse_peek(Partial::new(data));
+    }
+});
@@ -0,0 +1,416 @@
+//! A read-only [FUSE](https://www.kernel.org/doc/html/latest/filesystems/fuse.html)
+//! filesystem for zip archives, built on top of
+//! [rc-zip-sync](https://crates.io/crates/rc-zip-sync).
+//!
+//! [ZipFs] implements [fuser::Filesystem], and can be mounted with
+//! [fuser::mount2], which is what the `rc-zip-fuse` binary does.
+
+#![warn(missing_docs)]
+
+use fuser::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+    FUSE_ROOT_ID,
+};
+use lru::LruCache;
+use rc_zip_sync::{
+    rc_zip::parse::{Entry, EntryKind},
+    ArchiveHandle, EntryHandle, HasCursor,
+};
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    io::{self, Read},
+    num::NonZeroUsize,
+    os::unix::ffi::OsStrExt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// How long the kernel may cache attributes and lookups for: archives are
+/// mounted read-only, so nothing changes.
+const TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How many entries may have a reader kept open, to continue from where
+/// the last read stopped.
+const LIVE_READERS: usize = 16;
+
+/// A zip archive as a read-only filesystem.
+///
+/// The tree is built from the entries' [safe paths](Entry::safe_path), so
+/// entries that don't have one are left out, and directories that only
+/// exist as part of other entries' paths are made up. When an entry's path
+/// is taken by an entry of another kind (a file where a directory is
+/// needed, or the other way around), the entry that comes first in the
+/// central directory wins, and the others are left out. Otherwise, later
+/// entries replace earlier ones with the same path.
+///
+/// Entries are decompressed when they're read, in chunks, which are kept
+/// in an LRU cache: see [Self::with_cache_size]. Compressed data can't be
+/// read from the middle, so reading an entry from the start, like most
+/// programs do, is much faster than jumping around it. Reading a chunk
+/// continues with the entry's last reader if it hasn't gone past that
+/// chunk yet, or else starts over from the beginning of the entry.
+pub struct ZipFs<'a, F> {
+    nodes: Vec<Node<'a, F>>,
+    chunk_size: usize,
+    chunks: LruCache<(u64, u64), Arc<[u8]>>,
+    readers: LruCache<u64, LiveReader<'a>>,
+    mounted_at: SystemTime,
+}
+
+struct Node<'a, F> {
+    parent: u64,
+    kind: FileType,
+    /// None for directories that aren't in the archive
+    entry: Option<EntryHandle<'a, F>>,
+    /// inode and type of each child, by name
+    children: BTreeMap<String, (u64, FileType)>,
+}
+
+/// A reader that's positioned at the start of chunk `next`.
+struct LiveReader<'a> {
+    reader: Box<dyn Read + 'a>,
+    next: u64,
+}
+
+impl<'a, F> ZipFs<'a, F>
+where
+    F: HasCursor,
+{
+    /// Default size of the chunks entries are decompressed in.
+    pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+    /// Default size of the cache of decompressed chunks.
+    pub const DEFAULT_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+    /// Builds the tree of entries of `archive`.
+    pub fn new(archive: &'a ArchiveHandle<'_, F>) -> Self {
+        let mut fs = Self {
+            nodes: vec![Node::new(FUSE_ROOT_ID, FileType::Directory)],
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            chunks: LruCache::new(NonZeroUsize::MIN),
+            readers: LruCache::new(NonZeroUsize::new(LIVE_READERS).unwrap()),
+            mounted_at: SystemTime::now(),
+        };
+        fs = fs.with_cache_size(Self::DEFAULT_CACHE_SIZE);
+
+        'entries: for entry in archive.entries() {
+            let Some(path) = entry.safe_path() else {
+                continue;
+            };
+            let mut components = path.iter().filter_map(OsStr::to_str).peekable();
+            let mut parent = FUSE_ROOT_ID;
+            while let Some(name) = components.next() {
+                let kind = match entry.kind() {
+                    _ if components.peek().is_some() => FileType::Directory,
+                    EntryKind::Directory => FileType::Directory,
+                    EntryKind::File => FileType::RegularFile,
+                    EntryKind::Symlink => FileType::Symlink,
+                };
+                let is_last = components.peek().is_none();
+
+                let ino = match fs.nodes[slot(parent)].children.get(name) {
+                    Some(&(_, existing)) if existing != kind => continue 'entries,
+                    Some(&(ino, _)) => ino,
+                    None => {
+                        fs.nodes.push(Node::new(parent, kind));
+                        let ino = fs.nodes.len() as u64;
+                        let children = &mut fs.nodes[slot(parent)].children;
+                        children.insert(name.into(), (ino, kind));
+                        ino
+                    }
+                };
+                if is_last {
+                    fs.nodes[slot(ino)].entry = Some(entry);
+                    break;
+                }
+                parent = ino;
+            }
+        }
+        fs
+    }
+
+    /// Sets how many bytes of decompressed data are kept in memory, as
+    /// chunks of [Self::with_chunk_size] bytes. Defaults to
+    /// [Self::DEFAULT_CACHE_SIZE].
+    pub fn with_cache_size(mut self, cache_size: usize) -> Self {
+        let chunks = NonZeroUsize::new(cache_size / self.chunk_size).unwrap_or(NonZeroUsize::MIN);
+        self.chunks.resize(chunks);
+        self
+    }
+
+    /// Sets the size of the chunks entries are decompressed in, keeping the
+    /// cache size the same. Defaults to [Self::DEFAULT_CHUNK_SIZE].
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        let cache_size = self.chunks.cap().get() * self.chunk_size;
+        self.chunk_size = chunk_size.max(1);
+        self.chunks.clear();
+        self.readers.clear();
+        self.with_cache_size(cache_size)
+    }
+
+    /// Finds the inode of `name` in directory `parent`.
+    pub fn find(&self, parent: u64, name: &str) -> Option<u64> {
+        self.node(parent)?.children.get(name).map(|&(ino, _)| ino)
+    }
+
+    /// Finds the inode of `path`, relative to the root of the archive.
+    pub fn find_path(&self, path: &str) -> Option<u64> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(FUSE_ROOT_ID, |parent, name| self.find(parent, name))
+    }
+
+    /// Returns the entry inode `ino` was made from, if it's in the archive.
+    pub fn entry(&self, ino: u64) -> Option<&Entry> {
+        self.node(ino)?.entry.as_deref()
+    }
+
+    /// Returns the name, inode and type of the children of directory `ino`,
+    /// sorted by name.
+    pub fn children(&self, ino: u64) -> Option<impl Iterator<Item = (&str, u64, FileType)>> {
+        let node = self.node(ino)?;
+        Some(
+            node.children
+                .iter()
+                .map(|(name, &(ino, kind))| (name.as_str(), ino, kind)),
+        )
+    }
+
+    /// Reads up to `size` bytes of the entry behind inode `ino`, starting
+    /// at `offset`. Fewer bytes are returned at the end of the entry.
+    pub fn read_at(&mut self, ino: u64, offset: u64, size: u32) -> io::Result<Vec<u8>> {
+        let len = match self.node(ino) {
+            None => return Err(errno(libc::ENOENT)),
+            Some(node) if node.kind == FileType::Directory => return Err(errno(libc::EISDIR)),
+            Some(node) => node
+                .entry
+                .as_ref()
+                .map_or(0, |entry| entry.uncompressed_size),
+        };
+        let end = len.min(offset.saturating_add(size.into()));
+
+        let mut data = Vec::with_capacity(end.saturating_sub(offset) as usize);
+        let mut pos = offset;
+        while pos < end {
+            let chunk_size = self.chunk_size as u64;
+            let chunk = self.chunk(ino, pos / chunk_size)?;
+            let start = (pos % chunk_size) as usize;
+            if start >= chunk.len() {
+                // the entry is shorter than the central directory says
+                break;
+            }
+            let n = (chunk.len() - start).min((end - pos) as usize);
+            data.extend_from_slice(&chunk[start..start + n]);
+            pos += n as u64;
+        }
+        Ok(data)
+    }
+
+    /// Returns chunk `index` of the entry behind inode `ino`, which is
+    /// shorter than the chunk size at the end of the entry, and empty past
+    /// it.
+    fn chunk(&mut self, ino: u64, index: u64) -> io::Result<Arc<[u8]>> {
+        if let Some(chunk) = self.chunks.get(&(ino, index)) {
+            return Ok(chunk.clone());
+        }
+
+        let Some(entry) = self.nodes[slot(ino)].entry.as_ref() else {
+            return Ok(Arc::from([]));
+        };
+        let mut live = match self.readers.pop(&ino) {
+            Some(live) if live.next <= index => live,
+            _ => LiveReader {
+                reader: Box::new(entry.reader()),
+                next: 0,
+            },
+        };
+        loop {
+            let mut data = Vec::with_capacity(self.chunk_size);
+            live.reader
+                .by_ref()
+                .take(self.chunk_size as u64)
+                .read_to_end(&mut data)?;
+            let full = data.len() == self.chunk_size;
+            let chunk: Arc<[u8]> = data.into();
+            self.chunks.put((ino, live.next), chunk.clone());
+
+            live.next += 1;
+            if live.next > index {
+                if full {
+                    self.readers.put(ino, live);
+                }
+                return Ok(chunk);
+            }
+            if !full {
+                return Ok(Arc::from([]));
+            }
+        }
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node<'a, F>> {
+        self.nodes
+            .get(usize::try_from(ino.checked_sub(FUSE_ROOT_ID)?).ok()?)
+    }
+
+    fn attr(&self, ino: u64, req: &Request<'_>) -> Option<FileAttr> {
+        let node = self.node(ino)?;
+        let (size, perm, time) = match &node.entry {
+            Some(entry) => {
+                let size = match node.kind {
+                    FileType::Directory => 0,
+                    _ => entry.uncompressed_size,
+                };
+                (size, entry.mode.permissions(), entry.modified.into())
+            }
+            None => (0, 0, self.mounted_at),
+        };
+        // entries made on systems without permissions have none
+        let perm = match (perm, node.kind) {
+            (0, FileType::Directory) => 0o755,
+            (0, _) => 0o644,
+            (perm, _) => perm,
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind: node.kind,
+            perm: perm as u16,
+            nlink: match node.kind {
+                FileType::Directory => 2,
+                _ => 1,
+            },
+            // owners from the system the archive was made on don't mean
+            // anything here
+            uid: req.uid(),
+            gid: req.gid(),
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        })
+    }
+}
+
+impl<F> Node<'_, F> {
+    fn new(parent: u64, kind: FileType) -> Self {
+        Node {
+            parent,
+            kind,
+            entry: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+impl<F> Filesystem for ZipFs<'_, F>
+where
+    F: HasCursor,
+{
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let ino = name.to_str().and_then(|name| self.find(parent, name));
+        match ino.and_then(|ino| self.attr(ino, req)) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino, req) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        let Some(Node {
+            kind: FileType::Symlink,
+            entry: Some(entry),
+            ..
+        }) = self.node(ino)
+        else {
+            return reply.error(libc::EINVAL);
+        };
+        let len = entry.uncompressed_size.min(libc::PATH_MAX as u64) as u32;
+        let target = match self.read_at(ino, 0, len) {
+            Ok(target) => target,
+            Err(e) => return reply.error(e.raw_os_error().unwrap_or(libc::EIO)),
+        };
+
+        // the same rules as when extracting: links may not point out of
+        // the archive
+        let entry = self.entry(ino).expect("symlinks have an entry");
+        match std::str::from_utf8(&target)
+            .ok()
+            .and_then(|target| entry.safe_symlink_target(target))
+        {
+            Some(target) => reply.data(target.as_os_str().as_bytes()),
+            None => reply.error(libc::EPERM),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Ok(offset) = u64::try_from(offset) else {
+            return reply.error(libc::EINVAL);
+        };
+        match self.read_at(ino, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e.raw_os_error().unwrap_or(libc::EIO)),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(node) = self.node(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+        let Some(children) = self
+            .children(ino)
+            .filter(|_| node.kind == FileType::Directory)
+        else {
+            return reply.error(libc::ENOTDIR);
+        };
+
+        let dots = [
+            (".", ino, FileType::Directory),
+            ("..", node.parent, FileType::Directory),
+        ];
+        let offset = usize::try_from(offset).unwrap_or(0);
+        for (i, (name, ino, kind)) in dots.into_iter().chain(children).enumerate().skip(offset) {
+            // the offset of the next entry
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+fn slot(ino: u64) -> usize {
+    (ino - FUSE_ROOT_ID) as usize
+}
+
+fn errno(errno: i32) -> io::Error {
+    io::Error::from_raw_os_error(errno)
+}
@@ -0,0 +1,38 @@
+use clap::Parser;
+use fuser::MountOption;
+use rc_zip_fuse::ZipFs;
+use rc_zip_sync::ReadZip;
+
+use std::{error::Error, fs::File, path::PathBuf};
+
+/// Mounts a zip archive as a read-only filesystem, until it's unmounted
+/// with `fusermount -u`.
+#[derive(Parser)]
+struct Cli {
+    /// The archive to mount
+    zipfile: PathBuf,
+
+    /// Where to mount it, an existing directory
+    mountpoint: PathBuf,
+
+    /// How much decompressed data to keep in memory, in MiB
+    #[arg(long, default_value_t = 64)]
+    cache_size: usize,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    let file = File::open(&cli.zipfile)?;
+    let archive = file.read_zip()?;
+    let fs = ZipFs::new(&archive).with_cache_size(cli.cache_size * 1024 * 1024);
+
+    let options = [
+        MountOption::RO,
+        MountOption::FSName(cli.zipfile.display().to_string()),
+        MountOption::Subtype("rc-zip".into()),
+        MountOption::DefaultPermissions,
+    ];
+    fuser::mount2(fs, &cli.mountpoint, &options)?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use fuser::{FileType, FUSE_ROOT_ID};
+use rc_zip::{
+    corpus::{zips_dir, ZipBuilder, ZipEntry},
+    parse::Method,
+};
+use rc_zip_fuse::ZipFs;
+use rc_zip_sync::ReadZip;
+
+use std::fs::File;
+
+#[test]
+fn tree() {
+    let bytes = ZipBuilder::new()
+        .with_entry(ZipEntry::new("a/b.txt", "b"))
+        .with_entry(ZipEntry::new("a/c/d.txt", "d"))
+        .with_entry(ZipEntry::new("./a//e.txt", "e"))
+        .with_entry(ZipEntry::new("../evil.txt", "evil"))
+        .with_entry(ZipEntry::new("/etc/passwd", "evil"))
+        .with_entry(ZipEntry::new("top.txt", "first"))
+        .with_entry(ZipEntry::new("top.txt", "second"))
+        // a file where there's already a directory
+        .with_entry(ZipEntry::new("a", "not a directory"))
+        .build();
+    let archive = bytes.read_zip().unwrap();
+    let mut fs = ZipFs::new(&archive);
+
+    let names = |fs: &ZipFs<_>, ino| {
+        fs.children(ino)
+            .unwrap()
+            .map(|(name, _, kind)| (name.to_owned(), kind))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(&fs, FUSE_ROOT_ID),
+        [
+            ("a".to_owned(), FileType::Directory),
+            ("top.txt".to_owned(), FileType::RegularFile),
+        ]
+    );
+    let a = fs.find_path("a").unwrap();
+    assert_eq!(
+        names(&fs, a),
+        [
+            ("b.txt".to_owned(), FileType::RegularFile),
+            ("c".to_owned(), FileType::Directory),
+            ("e.txt".to_owned(), FileType::RegularFile),
+        ]
+    );
+    // made up from the path of a/c/d.txt
+    let c = fs.find(a, "c").unwrap();
+    assert!(fs.entry(c).is_none());
+    assert_eq!(fs.find_path("a/c/d.txt"), fs.find(c, "d.txt"));
+    assert!(fs.find_path("evil.txt").is_none());
+    assert!(fs.find_path("a/b.txt/nope").is_none());
+
+    let top = fs.find_path("top.txt").unwrap();
+    assert_eq!(fs.read_at(top, 0, 100).unwrap(), b"second");
+    let err = fs.read_at(a, 0, 100).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EISDIR));
+    let err = fs.read_at(12345, 0, 100).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+}
+
+#[test]
+fn chunked_reads() {
+    let mut state = 0x2545_f491_u32;
+    let data: Vec<u8> = (0..10_000)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            // compressible, but not too much
+            if i % 3 == 0 {
+                state as u8
+            } else {
+                b'a'
+            }
+        })
+        .collect();
+
+    for method in [Method::Store, Method::Deflate] {
+        let bytes = ZipBuilder::new()
+            .with_entry(ZipEntry::new("data.bin", data.clone()).with_method(method))
+            .with_entry(ZipEntry::new("other.bin", data.clone()).with_method(method))
+            .build();
+        let archive = bytes.read_zip().unwrap();
+        // two chunks fit in the cache: reading the data from the start again
+        // needs a new reader
+        let mut fs = ZipFs::new(&archive)
+            .with_chunk_size(1000)
+            .with_cache_size(2000);
+        let ino = fs.find_path("data.bin").unwrap();
+        let other = fs.find_path("other.bin").unwrap();
+
+        let check = |fs: &mut ZipFs<_>, ino, offset: usize, size: usize| {
+            let end = data.len().min(offset + size);
+            let expected = data.get(offset..end).unwrap_or_default();
+            let read = fs.read_at(ino, offset as u64, size as u32).unwrap();
+            assert_eq!(read, expected, "{method:?}: {offset}+{size}");
+        };
+        // sequentially, across chunks
+        for offset in (0..data.len()).step_by(700) {
+            check(&mut fs, ino, offset, 700);
+        }
+        // backwards, interleaved with another entry
+        for offset in (0..data.len()).step_by(1500).rev() {
+            check(&mut fs, ino, offset, 2500);
+            check(&mut fs, other, offset / 2, 300);
+        }
+        // at and past the end
+        check(&mut fs, ino, 9_990, 100);
+        check(&mut fs, ino, 10_000, 100);
+        check(&mut fs, ino, 20_000, 100);
+        check(&mut fs, ino, 0, 20_000);
+    }
+}
+
+#[test]
+fn symlinks() {
+    let file = File::open(zips_dir().join("symlink.zip")).unwrap();
+    let archive = file.read_zip().unwrap();
+    let fs = ZipFs::new(&archive);
+
+    let ino = fs.find_path("symlink").unwrap();
+    let kinds: Vec<_> = fs.children(FUSE_ROOT_ID).unwrap().collect();
+    assert_eq!(kinds, [("symlink", ino, FileType::Symlink)]);
+}
@@ -0,0 +1,107 @@
+use std::{io, pin::Pin, task};
+
+use futures_util::io::AsyncRead;
+use pin_project_lite::pin_project;
+use rc_zip::{
+    fsm::{DecompressionLimits, EntryFsm, FsmResult},
+    parse::Entry,
+};
+
+pin_project! {
+    pub(crate) struct EntryReader<R>
+    where
+        R: AsyncRead,
+    {
+        #[pin]
+        rd: R,
+        fsm: Option<EntryFsm>,
+    }
+}
+
+impl<R> EntryReader<R>
+where
+    R: AsyncRead,
+{
+    pub(crate) fn new<F>(entry: &Entry, get_reader: F) -> Self
+    where
+        F: FnOnce(u64) -> R,
+    {
+        Self {
+            rd: get_reader(entry.header_offset),
+            fsm: Some(EntryFsm::new(Some(entry.clone()), None)),
+        }
+    }
+
+    pub(crate) fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_limits(limits));
+        self
+    }
+
+    #[cfg(feature = "aes")]
+    pub(crate) fn with_password(mut self, password: &[u8]) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_password(password));
+        self
+    }
+}
+
+impl<R> AsyncRead for EntryReader<R>
+where
+    R: AsyncRead,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let mut this = self.as_mut().project();
+
+        loop {
+            let mut fsm = match this.fsm.take() {
+                Some(fsm) => fsm,
+                None => return Ok(0).into(),
+            };
+
+            let filled_bytes;
+            if fsm.wants_read() {
+                tracing::trace!(space_avail = fsm.space().len(), "fsm wants read");
+                let n = match this.rd.as_mut().poll_read(cx, fsm.space()) {
+                    task::Poll::Ready(res) => res?,
+                    task::Poll::Pending => {
+                        *this.fsm = Some(fsm);
+                        return task::Poll::Pending;
+                    }
+                };
+
+                tracing::trace!("read {} bytes", n);
+                fsm.fill(n);
+                filled_bytes = n;
+            } else {
+                tracing::trace!("fsm does not want read");
+                filled_bytes = 0;
+            }
+
+            match fsm.process(buf)? {
+                FsmResult::Continue((fsm, outcome)) => {
+                    *this.fsm = Some(fsm);
+                    if outcome.bytes_written > 0 {
+                        tracing::trace!("wrote {} bytes", outcome.bytes_written);
+                        return Ok(outcome.bytes_written).into();
+                    } else if filled_bytes > 0 || outcome.bytes_read > 0 {
+                        // progress was made, keep reading
+                        continue;
+                    } else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            "entry reader: no progress",
+                        ))
+                        .into();
+                    }
+                }
+                FsmResult::Done(_) => {
+                    // neat!
+                    return Ok(0).into();
+                }
+            }
+        }
+    }
+}
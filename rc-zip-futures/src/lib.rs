@@ -0,0 +1,37 @@
+//! A library for reading zip files asynchronously using futures-io traits,
+//! based on top of [rc-zip](https://crates.io/crates/rc-zip).
+//!
+//! Unlike [rc-zip-tokio](https://crates.io/crates/rc-zip-tokio), this
+//! doesn't depend on any runtime: it works with smol, async-std, or anything
+//! else that implements [futures_util::io::AsyncRead].
+//!
+//! Reading from a file on disk does depend on a runtime, for its blocking
+//! thread pool: enable the `async-std` or `smol` feature to read zips from an
+//! `Arc<RandomAccessFile>`.
+//!
+//! See also:
+//!
+//!   * [rc-zip-sync](https://crates.io/crates/rc-zip-sync) for using std I/O traits
+//!   * [rc-zip-tokio](https://crates.io/crates/rc-zip-tokio) for using tokio I/O traits
+
+#![warn(missing_docs)]
+
+mod entry_reader;
+mod read_zip;
+
+#[cfg(any(feature = "async-std", feature = "smol"))]
+mod random_access_file;
+#[cfg(any(feature = "async-std", feature = "smol"))]
+pub use random_access_file::AsyncRandomAccessFileCursor;
+
+mod streaming_entry_reader;
+pub use streaming_entry_reader::StreamingEntryReader;
+
+mod streaming_zip_reader;
+pub use streaming_zip_reader::StreamingZipReader;
+
+// re-exports
+pub use rc_zip;
+pub use read_zip::{
+    ArchiveHandle, DynHasCursor, EntryHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize,
+};
@@ -0,0 +1,156 @@
+use std::{
+    cmp, io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::{future::BoxFuture, io::AsyncRead};
+use positioned_io::{RandomAccessFile, ReadAt, Size};
+use rc_zip::{error::Error, fsm::ArchiveReadOptions};
+use tracing::trace;
+
+use crate::{ArchiveHandle, HasCursor, ReadZip, ReadZipWithSize};
+
+/// Runs blocking file I/O on the thread pool of whichever runtime was picked
+/// with cargo features: this is the only runtime-specific part of this crate.
+#[cfg(feature = "async-std")]
+fn unblock<T, F>(f: F) -> BoxFuture<'static, T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Box::pin(async_std::task::spawn_blocking(f))
+}
+
+#[cfg(all(feature = "smol", not(feature = "async-std")))]
+fn unblock<T, F>(f: F) -> BoxFuture<'static, T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Box::pin(blocking::unblock(f))
+}
+
+impl ReadZip for Arc<RandomAccessFile> {
+    type File = Self;
+
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let size = self.size()?.unwrap_or_default();
+        self.read_zip_with_size_and_options(size, options).await
+    }
+}
+
+impl HasCursor for Arc<RandomAccessFile> {
+    type Cursor<'a> = AsyncRandomAccessFileCursor
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        AsyncRandomAccessFileCursor {
+            state: ARAFCState::Idle(ARAFCCore {
+                file_offset: offset,
+                inner_buf: vec![0u8; 128 * 1024],
+                inner_buf_len: 0,
+                inner_buf_offset: 0,
+                file: self.clone(),
+            }),
+        }
+    }
+}
+
+struct ARAFCCore {
+    // offset we're reading from in the file
+    file_offset: u64,
+
+    // note: the length of this vec is the inner buffer capacity
+    inner_buf: Vec<u8>,
+
+    // the start of data we haven't returned put to caller buffets yet
+    inner_buf_offset: usize,
+
+    // the end of data we haven't returned put to caller buffets yet
+    inner_buf_len: usize,
+
+    file: Arc<RandomAccessFile>,
+}
+
+#[derive(Default)]
+enum ARAFCState {
+    Idle(ARAFCCore),
+    Reading {
+        fut: BoxFuture<'static, Result<ARAFCCore, io::Error>>,
+    },
+
+    #[default]
+    Transitioning,
+}
+
+/// A cursor for reading from a [RandomAccessFile] asynchronously, on the
+/// blocking thread pool of the runtime selected with the `async-std` or
+/// `smol` feature.
+pub struct AsyncRandomAccessFileCursor {
+    state: ARAFCState,
+}
+
+impl AsyncRead for AsyncRandomAccessFileCursor {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.state {
+            ARAFCState::Idle(core) => {
+                if core.inner_buf_offset < core.inner_buf_len || buf.is_empty() {
+                    // we have data in the inner buffer, don't even need
+                    // to go to the blocking pool
+                    let read_len = cmp::min(buf.len(), core.inner_buf_len - core.inner_buf_offset);
+
+                    buf[..read_len]
+                        .copy_from_slice(&core.inner_buf[core.inner_buf_offset..][..read_len]);
+                    core.inner_buf_offset += read_len;
+                    trace!(inner_buf_offset = %core.inner_buf_offset, inner_buf_len = %core.inner_buf_len, "read from inner buffer");
+
+                    return Poll::Ready(Ok(read_len));
+                }
+
+                let (file_offset, file, mut inner_buf) = {
+                    let core = match std::mem::take(&mut self.state) {
+                        ARAFCState::Idle(core) => core,
+                        _ => unreachable!(),
+                    };
+                    (core.file_offset, core.file, core.inner_buf)
+                };
+
+                let fut = unblock(move || {
+                    let read_bytes = file.read_at(file_offset, &mut inner_buf)?;
+                    trace!(%read_bytes, "read from file");
+                    Ok(ARAFCCore {
+                        file_offset: file_offset + read_bytes as u64,
+                        file,
+                        inner_buf,
+                        inner_buf_len: read_bytes,
+                        inner_buf_offset: 0,
+                    })
+                });
+                self.state = ARAFCState::Reading { fut };
+                self.poll_read(cx, buf)
+            }
+            ARAFCState::Reading { fut } => {
+                let core = futures_util::ready!(fut.as_mut().poll(cx))?;
+                let is_eof = core.inner_buf_len == 0;
+                self.state = ARAFCState::Idle(core);
+
+                if is_eof {
+                    // we're at EOF
+                    return Poll::Ready(Ok(0));
+                }
+                self.poll_read(cx, buf)
+            }
+            ARAFCState::Transitioning => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use std::{io, ops::Deref};
+
+use futures_util::io::{AsyncRead, AsyncReadExt, Take};
+
+use rc_zip::{
+    error::{Error, FormatError},
+    fsm::{
+        ArchiveFsm, ArchiveReadOptions, DecompressionLimits, FsmResult, RecoveryFsm,
+        StreamingEntryFsm,
+    },
+    jar::{Manifest, MANIFEST_NAME},
+    parse::{Archive, Entry},
+};
+use tracing::trace;
+
+use crate::{entry_reader::EntryReader, streaming_entry_reader::next_entry, StreamingEntryReader};
+
+/// A trait for reading something as a zip archive.
+///
+/// See also [ReadZip].
+pub trait ReadZipWithSize {
+    /// The type of the file to read from.
+    type File: HasCursor;
+
+    /// Reads self as a zip archive.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(size, Default::default())
+            .await
+    }
+
+    /// Reads self as a zip archive, with the given options.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_size_and_options(
+        &self,
+        size: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_at_with_options(0, size, options).await
+    }
+
+    /// Reads a zip archive embedded in self, that is `len` bytes long and
+    /// starts at `offset`, as found in some container formats (game packs,
+    /// firmware images, etc.)
+    ///
+    /// Nothing outside of that window is read: the end of central directory
+    /// is looked for at the end of the window, and entry offsets are
+    /// relative to its start.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_at(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_at_with_options(offset, len, Default::default())
+            .await
+    }
+
+    /// Reads a zip archive embedded in self, with the given options. See
+    /// [ReadZipWithSize::read_zip_at].
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_at_with_options(
+        &self,
+        offset: u64,
+        len: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Salvages what can be found of a damaged zip archive, by scanning all
+    /// of self for local headers rather than reading the central directory,
+    /// see [RecoveryFsm].
+    #[allow(async_fn_in_trait)]
+    async fn recover_zip_with_size(
+        &self,
+        size: u64,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+}
+
+/// A zip archive, read asynchronously from a file or other I/O resource.
+///
+/// This only contains metadata for the archive and its entries. Separate
+/// readers can be created for arbitraries entries on-demand using
+/// [EntryHandle::reader].
+pub trait ReadZip {
+    /// The type of the file to read from.
+    type File: HasCursor;
+
+    /// Reads self as a zip archive.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_options(Default::default()).await
+    }
+
+    /// Reads self as a zip archive, with the given options.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive that starts at `offset`, like one
+    /// embedded in a firmware image: offsets recorded in the archive are
+    /// relative to it, see [ArchiveReadOptions::base_offset]. Unlike
+    /// [ReadZipWithSize::read_zip_at], the archive's length needn't be
+    /// known, as long as it's near the end of self.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_offset(
+        &self,
+        offset: u64,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_options(ArchiveReadOptions::default().with_base_offset(offset))
+            .await
+    }
+}
+
+impl<F> ReadZipWithSize for F
+where
+    F: HasCursor,
+{
+    type File = F;
+
+    async fn read_zip_at_with_options(
+        &self,
+        offset: u64,
+        len: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
+        struct CursorState<'a, F: HasCursor + 'a> {
+            cursor: Take<<F as HasCursor>::Cursor<'a>>,
+            offset: u64,
+        }
+        let mut cstate: Option<CursorState<'_, F>> = None;
+
+        let window = Window { offset, len };
+        let mut fsm = ArchiveFsm::with_options(len, options);
+        loop {
+            if let Some(offset) = fsm.wants_read() {
+                let mut cstate_next = match cstate.take() {
+                    Some(cstate) if cstate.offset == offset => {
+                        // all good, re-using
+                        cstate
+                    }
+                    _ => {
+                        trace!(%offset, "read_zip_with_size: making new cursor");
+                        CursorState {
+                            cursor: window.cursor_at(self, offset),
+                            offset,
+                        }
+                    }
+                };
+
+                let read_bytes = cstate_next.cursor.read(fsm.space()).await?;
+                cstate_next.offset += read_bytes as u64;
+                cstate = Some(cstate_next);
+
+                trace!(%read_bytes, "filling fsm");
+                if read_bytes == 0 {
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                fsm.fill(read_bytes);
+            }
+
+            fsm = match fsm.process()? {
+                FsmResult::Done(archive) => {
+                    return Ok(ArchiveHandle {
+                        file: self,
+                        window,
+                        archive,
+                    })
+                }
+                FsmResult::Continue(fsm) => fsm,
+            }
+        }
+    }
+
+    async fn recover_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+        let window = Window {
+            offset: 0,
+            len: size,
+        };
+        let mut cursor = None;
+        let mut fsm = RecoveryFsm::new(size);
+        loop {
+            if let Some(offset) = fsm.wants_read() {
+                // data is read forward, the cursor is only replaced when
+                // parts of the file are skipped
+                if cursor.as_ref().map(|(cursor_offset, _)| *cursor_offset) != Some(offset) {
+                    cursor = Some((offset, window.cursor_at(self, offset)));
+                }
+                let (cursor_offset, rd) = cursor.as_mut().unwrap();
+                let read_bytes = rd.read(fsm.space()).await?;
+                if read_bytes == 0 {
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                *cursor_offset += read_bytes as u64;
+                fsm.fill(read_bytes);
+            }
+
+            fsm = match fsm.process()? {
+                FsmResult::Done(archive) => {
+                    return Ok(ArchiveHandle {
+                        file: self,
+                        window,
+                        archive,
+                    })
+                }
+                FsmResult::Continue(fsm) => fsm,
+            }
+        }
+    }
+}
+
+impl ReadZip for &[u8] {
+    type File = Self;
+
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len() as u64, options)
+            .await
+    }
+}
+
+impl ReadZip for Vec<u8> {
+    type File = Self;
+
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len() as u64, options)
+            .await
+    }
+}
+
+/// A zip archive, read asynchronously from a file or other I/O resource.
+pub struct ArchiveHandle<'a, F>
+where
+    F: HasCursor,
+{
+    file: &'a F,
+    window: Window,
+    archive: Archive,
+}
+
+impl<F> Deref for ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    type Target = Archive;
+
+    fn deref(&self) -> &Self::Target {
+        &self.archive
+    }
+}
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Iterate over all files in this zip, read from the central directory.
+    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
+        self.archive.entries().map(move |entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Attempts to look up an entry by name. This is usually a bad idea,
+    /// as names aren't necessarily normalized in zip archives.
+    pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Reads and parses the manifest, if this is a JAR file.
+    pub async fn manifest(&self) -> Result<Option<Manifest>, Error> {
+        let Some(entry) = self.by_name(MANIFEST_NAME) else {
+            return Ok(None);
+        };
+        Ok(Some(Manifest::parse(&entry.bytes().await?)?))
+    }
+}
+
+/// A single entry in a zip archive, read asynchronously from a file or other I/O resource.
+pub struct EntryHandle<'a, F> {
+    file: &'a F,
+    window: Window,
+    entry: &'a Entry,
+}
+
+impl<F> Deref for EntryHandle<'_, F> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        self.entry
+    }
+}
+
+impl<'a, F> EntryHandle<'a, F>
+where
+    F: HasCursor,
+{
+    /// Returns a reader for the entry.
+    pub fn reader(&self) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_password(password)
+    }
+
+    fn entry_reader(&self) -> EntryReader<Take<F::Cursor<'a>>> {
+        EntryReader::new(self.entry, |offset| {
+            self.window.cursor_at(self.file, offset)
+        })
+    }
+
+    /// Reads the entire entry into a vector.
+    pub async fn bytes(&self) -> io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        self.reader().read_to_end(&mut v).await?;
+        Ok(v)
+    }
+}
+
+/// Where an archive is within a [HasCursor]: usually all of it, but see
+/// [ReadZipWithSize::read_zip_at].
+#[derive(Clone, Copy)]
+struct Window {
+    offset: u64,
+    len: u64,
+}
+
+impl Window {
+    /// Returns a cursor at `offset` within the window, that stops at its end.
+    fn cursor_at<F: HasCursor>(self, file: &F, offset: u64) -> Take<F::Cursor<'_>> {
+        file.cursor_at(self.offset.saturating_add(offset))
+            .take(self.len.saturating_sub(offset))
+    }
+}
+
+/// A sliceable I/O resource: we can ask for an [AsyncRead] at a given offset.
+///
+/// This is the only thing rc-zip-futures needs from a backend, so
+/// implementing it is all it takes to read zips from files of whichever
+/// runtime is in use, object stores, etc.
+///
+/// The contract is:
+///
+///   * [HasCursor::cursor_at] should be cheap and not perform any I/O: reads
+///     happen when the cursor is polled.
+///   * A cursor yields the bytes of the resource starting at `offset`, in
+///     order. Short reads are fine, and a read of zero bytes means the end of
+///     the resource was reached.
+///   * Cursors may be requested for any offset, in any order, and several of
+///     them may be alive at the same time (one per entry reader, for
+///     example), so they must not share a read position.
+///
+/// The size of the resource is not part of this trait: pass it to
+/// [ReadZipWithSize::read_zip_with_size], or implement [ReadZip].
+pub trait HasCursor {
+    /// The type returned by [HasCursor::cursor_at].
+    type Cursor<'a>: AsyncRead + Unpin + 'a
+    where
+        Self: 'a;
+
+    /// Returns an [AsyncRead] that reads the resource from `offset` onwards.
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_>;
+}
+
+/// An object-safe form of [HasCursor], for when the type of the I/O resource
+/// isn't known statically.
+///
+/// It's implemented for every [HasCursor], and `dyn DynHasCursor` (as well as
+/// boxes of it) implements [HasCursor] in turn, by boxing every cursor.
+pub trait DynHasCursor {
+    /// Returns a boxed [AsyncRead] at the given offset.
+    fn dyn_cursor_at(&self, offset: u64) -> Box<dyn AsyncRead + Unpin + '_>;
+}
+
+impl<T> DynHasCursor for T
+where
+    T: HasCursor,
+{
+    fn dyn_cursor_at(&self, offset: u64) -> Box<dyn AsyncRead + Unpin + '_> {
+        Box::new(self.cursor_at(offset))
+    }
+}
+
+impl HasCursor for dyn DynHasCursor + '_ {
+    type Cursor<'a> = Box<dyn AsyncRead + Unpin + 'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.dyn_cursor_at(offset)
+    }
+}
+
+impl HasCursor for dyn DynHasCursor + Send + Sync + '_ {
+    type Cursor<'a> = Box<dyn AsyncRead + Unpin + 'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.dyn_cursor_at(offset)
+    }
+}
+
+impl<T> HasCursor for Box<T>
+where
+    T: HasCursor + ?Sized,
+{
+    type Cursor<'a> = T::Cursor<'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        (**self).cursor_at(offset)
+    }
+}
+
+/// Offsets past the end of a slice, including ones that don't even fit in
+/// a usize, result in an empty cursor, the same as reading past the end of a
+/// file would.
+fn slice_at(slice: &[u8], offset: u64) -> &[u8] {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| slice.get(offset..))
+        .unwrap_or_default()
+}
+
+impl HasCursor for &[u8] {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        slice_at(self, offset)
+    }
+}
+
+impl HasCursor for Vec<u8> {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        slice_at(self, offset)
+    }
+}
+
+/// Allows reading zip entries in a streaming fashion, without seeking,
+/// based only on local headers. THIS IS NOT RECOMMENDED, as correctly
+/// reading zip files requires reading the central directory (located at
+/// the end of the file).
+pub trait ReadZipStreaming<R>
+where
+    R: AsyncRead,
+{
+    /// Get the first zip entry from the stream as a [StreamingEntryReader].
+    ///
+    /// See the trait's documentation for why using this is
+    /// generally a bad idea: you might want to use [ReadZip] or
+    /// [ReadZipWithSize] instead.
+    #[allow(async_fn_in_trait)]
+    async fn stream_zip_entries_throwing_caution_to_the_wind(
+        self,
+    ) -> Result<StreamingEntryReader<R>, Error>;
+}
+
+impl<R> ReadZipStreaming<R> for R
+where
+    R: AsyncRead + Unpin,
+{
+    async fn stream_zip_entries_throwing_caution_to_the_wind(
+        self,
+    ) -> Result<StreamingEntryReader<Self>, Error> {
+        next_entry(StreamingEntryFsm::new(None), self)
+            .await?
+            .ok_or_else(|| FormatError::InvalidLocalHeader.into())
+    }
+}
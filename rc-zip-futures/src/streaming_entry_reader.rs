@@ -0,0 +1,245 @@
+use futures_util::io::{AsyncRead, AsyncReadExt};
+use oval::Buffer;
+use pin_project_lite::pin_project;
+use rc_zip::{
+    error::Error,
+    fsm::{
+        DecompressionLimits, EntryFsm, EntryTrailer, FsmResult, StreamingEntryFsm, StreamingNext,
+    },
+    parse::Entry,
+};
+use std::{io, pin::Pin, task};
+use tracing::trace;
+
+pin_project! {
+    /// Reads a zip entry based on a local header. Some information is missing,
+    /// not all name encodings may work, and only by reading it in its entirety
+    /// can you move on to the next entry.
+    ///
+    /// Moving on is done with [StreamingEntryReader::finish], which skips
+    /// whatever wasn't read of this entry. Dropping a reader doesn't touch the
+    /// underlying stream: if it was borrowed, it's left somewhere in the middle
+    /// of the entry, and can't be used to read the next one.
+    ///
+    /// However, it only requires an [AsyncRead], and does not need to seek.
+    pub struct StreamingEntryReader<R> {
+        entry: Entry,
+        #[pin]
+        rd: R,
+        state: State,
+        limits: DecompressionLimits,
+    }
+}
+
+#[derive(Default)]
+#[allow(clippy::large_enum_variant)]
+enum State {
+    Reading {
+        fsm: EntryFsm,
+    },
+    Finished {
+        /// remaining buffer for next entry
+        remain: Buffer,
+
+        /// the entry's data descriptor, if any
+        trailer: Option<EntryTrailer>,
+    },
+    #[default]
+    Transition,
+}
+
+impl<R> StreamingEntryReader<R>
+where
+    R: AsyncRead,
+{
+    pub(crate) fn new(fsm: EntryFsm, entry: Entry, rd: R) -> Self {
+        Self {
+            entry,
+            rd,
+            state: State::Reading { fsm },
+            limits: Default::default(),
+        }
+    }
+}
+
+impl<R> AsyncRead for StreamingEntryReader<R>
+where
+    R: AsyncRead,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let this = self.as_mut().project();
+
+        trace!("reading from streaming entry reader");
+
+        match std::mem::take(this.state) {
+            State::Reading { mut fsm } => {
+                let mut filled = 0;
+                if fsm.wants_read() {
+                    trace!("fsm wants read");
+                    let n = match this.rd.poll_read(cx, fsm.space()) {
+                        task::Poll::Ready(res) => res?,
+                        task::Poll::Pending => {
+                            *this.state = State::Reading { fsm };
+                            return task::Poll::Pending;
+                        }
+                    };
+
+                    trace!("giving fsm {} bytes from rd", n);
+                    filled = fsm.fill(n);
+                } else {
+                    trace!("fsm does not want read");
+                }
+
+                match fsm.process(buf)? {
+                    FsmResult::Continue((fsm, outcome)) => {
+                        trace!("fsm wants to continue");
+                        *this.state = State::Reading { fsm };
+
+                        if outcome.bytes_written > 0 {
+                            trace!("bytes have been written");
+                            Ok(outcome.bytes_written).into()
+                        } else if outcome.bytes_read == 0 && filled == 0 {
+                            trace!("no bytes have been written or read");
+                            // that's EOF, baby!
+                            Ok(0).into()
+                        } else {
+                            trace!("read some bytes, hopefully will write more later");
+                            // loop, it happens
+                            self.poll_read(cx, buf)
+                        }
+                    }
+                    FsmResult::Done((remain, trailer)) => {
+                        *this.state = State::Finished { remain, trailer };
+
+                        // neat!
+                        Ok(0).into()
+                    }
+                }
+            }
+            State::Finished { remain, trailer } => {
+                // wait for them to call finish
+                *this.state = State::Finished { remain, trailer };
+                Ok(0).into()
+            }
+            State::Transition => unreachable!(),
+        }
+    }
+}
+
+impl<R> StreamingEntryReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Return entry information for this reader
+    #[inline(always)]
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    /// Returns the data descriptor that followed the entry's data, once it's
+    /// been read to the end: `None` if there wasn't any, or if the entry
+    /// isn't read yet.
+    pub fn trailer(&self) -> Option<&EntryTrailer> {
+        match &self.state {
+            State::Finished { trailer, .. } => trailer.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Sets limits on what reading this entry, and the ones after it, may
+    /// produce, see [DecompressionLimits].
+    pub fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.limits = limits;
+        self.state = match std::mem::take(&mut self.state) {
+            State::Reading { fsm } => State::Reading {
+                fsm: fsm.with_limits(limits),
+            },
+            state => state,
+        };
+        self
+    }
+
+    /// Reads and discards the rest of this entry, returning how many
+    /// (uncompressed) bytes were skipped. The entry is still decompressed and
+    /// its checksum verified, since that's the only way to find where it ends
+    /// when sizes are only known from the data descriptor.
+    ///
+    /// Subsequent reads return no data.
+    pub async fn skip_rest(&mut self) -> Result<u64, Error> {
+        trace!("skipping rest of streaming entry");
+
+        let mut skipped = 0;
+        let mut scratch = vec![0u8; 16 * 1024];
+        while matches!(self.state, State::Reading { .. }) {
+            match self.read(&mut scratch).await? {
+                0 => break,
+                n => skipped += n as u64,
+            }
+        }
+
+        if matches!(self.state, State::Reading { .. }) {
+            return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(skipped)
+    }
+
+    /// Finish reading this entry, returning the next streaming entry reader, if
+    /// any. The rest of this entry is skipped if it wasn't fully read, see
+    /// [StreamingEntryReader::skip_rest].
+    ///
+    /// If this returns None, there's no entries left.
+    pub async fn finish(mut self) -> Result<Option<StreamingEntryReader<R>>, Error> {
+        trace!("finishing streaming entry reader");
+
+        self.skip_rest().await?;
+
+        match self.state {
+            State::Reading { .. } => {
+                unreachable!("skip_rest reads until the entry is finished");
+            }
+            State::Finished { remain, .. } => {
+                // parse the next entry, if any
+                let next = next_entry(StreamingEntryFsm::new(Some(remain)), self.rd).await?;
+                Ok(next.map(|next| next.with_limits(self.limits)))
+            }
+            State::Transition => unreachable!(),
+        }
+    }
+}
+
+/// Reads the next local header with `fsm`, or returns None if the central
+/// directory comes next.
+pub(crate) async fn next_entry<R>(
+    mut fsm: StreamingEntryFsm,
+    mut rd: R,
+) -> Result<Option<StreamingEntryReader<R>>, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        fsm = match fsm.process()? {
+            FsmResult::Continue(mut fsm) => {
+                let n = rd.read(fsm.space()).await?;
+                trace!("read {} bytes into buf for next zip entry", n);
+                if n == 0 {
+                    // neither another entry nor a central directory
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                fsm.fill(n);
+                fsm
+            }
+            FsmResult::Done(StreamingNext::Entry(mut fsm)) => {
+                let entry = fsm
+                    .process_till_header()?
+                    .expect("local header was parsed")
+                    .clone();
+                return Ok(Some(StreamingEntryReader::new(fsm, entry, rd)));
+            }
+            FsmResult::Done(StreamingNext::Trailer(_)) => return Ok(None),
+        };
+    }
+}
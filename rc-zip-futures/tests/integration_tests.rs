@@ -0,0 +1,165 @@
+use futures_executor::block_on;
+use futures_util::io::AsyncReadExt;
+use rc_zip::{
+    corpus::{self, zips_dir, Case, Files},
+    error::{Error, LimitExceeded},
+    fsm::DecompressionLimits,
+    parse::Archive,
+};
+use rc_zip_futures::{
+    ArchiveHandle, DynHasCursor, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    StreamingZipReader,
+};
+
+async fn check_case<F: HasCursor>(test: &Case, archive: Result<ArchiveHandle<'_, F>, Error>) {
+    corpus::check_case(test, archive.as_ref().map(|ar| -> &Archive { ar }));
+    let archive = match archive {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+
+    if let Files::ExhaustiveList(files) = &test.files {
+        for file in files {
+            let entry = archive
+                .by_name(file.name)
+                .unwrap_or_else(|| panic!("entry {} should exist", file.name));
+
+            corpus::check_file_against(file, &entry, &entry.bytes().await.unwrap()[..])
+        }
+    }
+}
+
+#[test]
+fn read_from_slice() {
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+        let slice = &bytes[..];
+        let archive = slice.read_zip().await.unwrap();
+        assert_eq!(archive.entries().count(), 2);
+    });
+}
+
+#[test]
+fn read_from_dyn_cursors() {
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+        let size = bytes.len() as u64;
+        let file: Box<dyn DynHasCursor> = Box::new(bytes);
+        let archive = file.read_zip_with_size(size).await.unwrap();
+        assert_eq!(archive.entries().count(), 2);
+    });
+}
+
+#[test]
+fn real_world_files() {
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        for case in corpus::test_cases() {
+            tracing::info!("============ testing {}", case.name);
+
+            let bytes = case.bytes();
+            let archive = bytes.read_zip().await;
+            check_case(&case, archive).await;
+        }
+    });
+}
+
+#[cfg(any(feature = "async-std", feature = "smol"))]
+#[test]
+fn real_world_files_on_disk() {
+    use positioned_io::RandomAccessFile;
+    use std::sync::Arc;
+
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        for case in corpus::test_cases() {
+            tracing::info!("============ testing {}", case.name);
+
+            let guarded_path = case.absolute_path();
+            let file = Arc::new(RandomAccessFile::open(&guarded_path.path).unwrap());
+            let archive = file.read_zip().await;
+            check_case(&case, archive).await;
+            drop(guarded_path)
+        }
+    });
+}
+
+#[test]
+fn streaming() {
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        for case in corpus::streaming_test_cases() {
+            let bytes = case.bytes();
+
+            let mut entry = (&bytes[..])
+                .stream_zip_entries_throwing_caution_to_the_wind()
+                .await
+                .unwrap();
+            loop {
+                let mut v = vec![];
+                let n = entry.read_to_end(&mut v).await.unwrap();
+                tracing::trace!("entry {} read {} bytes", entry.entry().name, n);
+
+                match entry.finish().await.unwrap() {
+                    Some(next) => entry = next,
+                    None => break,
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn streaming_zip_reader() {
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        let bytes = corpus::stored_zip(&[("a.txt", b"hello"), ("b.txt", b"world")]);
+        let mut zip = StreamingZipReader::new(&bytes[..]);
+        let mut read = Vec::new();
+        while let Some(entry) = zip.next_entry().await.unwrap() {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).await.unwrap();
+            read.push((entry.entry().name.clone(), contents));
+        }
+        assert_eq!(
+            read,
+            [
+                ("a.txt".to_owned(), "hello".to_owned()),
+                ("b.txt".to_owned(), "world".to_owned())
+            ]
+        );
+    });
+}
+
+#[test]
+fn decompression_limits() {
+    corpus::install_test_subscriber();
+
+    block_on(async {
+        let bytes = corpus::stored_zip(&[("zeros.bin", &[0; 4096])]);
+        let archive = bytes.read_zip().await.unwrap();
+        let entry = archive.by_name("zeros.bin").unwrap();
+        let limits = DecompressionLimits {
+            max_output: Some(1000),
+            ..Default::default()
+        };
+        let err = entry
+            .reader_with_limits(limits)
+            .read_to_end(&mut Vec::new())
+            .await
+            .unwrap_err();
+        let err = *err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert!(matches!(
+            err,
+            Error::LimitExceeded(LimitExceeded::OutputSize { limit: 1000 })
+        ));
+    });
+}
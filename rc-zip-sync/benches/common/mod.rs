@@ -0,0 +1,77 @@
+//! Fixtures shared by the benchmarks: archives are generated on first use,
+//! and kept in cargo's target directory between runs.
+
+use rc_zip_sync::ArchiveWriter;
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Where fixtures and extracted files go
+pub fn tmp_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+}
+
+/// Returns the path of the fixture called `name`, calling `write` to make it
+/// unless a previous run did.
+#[allow(dead_code)] // not every benchmark uses every helper
+pub fn fixture(
+    name: &str,
+    write: impl FnOnce(&mut ArchiveWriter<BufWriter<File>>) -> io::Result<()>,
+) -> io::Result<PathBuf> {
+    let path = tmp_dir().join(name);
+    if path.exists() {
+        return Ok(path);
+    }
+
+    // written under another name first, so interrupted runs don't leave
+    // half an archive behind
+    let partial = path.with_extension("partial");
+    let mut writer = ArchiveWriter::new(BufWriter::new(File::create(&partial)?));
+    write(&mut writer)?;
+    writer.finish()?.flush()?;
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Deterministic data for fixtures: pseudo-random bytes don't compress,
+/// words from a small vocabulary do.
+pub struct Data {
+    state: u32,
+}
+
+#[allow(dead_code)] // not every benchmark uses every helper
+impl Data {
+    const WORDS: [&'static str; 6] = ["zip", "entry", "local", "header", "central", "directory"];
+
+    pub fn new() -> Self {
+        Self { state: 0x2545_f491 }
+    }
+
+    fn next(&mut self) -> u32 {
+        // xorshift32
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    /// Fills `buf` with bytes that don't compress
+    pub fn fill_random(&mut self, buf: &mut [u8]) {
+        buf.fill_with(|| self.next() as u8);
+    }
+
+    /// Returns `len` bytes of text that compresses well
+    pub fn text(&mut self, len: usize) -> Vec<u8> {
+        let mut text = Vec::with_capacity(len + 16);
+        while text.len() < len {
+            let word = Self::WORDS[self.next() as usize % Self::WORDS.len()];
+            text.extend_from_slice(word.as_bytes());
+            text.push(b' ');
+        }
+        text.truncate(len);
+        text
+    }
+}
@@ -0,0 +1,182 @@
+//! Compares rc-zip with the `zip` crate and, for extraction, with libarchive
+//! through `bsdtar`, when it's installed. Measures:
+//!
+//! - parsing the central directory of an archive with many entries,
+//! - reading a large entry, for each compression method,
+//! - extracting a whole archive to disk.
+//!
+//! Fixtures are written by rc-zip's own [ArchiveWriter](rc_zip_sync::ArchiveWriter),
+//! which only supports the store and deflate methods. Run with
+//! `cargo bench -p rc-zip-sync --bench compare`.
+
+mod common;
+
+use common::Data;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rc_zip::{fsm::NewEntry, parse::Method};
+use rc_zip_sync::{ExtractOptions, ReadZip};
+
+use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Entries in the central directory benchmark's archive
+const DIRECTORY_ENTRIES: usize = 10_000;
+
+/// Size of the entry in the read benchmarks
+const ENTRY_LEN: usize = 16 * 1024 * 1024;
+
+/// Files in the extraction benchmark's archive, and their size
+const EXTRACTED_FILES: usize = 256;
+const EXTRACTED_LEN: usize = 64 * 1024;
+
+fn central_directory(c: &mut Criterion) {
+    let path = common::fixture("compare-directory.zip", |writer| {
+        for i in 0..DIRECTORY_ENTRIES {
+            writer.start_entry(NewEntry::new(format!("dir{}/file{i}.txt", i % 100)))?;
+            writer.write_all(b"some contents")?;
+        }
+        Ok(())
+    })
+    .unwrap();
+    let bytes = std::fs::read(path).unwrap();
+
+    let mut group = c.benchmark_group("central_directory");
+    group.throughput(Throughput::Elements(DIRECTORY_ENTRIES as u64));
+    group.bench_function("rc-zip", |b| {
+        b.iter(|| bytes.read_zip().unwrap().entries().count())
+    });
+    group.bench_function("zip", |b| {
+        b.iter(|| zip::ZipArchive::new(Cursor::new(&bytes)).unwrap().len())
+    });
+    group.finish();
+}
+
+fn read_entry(c: &mut Criterion) {
+    let methods = [("store", Method::Store), ("deflate", Method::Deflate)];
+    let path = common::fixture("compare-read.zip", |writer| {
+        let text = Data::new().text(ENTRY_LEN);
+        for (name, method) in methods {
+            writer.start_entry(NewEntry::new(name).with_method(method))?;
+            writer.write_all(&text)?;
+        }
+        Ok(())
+    })
+    .unwrap();
+    let bytes = std::fs::read(path).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let mut zip_archive = zip::ZipArchive::new(Cursor::new(&bytes)).unwrap();
+
+    for (name, _) in methods {
+        let mut group = c.benchmark_group(format!("read_entry/{name}"));
+        group.throughput(Throughput::Bytes(ENTRY_LEN as u64));
+        group.bench_function("rc-zip", |b| {
+            b.iter(|| {
+                let mut v = Vec::with_capacity(ENTRY_LEN);
+                archive
+                    .by_name(name)
+                    .unwrap()
+                    .reader()
+                    .read_to_end(&mut v)
+                    .unwrap();
+                v.len()
+            })
+        });
+        group.bench_function("zip", |b| {
+            b.iter(|| {
+                let mut v = Vec::with_capacity(ENTRY_LEN);
+                zip_archive
+                    .by_name(name)
+                    .unwrap()
+                    .read_to_end(&mut v)
+                    .unwrap();
+                v.len()
+            })
+        });
+        group.finish();
+    }
+}
+
+fn extract(c: &mut Criterion) {
+    let path = common::fixture("compare-extract.zip", |writer| {
+        let mut data = Data::new();
+        for i in 0..EXTRACTED_FILES {
+            let entry = NewEntry::new(format!("dir{}/file{i}.txt", i % 16));
+            writer.start_entry(entry.with_method(Method::Deflate))?;
+            writer.write_all(&data.text(EXTRACTED_LEN))?;
+        }
+        Ok(())
+    })
+    .unwrap();
+
+    let mut group = c.benchmark_group("extract");
+    group.sample_size(20);
+    group.throughput(Throughput::Bytes((EXTRACTED_FILES * EXTRACTED_LEN) as u64));
+    group.bench_function("rc-zip", |b| {
+        b.iter_batched(
+            || empty_dir("rc-zip"),
+            |dir| {
+                let file = File::open(&path).unwrap();
+                let archive = file.read_zip().unwrap();
+                archive.extract_all(dir, ExtractOptions::default()).unwrap()
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("zip", |b| {
+        b.iter_batched(
+            || empty_dir("zip"),
+            |dir| {
+                let file = File::open(&path).unwrap();
+                let mut archive = zip::ZipArchive::new(file).unwrap();
+                archive.extract(dir).unwrap()
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    if has_bsdtar() {
+        group.bench_function("libarchive", |b| {
+            b.iter_batched(
+                || empty_dir("libarchive"),
+                |dir| bsdtar_extract(&path, &dir),
+                BatchSize::PerIteration,
+            )
+        });
+    } else {
+        eprintln!("bsdtar not found, skipping libarchive");
+    }
+    group.finish();
+}
+
+/// Returns an empty directory to extract into, named after `name`
+fn empty_dir(name: &str) -> PathBuf {
+    let dir = common::tmp_dir().join("compare-extract").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn has_bsdtar() -> bool {
+    Command::new("bsdtar").arg("--version").output().is_ok()
+}
+
+/// Extracts with libarchive's command-line tool, which counts starting a
+/// process in its time.
+fn bsdtar_extract(archive: &Path, dir: &Path) {
+    let status = Command::new("bsdtar")
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "bsdtar failed: {status}");
+}
+
+criterion_group!(benches, central_directory, read_entry, extract);
+criterion_main!(benches);
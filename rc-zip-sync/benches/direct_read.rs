@@ -0,0 +1,138 @@
+//! Compares reading entries in small chunks, which goes through the entry
+//! reader's buffers, with reading them in large ones, which lets stored and
+//! deflated data skip them: see `EntryFsm::direct_read_len`.
+//!
+//! The archive is about 1 GiB. Run with
+//! `cargo bench -p rc-zip-sync --bench direct_read`.
+
+mod common;
+
+use common::Data;
+use humansize::{format_size, BINARY};
+use rc_zip::{
+    fsm::{NewEntry, Verification},
+    parse::Method,
+};
+use rc_zip_sync::ReadZip;
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+const STORED_LEN: usize = 1024 * 1024 * 1024;
+const DEFLATED_ENTRIES: usize = 512;
+const DEFLATED_LEN: usize = 256 * 1024;
+const SMALL_READ: usize = 16 * 1024;
+const LARGE_READ: usize = 1024 * 1024;
+const RUNS: usize = 3;
+
+fn main() -> io::Result<()> {
+    let path = archive()?;
+    let file = File::open(&path)?;
+    let archive = file.read_zip()?;
+    println!(
+        "{}: {}",
+        path.display(),
+        format_size(file.metadata()?.len(), BINARY)
+    );
+
+    let stored = archive.by_name("stored.bin").unwrap();
+    let range = stored.data_range()?;
+    report(
+        "stored, file read directly",
+        stored.uncompressed_size,
+        || {
+            let mut rd = File::open(&path)?;
+            rd.seek(SeekFrom::Start(range.start))?;
+            drain(rd.take(range.end - range.start), LARGE_READ)
+        },
+    )?;
+    for read_len in [SMALL_READ, LARGE_READ] {
+        let label = format!("stored, {} reads", format_size(read_len, BINARY));
+        report(&label, stored.uncompressed_size, || {
+            drain(stored.reader(), read_len)
+        })?;
+    }
+
+    report(
+        "stored, 1 MiB reads, no CRC-32",
+        stored.uncompressed_size,
+        || {
+            drain(
+                stored.reader_with_verification(Verification::Skip),
+                LARGE_READ,
+            )
+        },
+    )?;
+
+    let deflated: Vec<_> = archive
+        .entries()
+        .filter(|entry| entry.method == Method::Deflate)
+        .collect();
+    let deflated_len = deflated.iter().map(|entry| entry.uncompressed_size).sum();
+    let label = format!("deflated, {} reads", format_size(SMALL_READ, BINARY));
+    report(&label, deflated_len, || {
+        deflated
+            .iter()
+            .try_fold(0, |n, entry| Ok(n + drain(entry.reader(), SMALL_READ)?))
+    })?;
+    report("deflated, entry-sized reads", deflated_len, || {
+        deflated.iter().try_fold(0, |n, entry| {
+            Ok(n + drain(entry.reader(), entry.uncompressed_size as usize)?)
+        })
+    })?;
+    Ok(())
+}
+
+/// A stored entry of [STORED_LEN] bytes, and [DEFLATED_ENTRIES] deflated
+/// entries of [DEFLATED_LEN] bytes each.
+fn archive() -> io::Result<PathBuf> {
+    common::fixture("direct_read.zip", |writer| {
+        let mut data = Data::new();
+        writer.start_entry(NewEntry::new("stored.bin"))?;
+        let mut chunk = vec![0u8; LARGE_READ];
+        for _ in 0..STORED_LEN / chunk.len() {
+            data.fill_random(&mut chunk);
+            writer.write_all(&chunk)?;
+        }
+        for i in 0..DEFLATED_ENTRIES {
+            let entry = NewEntry::new(format!("deflated/{i}.txt")).with_method(Method::Deflate);
+            writer.start_entry(entry)?;
+            writer.write_all(&data.text(DEFLATED_LEN))?;
+        }
+        Ok(())
+    })
+}
+
+/// Reads `rd` to the end, `read_len` bytes at a time, returning how many
+/// bytes were read.
+fn drain(mut rd: impl Read, read_len: usize) -> io::Result<u64> {
+    let mut buf = vec![0u8; read_len];
+    let mut total = 0;
+    loop {
+        match rd.read(&mut buf)? {
+            0 => return Ok(total),
+            n => total += n as u64,
+        }
+    }
+}
+
+/// Runs `f` a few times, printing the best throughput.
+fn report(label: &str, expected: u64, mut f: impl FnMut() -> io::Result<u64>) -> io::Result<()> {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let n = f()?;
+        best = best.min(start.elapsed());
+        assert_eq!(n, expected, "{label}");
+    }
+    let per_second = expected as f64 / best.as_secs_f64();
+    println!(
+        "{label:>34}: {:>10}/s ({best:?})",
+        format_size(per_second as u64, BINARY)
+    );
+    Ok(())
+}
@@ -1,8 +1,10 @@
-use cfg_if::cfg_if;
 use clap::{Parser, Subcommand};
 use humansize::{format_size, BINARY};
-use rc_zip::parse::{Archive, EntryKind, Method, Version};
-use rc_zip_sync::{ReadZip, ReadZipStreaming};
+use rc_zip::{
+    fsm::{Verification, VerificationStatus},
+    parse::{Archive, EntryKind, Method, Version},
+};
+use rc_zip_sync::{Extractor, ReadZip, ReadZipStreaming};
 
 use std::{
     borrow::Cow,
@@ -71,6 +73,51 @@ enum Commands {
         #[arg(long)]
         dir: Option<String>,
     },
+    /// Read every entry, checking checksums, sizes and local headers
+    Check {
+        zipfile: PathBuf,
+
+        /// Fail on entries without a recorded checksum
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Write an entry, or a byte range of it, to stdout
+    Cat {
+        zipfile: PathBuf,
+
+        entry: String,
+
+        /// Bytes to write, as `start-end` (end excluded). Either can be
+        /// omitted, e.g. `1024-` or `-1024`.
+        #[arg(long, value_parser = parse_range)]
+        range: Option<ByteRange>,
+    },
+}
+
+#[derive(Clone, Copy, Default)]
+struct ByteRange {
+    start: u64,
+    end: Option<u64>,
+}
+
+fn parse_range(s: &str) -> Result<ByteRange, String> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expected start-end, got {s:?}"))?;
+    let parse = |x: &str| x.parse::<u64>().map_err(|e| format!("{x:?}: {e}"));
+
+    let range = ByteRange {
+        start: if start.is_empty() { 0 } else { parse(start)? },
+        end: if end.is_empty() {
+            None
+        } else {
+            Some(parse(end)?)
+        },
+    };
+    if range.end.is_some_and(|end| end < range.start) {
+        return Err(format!("range {s:?} ends before it starts"));
+    }
+    Ok(range)
 }
 
 fn main() {
@@ -197,66 +244,20 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             pbar.enable_steady_tick(Duration::from_millis(125));
 
             let start_time = std::time::SystemTime::now();
+            let extractor = Extractor::new(dir);
             for entry in reader.entries() {
-                let entry_name = match entry.sanitized_name() {
-                    Some(name) => name,
-                    None => continue,
-                };
-
-                pbar.set_message(entry_name.to_string());
+                pbar.set_message(entry.name.clone());
                 match entry.kind() {
                     EntryKind::Symlink => {
                         num_symlinks += 1;
-
-                        cfg_if! {
-                            if #[cfg(windows)] {
-                                let path = dir.join(entry_name);
-                                std::fs::create_dir_all(
-                                    path.parent()
-                                        .expect("all full entry paths should have parent paths"),
-                                )?;
-                                let mut entry_writer = File::create(path)?;
-                                let mut entry_reader = entry.reader();
-                                std::io::copy(&mut entry_reader, &mut entry_writer)?;
-                            } else {
-                                let path = dir.join(entry_name);
-                                std::fs::create_dir_all(
-                                    path.parent()
-                                        .expect("all full entry paths should have parent paths"),
-                                )?;
-                                if let Ok(metadata) = std::fs::symlink_metadata(&path) {
-                                    if metadata.is_file() {
-                                        std::fs::remove_file(&path)?;
-                                    }
-                                }
-
-                                let mut src = String::new();
-                                entry.reader().read_to_string(&mut src)?;
-
-                                // validate pointing path before creating a symbolic link
-                                if src.contains("..") {
-                                    continue;
-                                }
-                                std::os::unix::fs::symlink(src, &path)?;
-                            }
-                        }
+                        extractor.extract_entry(&entry, entry.reader())?;
                     }
                     EntryKind::Directory => {
                         num_dirs += 1;
-                        let path = dir.join(entry_name);
-                        std::fs::create_dir_all(
-                            path.parent()
-                                .expect("all full entry paths should have parent paths"),
-                        )?;
+                        extractor.extract_entry(&entry, entry.reader())?;
                     }
                     EntryKind::File => {
                         num_files += 1;
-                        let path = dir.join(entry_name);
-                        std::fs::create_dir_all(
-                            path.parent()
-                                .expect("all full entry paths should have parent paths"),
-                        )?;
-                        let mut entry_writer = File::create(path)?;
                         let entry_reader = entry.reader();
                         let before_entry_bytes = done_bytes;
                         let mut progress_reader =
@@ -264,8 +265,8 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                                 pbar.set_position(before_entry_bytes + prog.done);
                             });
 
-                        let copied_bytes = std::io::copy(&mut progress_reader, &mut entry_writer)?;
-                        done_bytes = before_entry_bytes + copied_bytes;
+                        extractor.extract_entry(&entry, &mut progress_reader)?;
+                        done_bytes = before_entry_bytes + progress_reader.progress.done;
                     }
                 }
             }
@@ -282,6 +283,54 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             let bps = (uncompressed_size as f64 / seconds) as u64;
             println!("Overall extraction speed: {} / s", format_size(bps, BINARY));
         }
+        Commands::Check { zipfile, strict } => {
+            let zipfile = File::open(zipfile)?;
+            let reader = zipfile.read_zip()?;
+            let verification = if strict {
+                Verification::Require
+            } else {
+                Verification::IfPresent
+            };
+
+            let report = reader.verify(verification);
+            for entry in &report.entries {
+                match &entry.result {
+                    Ok(VerificationStatus::NoChecksum) => {
+                        println!("{}: ok (no checksum)", entry.name)
+                    }
+                    Ok(_) => println!("{}: ok", entry.name),
+                    Err(e) => println!("{}: FAILED: {e}", entry.name),
+                }
+                for mismatch in &entry.mismatches {
+                    println!("    {mismatch:?}");
+                }
+            }
+            let failures = report.failures().count();
+            println!("{} entries, {} failed", report.entries.len(), failures);
+            if failures > 0 {
+                return Err(format!("{failures} entries failed verification").into());
+            }
+        }
+        Commands::Cat {
+            zipfile,
+            entry,
+            range,
+        } => {
+            let zipfile = File::open(zipfile)?;
+            let reader = zipfile.read_zip()?;
+            let entry = reader
+                .by_name(&entry)
+                .ok_or_else(|| format!("no entry named {entry:?}"))?;
+            let range = range.unwrap_or_default();
+
+            // decompressed data can't be seeked into, so read up to the
+            // start of the range and throw it away
+            let mut entry_reader = entry.reader();
+            io::copy(&mut (&mut entry_reader).take(range.start), &mut io::sink())?;
+
+            let len = range.end.map_or(u64::MAX, |end| end - range.start);
+            io::copy(&mut entry_reader.take(len), &mut io::stdout().lock())?;
+        }
         Commands::UnzipStreaming { zipfile, dir, .. } => {
             let zipfile = File::open(zipfile)?;
             let dir = PathBuf::from(dir.unwrap_or_else(|| ".".into()));
@@ -306,75 +355,30 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             let start_time = std::time::SystemTime::now();
 
             let mut entry_reader = zipfile.stream_zip_entries_throwing_caution_to_the_wind()?;
+            let extractor = Extractor::new(dir);
             loop {
-                let entry_name = match entry_reader.entry().sanitized_name() {
-                    Some(name) => name,
-                    None => continue,
-                };
-
-                pbar.set_message(entry_name.to_string());
-                match entry_reader.entry().kind() {
+                let entry = entry_reader.entry().clone();
+                pbar.set_message(entry.name.clone());
+                match entry.kind() {
                     EntryKind::Symlink => {
                         num_symlinks += 1;
-
-                        cfg_if! {
-                            if #[cfg(windows)] {
-                                let path = dir.join(entry_name);
-                                std::fs::create_dir_all(
-                                    path.parent()
-                                        .expect("all full entry paths should have parent paths"),
-                                )?;
-                                let mut entry_writer = File::create(path)?;
-                                std::io::copy(&mut entry_reader, &mut entry_writer)?;
-                            } else {
-                                let path = dir.join(entry_name);
-                                std::fs::create_dir_all(
-                                    path.parent()
-                                        .expect("all full entry paths should have parent paths"),
-                                )?;
-                                if let Ok(metadata) = std::fs::symlink_metadata(&path) {
-                                    if metadata.is_file() {
-                                        std::fs::remove_file(&path)?;
-                                    }
-                                }
-
-                                let mut src = String::new();
-                                entry_reader.read_to_string(&mut src)?;
-
-                                // validate pointing path before creating a symbolic link
-                                if src.contains("..") {
-                                    continue;
-                                }
-                                std::os::unix::fs::symlink(src, &path)?;
-                            }
-                        }
+                        extractor.extract_entry(&entry, &mut entry_reader)?;
                     }
                     EntryKind::Directory => {
                         num_dirs += 1;
-                        let path = dir.join(entry_name);
-                        std::fs::create_dir_all(
-                            path.parent()
-                                .expect("all full entry paths should have parent paths"),
-                        )?;
+                        extractor.extract_entry(&entry, &mut entry_reader)?;
                     }
                     EntryKind::File => {
                         num_files += 1;
-                        let path = dir.join(entry_name);
-                        std::fs::create_dir_all(
-                            path.parent()
-                                .expect("all full entry paths should have parent paths"),
-                        )?;
-                        let mut entry_writer = File::create(path)?;
                         let before_entry_bytes = done_bytes;
-                        let total = entry_reader.entry().uncompressed_size;
                         let mut progress_reader =
-                            ProgressReader::new(entry_reader, total, |prog| {
+                            ProgressReader::new(entry_reader, entry.uncompressed_size, |prog| {
                                 pbar.set_position(before_entry_bytes + prog.done);
                             });
 
-                        let copied_bytes = std::io::copy(&mut progress_reader, &mut entry_writer)?;
-                        uncompressed_size += copied_bytes;
-                        done_bytes = before_entry_bytes + copied_bytes;
+                        extractor.extract_entry(&entry, &mut progress_reader)?;
+                        uncompressed_size += progress_reader.progress.done;
+                        done_bytes = before_entry_bytes + progress_reader.progress.done;
                         entry_reader = progress_reader.into_inner();
                     }
                 }
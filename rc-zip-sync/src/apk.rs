@@ -0,0 +1,30 @@
+use rc_zip::{
+    apk::{EntryAlignment, ZipalignOptions},
+    error::Error,
+};
+
+use crate::{ArchiveHandle, HasCursor};
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Returns where each entry's data starts, and how it should be aligned
+    /// for Android, see [rc_zip::apk]. This reads every local header.
+    pub fn alignments(&self, options: &ZipalignOptions) -> Result<Vec<EntryAlignment>, Error> {
+        self.entries()
+            .map(|entry| Ok(options.entry_alignment(&entry, entry.data_offset()?)))
+            .collect()
+    }
+
+    /// Returns the entries whose data isn't aligned the way `zipalign`
+    /// would have, see [Self::alignments]: none for a properly aligned APK.
+    pub fn zipalign_violations(
+        &self,
+        options: &ZipalignOptions,
+    ) -> Result<Vec<EntryAlignment>, Error> {
+        let mut alignments = self.alignments(options)?;
+        alignments.retain(|alignment| !alignment.is_aligned());
+        Ok(alignments)
+    }
+}
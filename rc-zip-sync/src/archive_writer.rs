@@ -0,0 +1,134 @@
+use std::io;
+
+use rc_zip::{
+    error::Error,
+    fsm::{ArchiveWriterFsm, Compression, NewEntry},
+    parse::{Archive, Entry, Version},
+};
+
+/// Writes a zip archive to any [io::Write], see [ArchiveWriterFsm].
+///
+/// Start an entry with [Self::start_entry], then write its data through the
+/// [io::Write] implementation. Call [Self::finish] once all entries are
+/// written, otherwise the archive is left without a central directory.
+pub struct ArchiveWriter<W>
+where
+    W: io::Write,
+{
+    fsm: ArchiveWriterFsm,
+    wr: W,
+}
+
+impl<W> ArchiveWriter<W>
+where
+    W: io::Write,
+{
+    /// Create a new archive writer, writing to `wr`
+    pub fn new(wr: W) -> Self {
+        Self {
+            fsm: ArchiveWriterFsm::new(),
+            wr,
+        }
+    }
+
+    /// Appends entries to an existing archive, see [ArchiveWriterFsm::append]:
+    /// `wr` is seeked to the start of its central directory, which is then
+    /// overwritten.
+    ///
+    /// The new archive may be shorter than the existing one, for example if
+    /// it had a zip64 end of central directory record: once finished, files
+    /// should be truncated to the writer's position, with
+    /// [std::fs::File::set_len].
+    pub fn append(archive: &Archive, mut wr: W) -> Result<Self, Error>
+    where
+        W: io::Seek,
+    {
+        let fsm = ArchiveWriterFsm::append(archive)?;
+        wr.seek(io::SeekFrom::Start(archive.directory_offset()))?;
+        Ok(Self { fsm, wr })
+    }
+
+    /// Writes zip64 records whether they're needed or not, see
+    /// [ArchiveWriterFsm::force_zip64]
+    pub fn force_zip64(mut self, force: bool) -> Self {
+        self.fsm = self.fsm.force_zip64(force);
+        self
+    }
+
+    /// Sets the "version made by" of new entries, see
+    /// [ArchiveWriterFsm::creator_version]
+    pub fn creator_version(mut self, version: Version) -> Self {
+        self.fsm = self.fsm.creator_version(version);
+        self
+    }
+
+    /// Lets `selector` pick each entry's compression method and level, from
+    /// the entry and its first bytes, see
+    /// [ArchiveWriterFsm::select_compression]
+    pub fn select_compression(
+        mut self,
+        selector: impl FnMut(&NewEntry, &[u8]) -> Compression + Send + Sync + 'static,
+    ) -> Self {
+        self.fsm = self.fsm.select_compression(selector);
+        self
+    }
+
+    /// Sets the archive comment, written when the archive is finished
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.fsm.set_comment(comment);
+    }
+
+    /// Finishes the current entry, if any, and starts a new one
+    pub fn start_entry(&mut self, entry: NewEntry) -> Result<(), Error> {
+        self.fsm.start_entry(entry)?;
+        self.flush_output()?;
+        Ok(())
+    }
+
+    /// Finishes the current entry, if any, and copies `entry` from another
+    /// archive without recompressing it, see [ArchiveWriterFsm::start_raw_entry].
+    /// `rd` gives its data as stored, e.g. from
+    /// [EntryHandle::raw_entry](crate::EntryHandle::raw_entry).
+    pub fn copy_raw_entry(&mut self, entry: &Entry, mut rd: impl io::Read) -> Result<(), Error> {
+        self.fsm.start_raw_entry(entry)?;
+        self.flush_output()?;
+        io::copy(&mut rd, self)?;
+        self.fsm.finish_entry()?;
+        self.flush_output()?;
+        Ok(())
+    }
+
+    /// Finishes the current entry, if any, and writes the central directory.
+    /// Returns the underlying writer, flushed.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.fsm.finish()?;
+        self.flush_output()?;
+        self.wr.flush()?;
+        Ok(self.wr)
+    }
+
+    /// Writes all of the state machine's output to the underlying writer
+    fn flush_output(&mut self) -> io::Result<()> {
+        let output = self.fsm.output();
+        let n = output.len();
+        self.wr.write_all(output)?;
+        self.fsm.consume(n);
+        Ok(())
+    }
+}
+
+impl<W> io::Write for ArchiveWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fsm.write(buf)?;
+        self.flush_output()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_output()?;
+        self.wr.flush()
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use rc_zip::error::Error;
+
+/// A flag for cooperatively cancelling long reads and extractions, from
+/// another thread.
+///
+/// Clones share the same flag: hand one to
+/// [Extractor::with_cancellation](crate::Extractor::with_cancellation) or
+/// [EntryHandle::reader_with_cancellation](crate::EntryHandle::reader_with_cancellation),
+/// and call [CancellationToken::cancel] on another. It's checked between
+/// buffer fills, so work stops within one buffer's worth of data, with
+/// [Error::Cancelled] (wrapped in an [std::io::Error] for readers).
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that isn't cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels everything this token, or any of its clones, was given to.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether [CancellationToken::cancel] was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails with [Error::Cancelled] if the token was cancelled.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use std::{io, path::Path};
+
+use cap_std::fs::Dir;
+use rc_zip::parse::EntryKind;
+use tracing::trace;
+
+use crate::{ArchiveHandle, HasCursor};
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Extracts all entries into `dir`.
+    ///
+    /// All filesystem operations go through [cap_std::fs::Dir], so the OS
+    /// refuses any path that would resolve outside of `dir` — even through
+    /// `..` components or symlinks created by earlier entries. This is on
+    /// top of [rc_zip::parse::Entry::safe_path], not instead of it:
+    /// entries without a safe path are skipped.
+    ///
+    /// Symlink entries are only created on non-Windows platforms, and only
+    /// if they have a [safe target](rc_zip::parse::Entry::safe_symlink_target).
+    /// On Windows, they're extracted as regular files containing the target.
+    ///
+    /// See also [crate::Extractor], which doesn't need cap-std, and rejects
+    /// unsafe entries instead of skipping them.
+    pub fn extract_to_dir(&self, dir: &Dir) -> io::Result<()> {
+        for entry in self.entries() {
+            let name = match entry.safe_path() {
+                Some(name) => name,
+                None => {
+                    trace!(name = %entry.name, "extract_to_dir: skipping entry with unsafe name");
+                    continue;
+                }
+            };
+
+            match entry.kind() {
+                EntryKind::Directory => {
+                    dir.create_dir_all(&name)?;
+                }
+                EntryKind::File => {
+                    create_parent_dirs(dir, &name)?;
+                    let mut writer = dir.create(&name)?;
+                    io::copy(&mut entry.reader(), &mut writer)?;
+                }
+                EntryKind::Symlink => {
+                    create_parent_dirs(dir, &name)?;
+
+                    #[cfg(windows)]
+                    {
+                        let mut writer = dir.create(&name)?;
+                        io::copy(&mut entry.reader(), &mut writer)?;
+                    }
+
+                    #[cfg(not(windows))]
+                    {
+                        use std::io::Read;
+
+                        let mut target = String::new();
+                        entry.reader().read_to_string(&mut target)?;
+
+                        let target = match entry.safe_symlink_target(&target) {
+                            Some(target) => target,
+                            None => {
+                                trace!(name = %entry.name, %target, "extract_to_dir: skipping unsafe symlink");
+                                continue;
+                            }
+                        };
+
+                        if let Ok(metadata) = dir.symlink_metadata(&name) {
+                            if metadata.is_file() {
+                                dir.remove_file(&name)?;
+                            }
+                        }
+                        dir.symlink(target, &name)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn create_parent_dirs(dir: &Dir, name: &Path) -> io::Result<()> {
+    match name.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => dir.create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
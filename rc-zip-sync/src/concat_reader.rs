@@ -0,0 +1,103 @@
+use std::{
+    io::{self, Read},
+    sync::{Mutex, PoisonError},
+};
+
+use rc_zip::{fsm::Buffer, parse::Entry};
+
+use crate::{entry_reader::EntryReader, EntryHandle, HasCursor};
+
+/// A function returning bytes to insert before an entry, see
+/// [ConcatReader::with_separator].
+///
+/// It's only ever called through `&mut self`, so it doesn't need to be Sync
+/// for the reader to be: the mutex is never locked.
+type Separator<'a> = Mutex<Box<dyn FnMut(&Entry) -> Vec<u8> + Send + 'a>>;
+
+/// Reads the uncompressed contents of several entries back-to-back, as if
+/// they were a single file. Useful for payloads split into numbered entries.
+///
+/// Entries are read in the order they're given in, which doesn't need to be
+/// the order they appear in the archive. They're all read with the same
+/// buffer, see [EntryHandle::reader_with_buffer].
+pub struct ConcatReader<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    entries: std::vec::IntoIter<EntryHandle<'a, F>>,
+    current: Option<EntryReader<io::Take<F::Cursor<'a>>>>,
+    /// The buffer the last entry was read with, for the next one
+    buffer: Option<Buffer>,
+    separator: Option<Separator<'a>>,
+    pending: Vec<u8>,
+    pending_pos: usize,
+    started: bool,
+}
+
+impl<'a, F> ConcatReader<'a, F>
+where
+    F: HasCursor,
+{
+    /// Creates a reader for the given entries, in order.
+    pub fn new(entries: impl IntoIterator<Item = EntryHandle<'a, F>>) -> Self {
+        Self {
+            entries: entries.into_iter().collect::<Vec<_>>().into_iter(),
+            current: None,
+            buffer: None,
+            separator: None,
+            pending: Vec::new(),
+            pending_pos: 0,
+            started: false,
+        }
+    }
+
+    /// Calls `separator` between entries, with the entry that's about to be
+    /// read, and inserts the bytes it returns.
+    pub fn with_separator(mut self, separator: impl FnMut(&Entry) -> Vec<u8> + Send + 'a) -> Self {
+        self.separator = Some(Mutex::new(Box::new(separator)));
+        self
+    }
+}
+
+impl<F> Read for ConcatReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.pending_pos < self.pending.len() {
+                let n = buf.len().min(self.pending.len() - self.pending_pos);
+                buf[..n].copy_from_slice(&self.pending[self.pending_pos..][..n]);
+                self.pending_pos += n;
+                return Ok(n);
+            }
+
+            if let Some(current) = &mut self.current {
+                match current.read(buf)? {
+                    0 => self.buffer = self.current.take().and_then(EntryReader::into_buffer),
+                    n => return Ok(n),
+                }
+            }
+
+            let Some(next) = self.entries.next() else {
+                return Ok(0);
+            };
+            if self.started {
+                if let Some(separator) = &mut self.separator {
+                    let separator = separator.get_mut().unwrap_or_else(PoisonError::into_inner);
+                    self.pending = separator(&next);
+                    self.pending_pos = 0;
+                }
+            }
+            self.started = true;
+            self.current = Some(match self.buffer.take() {
+                Some(buffer) => next.reader_with_buffer(buffer),
+                None => next.entry_reader(),
+            });
+        }
+    }
+}
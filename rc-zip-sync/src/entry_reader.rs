@@ -1,28 +1,81 @@
 use rc_zip::{
-    fsm::{EntryFsm, FsmResult},
+    error::Error,
+    fsm::{Buffer, DecompressionLimits, EntryFsm, EntryTrailer, FsmResult, Verification},
     parse::Entry,
+    progress::Progress,
 };
-use std::io;
+use std::{io, sync::Arc};
 use tracing::trace;
 
-pub(crate) struct EntryReader<R>
+use crate::CancellationToken;
+
+/// Reads an entry's data, decompressing and checking it as it goes, see
+/// [EntryHandle::reader](crate::EntryHandle::reader).
+pub struct EntryReader<R>
 where
     R: io::Read,
 {
     rd: R,
     fsm: Option<EntryFsm>,
+    trailer: Option<EntryTrailer>,
+    /// The state machine's buffer, once it's done with it
+    buffer: Option<Buffer>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl<R> EntryReader<R>
 where
     R: io::Read,
 {
-    pub(crate) fn new(entry: &Entry, rd: R) -> Self {
+    pub(crate) fn new(entry: &Entry, rd: R, buffer: Option<Buffer>) -> Self {
         Self {
             rd,
-            fsm: Some(EntryFsm::new(Some(entry.clone()), None)),
+            fsm: Some(EntryFsm::new(Some(entry.clone()), buffer)),
+            trailer: None,
+            buffer: None,
+            cancellation: None,
         }
     }
+
+    /// Consumes the reader, returning its buffer to read another entry with,
+    /// see [EntryHandle::reader_with_buffer](crate::EntryHandle::reader_with_buffer).
+    /// Returns `None` if reading the entry failed, which loses the buffer.
+    pub fn into_buffer(self) -> Option<Buffer> {
+        self.buffer.or_else(|| self.fsm.map(EntryFsm::into_buffer))
+    }
+
+    /// Returns the data descriptor that followed the entry's data, once it's
+    /// been read to the end: `None` if there wasn't any, or if the entry
+    /// isn't read yet.
+    pub fn trailer(&self) -> Option<&EntryTrailer> {
+        self.trailer.as_ref()
+    }
+
+    pub(crate) fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_limits(limits));
+        self
+    }
+
+    pub(crate) fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_progress(progress));
+        self
+    }
+
+    pub(crate) fn with_verification(mut self, verification: Verification) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_verification(verification));
+        self
+    }
+
+    pub(crate) fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    #[cfg(feature = "aes")]
+    pub(crate) fn with_password(mut self, password: &[u8]) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_password(password));
+        self
+    }
 }
 
 impl<R> io::Read for EntryReader<R>
@@ -31,14 +84,31 @@ where
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         loop {
+            if let Some(cancellation) = &self.cancellation {
+                cancellation.check()?;
+            }
+
             let mut fsm = match self.fsm.take() {
                 Some(fsm) => fsm,
                 None => return Ok(0),
             };
 
+            // stored data can skip our buffer when the caller's is big enough,
+            // once what's buffered is processed
+            let direct_len = fsm.direct_read_len(buf.len());
+            if let Some(len) = direct_len.filter(|len| *len > 0) {
+                let n = self.rd.read(&mut buf[..len])?;
+                if n > 0 {
+                    fsm.fill_direct(&buf[..n])?;
+                    self.fsm = Some(fsm);
+                    return Ok(n);
+                }
+                // an early EOF is reported by the state machine
+            }
+
             #[allow(clippy::needless_late_init)] // don't tell me what to do
             let filled_bytes;
-            if fsm.wants_read() {
+            if direct_len != Some(0) && fsm.wants_read() {
                 tracing::trace!(space_avail = fsm.space().len(), "fsm wants read");
                 let n = self.rd.read(fsm.space())?;
                 fsm.fill(n);
@@ -65,7 +135,9 @@ where
                         ));
                     }
                 }
-                FsmResult::Done(_) => {
+                FsmResult::Done((buffer, trailer)) => {
+                    self.trailer = trailer;
+                    self.buffer = Some(buffer);
                     // neat!
                     return Ok(0);
                 }
@@ -73,3 +145,13 @@ where
         }
     }
 }
+
+/// Errors from the state machine come wrapped in an [io::Error], this gets
+/// them back out.
+pub(crate) fn unwrap_io_error(e: io::Error) -> Error {
+    if e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        *e.into_inner().unwrap().downcast::<Error>().unwrap()
+    } else {
+        Error::IO(e)
+    }
+}
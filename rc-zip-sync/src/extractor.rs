@@ -0,0 +1,743 @@
+use std::{
+    fs::{self, File, FileTimes},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::SystemTime,
+};
+
+use rc_zip::{
+    apple_double::is_apple_double,
+    chrono::{DateTime, Local, TimeZone, Utc},
+    error::{Error, FormatError},
+    fsm::{Verification, VerificationStatus},
+    parse::{Entry, EntryKind, Method, TimestampSource},
+    progress::Progress,
+};
+use tracing::trace;
+
+#[cfg(windows)]
+use rc_zip::parse::MsdosMode;
+
+use crate::{entry_reader::EntryReader, ArchiveHandle, CancellationToken, EntryHandle, HasCursor};
+
+/// Extracts entries into a directory, making sure nothing is written outside
+/// of it.
+///
+/// Entries are extracted to their [Entry::safe_path], and what symlink
+/// entries may point to depends on [SymlinkBehavior]: other entries are
+/// rejected with [FormatError::UnsafePath]. Existing symlinks are never
+/// followed, whether they come from an earlier entry or were there before:
+/// going through one is rejected the same way, and one where an entry is
+/// extracted is replaced.
+///
+/// Symlink entries are only created as symlinks on Unix. Elsewhere, they're
+/// extracted as regular files containing the target, unless they're denied.
+///
+/// The destination is checked before it's written to, so this doesn't
+/// protect against another process changing it at the same time: the
+/// `cap-std` feature's [ArchiveHandle::extract_to_dir] does, but needs an
+/// OS that supports it.
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    dir: PathBuf,
+    symlinks: SymlinkBehavior,
+    timestamps: TimestampBehavior,
+    verification: Verification,
+    permissions: bool,
+    ownership: bool,
+    msdos_attributes: bool,
+    apple_double: bool,
+    progress: Option<Arc<dyn Progress>>,
+    cancellation: Option<CancellationToken>,
+}
+
+/// What [Extractor] does with symlink entries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SymlinkBehavior {
+    /// Symlink entries are rejected, whatever their target.
+    Deny,
+
+    /// Symlink entries are created if they point inside the destination
+    /// directory, see [Entry::safe_symlink_target], and rejected otherwise.
+    #[default]
+    AllowWithinRoot,
+
+    /// Symlink entries are created whatever their target, which is only
+    /// reasonable for trusted archives: a symlink to `/etc` is then fine.
+    /// Later entries still aren't extracted through them.
+    AllowAll,
+}
+
+/// What [Extractor] does with entries' timestamps, see
+/// [Entry::timestamp_source] for where they come from.
+///
+/// Files and directories get their modification time, and their access
+/// time if the archive has one. Files also get their creation time on
+/// Windows. Symlinks keep the time they're extracted at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TimestampBehavior {
+    /// Timestamps are left alone: what's extracted is modified when it's
+    /// extracted.
+    #[default]
+    Skip,
+
+    /// Timestamps are applied, with MS-DOS timestamps taken as UTC, like
+    /// [Entry::modified] does.
+    Utc,
+
+    /// Timestamps are applied, with MS-DOS timestamps taken as local time,
+    /// which is what they usually are when the archive was made in the
+    /// same time zone.
+    Local,
+}
+
+/// Options for [ArchiveHandle::extract_all].
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// How many worker threads decompress file data. Defaults to the
+    /// available parallelism.
+    pub threads: usize,
+
+    /// See [Extractor::with_permissions].
+    pub permissions: bool,
+
+    /// See [Extractor::with_ownership].
+    pub ownership: bool,
+
+    /// See [Extractor::with_msdos_attributes].
+    pub msdos_attributes: bool,
+
+    /// See [Extractor::with_apple_double].
+    pub apple_double: bool,
+
+    /// See [Extractor::with_symlink_behavior].
+    pub symlinks: SymlinkBehavior,
+
+    /// See [Extractor::with_timestamps].
+    pub timestamps: TimestampBehavior,
+
+    /// See [Extractor::with_verification].
+    pub verification: Verification,
+
+    /// See [Extractor::with_progress].
+    pub progress: Option<Arc<dyn Progress>>,
+
+    /// See [Extractor::with_cancellation].
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            permissions: false,
+            ownership: false,
+            msdos_attributes: false,
+            apple_double: true,
+            symlinks: Default::default(),
+            timestamps: Default::default(),
+            verification: Default::default(),
+            progress: None,
+            cancellation: None,
+        }
+    }
+}
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor + Sync,
+{
+    /// Extracts all entries into `dest` concurrently, with an [Extractor]
+    /// configured from `options`. See [Extractor::extract_parallel].
+    pub fn extract_all(
+        &self,
+        dest: impl Into<PathBuf>,
+        options: ExtractOptions,
+    ) -> Result<(), Error> {
+        let mut extractor = Extractor::new(dest)
+            .with_permissions(options.permissions)
+            .with_ownership(options.ownership)
+            .with_msdos_attributes(options.msdos_attributes)
+            .with_apple_double(options.apple_double)
+            .with_symlink_behavior(options.symlinks)
+            .with_timestamps(options.timestamps)
+            .with_verification(options.verification);
+        extractor.progress = options.progress;
+        extractor.cancellation = options.cancellation;
+        extractor.extract_parallel(self, options.threads)
+    }
+}
+
+impl Extractor {
+    /// Extract into `dir`, which is created if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            symlinks: Default::default(),
+            timestamps: Default::default(),
+            verification: Default::default(),
+            permissions: false,
+            ownership: false,
+            msdos_attributes: false,
+            apple_double: true,
+            progress: None,
+            cancellation: None,
+        }
+    }
+
+    /// Sets whether files get the [permissions](rc_zip::parse::Mode::permissions)
+    /// they have in the archive, off by default. This is only done on
+    /// Unix, and only for files: directories keep their default
+    /// permissions, so later entries can always be extracted into them.
+    /// Files whose permissions are 0, made on systems that don't have
+    /// any, keep the default ones.
+    pub fn with_permissions(mut self, permissions: bool) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Sets whether what's extracted gets the owner it has in the archive,
+    /// see [Entry::uid] and [Entry::gid], off by default. This is only done
+    /// on Unix, for entries that record one, and typically needs to run as
+    /// root: otherwise, extraction fails with a permission error.
+    pub fn with_ownership(mut self, ownership: bool) -> Self {
+        self.ownership = ownership;
+        self
+    }
+
+    /// Sets whether files get the read-only, hidden and system attributes
+    /// they have in the archive, see [Entry::msdos_mode], off by default.
+    /// This is only done on Windows, and only for files. Elsewhere, the
+    /// read-only attribute is part of [Extractor::with_permissions].
+    pub fn with_msdos_attributes(mut self, msdos_attributes: bool) -> Self {
+        self.msdos_attributes = msdos_attributes;
+        self
+    }
+
+    /// Sets whether the AppleDouble files macOS adds to archives, and the
+    /// `__MACOSX/` directory they're in, are extracted by
+    /// [Extractor::extract] and [Extractor::extract_parallel], see
+    /// [is_apple_double]. They are by default, like any other entry.
+    pub fn with_apple_double(mut self, apple_double: bool) -> Self {
+        self.apple_double = apple_double;
+        self
+    }
+
+    /// Sets what to do with symlink entries, see [SymlinkBehavior].
+    pub fn with_symlink_behavior(mut self, symlinks: SymlinkBehavior) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets what to do with entries' timestamps, see [TimestampBehavior].
+    ///
+    /// A directory's modification time changes when entries are extracted
+    /// into it: [Extractor::extract] and [Extractor::extract_parallel] apply
+    /// directories' timestamps again once everything's extracted, but when
+    /// calling [Extractor::extract_entry] yourself, it's up to you.
+    pub fn with_timestamps(mut self, timestamps: TimestampBehavior) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Sets how entries' CRC-32 is checked, see [Verification].
+    ///
+    /// With [Verification::Skip], on Linux, the data of entries stored
+    /// without compression is copied from the archive to the extracted
+    /// file by the kernel, with `copy_file_range` or `sendfile`, when the
+    /// archive is read from a [File]: that's much faster for archives of
+    /// mostly stored entries, like asset packs.
+    pub fn with_verification(mut self, verification: Verification) -> Self {
+        self.verification = verification;
+        self
+    }
+
+    /// Sets what's told about progress as entries are extracted, see
+    /// [Progress]: [Progress::entry_done] is called for every file and
+    /// symlink, once it's been read entirely.
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Sets a token that stops extraction, with [Error::Cancelled], once
+    /// it's cancelled: it's checked before each entry, and as entry data is
+    /// read. The file being written when that happens is removed, but
+    /// entries extracted before it are left in place.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Returns the destination directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Extracts all entries of `archive`, stopping at the first one that
+    /// can't be extracted.
+    pub fn extract<F>(&self, archive: &ArchiveHandle<'_, F>) -> Result<(), Error>
+    where
+        F: HasCursor,
+    {
+        let mut dirs = Vec::new();
+        for entry in archive.entries().filter(|entry| self.wants(entry)) {
+            self.check_cancelled()?;
+            let (path, file) = self.create(&entry, self.reader(&entry))?;
+            if let Some(file) = file {
+                self.write_entry(&entry, file, &path)?;
+            }
+            if matches!(entry.kind(), EntryKind::Directory) {
+                dirs.push((entry, path));
+            }
+        }
+        for (entry, path) in &dirs {
+            self.set_dir_times(entry, path)?;
+        }
+        Ok(())
+    }
+
+    /// Extracts all entries of `archive` using `threads` worker threads,
+    /// stopping at the first batch that has an entry that can't be
+    /// extracted.
+    ///
+    /// Entries are handled in batches: within a batch, directories,
+    /// symlinks and files are created in order, like [Extractor::extract]
+    /// does, then the workers decompress file data into them, each with
+    /// its own cursor. The end result is the same as extracting serially:
+    /// once an entry fails, or extraction is cancelled, the workers stop,
+    /// and files created for entries they didn't get to are removed.
+    pub fn extract_parallel<F>(
+        &self,
+        archive: &ArchiveHandle<'_, F>,
+        threads: usize,
+    ) -> Result<(), Error>
+    where
+        F: HasCursor + Sync,
+    {
+        let threads = threads.max(1);
+        let entries: Vec<_> = archive
+            .entries()
+            .filter(|entry| self.wants(entry))
+            .collect();
+        let mut dirs = Vec::new();
+        // big enough that one large file doesn't leave other workers idle
+        // for long, small enough not to run out of file descriptors
+        for batch in entries.chunks(threads * 16) {
+            let mut files = Vec::new();
+            for entry in batch {
+                match self.create(entry, self.reader(entry))? {
+                    (path, Some(file)) => files.push((entry, path, file)),
+                    (path, None) if matches!(entry.kind(), EntryKind::Directory) => {
+                        dirs.push((entry, path))
+                    }
+                    _ => {}
+                }
+            }
+
+            let files = Mutex::new(files.into_iter());
+            // once a worker fails, the others stop taking files
+            let failed = AtomicBool::new(false);
+            let res = thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads)
+                    .map(|_| {
+                        scope.spawn(|| -> Result<(), Error> {
+                            while !failed.load(Ordering::Relaxed) {
+                                let next = files.lock().unwrap().next();
+                                let Some((entry, path, file)) = next else {
+                                    break;
+                                };
+                                if let Err(e) = self.check_cancelled() {
+                                    drop(file);
+                                    let _ = fs::remove_file(&path);
+                                    failed.store(true, Ordering::Relaxed);
+                                    return Err(e);
+                                }
+                                trace!(name = %entry.name, "extract: writing file");
+                                if let Err(e) = self.write_entry(entry, file, &path) {
+                                    failed.store(true, Ordering::Relaxed);
+                                    return Err(e);
+                                }
+                            }
+                            Ok(())
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|worker| worker.join().unwrap())
+                    .fold(Ok(()), Result::and)
+            });
+            if res.is_err() {
+                // files created for entries nobody got to would be left
+                // empty, where extracting serially wouldn't have created them
+                for (_, path, file) in files.into_inner().unwrap() {
+                    drop(file);
+                    let _ = fs::remove_file(&path);
+                }
+            }
+            res?;
+        }
+        for (entry, path) in dirs {
+            self.set_dir_times(entry, &path)?;
+        }
+        Ok(())
+    }
+
+    /// Extracts `entry`, whose data is read from `rd`: from
+    /// [EntryHandle::reader](crate::EntryHandle::reader), or from a
+    /// [StreamingEntryReader](crate::StreamingEntryReader), for example.
+    /// Returns the path it was extracted to.
+    ///
+    /// Nothing is read from `rd` for directories.
+    pub fn extract_entry(&self, entry: &Entry, mut rd: impl Read) -> Result<PathBuf, Error> {
+        self.check_cancelled()?;
+        let (path, file) = self.create(entry, &mut rd)?;
+        if let Some(file) = file {
+            self.write(entry, rd, file, &path)?;
+        }
+        Ok(path)
+    }
+
+    /// Writes the data of `entry` to `file`, which is at `path`: copied
+    /// directly if possible, see [Extractor::with_verification].
+    fn write_entry<F>(
+        &self,
+        entry: &EntryHandle<'_, F>,
+        file: File,
+        path: &Path,
+    ) -> Result<(), Error>
+    where
+        F: HasCursor,
+    {
+        if self.copy_stored(entry, &file)? {
+            return self.finish(entry, &file);
+        }
+        self.write(entry, self.reader(entry), file, path)
+    }
+
+    /// Copies the data of `entry` to `file` within the kernel, if it's stored
+    /// without compression, read from a file, and its CRC-32 isn't checked.
+    /// Returns false if that's not possible, in which case nothing was
+    /// written.
+    fn copy_stored<F>(&self, entry: &EntryHandle<'_, F>, file: &File) -> Result<bool, Error>
+    where
+        F: HasCursor,
+    {
+        let Some(src) = entry.file().as_file() else {
+            return Ok(false);
+        };
+        if entry.method != Method::Store
+            || entry.is_encrypted()
+            || self.verification != Verification::Skip
+        {
+            return Ok(false);
+        }
+
+        let range = entry.data_range()?;
+        trace!(name = %entry.name, ?range, "extract: copying stored data");
+        if !kernel_copy::copy(src, range.start, range.end - range.start, file)? {
+            return Ok(false);
+        }
+        if let Some(progress) = &self.progress {
+            progress.entry(entry, entry.compressed_size, entry.uncompressed_size);
+            progress.entry_done(entry, VerificationStatus::Skipped);
+        }
+        Ok(true)
+    }
+
+    /// Returns a reader for `entry`, which reports to
+    /// [Extractor::with_progress] and stops on [Extractor::with_cancellation].
+    fn reader<'a, F>(&self, entry: &EntryHandle<'a, F>) -> EntryReader<io::Take<F::Cursor<'a>>>
+    where
+        F: HasCursor,
+    {
+        let mut rd = entry.entry_reader().with_verification(self.verification);
+        if let Some(progress) = &self.progress {
+            rd = rd.with_progress(progress.clone());
+        }
+        if let Some(cancellation) = &self.cancellation {
+            rd = rd.with_cancellation(cancellation.clone());
+        }
+        rd
+    }
+
+    /// Whether `entry` is extracted along with the rest of the archive.
+    fn wants(&self, entry: &Entry) -> bool {
+        self.apple_double || !is_apple_double(&entry.name)
+    }
+
+    fn check_cancelled(&self) -> Result<(), Error> {
+        match &self.cancellation {
+            Some(cancellation) => cancellation.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the data read from `rd` to `file`, which is at `path` and
+    /// was created for `entry`. If that's cancelled, the partially written
+    /// file is removed.
+    fn write(
+        &self,
+        entry: &Entry,
+        mut rd: impl Read,
+        mut file: File,
+        path: &Path,
+    ) -> Result<(), Error> {
+        match io::copy(&mut rd, &mut file) {
+            Ok(_) => self.finish(entry, &file),
+            Err(e) => {
+                if self.check_cancelled().is_err() {
+                    drop(file);
+                    let _ = fs::remove_file(path);
+                    return Err(Error::Cancelled);
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Applies what's left of `entry`'s metadata to `file`, once its data is
+    /// written.
+    fn finish(&self, entry: &Entry, file: &File) -> Result<(), Error> {
+        if let Some(times) = self.file_times(entry) {
+            file.set_times(times)?;
+        }
+        // only now, so the file can still be written to
+        #[cfg(windows)]
+        if self.msdos_attributes && entry.msdos_mode().has(MsdosMode::READ_ONLY) {
+            let mut permissions = file.metadata()?.permissions();
+            permissions.set_readonly(true);
+            file.set_permissions(permissions)?;
+        }
+        Ok(())
+    }
+
+    /// Creates what `entry` should be extracted to. Directories and
+    /// symlinks are done then, files are returned open to have their data
+    /// written. Returns the path it was extracted to.
+    fn create(&self, entry: &Entry, mut rd: impl Read) -> Result<(PathBuf, Option<File>), Error> {
+        if matches!(entry.kind(), EntryKind::Symlink) && self.symlinks == SymlinkBehavior::Deny {
+            trace!(name = %entry.name, "extract: denying symlink");
+            return Err(unsafe_path(entry));
+        }
+        let path = self.prepare(entry)?;
+
+        match entry.kind() {
+            EntryKind::Directory => {
+                fs::create_dir_all(&path)?;
+                self.set_owner(entry, &path)?;
+                self.set_dir_times(entry, &path)?;
+                Ok((path, None))
+            }
+            EntryKind::File => {
+                let mut options = File::options();
+                options.write(true).create(true).truncate(true);
+                #[cfg(windows)]
+                if self.msdos_attributes {
+                    use std::os::windows::fs::OpenOptionsExt;
+
+                    // those bits are the same as Windows' file attributes
+                    let attributes = entry.msdos_mode() & (MsdosMode::HIDDEN | MsdosMode::SYSTEM);
+                    options.attributes(attributes.0);
+                }
+                let file = options.open(&path)?;
+
+                #[cfg(unix)]
+                if self.permissions {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    let permissions = entry.mode.permissions();
+                    if permissions != 0 {
+                        file.set_permissions(fs::Permissions::from_mode(permissions))?;
+                    }
+                }
+                self.set_owner(entry, &path)?;
+                Ok((path, Some(file)))
+            }
+            EntryKind::Symlink => {
+                let mut target = String::new();
+                rd.read_to_string(&mut target)?;
+                let target = match self.symlinks {
+                    SymlinkBehavior::AllowAll => PathBuf::from(target),
+                    _ => entry
+                        .safe_symlink_target(&target)
+                        .ok_or_else(|| unsafe_path(entry))?,
+                };
+
+                #[cfg(unix)]
+                {
+                    if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_file()) {
+                        fs::remove_file(&path)?;
+                    }
+                    std::os::unix::fs::symlink(target, &path)?;
+                }
+
+                #[cfg(not(unix))]
+                fs::write(&path, target.to_string_lossy().as_bytes())?;
+                self.set_owner(entry, &path)?;
+                Ok((path, None))
+            }
+        }
+    }
+
+    /// Gives what `entry` was extracted to the owner it has in the archive,
+    /// with [Extractor::with_ownership]. Symlinks themselves are changed,
+    /// not what they point to.
+    fn set_owner(&self, entry: &Entry, path: &Path) -> Result<(), Error> {
+        #[cfg(unix)]
+        if self.ownership && (entry.uid.is_some() || entry.gid.is_some()) {
+            std::os::unix::fs::lchown(path, entry.uid, entry.gid)?;
+        }
+        #[cfg(not(unix))]
+        let _ = (entry, path);
+        Ok(())
+    }
+
+    /// Returns the timestamps `entry` should be extracted with, if they're
+    /// applied, see [Extractor::with_timestamps].
+    fn file_times(&self, entry: &Entry) -> Option<FileTimes> {
+        let local = match self.timestamps {
+            TimestampBehavior::Skip => return None,
+            TimestampBehavior::Utc => false,
+            TimestampBehavior::Local => entry.timestamp_source == TimestampSource::Msdos,
+        };
+        let time = |time: DateTime<Utc>| -> SystemTime {
+            if !local {
+                return time.into();
+            }
+            // ambiguous or missing local times are rare enough not to matter
+            Local
+                .from_local_datetime(&time.naive_utc())
+                .earliest()
+                .map_or(time, |local| local.with_timezone(&Utc))
+                .into()
+        };
+
+        let mut times = FileTimes::new().set_modified(time(entry.modified));
+        if let Some(accessed) = entry.accessed {
+            times = times.set_accessed(time(accessed));
+        }
+        #[cfg(windows)]
+        if let Some(created) = entry.created {
+            use std::os::windows::fs::FileTimesExt;
+
+            times = times.set_created(time(created));
+        }
+        Some(times)
+    }
+
+    /// Gives the directory `entry` was extracted to at `path` its
+    /// timestamps, see [Extractor::with_timestamps]. This is only done on
+    /// Unix, where directories can be opened like files.
+    fn set_dir_times(&self, entry: &Entry, path: &Path) -> Result<(), Error> {
+        #[cfg(unix)]
+        if let Some(times) = self.file_times(entry) {
+            File::open(path)?.set_times(times)?;
+        }
+        #[cfg(not(unix))]
+        let _ = (entry, path);
+        Ok(())
+    }
+
+    /// Creates the parent directories of `entry`, and removes the symlink
+    /// where it should be extracted, if there's one. Returns the path to
+    /// extract it to.
+    fn prepare(&self, entry: &Entry) -> Result<PathBuf, Error> {
+        let relative = entry.safe_path().ok_or_else(|| unsafe_path(entry))?;
+        fs::create_dir_all(&self.dir)?;
+
+        let mut path = self.dir.clone();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            path.push(component);
+            let is_last = components.peek().is_none();
+
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    if !is_last {
+                        trace!(name = %entry.name, "extract: refusing to go through symlink {}", path.display());
+                        return Err(unsafe_path(entry));
+                    }
+                    fs::remove_file(&path)?;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    if !is_last {
+                        fs::create_dir(&path)?;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(path)
+    }
+}
+
+fn unsafe_path(entry: &Entry) -> Error {
+    FormatError::UnsafePath {
+        name: entry.name.clone(),
+    }
+    .into()
+}
+
+/// Copies between files without going through userspace buffers.
+#[cfg(target_os = "linux")]
+mod kernel_copy {
+    use std::{fs::File, io, os::fd::AsRawFd, ptr};
+
+    /// Copies `len` bytes at `offset` in `src` to `dst`, at its current
+    /// position, with `copy_file_range`, or `sendfile` on kernels and file
+    /// systems that don't support it. Returns false if neither works, in
+    /// which case nothing was copied.
+    pub(super) fn copy(src: &File, offset: u64, len: u64, dst: &File) -> io::Result<bool> {
+        let (src, dst) = (src.as_raw_fd(), dst.as_raw_fd());
+        let mut use_sendfile = false;
+        let mut copied = 0;
+        while copied < len {
+            let mut src_offset = i64::try_from(offset + copied)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+            let count = (len - copied).min(1 << 30) as usize;
+            // SAFETY: both descriptors are open for the duration of the
+            // call, and the offset is a valid pointer to an off_t
+            let n = unsafe {
+                if use_sendfile {
+                    libc::sendfile(dst, src, &mut src_offset, count)
+                } else {
+                    libc::copy_file_range(src, &mut src_offset, dst, ptr::null_mut(), count, 0)
+                }
+            };
+            match n {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n if n > 0 => copied += n as u64,
+                _ => {
+                    let error = io::Error::last_os_error();
+                    let unsupported = matches!(
+                        error.raw_os_error(),
+                        Some(libc::ENOSYS | libc::EXDEV | libc::EINVAL | libc::EOPNOTSUPP)
+                    );
+                    match (unsupported && copied == 0, use_sendfile) {
+                        (true, false) => use_sendfile = true,
+                        (true, true) => return Ok(false),
+                        (false, _) => return Err(error),
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod kernel_copy {
+    use std::{fs::File, io};
+
+    pub(super) fn copy(_src: &File, _offset: u64, _len: u64, _dst: &File) -> io::Result<bool> {
+        Ok(false)
+    }
+}
@@ -1,5 +1,5 @@
-//! A library for reading zip files synchronously using std I/O traits,
-//! built on top of [rc-zip](https://crates.io/crates/rc-zip).
+//! A library for reading and writing zip files synchronously using std I/O
+//! traits, built on top of [rc-zip](https://crates.io/crates/rc-zip).
 //!
 //! See also:
 //!
@@ -7,14 +7,50 @@
 
 #![warn(missing_docs)]
 
+mod apk;
+mod archive_writer;
+mod cancel;
+mod concat_reader;
 mod entry_reader;
+mod extractor;
+mod multi_file;
+mod nested;
 mod read_zip;
+mod seekable_entry_reader;
+mod stored_entry;
+mod verify;
+
+pub use archive_writer::ArchiveWriter;
+pub use cancel::CancellationToken;
+pub use concat_reader::ConcatReader;
+pub use entry_reader::EntryReader;
+pub use extractor::{ExtractOptions, Extractor, SymlinkBehavior, TimestampBehavior};
+pub use multi_file::{MultiFileCursor, MultiFileSource};
+pub use nested::NestedFile;
+pub use seekable_entry_reader::SeekableEntryReader;
+pub use stored_entry::StoredEntry;
 
 mod streaming_entry_reader;
 pub use streaming_entry_reader::StreamingEntryReader;
 
+mod streaming_zip_reader;
+pub use streaming_zip_reader::StreamingZipReader;
+
+#[cfg(feature = "cap-std")]
+mod cap_std_extract;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapSource;
+
 // re-exports
+#[cfg(feature = "cap-std")]
+pub use cap_std;
+#[cfg(feature = "mmap")]
+pub use memmap2;
 pub use rc_zip;
 pub use read_zip::{
-    ArchiveHandle, EntryHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    ArchiveHandle, DynHasCursor, EntryHandle, HasCursor, OwnedArchiveHandle, OwnedEntryHandle,
+    ReadZip, ReadZipStreaming, ReadZipWithSize, SharedEntryHandle,
 };
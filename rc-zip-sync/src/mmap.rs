@@ -0,0 +1,65 @@
+use std::{fs::File, io, path::Path};
+
+use memmap2::Mmap;
+use rc_zip::{error::Error, fsm::ArchiveReadOptions};
+
+use crate::{read_zip::slice_at, ArchiveHandle, HasCursor, ReadZip, ReadZipWithSize};
+
+/// A file mapped in memory, read as a zip archive without any read syscalls:
+/// cursors are slices of the map, and the OS pages data in as it's needed.
+///
+/// This mostly helps with large archives, where reading entries through
+/// [std::fs::File] means many small `pread` calls.
+pub struct MmapSource {
+    map: Mmap,
+}
+
+impl MmapSource {
+    /// Maps the file at `path` in memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, by this process or any other, while
+    /// the map is alive: the archive would change under rc-zip's feet, which
+    /// is undefined behavior. See [Mmap::map].
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(Mmap::map(&file)?))
+    }
+
+    /// Reads from an existing map.
+    pub fn new(map: Mmap) -> Self {
+        Self { map }
+    }
+
+    /// Returns the size of the map.
+    pub fn size(&self) -> u64 {
+        self.map.len() as u64
+    }
+
+    /// Returns the underlying map.
+    pub fn into_inner(self) -> Mmap {
+        self.map
+    }
+}
+
+impl HasCursor for MmapSource {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        slice_at(&self.map, offset)
+    }
+}
+
+impl ReadZip for MmapSource {
+    type File = Self;
+
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.size(), options)
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{self, Read};
+
+use rc_zip::{error::Error, fsm::ArchiveReadOptions};
+
+use crate::{ArchiveHandle, HasCursor, ReadZip, ReadZipWithSize};
+
+/// The parts of a split archive, like those made by `zip -s`, read as a
+/// single archive: the parts are concatenated, in order, and the archive's
+/// per-part offsets resolved with
+/// [ArchiveReadOptions::disk_sizes], so entries that span several parts are
+/// read like any other.
+///
+/// Parts are numbered from 0 in the archive, in the order they were
+/// written: for `zip -s`, that's `.z01`, `.z02`, etc., then `.zip` last.
+pub struct MultiFileSource<F> {
+    parts: Vec<F>,
+    sizes: Vec<u64>,
+}
+
+impl<F> MultiFileSource<F>
+where
+    F: HasCursor,
+{
+    /// Creates a source from the parts of a split archive and their sizes,
+    /// ordered by disk number.
+    pub fn new(parts: impl IntoIterator<Item = (F, u64)>) -> Self {
+        let (parts, sizes) = parts.into_iter().unzip();
+        Self { parts, sizes }
+    }
+
+    /// Returns the size of each part, in order.
+    pub fn sizes(&self) -> &[u64] {
+        &self.sizes
+    }
+
+    /// Returns the total size of all parts.
+    pub fn size(&self) -> u64 {
+        self.sizes.iter().sum()
+    }
+
+    /// Returns the parts.
+    pub fn into_parts(self) -> Vec<F> {
+        self.parts
+    }
+}
+
+#[cfg(feature = "file")]
+impl MultiFileSource<std::fs::File> {
+    /// Opens the split archive whose last part is at `path`, named like
+    /// `zip -s` names them: `archive.zip` comes after `archive.z01`,
+    /// `archive.z02`, etc. which are looked for next to it. If there are
+    /// none, `path` is the only part.
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut parts = Vec::new();
+        for n in 1.. {
+            let part = path.with_extension(format!("z{n:02}"));
+            match std::fs::File::open(&part) {
+                Ok(file) => parts.push(file),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => return Err(e),
+            }
+        }
+        parts.push(std::fs::File::open(path)?);
+
+        let parts = parts
+            .into_iter()
+            .map(|file| {
+                let size = file.metadata()?.len();
+                Ok((file, size))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self::new(parts))
+    }
+}
+
+impl<F> HasCursor for MultiFileSource<F>
+where
+    F: HasCursor,
+{
+    type Cursor<'a> = MultiFileCursor<'a, F>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // find the part `offset` is in, and where in it
+        let mut part = 0;
+        let mut part_offset = offset;
+        while part < self.sizes.len() && part_offset >= self.sizes[part] {
+            part_offset -= self.sizes[part];
+            part += 1;
+        }
+        MultiFileCursor {
+            source: self,
+            part,
+            current: None,
+            part_offset,
+        }
+    }
+}
+
+impl<F> ReadZip for MultiFileSource<F>
+where
+    F: HasCursor,
+{
+    type File = Self;
+
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self>, Error> {
+        let options = options.with_disk_sizes(self.sizes.clone());
+        self.read_zip_with_size_and_options(self.size(), options)
+    }
+}
+
+/// The cursor type for [MultiFileSource]: it reads across parts.
+pub struct MultiFileCursor<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    source: &'a MultiFileSource<F>,
+
+    /// index of the part being read
+    part: usize,
+    current: Option<io::Take<F::Cursor<'a>>>,
+
+    /// where to start reading the part, when `current` is None
+    part_offset: u64,
+}
+
+impl<F> Read for MultiFileCursor<'_, F>
+where
+    F: HasCursor,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.part < self.source.parts.len() {
+            let part = self.part;
+            let source = self.source;
+            let offset = self.part_offset;
+            let current = self.current.get_or_insert_with(|| {
+                source.parts[part]
+                    .cursor_at(offset)
+                    .take(source.sizes[part] - offset)
+            });
+            match current.read(buf)? {
+                0 if current.limit() > 0 => {
+                    // the part is shorter than it was said to be
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                0 => {
+                    self.part += 1;
+                    self.part_offset = 0;
+                    self.current = None;
+                }
+                n => return Ok(n),
+            }
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Read;
+
+use rc_zip::{
+    error::{Error, LimitExceeded},
+    fsm::{ArchiveReadOptions, DecompressionLimits},
+};
+
+use crate::{
+    entry_reader::unwrap_io_error,
+    read_zip::{slice_at, EntryHandle},
+    ArchiveHandle, HasCursor, OwnedArchiveHandle, ReadZip, ReadZipWithSize, StoredEntry,
+};
+
+/// The data of an entry read as a zip archive, see [EntryHandle::read_zip].
+pub enum NestedFile<'a, F> {
+    /// The entry is stored without compression, and read in place from the
+    /// outer archive.
+    Stored(StoredEntry<'a, F>),
+
+    /// The entry was decompressed into memory.
+    Decompressed(Vec<u8>),
+}
+
+impl<F> NestedFile<'_, F>
+where
+    F: HasCursor,
+{
+    /// The size of the nested archive, in bytes.
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Stored(entry) => entry.len(),
+            Self::Decompressed(data) => data.len() as u64,
+        }
+    }
+
+    /// Returns true if the nested archive is empty, which makes it invalid.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<F> HasCursor for NestedFile<'_, F>
+where
+    F: HasCursor,
+{
+    type Cursor<'b> = Box<dyn Read + 'b>
+    where
+        Self: 'b;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        match self {
+            Self::Stored(entry) => Box::new(entry.reader_at(offset)),
+            Self::Decompressed(data) => Box::new(slice_at(data, offset)),
+        }
+    }
+}
+
+impl<F> ReadZip for NestedFile<'_, F>
+where
+    F: HasCursor,
+{
+    type File = Self;
+
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len(), options)
+    }
+}
+
+impl<'a, F> EntryHandle<'a, F>
+where
+    F: HasCursor,
+{
+    /// Reads the entry as a zip archive nested in this one, like plugins
+    /// bundled with an application. Stored entries are read in place,
+    /// others are decompressed into memory first, within `limits`.
+    ///
+    /// Fails with [LimitExceeded::Depth] if the nested archive is deeper
+    /// than [DecompressionLimits::max_depth], and with
+    /// [LimitExceeded::OutputSize] if it's larger than
+    /// [DecompressionLimits::max_output]. Its own entries are one level
+    /// deeper: read them with [DecompressionLimits::nested], so that
+    /// archives that contain themselves are eventually rejected.
+    pub fn read_zip(
+        &self,
+        limits: DecompressionLimits,
+    ) -> Result<OwnedArchiveHandle<NestedFile<'a, F>>, Error> {
+        limits.nested()?;
+
+        let file = match self.stored()? {
+            Some(entry) => {
+                if let Some(limit) = limits.max_output.filter(|&limit| entry.len() > limit) {
+                    return Err(Error::LimitExceeded(LimitExceeded::OutputSize { limit }));
+                }
+                NestedFile::Stored(entry)
+            }
+            None => {
+                let mut data = Vec::new();
+                self.reader_with_limits(limits)
+                    .read_to_end(&mut data)
+                    .map_err(unwrap_io_error)?;
+                NestedFile::Decompressed(data)
+            }
+        };
+        file.read_zip_owned()
+    }
+}
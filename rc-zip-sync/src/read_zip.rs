@@ -1,14 +1,24 @@
+use rc_zip::parse::{Entry, HeaderMismatch, LocalFileHeader, Located, Method, Offsets};
 use rc_zip::{
-    error::Error,
-    fsm::{ArchiveFsm, FsmResult},
+    error::{Error, FormatError},
+    fsm::{
+        ArchiveFsm, ArchiveReadOptions, Buffer, DecompressionLimits, FsmResult, RecoveryFsm,
+        StreamingEntryFsm, Verification,
+    },
+    jar::{Manifest, MANIFEST_NAME},
     parse::Archive,
+    progress::Progress,
 };
-use rc_zip::{fsm::EntryFsm, parse::Entry};
 use tracing::trace;
 
 use crate::entry_reader::EntryReader;
-use crate::streaming_entry_reader::StreamingEntryReader;
-use std::{io::Read, ops::Deref};
+use crate::streaming_entry_reader::{next_entry, StreamingEntryReader};
+use crate::{CancellationToken, SeekableEntryReader, StoredEntry};
+use std::{
+    io::{self, Read},
+    ops::{Deref, Range},
+    sync::Arc,
+};
 
 /// A trait for reading something as a zip archive
 ///
@@ -18,7 +28,43 @@ pub trait ReadZipWithSize {
     type File: HasCursor;
 
     /// Reads self as a zip archive.
-    fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+    fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(size, Default::default())
+    }
+
+    /// Reads self as a zip archive, with the given options.
+    fn read_zip_with_size_and_options(
+        &self,
+        size: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_at_with_options(0, size, options)
+    }
+
+    /// Reads a zip archive embedded in self, that is `len` bytes long and
+    /// starts at `offset`, as found in some container formats (game packs,
+    /// firmware images, etc.)
+    ///
+    /// Nothing outside of that window is read: the end of central directory
+    /// is looked for at the end of the window, and entry offsets are
+    /// relative to its start.
+    fn read_zip_at(&self, offset: u64, len: u64) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_at_with_options(offset, len, Default::default())
+    }
+
+    /// Reads a zip archive embedded in self, with the given options. See
+    /// [ReadZipWithSize::read_zip_at].
+    fn read_zip_at_with_options(
+        &self,
+        offset: u64,
+        len: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Salvages what can be found of a damaged zip archive, by scanning all
+    /// of self for local headers rather than reading the central directory,
+    /// see [RecoveryFsm].
+    fn recover_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
 }
 
 /// A trait for reading something as a zip archive when we can tell size from
@@ -30,7 +76,52 @@ pub trait ReadZip {
     type File: HasCursor;
 
     /// Reads self as a zip archive.
-    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_options(Default::default())
+    }
+
+    /// Reads self as a zip archive, with the given options.
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive that starts at `offset`, like one
+    /// embedded in a firmware image: offsets recorded in the archive are
+    /// relative to it, see [ArchiveReadOptions::base_offset]. Unlike
+    /// [ReadZipWithSize::read_zip_at], the archive's length needn't be
+    /// known, as long as it's near the end of self.
+    fn read_zip_with_offset(&self, offset: u64) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_options(ArchiveReadOptions::default().with_base_offset(offset))
+    }
+
+    /// Reads self as a zip archive, which then owns self: unlike
+    /// [ArchiveHandle], it can be stored in structs, or moved to other
+    /// threads.
+    fn read_zip_owned(self) -> Result<OwnedArchiveHandle<Self>, Error>
+    where
+        Self: ReadZip<File = Self> + HasCursor + Sized,
+    {
+        self.read_zip_owned_with_options(Default::default())
+    }
+
+    /// Like [ReadZip::read_zip_owned], with the given options.
+    fn read_zip_owned_with_options(
+        self,
+        options: ArchiveReadOptions,
+    ) -> Result<OwnedArchiveHandle<Self>, Error>
+    where
+        Self: ReadZip<File = Self> + HasCursor + Sized,
+    {
+        let ArchiveHandle {
+            window, archive, ..
+        } = self.read_zip_with_options(options)?;
+        Ok(OwnedArchiveHandle {
+            file: Arc::new(self),
+            window,
+            archive,
+        })
+    }
 }
 
 impl<F> ReadZipWithSize for F
@@ -39,14 +130,20 @@ where
 {
     type File = F;
 
-    fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+    fn read_zip_at_with_options(
+        &self,
+        offset: u64,
+        len: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
         struct CursorState<'a, F: HasCursor + 'a> {
-            cursor: <F as HasCursor>::Cursor<'a>,
+            cursor: io::Take<<F as HasCursor>::Cursor<'a>>,
             offset: u64,
         }
         let mut cstate: Option<CursorState<'_, F>> = None;
 
-        let mut fsm = ArchiveFsm::new(size);
+        let window = Window { offset, len };
+        let mut fsm = ArchiveFsm::with_options(len, options);
         loop {
             if let Some(offset) = fsm.wants_read() {
                 trace!(%offset, "read_zip_with_size: wants_read, space len = {}", fsm.space().len());
@@ -58,13 +155,13 @@ where
                             cstate
                         } else {
                             CursorState {
-                                cursor: self.cursor_at(offset),
+                                cursor: window.cursor_at(self, offset),
                                 offset,
                             }
                         }
                     }
                     None => CursorState {
-                        cursor: self.cursor_at(offset),
+                        cursor: window.cursor_at(self, offset),
                         offset,
                     },
                 };
@@ -89,6 +186,7 @@ where
                     trace!("read_zip_with_size: done");
                     return Ok(ArchiveHandle {
                         file: self,
+                        window,
                         archive,
                     });
                 }
@@ -96,21 +194,63 @@ where
             }
         }
     }
+
+    fn recover_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+        let window = Window {
+            offset: 0,
+            len: size,
+        };
+        let mut cursor = None;
+        let mut fsm = RecoveryFsm::new(size);
+        loop {
+            if let Some(offset) = fsm.wants_read() {
+                // data is read forward, the cursor is only replaced when
+                // parts of the file are skipped
+                if cursor.as_ref().map(|(cursor_offset, _)| *cursor_offset) != Some(offset) {
+                    cursor = Some((offset, window.cursor_at(self, offset)));
+                }
+                let (cursor_offset, rd) = cursor.as_mut().unwrap();
+                let read_bytes = rd.read(fsm.space())?;
+                if read_bytes == 0 {
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                *cursor_offset += read_bytes as u64;
+                fsm.fill(read_bytes);
+            }
+
+            fsm = match fsm.process()? {
+                FsmResult::Done(archive) => {
+                    return Ok(ArchiveHandle {
+                        file: self,
+                        window,
+                        archive,
+                    })
+                }
+                FsmResult::Continue(fsm) => fsm,
+            }
+        }
+    }
 }
 
 impl ReadZip for &[u8] {
     type File = Self;
 
-    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
-        self.read_zip_with_size(self.len() as u64)
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len() as u64, options)
     }
 }
 
 impl ReadZip for Vec<u8> {
     type File = Self;
 
-    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
-        self.read_zip_with_size(self.len() as u64)
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len() as u64, options)
     }
 }
 
@@ -124,6 +264,7 @@ where
     F: HasCursor,
 {
     file: &'a F,
+    window: Window,
     archive: Archive,
 }
 
@@ -138,7 +279,7 @@ where
     }
 }
 
-impl<F> ArchiveHandle<'_, F>
+impl<'a, F> ArchiveHandle<'a, F>
 where
     F: HasCursor,
 {
@@ -146,26 +287,127 @@ where
     pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
         self.archive.entries().map(move |entry| EntryHandle {
             file: self.file,
+            window: self.window,
             entry,
         })
     }
 
+    /// Consumes the archive, returning handles that own their entry and only
+    /// borrow the underlying file. Unlike [EntryHandle], they can be kept
+    /// around, sorted, or moved to other threads independently of the
+    /// archive.
+    pub fn into_entries(self) -> impl Iterator<Item = OwnedEntryHandle<'a, F>> {
+        let (file, window) = (self.file, self.window);
+        self.archive
+            .into_entries()
+            .map(move |entry| OwnedEntryHandle {
+                file,
+                window,
+                entry,
+            })
+    }
+
     /// Attempts to look up an entry by name. This is usually a bad idea,
     /// as names aren't necessarily normalized in zip archives.
     pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Looks up an entry by name ignoring case and how it's normalized, see
+    /// [Archive::by_name_ci].
+    pub fn by_name_ci<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name_ci(name).map(|entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Returns the AppleDouble entry holding `entry`'s macOS metadata, see
+    /// [Archive::apple_double_for].
+    pub fn apple_double_for(&self, entry: &Entry) -> Option<EntryHandle<'_, F>> {
         self.archive
-            .entries()
-            .find(|&x| x.name == name.as_ref())
+            .apple_double_for(entry)
             .map(|entry| EntryHandle {
                 file: self.file,
+                window: self.window,
                 entry,
             })
     }
+
+    /// Looks up an entry of a multi-release JAR, see
+    /// [Archive::by_name_for_release].
+    pub fn by_name_for_release<N: AsRef<str>>(
+        &self,
+        name: N,
+        release: u32,
+    ) -> Option<EntryHandle<'_, F>> {
+        self.archive
+            .by_name_for_release(name, release)
+            .map(|entry| EntryHandle {
+                file: self.file,
+                window: self.window,
+                entry,
+            })
+    }
+
+    /// Returns all entries named `name`, see [Archive::entries_by_name].
+    pub fn entries_by_name<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> impl Iterator<Item = EntryHandle<'b, F>> + 'b {
+        self.archive
+            .entries_by_name(name)
+            .map(move |entry| EntryHandle {
+                file: self.file,
+                window: self.window,
+                entry,
+            })
+    }
+
+    /// Returns the entries under the directory `dir`, see
+    /// [Archive::entries_under].
+    pub fn entries_under<'b>(&'b self, dir: &str) -> impl Iterator<Item = EntryHandle<'b, F>> + 'b {
+        self.archive
+            .entries_under(dir)
+            .map(move |entry| EntryHandle {
+                file: self.file,
+                window: self.window,
+                entry,
+            })
+    }
+
+    /// Returns the entries whose name matches the glob `pattern`, see
+    /// [Archive::glob].
+    #[cfg(feature = "glob")]
+    pub fn glob(
+        &self,
+        pattern: &str,
+    ) -> Result<impl Iterator<Item = EntryHandle<'_, F>>, rc_zip::globset::Error> {
+        Ok(self.archive.glob(pattern)?.map(move |entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        }))
+    }
+
+    /// Reads and parses the manifest, if this is a JAR file.
+    pub fn manifest(&self) -> Result<Option<Manifest>, Error> {
+        let Some(entry) = self.by_name(MANIFEST_NAME) else {
+            return Ok(None);
+        };
+        Ok(Some(Manifest::parse(&entry.bytes()?)?))
+    }
 }
 
 /// A zip entry, read synchronously from a file or other I/O resource.
 pub struct EntryHandle<'a, F> {
     file: &'a F,
+    window: Window,
     entry: &'a Entry,
 }
 
@@ -181,9 +423,165 @@ impl<'a, F> EntryHandle<'a, F>
 where
     F: HasCursor,
 {
-    /// Returns a reader for the entry.
-    pub fn reader(&self) -> impl Read + 'a {
-        EntryReader::new(self.entry, self.file.cursor_at(self.entry.header_offset))
+    /// Returns a reader for the entry. Once it's read to the end,
+    /// [EntryReader::trailer] tells about the entry's data descriptor.
+    pub fn reader(&self) -> EntryReader<io::Take<F::Cursor<'a>>> {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl Read + 'a {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which tells `progress` how far along
+    /// decompressing it is, see [Progress].
+    pub fn reader_with_progress(&self, progress: Arc<dyn Progress>) -> impl Read + 'a {
+        self.entry_reader().with_progress(progress)
+    }
+
+    /// Returns a reader for the entry, which checks its CRC-32 once it's
+    /// read according to `verification`, see [Verification].
+    pub fn reader_with_verification(&self, verification: Verification) -> impl Read + 'a {
+        self.entry_reader().with_verification(verification)
+    }
+
+    /// Returns a reader for the entry, which fails with [Error::Cancelled]
+    /// once `cancellation` is cancelled, see [CancellationToken].
+    pub fn reader_with_cancellation(&self, cancellation: CancellationToken) -> impl Read + 'a {
+        self.entry_reader().with_cancellation(cancellation)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl Read + 'a {
+        self.entry_reader().with_password(password)
+    }
+
+    /// Returns a reader for the entry that reads into `buffer` rather than
+    /// allocating one, see [rc_zip::fsm::EntryFsm::DEFAULT_BUFFER_SIZE]. Once the entry
+    /// is read, [EntryReader::into_buffer] gives it back, to read the next
+    /// one with: that saves an allocation per entry.
+    ///
+    /// The buffer's size is up to the caller, as long as it's at least
+    /// [rc_zip::fsm::EntryFsm::MIN_BUFFER_SIZE]. Whatever it holds is discarded.
+    pub fn reader_with_buffer(&self, mut buffer: Buffer) -> EntryReader<io::Take<F::Cursor<'a>>> {
+        buffer.reset();
+        self.new_entry_reader(Some(buffer))
+    }
+
+    pub(crate) fn entry_reader(&self) -> EntryReader<io::Take<F::Cursor<'a>>> {
+        self.new_entry_reader(None)
+    }
+
+    fn new_entry_reader(&self, buffer: Option<Buffer>) -> EntryReader<io::Take<F::Cursor<'a>>> {
+        EntryReader::new(
+            self.entry,
+            self.window.cursor_at(self.file, self.entry.header_offset),
+            buffer,
+        )
+    }
+
+    /// Returns a reader for the entry that can also seek, see
+    /// [SeekableEntryReader]. For entries stored without compression, this
+    /// reads their local header first.
+    pub fn seekable_reader(&self) -> Result<SeekableEntryReader<'a, F>, Error> {
+        let data_offset = self.stored()?.map(|stored| stored.data_offset());
+        Ok(SeekableEntryReader::new(self.copy(), data_offset))
+    }
+
+    /// Returns the entry as a [StoredEntry], whose data can be read at any
+    /// offset, if it's stored without compression or encryption. This reads
+    /// its local header, to find where its data starts.
+    pub fn stored(&self) -> Result<Option<StoredEntry<'a, F>>, Error> {
+        if self.entry.method != Method::Store || self.entry.is_encrypted() {
+            return Ok(None);
+        }
+        Ok(Some(StoredEntry::new(self.copy(), self.data_offset()?)))
+    }
+
+    fn copy(&self) -> Self {
+        EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry: self.entry,
+        }
+    }
+
+    /// Returns a cursor at `offset` in the archive.
+    pub(crate) fn cursor_at(&self, offset: u64) -> io::Take<F::Cursor<'a>> {
+        self.window.cursor_at(self.file, offset)
+    }
+
+    /// Reads this entry's local file header verbatim, and returns it along
+    /// with its offset. Comparing it with [Entry::raw_central_record] shows
+    /// discrepancies that are otherwise papered over.
+    pub fn raw_local_header(&self) -> Result<Located<Vec<u8>>, Error> {
+        let offset = self.entry.header_offset;
+        let mut rd = self.window.cursor_at(self.file, offset);
+        let mut bytes = Vec::new();
+        loop {
+            match LocalFileHeader::measure(&bytes)? {
+                Some(len) if bytes.len() >= len => {
+                    bytes.truncate(len);
+                    return Ok(Located {
+                        offset,
+                        inner: bytes,
+                    });
+                }
+                _ => {}
+            }
+            if (&mut rd).take(256).read_to_end(&mut bytes)? == 0 {
+                return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+
+    /// Reads this entry's local file header, and returns the fields it
+    /// disagrees on with the central directory, see
+    /// [LocalFileHeader::mismatches].
+    pub fn header_mismatches(&self) -> Result<Vec<HeaderMismatch>, Error> {
+        let header = self.raw_local_header()?;
+        Ok(LocalFileHeader::parse_raw(&header.inner)?.mismatches(self.entry))
+    }
+
+    /// Returns a reader for the entry's data as stored in the archive,
+    /// compressed and possibly encrypted, e.g. to copy it to another archive
+    /// with [ArchiveWriter::copy_raw_entry](crate::ArchiveWriter::copy_raw_entry).
+    pub fn raw_entry(&self) -> Result<impl Read + 'a, Error> {
+        Ok(self
+            .window
+            .cursor_at(self.file, self.data_offset()?)
+            .take(self.entry.compressed_size))
+    }
+
+    /// Where the entry's data starts, relative to the start of the archive:
+    /// right after its local header, which this reads.
+    pub fn data_offset(&self) -> Result<u64, Error> {
+        let header = self.raw_local_header()?;
+        Ok(Offsets::add(header.offset, header.inner.len() as u64)?)
+    }
+
+    /// Where the entry's data, as stored in the archive, is in the file or
+    /// other I/O resource it's read from, e.g. to map it into memory or send
+    /// it over a socket directly. Unlike [Self::data_offset], this accounts
+    /// for archives embedded in a larger file, see
+    /// [ReadZipWithSize::read_zip_at]. This reads the local header.
+    pub fn data_range(&self) -> Result<Range<u64>, Error> {
+        let start = self.file_offset(self.data_offset()?);
+        Ok(start..start.saturating_add(self.entry.compressed_size))
+    }
+
+    /// The file or other I/O resource the archive is read from.
+    pub(crate) fn file(&self) -> &'a F {
+        self.file
+    }
+
+    /// Where `offset`, relative to the start of the archive, is in the file.
+    pub(crate) fn file_offset(&self, offset: u64) -> u64 {
+        self.window.offset.saturating_add(offset)
     }
 
     /// Reads the entire entry into a vector.
@@ -194,6 +592,219 @@ where
     }
 }
 
+/// A zip entry that owns its metadata, obtained through
+/// [ArchiveHandle::into_entries].
+pub struct OwnedEntryHandle<'a, F> {
+    file: &'a F,
+    window: Window,
+    entry: Entry,
+}
+
+impl<F> Clone for OwnedEntryHandle<'_, F> {
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file,
+            window: self.window,
+            entry: self.entry.clone(),
+        }
+    }
+}
+
+impl<F> Deref for OwnedEntryHandle<'_, F> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl<F> OwnedEntryHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Returns a reader for the entry.
+    pub fn reader(&self) -> EntryReader<io::Take<F::Cursor<'_>>> {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl Read + '_ {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl Read + '_ {
+        self.entry_reader().with_password(password)
+    }
+
+    fn entry_reader(&self) -> EntryReader<io::Take<F::Cursor<'_>>> {
+        EntryReader::new(
+            &self.entry,
+            self.window.cursor_at(self.file, self.entry.header_offset),
+            None,
+        )
+    }
+
+    /// Reads the entire entry into a vector.
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        self.reader().read_to_end(&mut v)?;
+        Ok(v)
+    }
+
+    /// Returns the entry's metadata, dropping the handle.
+    pub fn into_entry(self) -> Entry {
+        self.entry
+    }
+}
+
+/// A zip archive that owns the file or other I/O resource it's read from,
+/// obtained through [ReadZip::read_zip_owned].
+pub struct OwnedArchiveHandle<F> {
+    file: Arc<F>,
+    window: Window,
+    archive: Archive,
+}
+
+impl<F> Deref for OwnedArchiveHandle<F> {
+    type Target = Archive;
+
+    fn deref(&self) -> &Self::Target {
+        &self.archive
+    }
+}
+
+impl<F> OwnedArchiveHandle<F>
+where
+    F: HasCursor,
+{
+    /// Iterate over all files in this zip, read from the central directory.
+    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
+        self.archive.entries().map(move |entry| EntryHandle {
+            file: &*self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Consumes the archive, returning handles that own their entry and
+    /// share the underlying file: they can outlive the archive, and be
+    /// moved to other threads independently of each other.
+    pub fn into_entries(self) -> impl Iterator<Item = SharedEntryHandle<F>> {
+        let (file, window) = (self.file, self.window);
+        self.archive
+            .into_entries()
+            .map(move |entry| SharedEntryHandle {
+                file: file.clone(),
+                window,
+                entry,
+            })
+    }
+
+    /// Attempts to look up an entry by name. This is usually a bad idea,
+    /// as names aren't necessarily normalized in zip archives.
+    pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| EntryHandle {
+            file: &*self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Returns the file or other I/O resource the archive is read from.
+    pub fn file(&self) -> &F {
+        &self.file
+    }
+}
+
+/// A zip entry that owns its metadata and shares the file it's read from,
+/// obtained through [OwnedArchiveHandle::into_entries].
+pub struct SharedEntryHandle<F> {
+    file: Arc<F>,
+    window: Window,
+    entry: Entry,
+}
+
+impl<F> Clone for SharedEntryHandle<F> {
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file.clone(),
+            window: self.window,
+            entry: self.entry.clone(),
+        }
+    }
+}
+
+impl<F> Deref for SharedEntryHandle<F> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl<F> SharedEntryHandle<F>
+where
+    F: HasCursor,
+{
+    /// Returns a reader for the entry.
+    pub fn reader(&self) -> EntryReader<io::Take<F::Cursor<'_>>> {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl Read + '_ {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl Read + '_ {
+        self.entry_reader().with_password(password)
+    }
+
+    fn entry_reader(&self) -> EntryReader<io::Take<F::Cursor<'_>>> {
+        EntryReader::new(
+            &self.entry,
+            self.window.cursor_at(&*self.file, self.entry.header_offset),
+            None,
+        )
+    }
+
+    /// Reads the entire entry into a vector.
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        self.reader().read_to_end(&mut v)?;
+        Ok(v)
+    }
+
+    /// Returns the entry's metadata, dropping the handle.
+    pub fn into_entry(self) -> Entry {
+        self.entry
+    }
+}
+
+/// Where an archive is within a [HasCursor]: usually all of it, but see
+/// [ReadZipWithSize::read_zip_at].
+#[derive(Clone, Copy)]
+struct Window {
+    offset: u64,
+    len: u64,
+}
+
+impl Window {
+    /// Returns a cursor at `offset` within the window, that stops at its end.
+    fn cursor_at<F: HasCursor>(self, file: &F, offset: u64) -> io::Take<F::Cursor<'_>> {
+        file.cursor_at(self.offset.saturating_add(offset))
+            .take(self.len.saturating_sub(offset))
+    }
+}
+
 /// A sliceable I/O resource: we can ask for a [Read] at a given offset.
 pub trait HasCursor {
     /// The type of [Read] returned by [HasCursor::cursor_at].
@@ -203,6 +814,23 @@ pub trait HasCursor {
 
     /// Returns a [Read] at the given offset.
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_>;
+
+    /// Returns the file this reads from, if it's one, so that data can be
+    /// copied from it without going through a [Read], see
+    /// [Extractor::with_verification](crate::Extractor::with_verification).
+    fn as_file(&self) -> Option<&std::fs::File> {
+        None
+    }
+}
+
+/// Offsets past the end of a slice, including ones that don't even fit in
+/// a usize, result in an empty cursor, the same as reading past the end of a
+/// file would.
+pub(crate) fn slice_at(slice: &[u8], offset: u64) -> &[u8] {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| slice.get(offset..))
+        .unwrap_or_default()
 }
 
 impl HasCursor for &[u8] {
@@ -211,7 +839,7 @@ impl HasCursor for &[u8] {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        slice_at(self, offset)
     }
 }
 
@@ -221,7 +849,7 @@ impl HasCursor for Vec<u8> {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        slice_at(self, offset)
     }
 }
 
@@ -234,15 +862,79 @@ impl HasCursor for std::fs::File {
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
         positioned_io::Cursor::new_pos(self, offset)
     }
+
+    fn as_file(&self) -> Option<&std::fs::File> {
+        Some(self)
+    }
 }
 
 #[cfg(feature = "file")]
 impl ReadZip for std::fs::File {
     type File = Self;
 
-    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self>, Error> {
+    fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self>, Error> {
         let size = self.metadata()?.len();
-        self.read_zip_with_size(size)
+        self.read_zip_with_size_and_options(size, options)
+    }
+}
+
+/// An object-safe form of [HasCursor], for when the type of the I/O resource
+/// isn't known statically, e.g. to keep files, in-memory buffers and network
+/// sources in one collection of `Box<dyn DynHasCursor>`.
+///
+/// It's implemented for every [HasCursor], and `dyn DynHasCursor` (as well as
+/// boxes of it) implements [HasCursor] in turn, by boxing every cursor.
+pub trait DynHasCursor {
+    /// Returns a boxed [Read] at the given offset.
+    fn dyn_cursor_at(&self, offset: u64) -> Box<dyn Read + '_>;
+}
+
+impl<T> DynHasCursor for T
+where
+    T: HasCursor,
+{
+    fn dyn_cursor_at(&self, offset: u64) -> Box<dyn Read + '_> {
+        Box::new(self.cursor_at(offset))
+    }
+}
+
+impl HasCursor for dyn DynHasCursor + '_ {
+    type Cursor<'a> = Box<dyn Read + 'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.dyn_cursor_at(offset)
+    }
+}
+
+impl HasCursor for dyn DynHasCursor + Send + Sync + '_ {
+    type Cursor<'a> = Box<dyn Read + 'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.dyn_cursor_at(offset)
+    }
+}
+
+impl<T> HasCursor for Box<T>
+where
+    T: HasCursor + ?Sized,
+{
+    type Cursor<'a> = T::Cursor<'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        (**self).cursor_at(offset)
+    }
+
+    fn as_file(&self) -> Option<&std::fs::File> {
+        (**self).as_file()
     }
 }
 
@@ -269,21 +961,9 @@ where
     R: Read,
 {
     fn stream_zip_entries_throwing_caution_to_the_wind(
-        mut self,
+        self,
     ) -> Result<StreamingEntryReader<Self>, Error> {
-        let mut fsm = EntryFsm::new(None, None);
-
-        loop {
-            if fsm.wants_read() {
-                let n = self.read(fsm.space())?;
-                trace!("read {} bytes into buf for first zip entry", n);
-                fsm.fill(n);
-            }
-
-            if let Some(entry) = fsm.process_till_header()? {
-                let entry = entry.clone();
-                return Ok(StreamingEntryReader::new(fsm, entry, self));
-            }
-        }
+        next_entry(StreamingEntryFsm::new(None), self)?
+            .ok_or(Error::Format(FormatError::InvalidLocalHeader))
     }
 }
@@ -0,0 +1,138 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::{entry_reader::EntryReader, read_zip::EntryHandle, HasCursor};
+
+/// A reader for an entry that can also [Seek], see
+/// [EntryHandle::seekable_reader].
+///
+/// Entries stored without compression or encryption are read in place,
+/// wherever the reader seeks to. Others have to be decompressed from the
+/// start up to where the reader seeks to: seeking forward decompresses the
+/// data in between, and seeking backward starts over from the beginning.
+///
+/// The checksum of stored entries isn't verified, and the checksum of other
+/// entries only when they're read to the end in one go.
+pub struct SeekableEntryReader<'a, F>
+where
+    F: HasCursor,
+{
+    handle: EntryHandle<'a, F>,
+    // where the data of stored entries starts
+    data_offset: Option<u64>,
+    // where the next read should happen
+    pos: u64,
+    current: Option<Current<'a, F>>,
+}
+
+/// The reader to read from, and where it is.
+struct Current<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    rd: Reader<'a, F>,
+    at: u64,
+}
+
+enum Reader<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    Stored(io::Take<io::Take<F::Cursor<'a>>>),
+    Decoded(Box<EntryReader<io::Take<F::Cursor<'a>>>>),
+}
+
+impl<'a, F> SeekableEntryReader<'a, F>
+where
+    F: HasCursor,
+{
+    pub(crate) fn new(handle: EntryHandle<'a, F>, data_offset: Option<u64>) -> Self {
+        Self {
+            handle,
+            data_offset,
+            pos: 0,
+            current: None,
+        }
+    }
+
+    /// Returns a reader at `self.pos`, or `None` if that's past the end.
+    fn current(&mut self) -> io::Result<Option<&mut Current<'a, F>>> {
+        let at = self.current.as_ref().map(|current| current.at);
+        if at == Some(self.pos) {
+            return Ok(self.current.as_mut());
+        }
+
+        if let Some(data_offset) = self.data_offset {
+            let len = self.handle.compressed_size.saturating_sub(self.pos);
+            let rd = self
+                .handle
+                .cursor_at(data_offset.saturating_add(self.pos))
+                .take(len);
+            return Ok(Some(self.current.insert(Current {
+                rd: Reader::Stored(rd),
+                at: self.pos,
+            })));
+        }
+
+        // decompression only goes forward
+        if at.map_or(true, |at| at > self.pos) {
+            self.current = Some(Current {
+                rd: Reader::Decoded(Box::new(self.handle.entry_reader())),
+                at: 0,
+            });
+        }
+        let Some(Current {
+            rd: Reader::Decoded(rd),
+            at,
+        }) = &mut self.current
+        else {
+            unreachable!("entries that aren't stored are decoded");
+        };
+        *at += io::copy(&mut rd.take(self.pos - *at), &mut io::sink())?;
+        if *at < self.pos {
+            return Ok(None);
+        }
+        Ok(self.current.as_mut())
+    }
+}
+
+impl<F> Read for SeekableEntryReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(Current { rd, at }) = self.current()? else {
+            return Ok(0);
+        };
+        let n = match rd {
+            Reader::Stored(rd) => rd.read(buf)?,
+            Reader::Decoded(rd) => rd.read(buf)?,
+        };
+        *at += n as u64;
+        self.pos = *at;
+        Ok(n)
+    }
+}
+
+impl<F> Seek for SeekableEntryReader<'_, F>
+where
+    F: HasCursor,
+{
+    /// Seeking relative to the end uses [Entry::uncompressed_size], which
+    /// comes from the archive and may be wrong.
+    ///
+    /// [Entry::uncompressed_size]: rc_zip::parse::Entry::uncompressed_size
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.handle.uncompressed_size.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
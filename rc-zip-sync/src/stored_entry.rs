@@ -0,0 +1,82 @@
+use std::{
+    io::{self, Read},
+    ops::{Deref, Range},
+};
+
+use rc_zip::parse::Entry;
+
+use crate::{read_zip::EntryHandle, HasCursor};
+
+/// An entry stored without compression or encryption, whose data can be read
+/// at any offset, see [EntryHandle::stored].
+///
+/// Reads map directly to reads of the underlying [HasCursor] at the right
+/// position, without going through rc-zip's state machines: this suits asset
+/// packs and the like, where only slices of big files are needed. As a
+/// consequence, the entry's checksum isn't verified.
+pub struct StoredEntry<'a, F> {
+    handle: EntryHandle<'a, F>,
+    data_offset: u64,
+}
+
+impl<F> Deref for StoredEntry<'_, F> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl<'a, F> StoredEntry<'a, F>
+where
+    F: HasCursor,
+{
+    pub(crate) fn new(handle: EntryHandle<'a, F>, data_offset: u64) -> Self {
+        Self {
+            handle,
+            data_offset,
+        }
+    }
+
+    /// Where the entry's data starts, relative to the start of the archive.
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// Where the entry's data is in the file or other I/O resource the
+    /// archive is read from, see [EntryHandle::data_range].
+    pub fn data_range(&self) -> Range<u64> {
+        let start = self.handle.file_offset(self.data_offset);
+        start..start.saturating_add(self.len())
+    }
+
+    /// The size of the entry's data, in bytes.
+    pub fn len(&self) -> u64 {
+        self.handle.compressed_size
+    }
+
+    /// Returns true if the entry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads data at `offset` within the entry into `buf`, returning how
+    /// many bytes were read: this is 0 at or past the end of the entry, and
+    /// may be less than asked for, like [Read::read].
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader_at(offset).read(buf)
+    }
+
+    /// Reads exactly enough data at `offset` within the entry to fill `buf`,
+    /// failing with [io::ErrorKind::UnexpectedEof] if the entry ends first.
+    pub fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.reader_at(offset).read_exact(buf)
+    }
+
+    /// Returns a reader for the entry's data from `offset` onwards.
+    pub fn reader_at(&self, offset: u64) -> impl Read + 'a {
+        self.handle
+            .cursor_at(self.data_offset.saturating_add(offset))
+            .take(self.len().saturating_sub(offset))
+    }
+}
@@ -1,7 +1,9 @@
 use oval::Buffer;
 use rc_zip::{
-    error::{Error, FormatError},
-    fsm::{EntryFsm, FsmResult},
+    error::Error,
+    fsm::{
+        DecompressionLimits, EntryFsm, EntryTrailer, FsmResult, StreamingEntryFsm, StreamingNext,
+    },
     parse::Entry,
 };
 use std::io::{self, Read};
@@ -11,11 +13,17 @@ use tracing::trace;
 /// not all name encodings may work, and only by reading it in its entirety
 /// can you move on to the next entry.
 ///
+/// Moving on is done with [StreamingEntryReader::finish], which skips
+/// whatever wasn't read of this entry. Dropping a reader doesn't touch the
+/// underlying stream: if it was borrowed, it's left somewhere in the middle
+/// of the entry, and can't be used to read the next one.
+///
 /// However, it only requires an [io::Read], and does not need to seek.
 pub struct StreamingEntryReader<R> {
     entry: Entry,
     rd: R,
     state: State,
+    limits: DecompressionLimits,
 }
 
 #[derive(Default)]
@@ -27,6 +35,9 @@ enum State {
     Finished {
         /// remaining buffer for next entry
         remain: Buffer,
+
+        /// the entry's data descriptor, if any
+        trailer: Option<EntryTrailer>,
     },
     #[default]
     Transition,
@@ -41,6 +52,7 @@ where
             entry,
             rd,
             state: State::Reading { fsm },
+            limits: Default::default(),
         }
     }
 }
@@ -54,11 +66,12 @@ where
 
         match std::mem::take(&mut self.state) {
             State::Reading { mut fsm } => {
+                let mut filled = 0;
                 if fsm.wants_read() {
                     trace!("fsm wants read");
                     let n = self.rd.read(fsm.space())?;
                     trace!("giving fsm {} bytes from rd", n);
-                    fsm.fill(n);
+                    filled = fsm.fill(n);
                 } else {
                     trace!("fsm does not want read");
                 }
@@ -71,7 +84,7 @@ where
                         if outcome.bytes_written > 0 {
                             trace!("bytes have been written");
                             Ok(outcome.bytes_written)
-                        } else if outcome.bytes_read == 0 {
+                        } else if outcome.bytes_read == 0 && filled == 0 {
                             trace!("no bytes have been written or read");
                             // that's EOF, baby!
                             Ok(0)
@@ -81,17 +94,17 @@ where
                             self.read(buf)
                         }
                     }
-                    FsmResult::Done(remain) => {
-                        self.state = State::Finished { remain };
+                    FsmResult::Done((remain, trailer)) => {
+                        self.state = State::Finished { remain, trailer };
 
                         // neat!
                         Ok(0)
                     }
                 }
             }
-            State::Finished { remain } => {
+            State::Finished { remain, trailer } => {
                 // wait for them to call finish
-                self.state = State::Finished { remain };
+                self.state = State::Finished { remain, trailer };
                 Ok(0)
             }
             State::Transition => unreachable!(),
@@ -109,53 +122,106 @@ where
         &self.entry
     }
 
+    /// Returns the data descriptor that followed the entry's data, once it's
+    /// been read to the end: `None` if there wasn't any, or if the entry
+    /// isn't read yet.
+    pub fn trailer(&self) -> Option<&EntryTrailer> {
+        match &self.state {
+            State::Finished { trailer, .. } => trailer.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Sets limits on what reading this entry, and the ones after it, may
+    /// produce, see [DecompressionLimits].
+    pub fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.limits = limits;
+        self.state = match std::mem::take(&mut self.state) {
+            State::Reading { fsm } => State::Reading {
+                fsm: fsm.with_limits(limits),
+            },
+            state => state,
+        };
+        self
+    }
+
+    /// Reads and discards the rest of this entry, returning how many
+    /// (uncompressed) bytes were skipped. The entry is still decompressed and
+    /// its checksum verified, since that's the only way to find where it ends
+    /// when sizes are only known from the data descriptor.
+    ///
+    /// Subsequent reads return no data.
+    pub fn skip_rest(&mut self) -> Result<u64, Error> {
+        trace!("skipping rest of streaming entry");
+
+        let mut skipped = 0;
+        let mut scratch = vec![0u8; 16 * 1024];
+        while matches!(self.state, State::Reading { .. }) {
+            match self.read(&mut scratch)? {
+                0 => break,
+                n => skipped += n as u64,
+            }
+        }
+
+        if matches!(self.state, State::Reading { .. }) {
+            return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(skipped)
+    }
+
     /// Finish reading this entry, returning the next streaming entry reader, if
-    /// any. This panics if the entry is not fully read.
+    /// any. The rest of this entry is skipped if it wasn't fully read, see
+    /// [StreamingEntryReader::skip_rest].
     ///
     /// If this returns None, there's no entries left.
     pub fn finish(mut self) -> Result<Option<StreamingEntryReader<R>>, Error> {
         trace!("finishing streaming entry reader");
 
-        if matches!(self.state, State::Reading { .. }) {
-            // this should transition to finished if there's no data
-            _ = self.read(&mut [0u8; 1])?;
-        }
+        self.skip_rest()?;
 
         match self.state {
             State::Reading { .. } => {
-                panic!("entry not fully read");
+                unreachable!("skip_rest reads until the entry is finished");
             }
-            State::Finished { remain } => {
+            State::Finished { remain, .. } => {
                 // parse the next entry, if any
-                let mut fsm = EntryFsm::new(None, Some(remain));
-
-                loop {
-                    if fsm.wants_read() {
-                        let n = self.rd.read(fsm.space())?;
-                        trace!("read {} bytes into buf for first zip entry", n);
-                        fsm.fill(n);
-                    }
-
-                    match fsm.process_till_header() {
-                        Ok(Some(entry)) => {
-                            let entry = entry.clone();
-                            return Ok(Some(StreamingEntryReader::new(fsm, entry, self.rd)));
-                        }
-                        Ok(None) => {
-                            // needs more turns
-                        }
-                        Err(e) => match e {
-                            Error::Format(FormatError::InvalidLocalHeader) => {
-                                // we probably reached the end of central directory!
-                                // TODO: we should probably check for the end of central directory
-                                return Ok(None);
-                            }
-                            _ => return Err(e),
-                        },
-                    }
-                }
+                let next = next_entry(StreamingEntryFsm::new(Some(remain)), self.rd)?;
+                Ok(next.map(|next| next.with_limits(self.limits)))
             }
             State::Transition => unreachable!(),
         }
     }
 }
+
+/// Reads the next local header with `fsm`, or returns None if the central
+/// directory comes next.
+pub(crate) fn next_entry<R>(
+    mut fsm: StreamingEntryFsm,
+    mut rd: R,
+) -> Result<Option<StreamingEntryReader<R>>, Error>
+where
+    R: io::Read,
+{
+    loop {
+        fsm = match fsm.process()? {
+            FsmResult::Continue(mut fsm) => {
+                let n = rd.read(fsm.space())?;
+                trace!("read {} bytes into buf for next zip entry", n);
+                if n == 0 {
+                    // neither another entry nor a central directory
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                fsm.fill(n);
+                fsm
+            }
+            FsmResult::Done(StreamingNext::Entry(mut fsm)) => {
+                let entry = fsm
+                    .process_till_header()?
+                    .expect("local header was parsed")
+                    .clone();
+                return Ok(Some(StreamingEntryReader::new(fsm, entry, rd)));
+            }
+            FsmResult::Done(StreamingNext::Trailer(_)) => return Ok(None),
+        };
+    }
+}
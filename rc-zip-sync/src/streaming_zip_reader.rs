@@ -0,0 +1,85 @@
+use std::io;
+
+use rc_zip::{
+    error::Error,
+    fsm::{DecompressionLimits, StreamingEntryFsm},
+};
+
+use crate::{streaming_entry_reader::next_entry, StreamingEntryReader};
+
+/// Reads the entries of a zip archive one after the other, from an
+/// [io::Read] that can't seek: stdin, a socket, etc.
+///
+/// Entries are read from their local headers, so the same caveats as
+/// [crate::ReadZipStreaming] apply. Sizes missing from local headers are
+/// taken from data descriptors.
+///
+/// ```no_run
+/// # fn main() -> Result<(), rc_zip::error::Error> {
+/// use std::io::Read;
+/// use rc_zip_sync::StreamingZipReader;
+///
+/// let mut zip = StreamingZipReader::new(std::io::stdin());
+/// while let Some(entry) = zip.next_entry()? {
+///     let mut contents = Vec::new();
+///     entry.read_to_end(&mut contents)?;
+///     println!("{}: {} bytes", entry.entry().name, contents.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct StreamingZipReader<R> {
+    state: State<R>,
+    limits: DecompressionLimits,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum State<R> {
+    Start(R),
+    Entry(StreamingEntryReader<R>),
+    Done,
+}
+
+impl<R> StreamingZipReader<R>
+where
+    R: io::Read,
+{
+    /// Create a new streaming reader, reading from `rd`
+    pub fn new(rd: R) -> Self {
+        Self {
+            state: State::Start(rd),
+            limits: Default::default(),
+        }
+    }
+
+    /// Sets limits on what reading entries may produce, see
+    /// [DecompressionLimits].
+    pub fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Skips whatever wasn't read of the current entry, if any, and returns
+    /// the next one, or None once the central directory is reached.
+    ///
+    /// After an error, no more entries are returned.
+    pub fn next_entry(&mut self) -> Result<Option<&mut StreamingEntryReader<R>>, Error> {
+        let next = match std::mem::replace(&mut self.state, State::Done) {
+            State::Start(rd) => next_entry(StreamingEntryFsm::new(None), rd)?
+                .map(|entry| entry.with_limits(self.limits)),
+            State::Entry(entry) => entry.finish()?,
+            State::Done => None,
+        };
+
+        match next {
+            Some(entry) => {
+                self.state = State::Entry(entry);
+                match &mut self.state {
+                    State::Entry(entry) => Ok(Some(entry)),
+                    _ => unreachable!(),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+}
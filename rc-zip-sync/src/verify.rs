@@ -0,0 +1,85 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use rc_zip::{
+    error::{Error, FormatError},
+    fsm::{Verification, VerificationStatus},
+    parse::Entry,
+    progress::Progress,
+    verify::{EntryReport, VerifyReport},
+};
+use tracing::trace;
+
+use crate::{entry_reader::unwrap_io_error, read_zip::EntryHandle, ArchiveHandle, HasCursor};
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Reads every entry to check it, and returns what was found for each
+    /// of them, see [VerifyReport]: this doesn't stop at the first entry
+    /// that fails.
+    ///
+    /// Each entry's local header must agree with the central directory, see
+    /// [EntryHandle::header_mismatches], and its data must decompress to the
+    /// right size and checksum, following `verification`. Encrypted entries
+    /// fail, since there's no password to read them with.
+    pub fn verify(&self, verification: Verification) -> VerifyReport {
+        let entries = self
+            .entries()
+            .map(|entry| {
+                let (mismatches, result) = match entry.header_mismatches() {
+                    Ok(mismatches) if mismatches.is_empty() => {
+                        (mismatches, verify_entry(&entry, verification))
+                    }
+                    Ok(mismatches) => {
+                        let e = FormatError::LocalHeaderMismatch {
+                            name: entry.name.clone(),
+                        };
+                        (mismatches, Err(e.into()))
+                    }
+                    Err(e) => (Vec::new(), Err(e)),
+                };
+                if let Err(e) = &result {
+                    trace!(name = %entry.name, "verify: {e}");
+                }
+                EntryReport {
+                    name: entry.name.clone(),
+                    header_offset: entry.header_offset,
+                    mismatches,
+                    result,
+                }
+            })
+            .collect();
+        VerifyReport { entries }
+    }
+}
+
+fn verify_entry<F>(
+    entry: &EntryHandle<'_, F>,
+    verification: Verification,
+) -> Result<VerificationStatus, Error>
+where
+    F: HasCursor,
+{
+    let status = Arc::new(StatusCell::default());
+    let mut rd = entry
+        .entry_reader()
+        .with_verification(verification)
+        .with_progress(status.clone());
+    io::copy(&mut rd, &mut io::sink()).map_err(unwrap_io_error)?;
+    let status = status.0.lock().unwrap().take();
+    status.ok_or_else(|| Error::IO(io::ErrorKind::UnexpectedEof.into()))
+}
+
+/// Remembers how an entry's checksum was verified.
+#[derive(Default)]
+struct StatusCell(Mutex<Option<VerificationStatus>>);
+
+impl Progress for StatusCell {
+    fn entry_done(&self, _entry: &Entry, status: VerificationStatus) {
+        *self.0.lock().unwrap() = Some(status);
+    }
+}
@@ -1,13 +1,26 @@
 use rc_zip::{
+    apk::ZipalignOptions,
+    chrono::{TimeZone, Utc},
     corpus::{self, zips_dir, Case, Files},
-    error::Error,
-    parse::Archive,
+    error::{Error, FormatError, LimitExceeded},
+    fsm::{
+        ArchiveReadOptions, Buffer, DecompressionLimits, EntryFsm, NewEntry, Verification,
+        VerificationStatus,
+    },
+    parse::{
+        Archive, DataDescriptorRecord, DuplicateNamePolicy, EntryKind, EntryTrust, HeaderMismatch,
+        HostSystem, LocalFileHeader, Method, MethodSpecific, MsdosTimestamp, UnixMode, Version,
+    },
+};
+use rc_zip_sync::{
+    ArchiveHandle, ArchiveWriter, ConcatReader, DynHasCursor, ExtractOptions, Extractor, HasCursor,
+    MultiFileSource, NestedFile, OwnedArchiveHandle, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    StreamingZipReader, SymlinkBehavior, TimestampBehavior,
 };
-use rc_zip_sync::{ArchiveHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize};
 
 use std::{
     fs::File,
-    io::{self, Read},
+    io::{self, Read, Write},
 };
 
 fn check_case<F: HasCursor>(test: &Case, archive: Result<ArchiveHandle<'_, F>, Error>) {
@@ -49,6 +62,97 @@ fn read_from_file() {
     assert_eq!(archive.entries().count(), 2);
 }
 
+#[test]
+#[cfg(feature = "mmap")]
+fn read_from_mmap() {
+    use rc_zip_sync::MmapSource;
+
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let guarded_path = case.absolute_path();
+        // SAFETY: test files aren't modified while tests run
+        let source = unsafe { MmapSource::open(&guarded_path.path) }.unwrap();
+        check_case(&case, source.read_zip());
+        drop(guarded_path)
+    }
+}
+
+#[test]
+fn read_from_dyn_cursors() {
+    corpus::install_test_subscriber();
+
+    let path = zips_dir().join("test.zip");
+    let bytes = std::fs::read(&path).unwrap();
+    let size = bytes.len() as u64;
+    let sources: Vec<Box<dyn DynHasCursor>> =
+        vec![Box::new(File::open(&path).unwrap()), Box::new(bytes)];
+
+    for source in sources {
+        let archive = source.read_zip_with_size(size).unwrap();
+        assert_eq!(archive.entries().count(), 2);
+        for entry in archive.entries() {
+            entry.bytes().unwrap();
+        }
+    }
+}
+
+#[test]
+fn duplicate_names() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("config.toml", b"first"), ("config.toml", b"second")]);
+    let read = |duplicate_names| {
+        bytes.read_zip_with_options(
+            ArchiveReadOptions::default().with_duplicate_names(duplicate_names),
+        )
+    };
+
+    let archive = read(DuplicateNamePolicy::FirstWins).unwrap();
+    assert_eq!(archive.entries().count(), 2);
+    let entry = archive.by_name("config.toml").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"first");
+
+    let archive = read(DuplicateNamePolicy::LastWins).unwrap();
+    let entry = archive.by_name("config.toml").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"second");
+
+    // all of them, whatever the policy
+    let contents: Vec<_> = archive
+        .entries_by_name("config.toml")
+        .map(|entry| entry.bytes().unwrap())
+        .collect();
+    assert_eq!(contents, [&b"first"[..], &b"second"[..]]);
+    assert_eq!(archive.entries_by_name("other.toml").count(), 0);
+
+    match read(DuplicateNamePolicy::Error) {
+        Err(Error::Format(FormatError::DuplicateEntryName { name })) => {
+            assert_eq!(name, "config.toml")
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+
+    // names that are only the same once normalized are still extracted to
+    // the same path
+    for (first, second) in [
+        ("a/b.txt", "./a//b.txt"),
+        ("a/b.txt", "a\\b.txt"),
+        ("caf\u{e9}.txt", "cafe\u{301}.txt"),
+    ] {
+        let bytes = corpus::stored_zip(&[(first, b"first"), (second, b"second")]);
+        let options =
+            ArchiveReadOptions::default().with_duplicate_names(DuplicateNamePolicy::Error);
+        match bytes.read_zip_with_options(options) {
+            Err(Error::Format(FormatError::DuplicateEntryName { name })) => {
+                assert_eq!(name, second)
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("{first:?} and {second:?} should be duplicates"),
+        }
+    }
+}
+
 #[test]
 fn real_world_files() {
     corpus::install_test_subscriber();
@@ -97,26 +201,2226 @@ fn streaming() {
     }
 }
 
-// This helps find bugs in state machines!
+#[test]
+fn jar() {
+    corpus::install_test_subscriber();
 
-struct OneByteReadWrapper<R>(R);
+    let bytes = corpus::stored_zip(&[
+        (
+            "META-INF/MANIFEST.MF",
+            b"Manifest-Version: 1.0\nMulti-Release: true\n",
+        ),
+        ("a/B.class", b"base"),
+        ("META-INF/versions/11/a/B.class", b"11"),
+    ]);
+    let archive = bytes.read_zip().unwrap();
+    let manifest = archive.manifest().unwrap().unwrap();
+    assert!(manifest.is_multi_release());
+    let entry = archive.by_name_for_release("a/B.class", 17).unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"11");
 
-impl<R> io::Read for OneByteReadWrapper<R>
-where
-    R: io::Read,
-{
+    let bytes = corpus::stored_zip(&[("a/B.class", b"base")]);
+    assert!(bytes.read_zip().unwrap().manifest().unwrap().is_none());
+}
+
+#[test]
+fn raw_local_header() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    let archive = bytes.read_zip().unwrap();
+    for entry in archive.entries() {
+        let raw = entry.raw_local_header().unwrap();
+        assert_eq!(raw.offset, entry.header_offset);
+        assert!(raw.inner.starts_with(b"PK\x03\x04"));
+        assert_eq!(raw.inner.len(), 30 + entry.name.len());
+        assert!(raw.inner.ends_with(entry.name.as_bytes()));
+    }
+}
+
+#[test]
+fn slice_cursor_past_end() {
+    let bytes = b"hello".to_vec();
+    for offset in [5, 6, u32::MAX as u64 + 1, u64::MAX] {
+        let mut v = vec![];
+        bytes.cursor_at(offset).read_to_end(&mut v).unwrap();
+        assert!(v.is_empty());
+    }
+}
+
+#[test]
+fn readers_are_send_sync() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("hello.txt").unwrap();
+    assert_send_sync(&entry.reader());
+    assert_send_sync(&ConcatReader::new(archive.entries()));
+    // a separator only needs to be Send
+    let count = std::cell::Cell::new(0);
+    assert_send_sync(
+        &ConcatReader::new(archive.entries()).with_separator(move |_| {
+            count.set(count.get() + 1);
+            vec![]
+        }),
+    );
+    let owned = archive.into_entries().next().unwrap();
+    assert_send_sync(&owned.reader());
+    assert_send_sync(&owned);
+
+    let streaming = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .unwrap();
+    assert_send_sync(&streaming);
+}
+
+#[test]
+#[cfg(feature = "aes")]
+fn aes_encrypted_entries() {
+    corpus::install_test_subscriber();
+
+    let zip_error = |e: io::Error| *e.into_inner().unwrap().downcast::<Error>().unwrap();
+
+    let mut bytes = std::fs::read(zips_dir().join("aes.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    // all entries are prefixes of the same text
+    let mut text = vec![];
+    let entry = archive.by_name("deflated.txt").unwrap();
+    entry
+        .reader_with_password(b"rc-zip")
+        .read_to_end(&mut text)
+        .unwrap();
+    assert!(text.starts_with(b"line 0: "));
+    for entry in archive.entries() {
+        let mut v = vec![];
+        entry
+            .reader_with_password(b"rc-zip")
+            .read_to_end(&mut v)
+            .unwrap();
+        assert_eq!(v, text[..entry.uncompressed_size as usize]);
+
+        let mut v = vec![];
+        OneByteReadWrapper(entry.reader_with_password(b"rc-zip"))
+            .read_to_end(&mut v)
+            .unwrap();
+        assert_eq!(v, text[..entry.uncompressed_size as usize]);
+    }
+
+    let entry = archive.by_name("stored.txt").unwrap();
+    let err = entry.bytes().unwrap_err();
+    assert!(matches!(zip_error(err), Error::PasswordRequired));
+    let err = entry
+        .reader_with_password(b"hunter2")
+        .read_to_end(&mut vec![])
+        .unwrap_err();
+    assert!(matches!(zip_error(err), Error::WrongPassword));
+
+    // flip a bit in the encrypted data, past the local header, salt and
+    // password verification value
+    let offset = entry.header_offset as usize + 30 + entry.name.len() + 11 + 10 + 1234;
+    bytes[offset] ^= 1;
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("stored.txt").unwrap();
+    let err = entry
+        .reader_with_password(b"rc-zip")
+        .read_to_end(&mut vec![])
+        .unwrap_err();
+    assert!(matches!(
+        zip_error(err),
+        Error::Format(FormatError::WrongAuthenticationCode)
+    ));
+}
+
+#[test]
+fn read_embedded() {
+    corpus::install_test_subscriber();
+
+    let zip = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    // the trailing zip would be found by a regular `read_zip`
+    let other_zip = corpus::stored_zip(&[("other.txt", b"other")]);
+    let mut container = vec![0xAAu8; 1234];
+    container.extend_from_slice(&zip);
+    container.extend_from_slice(&other_zip);
+
+    let archive = container.read_zip_at(1234, zip.len() as u64).unwrap();
+    assert_eq!(archive.size(), zip.len() as u64);
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, ["hello.txt", "world.txt"]);
+    for (name, contents) in [("hello.txt", b"hello"), ("world.txt", b"world")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().unwrap(), contents);
+
+        // data ranges are within the container, offsets within the archive
+        let range = entry.data_range().unwrap();
+        assert_eq!(
+            &container[range.start as usize..range.end as usize],
+            contents
+        );
+        assert_eq!(entry.data_offset().unwrap(), range.start - 1234);
+        assert_eq!(entry.stored().unwrap().unwrap().data_range(), range);
+    }
+
+    // a window too short to contain the end of central directory
+    assert!(container.read_zip_at(1234, 100).is_err());
+}
+
+#[test]
+fn read_with_offset() {
+    corpus::install_test_subscriber();
+
+    // padding between the central directory and the end of central
+    // directory record throws off the guess of where the archive starts
+    let mut zip = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    let eocd = zip.split_off(zip.len() - 22);
+    zip.extend_from_slice(&[0; 16]);
+    zip.extend_from_slice(&eocd);
+
+    let mut container = vec![0xAAu8; 1234];
+    container.extend_from_slice(&zip);
+    container.extend_from_slice(&[0x55; 3000]);
+    assert!(container.read_zip().is_err());
+
+    let archive = container.read_zip_with_offset(1234).unwrap();
+    assert_eq!(archive.global_offset(), 1234);
+    for (name, contents) in [("hello.txt", b"hello"), ("world.txt", b"world")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().unwrap(), contents);
+    }
+
+    // an offset past the end of the file
+    assert!(container.read_zip_with_offset(1 << 40).is_err());
+}
+
+#[test]
+fn zipalign() {
+    corpus::install_test_subscriber();
+
+    // zipalign pads the extra field of local headers
+    let padding = |len: usize| {
+        let mut extra = 0xd935_u16.to_le_bytes().to_vec();
+        extra.extend_from_slice(&(len as u16 - 4).to_le_bytes());
+        extra.resize(len, 0);
+        extra
+    };
+    let (lib_padding, res_padding) = (padding(4096 - 30 - 23), padding(5));
+    let bytes = corpus::stored_zip_with_extra(&[
+        ("lib/arm64-v8a/libfoo.so", b"\x7fELF....", &lib_padding),
+        ("res/raw/a.bin", b"abcd", &res_padding),
+        ("odd.txt", b"odd", &[]),
+    ]);
+    let archive = bytes.read_zip().unwrap();
+
+    let options = ZipalignOptions::default();
+    let alignments = archive.alignments(&options).unwrap();
+    let offsets: Vec<_> = alignments
+        .iter()
+        .map(|a| (a.data_offset, a.required, a.is_aligned()))
+        .collect();
+    assert_eq!(
+        offsets,
+        [
+            (4096, Some(4096), true),
+            (4152, Some(4), true),
+            (4193, Some(4), false)
+        ]
+    );
+    assert_eq!(alignments[0].alignment(), 4096);
+    assert_eq!(alignments[1].alignment(), 8);
+    let entry = archive.by_name("res/raw/a.bin").unwrap();
+    assert_eq!(entry.data_offset().unwrap(), 4152);
+
+    let violations = archive.zipalign_violations(&options).unwrap();
+    let names: Vec<_> = violations.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["odd.txt"]);
+
+    // without page alignment, libraries only need the default alignment
+    let options = ZipalignOptions {
+        page_alignment: None,
+        ..Default::default()
+    };
+    assert_eq!(archive.alignments(&options).unwrap()[0].required, Some(4));
+}
+
+#[test]
+fn owned_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("b.txt", b"bb"), ("c.txt", b"c"), ("a.txt", b"aaa")]);
+    let mut entries: Vec<_> = bytes.read_zip().unwrap().into_entries().collect();
+    entries.sort_by_key(|e| e.uncompressed_size);
+
+    let contents: Vec<Vec<u8>> = std::thread::scope(|s| {
+        let workers: Vec<_> = entries
+            .into_iter()
+            .map(|entry| s.spawn(move || entry.bytes().unwrap()))
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    assert_eq!(contents, [&b"c"[..], b"bb", b"aaa"]);
+}
+
+#[test]
+fn recover_archive() {
+    corpus::install_test_subscriber();
+
+    // entries with their sizes in their local header
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let original = bytes.read_zip().unwrap();
+    let directory_offset = original.directory_offset() as usize;
+
+    let truncated = &bytes[..directory_offset + 10];
+    assert!(truncated.read_zip().is_err());
+    let archive = truncated
+        .recover_zip_with_size(truncated.len() as u64)
+        .unwrap();
+    assert_eq!(archive.entries().count(), 2);
+    for entry in original.entries() {
+        let recovered = archive.by_name(&entry.name).unwrap();
+        assert_eq!(recovered.trust, EntryTrust::LocalHeader);
+        assert_eq!(recovered.bytes().unwrap(), entry.bytes().unwrap());
+    }
+
+    // the last entry is cut short
+    let truncated = &bytes[..directory_offset - 100];
+    let archive = truncated
+        .recover_zip_with_size(truncated.len() as u64)
+        .unwrap();
+    assert_eq!(
+        archive.by_name("test.txt").unwrap().trust,
+        EntryTrust::LocalHeader
+    );
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    assert_eq!(entry.trust, EntryTrust::Truncated);
+    assert!(entry.bytes().is_err());
+
+    // entries with their sizes in a data descriptor
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for (name, data) in [
+        ("a.txt", &b"PK\x07\x08 isn't a data descriptor"[..]),
+        ("b.txt", b"bb"),
+    ] {
+        writer
+            .start_entry(NewEntry::new(name).with_method(Method::Store))
+            .unwrap();
+        writer.write_all(data).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let directory_offset = bytes.read_zip().unwrap().directory_offset() as usize;
+
+    let truncated = &bytes[..directory_offset];
+    let archive = truncated
+        .recover_zip_with_size(truncated.len() as u64)
+        .unwrap();
+    let entry = archive.by_name("a.txt").unwrap();
+    assert_eq!(entry.trust, EntryTrust::DataDescriptor);
+    assert_eq!(
+        entry.bytes().unwrap(),
+        b"PK\x07\x08 isn't a data descriptor"
+    );
+    let entry = archive.by_name("b.txt").unwrap();
+    assert_eq!(entry.trust, EntryTrust::DataDescriptor);
+    assert_eq!(entry.bytes().unwrap(), b"bb");
+}
+
+#[test]
+fn self_extracting_archive() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    for name in ["test.zip", "zip64.zip"] {
+        let case = cases.iter().find(|x| x.name == name).unwrap();
+        let original = case.bytes();
+        assert_eq!(original.read_zip().unwrap().prepended_data_len(), 0);
+
+        // an executable stub, which offsets in the archive don't account for
+        let mut bytes = b"MZ".to_vec();
+        bytes.resize(1024, 0);
+        bytes.extend_from_slice(&original);
+
+        let archive = bytes.read_zip().unwrap();
+        assert_eq!(archive.global_offset(), 1024, "{name}");
+        assert_eq!(archive.prepended_data_len(), 1024, "{name}");
+
+        let Files::ExhaustiveList(files) = &case.files else {
+            unreachable!()
+        };
+        for file in files {
+            let entry = archive.by_name(file.name).unwrap();
+            corpus::check_file_against(file, &entry, &entry.bytes().unwrap()[..]);
+        }
+    }
+}
+
+#[test]
+fn split_archive() {
+    corpus::install_test_subscriber();
+
+    fn check<F: HasCursor>(archive: ArchiveHandle<'_, F>) {
+        let numbers: Vec<u8> = (0..9000)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect();
+        let readme = archive.by_name("readme.txt").unwrap();
+        assert_eq!(
+            readme.bytes().unwrap(),
+            b"This archive was split with zip -s 64k.\n"
+        );
+
+        // this one starts in the first part and ends in the second
+        let entry = archive.by_name("numbers.txt").unwrap();
+        assert!(entry.header_offset < 65536);
+        assert!(entry.header_offset + entry.compressed_size > 65536);
+        assert_eq!(entry.bytes().unwrap(), numbers);
+    }
+
+    let source = MultiFileSource::open(zips_dir().join("split.zip")).unwrap();
+    assert_eq!(source.sizes().len(), 2);
+    check(source.read_zip().unwrap());
+
+    let parts = ["split.z01", "split.zip"].map(|name| {
+        let bytes = std::fs::read(zips_dir().join(name)).unwrap();
+        let size = bytes.len() as u64;
+        (bytes, size)
+    });
+    let source = MultiFileSource::new(parts);
+    check(source.read_zip().unwrap());
+}
+
+#[test]
+fn owned_archive() {
+    corpus::install_test_subscriber();
+
+    struct Library {
+        archive: OwnedArchiveHandle<Vec<u8>>,
+    }
+
+    let library = {
+        let bytes = corpus::stored_zip(&[("a.txt", b"aaa"), ("b.txt", b"bb")]);
+        Library {
+            archive: bytes.read_zip_owned().unwrap(),
+        }
+    };
+    let entry = library.archive.by_name("b.txt").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"bb");
+
+    let workers: Vec<_> = library
+        .archive
+        .into_entries()
+        .map(|entry| std::thread::spawn(move || (entry.name.clone(), entry.bytes().unwrap())))
+        .collect();
+    let contents: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(
+        contents,
+        [
+            ("a.txt".to_owned(), b"aaa".to_vec()),
+            ("b.txt".to_owned(), b"bb".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn concatenated_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[
+        ("part.2", b"world"),
+        ("unrelated", b"nope"),
+        ("part.1", b"hello"),
+        ("part.3", b""),
+        ("part.4", b"!"),
+    ]);
+    let archive = bytes.read_zip().unwrap();
+    let parts = || (1..=4).map(|i| archive.by_name(format!("part.{i}")).unwrap());
+
+    let mut v = vec![];
+    ConcatReader::new(parts()).read_to_end(&mut v).unwrap();
+    assert_eq!(v, b"helloworld!");
+
+    let mut v = vec![];
+    ConcatReader::new(parts())
+        .with_separator(|entry| format!("<{}>", entry.name).into_bytes())
+        .read_to_end(&mut v)
+        .unwrap();
+    assert_eq!(v, b"hello<part.2>world<part.3><part.4>!");
+}
+
+#[test]
+fn reused_buffer() {
+    corpus::install_test_subscriber();
+
+    // an unknown extra field that doesn't fit in the smallest buffer
+    let mut extra = vec![0xfe, 0xca];
+    extra.extend_from_slice(&4000u16.to_le_bytes());
+    extra.resize(4004, 0);
+
+    let names: Vec<_> = (0..1000).map(|i| format!("{i}.txt")).collect();
+    let mut entries: Vec<(&str, &[u8], &[u8])> = names
+        .iter()
+        .map(|name| (name.as_str(), name.as_bytes(), &[][..]))
+        .collect();
+    entries.push(("long-extra.txt", b"still fine", &extra));
+    let bytes = corpus::stored_zip_with_extra(&entries);
+    let archive = bytes.read_zip().unwrap();
+
+    let mut buffer = Buffer::with_capacity(EntryFsm::MIN_BUFFER_SIZE);
+    for (name, data, _) in &entries {
+        let mut rd = archive.by_name(name).unwrap().reader_with_buffer(buffer);
+        let mut v = vec![];
+        rd.read_to_end(&mut v).unwrap();
+        assert_eq!(v, *data, "{name}");
+        buffer = rd.into_buffer().unwrap();
+    }
+    assert!(buffer.capacity() > EntryFsm::MIN_BUFFER_SIZE);
+
+    // a reader that fails gives nothing back
+    let corrupt = corpus::stored_zip(&[("a.txt", b"hello")]);
+    let mut corrupt = corrupt.to_vec();
+    corrupt[0] = b'X';
+    let archive = corrupt.read_zip().unwrap();
+    let mut rd = archive.by_name("a.txt").unwrap().reader_with_buffer(buffer);
+    rd.read_to_end(&mut vec![]).unwrap_err();
+    assert!(rd.into_buffer().is_none());
+}
+
+#[test]
+fn streaming_skip() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[
+        ("partially-read.txt", b"abcdef"),
+        ("skipped.txt", b"ghijkl"),
+        ("untouched.txt", b"mnopqr"),
+        ("last.txt", b"stuvwx"),
+    ]);
+
+    let mut entry = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .unwrap();
+    assert_eq!(entry.entry().name, "partially-read.txt");
+    let mut buf = [0u8; 2];
+    entry.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ab");
+
+    let mut entry = entry.finish().unwrap().unwrap();
+    assert_eq!(entry.entry().name, "skipped.txt");
+    assert_eq!(entry.skip_rest().unwrap(), 6);
+    assert_eq!(entry.read(&mut buf).unwrap(), 0);
+
+    let entry = entry.finish().unwrap().unwrap();
+    assert_eq!(entry.entry().name, "untouched.txt");
+
+    let mut entry = entry.finish().unwrap().unwrap();
+    assert_eq!(entry.entry().name, "last.txt");
+    let mut v = vec![];
+    entry.read_to_end(&mut v).unwrap();
+    assert_eq!(v, b"stuvwx");
+    assert!(entry.finish().unwrap().is_none());
+}
+
+#[test]
+#[cfg(unix)]
+fn extract_with_ownership() {
+    use std::os::unix::fs::MetadataExt;
+
+    corpus::install_test_subscriber();
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_with_ownership");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    // giving files away needs root, but taking them is always allowed
+    let metadata = std::fs::metadata(&root).unwrap();
+    let (uid, gid) = (metadata.uid(), metadata.gid());
+
+    // Info-ZIP New Unix, with 32-bit ids
+    let mut new_unix = vec![0x75, 0x78, 11, 0, 1, 4];
+    new_unix.extend_from_slice(&uid.to_le_bytes());
+    new_unix.push(4);
+    new_unix.extend_from_slice(&gid.to_le_bytes());
+    // Info-ZIP Unix type 2, with 16-bit ids
+    let unix2 = [0x55, 0x78, 4, 0, 0xe8, 0x03, 0x64, 0x00];
+
+    let bytes = corpus::stored_zip_with_extra(&[
+        ("mine.txt", b"hello", &new_unix),
+        ("old.txt", b"world", &unix2),
+    ]);
+    let archive = bytes.read_zip().unwrap();
+    let old = archive.by_name("old.txt").unwrap();
+    assert_eq!((old.uid, old.gid), (Some(1000), Some(100)));
+    let mine = archive.by_name("mine.txt").unwrap();
+    assert_eq!((mine.uid, mine.gid), (Some(uid), Some(gid)));
+
+    let dest = root.join("dest");
+    let extractor = Extractor::new(dest).with_ownership(true);
+    let path = extractor.extract_entry(&mine, mine.reader()).unwrap();
+    let metadata = std::fs::metadata(path).unwrap();
+    assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+}
+
+#[test]
+fn zip64_sparse() {
+    corpus::install_test_subscriber();
+
+    // just past what fits in 32 bits, so the next entry's offset doesn't
+    let big = u32::MAX as u64 + 10;
+    for data_descriptors in [false, true] {
+        let sparse = Sparse(corpus::sparse_zip(&[big, 3], data_descriptors));
+        let archive = sparse.read_zip_with_size(sparse.0.len()).unwrap();
+        let entries: Vec<_> = archive.entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (entries[0].compressed_size, entries[0].uncompressed_size),
+            (big, big)
+        );
+        assert!(entries[1].header_offset > u32::MAX as u64);
+        assert_eq!(entries[1].bytes().unwrap(), [0; 3]);
+
+        // reading 4 GiB takes a while in debug builds, so each way of
+        // reading the large entry is only tried once
+        if data_descriptors {
+            let mut rd = entries[0].reader();
+            assert_eq!(io::copy(&mut rd, &mut io::sink()).unwrap(), big);
+            let trailer = rd.trailer().unwrap();
+            assert!(trailer.is_zip64);
+            assert_eq!(trailer.descriptor.uncompressed_size, big);
+        } else {
+            // sizes are in the local header's zip64 extra field
+            let mut entry = sparse
+                .cursor_at(0)
+                .stream_zip_entries_throwing_caution_to_the_wind()
+                .unwrap();
+            assert_eq!(io::copy(&mut entry, &mut io::sink()).unwrap(), big);
+            let mut entry = entry.finish().unwrap().unwrap();
+            assert_eq!(entry.entry().name, "1.bin");
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            assert_eq!(data, [0; 3]);
+            assert!(entry.finish().unwrap().is_none());
+        }
+    }
+}
+
+#[test]
+fn zip64_many_entries() {
+    corpus::install_test_subscriber();
+
+    let count = u16::MAX as usize + 10;
+    for data_descriptors in [false, true] {
+        let sparse = Sparse(corpus::sparse_zip(&vec![1; count], data_descriptors));
+        let archive = sparse.read_zip_with_size(sparse.0.len()).unwrap();
+        assert_eq!(archive.entries().count(), count);
+        let last = archive.by_name(format!("{}.bin", count - 1)).unwrap();
+        assert_eq!(last.bytes().unwrap(), [0]);
+    }
+}
+
+// A backend for archives too large to keep in memory, made by
+// corpus::sparse_zip
+
+struct Sparse(corpus::SparseZip);
+
+struct SparseCursor<'a> {
+    zip: &'a corpus::SparseZip,
+    offset: u64,
+}
+
+impl Read for SparseCursor<'_> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(&mut buf[..1])
+        let n = self.zip.read_at(self.offset, buf);
+        self.offset += n as u64;
+        Ok(n)
     }
 }
 
-impl<R> HasCursor for OneByteReadWrapper<R>
-where
-    R: HasCursor,
-{
-    type Cursor<'a> = OneByteReadWrapper<R::Cursor<'a>> where R: 'a;
+impl HasCursor for Sparse {
+    type Cursor<'a> = SparseCursor<'a>;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        OneByteReadWrapper(self.0.cursor_at(offset))
+        SparseCursor {
+            zip: &self.0,
+            offset,
+        }
+    }
+}
+
+#[test]
+fn zip64_masked_offsets() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip_with_zip64_offsets(&[("a.txt", b"a"), ("b.txt", b"b")]);
+    let archive = bytes.read_zip().unwrap();
+    for (name, contents) in [("a.txt", b"a"), ("b.txt", b"b")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().unwrap(), contents);
+        let stored = entry.stored().unwrap().unwrap();
+        let mut buf = [0];
+        stored.read_exact_at(0, &mut buf).unwrap();
+        assert_eq!(&buf, contents);
+    }
+
+    // a zip64 extra field in the local header, with sizes left to a data
+    // descriptor, means the data descriptor is in zip64 form
+    let mut extra = vec![1, 0, 16, 0];
+    extra.extend_from_slice(&[0; 16]);
+    let mut bytes = Vec::new();
+    LocalFileHeader {
+        reader_version: Version {
+            host_system: HostSystem::Unix,
+            version: 45,
+        },
+        flags: 0x8,
+        method: Method::Store,
+        modified: MsdosTimestamp { time: 0, date: 33 },
+        crc32: 0,
+        compressed_size: 0,
+        uncompressed_size: 0,
+        name: b"hello.txt"[..].into(),
+        extra: extra.into(),
+        method_specific: MethodSpecific::None,
+    }
+    .write_to(&mut bytes)
+    .unwrap();
+    bytes.extend_from_slice(b"hello");
+    DataDescriptorRecord {
+        crc32: 0x3610a686,
+        compressed_size: 5,
+        uncompressed_size: 5,
+    }
+    .write_to(&mut bytes, true)
+    .unwrap();
+
+    let mut entry = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .unwrap();
+    let mut contents = Vec::new();
+    entry.read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, b"hello");
+    assert!(entry.trailer().unwrap().is_zip64);
+}
+
+#[test]
+fn extract_with_timestamps() {
+    corpus::install_test_subscriber();
+
+    let mut extended = vec![0x55, 0x54, 5, 0, 1];
+    extended.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+    let bytes = corpus::stored_zip_with_extra(&[
+        ("dir/", b"", b""),
+        ("dir/msdos.txt", b"hello", b""),
+        ("dir/extended.txt", b"hello", &extended),
+    ]);
+    let archive = bytes.read_zip().unwrap();
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_with_timestamps");
+    let _ = std::fs::remove_dir_all(&dest);
+    Extractor::new(&dest)
+        .with_timestamps(TimestampBehavior::Utc)
+        .extract(&archive)
+        .unwrap();
+
+    let modified = |name: &str| {
+        std::fs::metadata(dest.join(name))
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    };
+    // 1980-01-01, as MS-DOS timestamps are taken as UTC
+    assert_eq!(modified("dir/msdos.txt"), 315_532_800);
+    assert_eq!(modified("dir/extended.txt"), 1_700_000_000);
+    #[cfg(unix)]
+    assert_eq!(modified("dir"), 315_532_800);
+}
+
+#[test]
+fn extract_without_apple_double() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[
+        ("file.txt", b"hello"),
+        ("__MACOSX/", b""),
+        ("__MACOSX/._file.txt", b"metadata"),
+    ]);
+    let archive = bytes.read_zip().unwrap();
+    let file = archive.by_name("file.txt").unwrap();
+    assert_eq!(
+        archive.apple_double_for(&file).unwrap().bytes().unwrap(),
+        b"metadata"
+    );
+
+    let dest =
+        std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_without_apple_double");
+    let _ = std::fs::remove_dir_all(&dest);
+    Extractor::new(&dest)
+        .with_apple_double(false)
+        .extract(&archive)
+        .unwrap();
+    assert_eq!(std::fs::read(dest.join("file.txt")).unwrap(), b"hello");
+    assert!(!dest.join("__MACOSX").exists());
+}
+
+#[test]
+#[cfg(feature = "file")]
+fn extract_without_verification() {
+    corpus::install_test_subscriber();
+
+    let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let mut bytes =
+        corpus::stored_zip(&[("hello.txt", b"hello"), ("big.bin", &big), ("empty", b"")]);
+    // a wrong CRC-32 for hello.txt in the central directory
+    let eocd_offset = bytes.len() - 22;
+    let directory_offset =
+        u32::from_le_bytes(bytes[eocd_offset + 16..][..4].try_into().unwrap()) as usize;
+    bytes[directory_offset + 16] ^= 0xff;
+
+    let root =
+        std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_without_verification");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let zip_path = root.join("archive.zip");
+    std::fs::write(&zip_path, &bytes).unwrap();
+    let file = File::open(&zip_path).unwrap();
+    let archive = file.read_zip().unwrap();
+
+    assert!(Extractor::new(root.join("checked"))
+        .extract(&archive)
+        .is_err());
+
+    // stored entries of archives read from files are copied directly
+    let dest = root.join("unchecked");
+    Extractor::new(&dest)
+        .with_verification(Verification::Skip)
+        .extract(&archive)
+        .unwrap();
+    assert_eq!(std::fs::read(dest.join("hello.txt")).unwrap(), b"hello");
+    assert_eq!(std::fs::read(dest.join("big.bin")).unwrap(), big);
+    assert_eq!(std::fs::read(dest.join("empty")).unwrap(), b"");
+
+    let dest = root.join("parallel");
+    let options = ExtractOptions {
+        verification: Verification::Skip,
+        threads: 2,
+        ..Default::default()
+    };
+    archive.extract_all(&dest, options).unwrap();
+    assert_eq!(std::fs::read(dest.join("big.bin")).unwrap(), big);
+
+    // the same, read from memory
+    let dest = root.join("from_memory");
+    Extractor::new(&dest)
+        .with_verification(Verification::Skip)
+        .extract(&bytes.read_zip().unwrap())
+        .unwrap();
+    assert_eq!(std::fs::read(dest.join("hello.txt")).unwrap(), b"hello");
+}
+
+#[test]
+fn entry_trailer() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(NewEntry::new("hello.txt").with_size_hint(5))
+        .unwrap();
+    writer.write_all(b"hello").unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("hello.txt").unwrap();
+    let mut rd = entry.reader();
+    assert!(rd.trailer().is_none());
+    io::copy(&mut rd, &mut io::sink()).unwrap();
+    let trailer = *rd.trailer().unwrap();
+    assert!(trailer.has_signature);
+    assert!(!trailer.is_zip64);
+    assert_eq!(trailer.descriptor.crc32, entry.crc32);
+    assert_eq!(trailer.descriptor.uncompressed_size, 5);
+
+    let mut entry = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .unwrap();
+    entry.skip_rest().unwrap();
+    assert_eq!(entry.trailer(), Some(&trailer));
+
+    // entries without a data descriptor don't have a trailer
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    let archive = bytes.read_zip().unwrap();
+    let mut rd = archive.by_name("hello.txt").unwrap().reader();
+    io::copy(&mut rd, &mut io::sink()).unwrap();
+    assert!(rd.trailer().is_none());
+}
+
+#[test]
+#[cfg(feature = "cap-std")]
+fn extract_to_dir() {
+    use rc_zip_sync::cap_std::{ambient_authority, fs::Dir};
+
+    corpus::install_test_subscriber();
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_to_dir");
+    let _ = std::fs::remove_dir_all(&dest);
+    std::fs::create_dir_all(&dest).unwrap();
+    let dir = Dir::open_ambient_dir(&dest, ambient_authority()).unwrap();
+
+    let f = File::open(zips_dir().join("test.zip")).unwrap();
+    let archive = f.read_zip().unwrap();
+    archive.extract_to_dir(&dir).unwrap();
+
+    for entry in archive.entries() {
+        let on_disk = std::fs::read(dest.join(&entry.name)).unwrap();
+        assert_eq!(on_disk, entry.bytes().unwrap());
+    }
+}
+
+#[test]
+#[cfg(all(feature = "cap-std", unix))]
+fn extract_to_dir_unsafe_entries() {
+    use rc_zip_sync::cap_std::{ambient_authority, fs::Dir};
+
+    corpus::install_test_subscriber();
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_to_dir_unsafe");
+    let _ = std::fs::remove_dir_all(&root);
+    let (dest, outside) = (root.join("dest"), root.join("outside"));
+    std::fs::create_dir_all(&dest).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    let dir = Dir::open_ambient_dir(&dest, ambient_authority()).unwrap();
+
+    // makes the named entry a symlink, whose data is its target, by
+    // changing the mode in its central directory record
+    let make_symlink = |bytes: &mut Vec<u8>, name: &str| {
+        let mut offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        loop {
+            let name_len = u16::from_le_bytes([bytes[offset + 28], bytes[offset + 29]]) as usize;
+            if &bytes[offset + 46..][..name_len] == name.as_bytes() {
+                let mode = (UnixMode::IFLNK | UnixMode(0o777)).0 << 16;
+                bytes[offset + 38..][..4].copy_from_slice(&mode.to_le_bytes());
+                return;
+            }
+            offset += 46 + name_len;
+        }
+    };
+
+    let absolute = outside.join("absolute.txt");
+    let mut bytes = corpus::stored_zip(&[
+        ("../evil.txt", b"evil"),
+        ("dir/../../evil.txt", b"evil"),
+        (absolute.to_str().unwrap(), b"evil"),
+        ("link", b"../outside"),
+        ("dir/link", b"../../outside"),
+        ("ok.txt", b"ok"),
+    ]);
+    make_symlink(&mut bytes, "link");
+    make_symlink(&mut bytes, "dir/link");
+    let archive = bytes.read_zip().unwrap();
+    assert!(matches!(
+        archive.by_name("link").unwrap().kind(),
+        EntryKind::Symlink
+    ));
+    archive.extract_to_dir(&dir).unwrap();
+
+    // unsafe entries are skipped
+    assert_eq!(std::fs::read(dest.join("ok.txt")).unwrap(), b"ok");
+    assert!(dest.join("link").symlink_metadata().is_err());
+    assert!(dest.join("dir/link").symlink_metadata().is_err());
+    assert!(!root.join("evil.txt").exists());
+    assert!(!absolute.exists());
+
+    // a symlink that's already there isn't gone through, even though the
+    // entry's path is safe
+    std::os::unix::fs::symlink(&outside, dest.join("planted")).unwrap();
+    let bytes = corpus::stored_zip(&[("planted/evil.txt", b"evil")]);
+    let archive = bytes.read_zip().unwrap();
+    assert!(archive.extract_to_dir(&dir).is_err());
+
+    assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+    let names: Vec<_> = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 2, "{names:?}");
+}
+
+#[test]
+#[cfg(unix)]
+fn extractor() {
+    corpus::install_test_subscriber();
+
+    let zip = |entries: &[(&str, UnixMode, &str)]| {
+        let mut writer = ArchiveWriter::new(Vec::new());
+        for (name, mode, data) in entries {
+            writer
+                .start_entry(NewEntry::new(*name).with_mode(*mode | UnixMode(0o755)))
+                .unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap()
+    };
+    let is_unsafe_path =
+        |res: Result<(), Error>| matches!(res, Err(Error::Format(FormatError::UnsafePath { .. })));
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extractor");
+    let _ = std::fs::remove_dir_all(&root);
+    let dest = root.join("dest");
+    let extractor = Extractor::new(&dest);
+
+    let bytes = zip(&[
+        ("dir/", UnixMode::IFDIR, ""),
+        ("dir/a.txt", UnixMode::IFREG, "aaa"),
+        ("dir/link", UnixMode::IFLNK, "a.txt"),
+        ("dir/sub/up", UnixMode::IFLNK, "../../dir/a.txt"),
+        ("./b\\/c.txt", UnixMode::IFREG, "ccc"),
+    ]);
+    extractor.extract(&bytes.read_zip().unwrap()).unwrap();
+    assert_eq!(std::fs::read(dest.join("dir/a.txt")).unwrap(), b"aaa");
+    assert_eq!(std::fs::read(dest.join("dir/link")).unwrap(), b"aaa");
+    assert_eq!(std::fs::read(dest.join("dir/sub/up")).unwrap(), b"aaa");
+    assert_eq!(std::fs::read(dest.join("b/c.txt")).unwrap(), b"ccc");
+
+    for name in [
+        "../evil.txt",
+        "/evil.txt",
+        "C:/evil.txt",
+        "dir/../../evil.txt",
+    ] {
+        let bytes = zip(&[(name, UnixMode::IFREG, "evil")]);
+        assert!(
+            is_unsafe_path(extractor.extract(&bytes.read_zip().unwrap())),
+            "{name}"
+        );
+    }
+    for target in ["/etc/passwd", "../evil", "dir/../../evil", "."] {
+        let bytes = zip(&[("link", UnixMode::IFLNK, target)]);
+        assert!(
+            is_unsafe_path(extractor.extract(&bytes.read_zip().unwrap())),
+            "{target}"
+        );
+    }
+
+    // a symlink from an earlier entry isn't gone through...
+    let bytes = zip(&[
+        ("through", UnixMode::IFLNK, "dir"),
+        ("through/evil.txt", UnixMode::IFREG, "evil"),
+    ]);
+    assert!(is_unsafe_path(
+        extractor.extract(&bytes.read_zip().unwrap())
+    ));
+    assert!(!dest.join("dir/evil.txt").exists());
+
+    // ...and one that was already there is replaced, not followed
+    std::fs::write(root.join("outside.txt"), "outside").unwrap();
+    std::os::unix::fs::symlink(root.join("outside.txt"), dest.join("replaced.txt")).unwrap();
+    let bytes = zip(&[("replaced.txt", UnixMode::IFREG, "replaced")]);
+    extractor.extract(&bytes.read_zip().unwrap()).unwrap();
+    assert_eq!(std::fs::read(root.join("outside.txt")).unwrap(), b"outside");
+    assert_eq!(
+        std::fs::read(dest.join("replaced.txt")).unwrap(),
+        b"replaced"
+    );
+    assert!(root.read_dir().unwrap().count() == 2);
+}
+
+#[test]
+#[cfg(unix)]
+fn extract_all() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for i in 0..200 {
+        writer
+            .start_entry(NewEntry::new(format!("dir{}/file{i}.txt", i % 7)))
+            .unwrap();
+        writer
+            .write_all(i.to_string().repeat(i).as_bytes())
+            .unwrap();
+    }
+    // entries are still created in order, so this replaces a file
+    writer
+        .start_entry(NewEntry::new("dir1/file1.txt").with_mode(UnixMode::IFLNK | UnixMode(0o777)))
+        .unwrap();
+    writer.write_all(b"file8.txt").unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_all");
+    let _ = std::fs::remove_dir_all(&dest);
+    let options = ExtractOptions {
+        threads: 4,
+        ..Default::default()
+    };
+    bytes
+        .read_zip()
+        .unwrap()
+        .extract_all(&dest, options)
+        .unwrap();
+
+    for i in (0..200).filter(|i| *i != 1) {
+        let on_disk = std::fs::read(dest.join(format!("dir{}/file{i}.txt", i % 7))).unwrap();
+        assert_eq!(on_disk, i.to_string().repeat(i).as_bytes());
+    }
+    let link = dest.join("dir1/file1.txt");
+    assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+    assert_eq!(std::fs::read(link).unwrap(), b"8".repeat(8));
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(NewEntry::new("through").with_mode(UnixMode::IFLNK | UnixMode(0o777)))
+        .unwrap();
+    writer.write_all(b"dir0").unwrap();
+    writer
+        .start_entry(NewEntry::new("through/evil.txt"))
+        .unwrap();
+    writer.write_all(b"evil").unwrap();
+    let bytes = writer.finish().unwrap();
+    assert!(matches!(
+        bytes
+            .read_zip()
+            .unwrap()
+            .extract_all(&dest, Default::default()),
+        Err(Error::Format(FormatError::UnsafePath { .. }))
+    ));
+    assert!(!dest.join("dir0/evil.txt").exists());
+}
+
+#[test]
+fn progress() {
+    use rc_zip::progress::Progress;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder {
+        directory: Mutex<Vec<(u64, u64)>>,
+        entries: Mutex<Vec<(String, u64, u64)>>,
+        done: Mutex<Vec<String>>,
+    }
+
+    impl Progress for Recorder {
+        fn central_directory(&self, read: u64, total: u64) {
+            self.directory.lock().unwrap().push((read, total));
+        }
+
+        fn entry(&self, entry: &rc_zip::parse::Entry, compressed: u64, uncompressed: u64) {
+            let name = entry.name.clone();
+            self.entries
+                .lock()
+                .unwrap()
+                .push((name, compressed, uncompressed));
+        }
+
+        fn entry_done(&self, entry: &rc_zip::parse::Entry, _status: VerificationStatus) {
+            self.done.lock().unwrap().push(entry.name.clone());
+        }
+    }
+
+    corpus::install_test_subscriber();
+
+    let recorder = Arc::new(Recorder::default());
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let options = ArchiveReadOptions::default().with_progress(recorder.clone());
+    let archive = bytes.read_zip_with_options(options).unwrap();
+
+    let directory = recorder.directory.lock().unwrap().clone();
+    let (read, total) = *directory.last().unwrap();
+    assert_eq!(read, total);
+    assert!(total > 0);
+
+    for entry in archive.entries() {
+        let mut data = Vec::new();
+        entry
+            .reader_with_progress(recorder.clone())
+            .read_to_end(&mut data)
+            .unwrap();
+        let (name, compressed, uncompressed) =
+            recorder.entries.lock().unwrap().last().unwrap().clone();
+        assert_eq!(name, entry.name);
+        assert_eq!(compressed, entry.compressed_size);
+        assert_eq!(uncompressed, data.len() as u64);
+    }
+    let names: Vec<_> = archive.entries().map(|entry| entry.name.clone()).collect();
+    assert_eq!(*recorder.done.lock().unwrap(), names);
+
+    let recorder = Arc::new(Recorder::default());
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("progress");
+    let _ = std::fs::remove_dir_all(&dest);
+    Extractor::new(&dest)
+        .with_progress(recorder.clone())
+        .extract(&archive)
+        .unwrap();
+    assert_eq!(*recorder.done.lock().unwrap(), names);
+}
+
+#[test]
+fn cancellation() {
+    use rc_zip::{parse::Entry, progress::Progress};
+    use rc_zip_sync::CancellationToken;
+    use std::sync::Arc;
+
+    struct CancelOnData(CancellationToken);
+
+    impl Progress for CancelOnData {
+        fn entry(&self, entry: &Entry, _compressed: u64, _uncompressed: u64) {
+            if entry.name == "big.bin" {
+                self.0.cancel();
+            }
+        }
+    }
+
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("small.txt")).unwrap();
+    writer.write_all(b"small").unwrap();
+    writer
+        .start_entry(NewEntry::new("big.bin").with_method(Method::Store))
+        .unwrap();
+    writer.write_all(&vec![7u8; 4 * 1024 * 1024]).unwrap();
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let entry = archive.by_name("small.txt").unwrap();
+    let err = entry
+        .reader_with_cancellation(token)
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert!(matches!(
+        err.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+        Some(Error::Cancelled)
+    ));
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("cancellation");
+    let _ = std::fs::remove_dir_all(&dest);
+    let token = CancellationToken::new();
+    let res = Extractor::new(&dest)
+        .with_progress(Arc::new(CancelOnData(token.clone())))
+        .with_cancellation(token.clone())
+        .extract(&archive);
+    assert!(matches!(res, Err(Error::Cancelled)));
+    assert_eq!(std::fs::read(dest.join("small.txt")).unwrap(), b"small");
+    assert!(!dest.join("big.bin").exists());
+
+    // in parallel, files are created ahead of being written: the ones
+    // nobody got to aren't left empty
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("small.txt")).unwrap();
+    writer.write_all(b"small").unwrap();
+    writer
+        .start_entry(NewEntry::new("big.bin").with_method(Method::Store))
+        .unwrap();
+    writer.write_all(&vec![7u8; 4 * 1024 * 1024]).unwrap();
+    for i in 0..20 {
+        writer
+            .start_entry(NewEntry::new(format!("after-{i}.txt")))
+            .unwrap();
+        writer.write_all(b"after").unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+    for threads in [1, 4] {
+        let _ = std::fs::remove_dir_all(&dest);
+        let token = CancellationToken::new();
+        let res = Extractor::new(&dest)
+            .with_progress(Arc::new(CancelOnData(token.clone())))
+            .with_cancellation(token.clone())
+            .extract_parallel(&archive, threads);
+        assert!(matches!(res, Err(Error::Cancelled)));
+        assert!(!dest.join("big.bin").exists());
+        for file in std::fs::read_dir(&dest).unwrap() {
+            let file = file.unwrap();
+            assert_ne!(file.metadata().unwrap().len(), 0, "{:?}", file.path());
+        }
+        if threads == 1 {
+            assert!(!dest.join("after-0.txt").exists());
+        }
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn extractor_symlink_behavior() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("a.txt")).unwrap();
+    writer.write_all(b"aaa").unwrap();
+    writer
+        .start_entry(NewEntry::new("link").with_mode(UnixMode::IFLNK | UnixMode(0o777)))
+        .unwrap();
+    writer.write_all(b"a.txt").unwrap();
+    writer
+        .start_entry(NewEntry::new("etc").with_mode(UnixMode::IFLNK | UnixMode(0o777)))
+        .unwrap();
+    writer.write_all(b"/etc").unwrap();
+    writer
+        .start_entry(NewEntry::new("etc/evil.conf").with_mode(UnixMode::IFREG | UnixMode(0o644)))
+        .unwrap();
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extractor_symlinks");
+    let _ = std::fs::remove_dir_all(&root);
+    let extract = |behavior, dest| {
+        let extractor = Extractor::new(root.join(dest)).with_symlink_behavior(behavior);
+        let res = archive
+            .entries()
+            .map(|entry| extractor.extract_entry(&entry, entry.reader()).map(|_| ()))
+            .collect::<Vec<_>>();
+        res.into_iter()
+            .map(|res| match res {
+                Ok(()) => true,
+                Err(Error::Format(FormatError::UnsafePath { .. })) => false,
+                Err(e) => panic!("{e}"),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        extract(SymlinkBehavior::Deny, "deny"),
+        [true, false, false, true]
+    );
+    assert!(!root.join("deny/link").exists());
+    assert_eq!(
+        extract(SymlinkBehavior::AllowWithinRoot, "within"),
+        [true, true, false, true]
+    );
+    assert_eq!(std::fs::read(root.join("within/link")).unwrap(), b"aaa");
+
+    // the symlink to /etc is created, but not written through
+    assert_eq!(
+        extract(SymlinkBehavior::AllowAll, "all"),
+        [true, true, true, false]
+    );
+    assert_eq!(
+        std::fs::read_link(root.join("all/etc")).unwrap(),
+        std::path::Path::new("/etc")
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn extractor_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(NewEntry::new("bin/run.sh").with_mode(UnixMode::IFREG | UnixMode(0o750)))
+        .unwrap();
+    writer.write_all(b"#!/bin/sh").unwrap();
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extractor_permissions");
+    let _ = std::fs::remove_dir_all(&root);
+    let permissions = |path: &str| {
+        std::fs::metadata(root.join(path))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    };
+
+    Extractor::new(root.join("default"))
+        .extract(&archive)
+        .unwrap();
+    assert_ne!(permissions("default/bin/run.sh"), 0o750);
+
+    Extractor::new(root.join("applied"))
+        .with_permissions(true)
+        .extract(&archive)
+        .unwrap();
+    assert_eq!(permissions("applied/bin/run.sh"), 0o750);
+}
+
+// This helps find bugs in state machines!
+
+struct OneByteReadWrapper<R>(R);
+
+impl<R> io::Read for OneByteReadWrapper<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(&mut buf[..1])
+    }
+}
+
+impl<R> HasCursor for OneByteReadWrapper<R>
+where
+    R: HasCursor,
+{
+    type Cursor<'a> = OneByteReadWrapper<R::Cursor<'a>> where R: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        OneByteReadWrapper(self.0.cursor_at(offset))
+    }
+}
+
+#[test]
+fn write_archive() {
+    corpus::install_test_subscriber();
+
+    let modified = Utc.with_ymd_and_hms(2024, 2, 29, 13, 37, 42).unwrap();
+    let method = if cfg!(feature = "deflate") {
+        Method::Deflate
+    } else {
+        Method::Store
+    };
+    let data: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+
+    // modes are only read back with a Unix host system, which isn't the
+    // default on Windows
+    let mut writer = ArchiveWriter::new(Vec::new()).creator_version(Version {
+        host_system: HostSystem::Unix,
+        version: 20,
+    });
+    writer.set_comment("an archive");
+    writer
+        .start_entry(NewEntry::new("hello.txt").with_modified(modified))
+        .unwrap();
+    writer.write_all(b"hello").unwrap();
+    writer.start_entry(NewEntry::new("dir/")).unwrap();
+    writer
+        .start_entry(
+            NewEntry::new("dir/données.bin")
+                .with_method(method)
+                .with_mode(UnixMode::IFREG | UnixMode(0o600))
+                .with_comment("some data"),
+        )
+        .unwrap();
+    for chunk in data.chunks(1000) {
+        writer.write_all(chunk).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.comment(), "an archive");
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, ["hello.txt", "dir/", "dir/données.bin"]);
+
+    let entry = archive.by_name("hello.txt").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"hello");
+    assert_eq!(entry.modified, modified);
+    assert_eq!(entry.mode.0 & 0o777, 0o644);
+
+    let entry = archive.by_name("dir/").unwrap();
+    assert!(matches!(entry.kind(), EntryKind::Directory));
+    assert!(entry.bytes().unwrap().is_empty());
+
+    let entry = archive.by_name("dir/données.bin").unwrap();
+    assert_eq!(entry.method, method);
+    assert_eq!(entry.comment, "some data");
+    assert_eq!(entry.mode.0 & 0o777, 0o600);
+    assert_eq!(entry.bytes().unwrap(), data);
+}
+
+#[test]
+#[cfg(feature = "deflate")]
+fn select_compression() {
+    use rc_zip::fsm::Compression;
+
+    corpus::install_test_subscriber();
+
+    // JPEG data starts with a start of image marker
+    let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0];
+    jpeg.extend((0..10_000u32).map(|i| (i * 7 % 256) as u8));
+    let text = "the quick brown fox jumps over the lazy dog\n".repeat(100);
+
+    let mut writer = ArchiveWriter::new(Vec::new()).select_compression(|entry, leading| {
+        if entry.name.ends_with(".jpg") || leading.starts_with(&[0xff, 0xd8, 0xff]) {
+            Compression {
+                method: Method::Store,
+                level: None,
+            }
+        } else {
+            Compression {
+                method: Method::Deflate,
+                level: Some(9),
+            }
+        }
+    });
+    let entries: [(&str, &[u8]); 4] = [
+        ("photo.jpg", &jpeg),
+        ("notes.txt", text.as_bytes()),
+        // sniffed, whatever its name
+        ("photo.bin", &jpeg),
+        // shorter than what the selector is given
+        ("short.txt", b"short"),
+    ];
+    for (name, data) in entries {
+        // the method set here is overridden
+        writer
+            .start_entry(NewEntry::new(name).with_method(Method::Deflate))
+            .unwrap();
+        for chunk in data.chunks(100) {
+            writer.write_all(chunk).unwrap();
+        }
+    }
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    for (name, data) in entries {
+        let entry = archive.by_name(name).unwrap();
+        let expected = match name {
+            "photo.jpg" | "photo.bin" => Method::Store,
+            _ => Method::Deflate,
+        };
+        assert_eq!(entry.method, expected, "{name}");
+        assert_eq!(entry.bytes().unwrap(), data, "{name}");
+    }
+    let entry = archive.by_name("notes.txt").unwrap();
+    assert!(entry.compressed_size < entry.uncompressed_size / 10);
+}
+
+#[test]
+fn append_to_archive() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("a.txt", b"aaa"), ("b.txt", b"bb")]);
+    let archive = bytes.read_zip().unwrap();
+    assert!(ArchiveWriter::append(&archive, io::Cursor::new(vec![])).is_err());
+
+    let options = ArchiveReadOptions::default().with_keep_raw_records(true);
+    let archive = bytes.read_zip_with_options(options).unwrap();
+    let mut wr = io::Cursor::new(bytes.clone());
+    let mut writer = ArchiveWriter::append(&archive, &mut wr).unwrap();
+    writer.start_entry(NewEntry::new("c.txt")).unwrap();
+    writer.write_all(b"c").unwrap();
+    writer.finish().unwrap();
+    // entry data was left as it was
+    assert_eq!(
+        wr.get_ref()[..archive.directory_offset() as usize],
+        bytes[..archive.directory_offset() as usize]
+    );
+
+    let bytes = wr.into_inner();
+    let archive = bytes.read_zip().unwrap();
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, ["a.txt", "b.txt", "c.txt"]);
+    for (name, contents) in [("a.txt", &b"aaa"[..]), ("b.txt", b"bb"), ("c.txt", b"c")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().unwrap(), contents);
+    }
+}
+
+#[test]
+fn large_reads() {
+    corpus::install_test_subscriber();
+
+    // words from a small vocabulary, so deflate refers back a lot
+    let words = ["zip", "entry", "local", "header", "central", "directory"];
+    let mut state = 0x2545_f491_u32;
+    let mut data = Vec::new();
+    while data.len() < 2 * 1024 * 1024 {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        data.extend_from_slice(words[state as usize % words.len()].as_bytes());
+        data.push(b' ');
+    }
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("stored.txt")).unwrap();
+    writer.write_all(&data).unwrap();
+    #[cfg(feature = "deflate")]
+    {
+        let entry = NewEntry::new("deflated.txt").with_method(Method::Deflate);
+        writer.start_entry(entry).unwrap();
+        writer.write_all(&data).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    fn read_all(mut rd: impl Read, read_len: usize) -> io::Result<Vec<u8>> {
+        let mut v = vec![];
+        let mut buf = vec![0u8; read_len];
+        loop {
+            match rd.read(&mut buf)? {
+                0 => return Ok(v),
+                n => v.extend_from_slice(&buf[..n]),
+            }
+        }
+    }
+
+    // reads big enough to skip the entry reader's buffers, or not
+    for read_len in [1024 * 1024, 100_000, 4096] {
+        for entry in archive.entries() {
+            let v = read_all(entry.reader(), read_len).unwrap();
+            assert!(v == data, "{} with {read_len}-byte reads", entry.name);
+        }
+    }
+
+    // data read straight into the caller's buffer is still checked
+    let mut bytes = corpus::stored_zip(&[("a.bin", &data)]);
+    bytes[100] ^= 1;
+    let archive = bytes.read_zip().unwrap();
+    let err = read_all(archive.by_name("a.bin").unwrap().reader(), 1024 * 1024).unwrap_err();
+    assert!(err.to_string().contains("checksum"), "{err}");
+}
+
+#[test]
+fn write_zip64() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+    let mut writer = ArchiveWriter::new(Vec::new()).force_zip64(true);
+    for name in ["a.bin", "b.bin"] {
+        let entry = NewEntry::new(name);
+        #[cfg(feature = "deflate")]
+        let entry = entry.with_method(Method::Deflate);
+        writer.start_entry(entry).unwrap();
+        writer.write_all(&data).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 2);
+    for entry in archive.entries() {
+        assert_eq!(entry.reader_version.version, 45);
+        assert_eq!(entry.bytes().unwrap(), data);
+    }
+
+    // more entries than the end of central directory record can count
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for i in 0..70_000 {
+        writer.start_entry(NewEntry::new(format!("{i}"))).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 70_000);
+    assert_eq!(archive.entries().last().unwrap().name, "69999");
+}
+
+#[test]
+fn write_size_hints() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    let entries = [
+        NewEntry::new("small.txt").with_size_hint(5),
+        NewEntry::new("large.txt").with_size_hint(5 << 30),
+        NewEntry::new("unknown.txt"),
+    ];
+    for entry in entries {
+        writer.start_entry(entry).unwrap();
+        writer.write_all(b"hello").unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    for entry in archive.entries() {
+        // only entries that may need 64-bit sizes get a zip64 extra field
+        let zip64 = entry.name != "small.txt";
+        let raw = entry.raw_local_header().unwrap();
+        let has_extra = raw.inner.len() > 30 + entry.name.len();
+        assert_eq!(has_extra, zip64, "{}", entry.name);
+        // the low byte of the version needed to extract
+        assert_eq!(raw.inner[4], if zip64 { 45 } else { 20 }, "{}", entry.name);
+
+        let mut rd = entry.reader();
+        let mut data = vec![];
+        rd.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hello");
+        assert_eq!(rd.trailer().unwrap().is_zip64, zip64, "{}", entry.name);
+    }
+
+    // the sizes are found in the right data descriptors without the
+    // central directory too
+    let mut rd = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .unwrap();
+    for name in ["small.txt", "large.txt"] {
+        assert_eq!(rd.entry().name, name);
+        rd.skip_rest().unwrap();
+        rd = rd.finish().unwrap().unwrap();
+    }
+    assert_eq!(rd.entry().name, "unknown.txt");
+    let mut data = vec![];
+    rd.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"hello");
+    assert!(rd.finish().unwrap().is_none());
+
+    let truncated = &bytes[..archive.directory_offset() as usize];
+    let recovered = truncated
+        .recover_zip_with_size(truncated.len() as u64)
+        .unwrap();
+    assert_eq!(recovered.entries().count(), 3);
+    for entry in recovered.entries() {
+        assert_eq!(entry.trust, EntryTrust::DataDescriptor);
+        assert_eq!(entry.uncompressed_size, 5, "{}", entry.name);
+        assert_eq!(entry.bytes().unwrap(), b"hello");
+    }
+}
+
+#[test]
+fn write_utf8_flag() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    let entries = [
+        (NewEntry::new("ascii.txt"), false),
+        (NewEntry::new("café.txt"), true),
+        (NewEntry::new("forced.txt").with_utf8_flag(Some(true)), true),
+        (
+            NewEntry::new("cleared-é.txt").with_utf8_flag(Some(false)),
+            false,
+        ),
+    ];
+    for (entry, _) in entries.clone() {
+        writer.start_entry(entry).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    for ((new_entry, utf8), entry) in entries.iter().zip(archive.entries()) {
+        assert_eq!(entry.flags & 0x800 != 0, *utf8, "{}", new_entry.name);
+        let raw = entry.raw_local_header().unwrap();
+        let flags = u16::from_le_bytes([raw.inner[6], raw.inner[7]]);
+        assert_eq!(flags & 0x800 != 0, *utf8, "{}", new_entry.name);
+    }
+}
+
+#[test]
+fn write_creator_version() {
+    corpus::install_test_subscriber();
+
+    let default_host = if cfg!(windows) {
+        HostSystem::MsDos
+    } else {
+        HostSystem::Unix
+    };
+    let versions = [
+        None,
+        Some(Version {
+            host_system: HostSystem::WindowsNtfs,
+            version: 63,
+        }),
+        Some(Version {
+            host_system: HostSystem::Osx,
+            version: 30,
+        }),
+    ];
+    for version in versions {
+        let mut writer = ArchiveWriter::new(Vec::new()).force_zip64(true);
+        if let Some(version) = version {
+            writer = writer.creator_version(version);
+        }
+        writer.start_entry(NewEntry::new("hello.txt")).unwrap();
+        writer.write_all(b"hello").unwrap();
+        writer.start_entry(NewEntry::new("dir/")).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let expected = version.unwrap_or(Version {
+            host_system: default_host,
+            version: 20,
+        });
+        let options = ArchiveReadOptions::default().with_keep_raw_records(true);
+        let archive = bytes.read_zip_with_options(options).unwrap();
+        for entry in archive.entries() {
+            let raw = entry.raw_central_record.as_ref().unwrap();
+            let creator_version = Version {
+                version: raw.inner[4],
+                host_system: raw.inner[5].into(),
+            };
+            assert_eq!(creator_version, expected, "{}", entry.name);
+            assert_eq!(entry.reader_version.host_system, expected.host_system);
+        }
+        let entry = archive.by_name("dir/").unwrap();
+        assert!(matches!(entry.kind(), EntryKind::Directory));
+    }
+}
+
+#[test]
+fn copy_raw_entries() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("a.txt")).unwrap();
+    writer.write_all(b"aaa").unwrap();
+    let entry = NewEntry::new("b.bin").with_comment("some data");
+    #[cfg(feature = "deflate")]
+    let entry = entry.with_method(Method::Deflate);
+    writer.start_entry(entry).unwrap();
+    writer.write_all(&data).unwrap();
+    let source = writer.finish().unwrap();
+    let source = source.read_zip().unwrap();
+
+    for force_zip64 in [false, true] {
+        let mut writer = ArchiveWriter::new(Vec::new()).force_zip64(force_zip64);
+        for entry in source.entries() {
+            writer
+                .copy_raw_entry(&entry, entry.raw_entry().unwrap())
+                .unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let archive = bytes.read_zip().unwrap();
+        assert_eq!(archive.entries().count(), 2);
+        for (copy, entry) in archive.entries().zip(source.entries()) {
+            assert_eq!(copy.name, entry.name);
+            assert_eq!(copy.method, entry.method);
+            assert_eq!(copy.crc32, entry.crc32);
+            assert_eq!(copy.compressed_size, entry.compressed_size);
+            assert_eq!(copy.comment, entry.comment);
+            assert_eq!(copy.bytes().unwrap(), entry.bytes().unwrap());
+        }
+    }
+
+    // the data given has to match the entry's compressed size
+    let entry = source.by_name("a.txt").unwrap();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    assert!(writer.copy_raw_entry(&entry, &b"aa"[..]).is_err());
+}
+
+/// Gives out at most a few bytes per read
+struct TrickleReader<R>(R);
+
+impl<R: Read> Read for TrickleReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(7);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn decompression_limits() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("small.txt")).unwrap();
+    writer.write_all(b"hello").unwrap();
+    let entry = NewEntry::new("zeros.bin");
+    #[cfg(feature = "deflate")]
+    let entry = entry.with_method(Method::Deflate);
+    writer.start_entry(entry).unwrap();
+    writer.write_all(&vec![0u8; 1 << 20]).unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let zip_error = |e: io::Error| *e.into_inner().unwrap().downcast::<Error>().unwrap();
+    let read = |limits: DecompressionLimits| {
+        let archive = bytes.read_zip().unwrap();
+        let entry = archive.by_name("zeros.bin").unwrap();
+        let mut contents = Vec::new();
+        let result = entry.reader_with_limits(limits).read_to_end(&mut contents);
+        result.map_err(zip_error)
+    };
+
+    let limits = DecompressionLimits {
+        max_output: Some(1 << 20),
+        ..Default::default()
+    };
+    assert_eq!(read(limits).unwrap(), 1 << 20);
+    let limits = DecompressionLimits {
+        max_output: Some(1000),
+        ..Default::default()
+    };
+    assert!(matches!(
+        read(limits),
+        Err(Error::LimitExceeded(LimitExceeded::OutputSize {
+            limit: 1000
+        }))
+    ));
+
+    #[cfg(feature = "deflate")]
+    {
+        let limits = DecompressionLimits {
+            max_ratio: Some(100),
+            ..Default::default()
+        };
+        assert!(matches!(
+            read(limits),
+            Err(Error::LimitExceeded(LimitExceeded::Ratio { limit: 100 }))
+        ));
+    }
+
+    let limits = DecompressionLimits {
+        max_depth: Some(1),
+        ..Default::default()
+    };
+    let nested = limits.nested().unwrap();
+    assert_eq!(nested.depth, 1);
+    assert_eq!(read(nested).unwrap(), 1 << 20);
+    assert!(matches!(
+        nested.nested(),
+        Err(Error::LimitExceeded(LimitExceeded::Depth { limit: 1 }))
+    ));
+
+    // streaming readers keep their limits from one entry to the next
+    let limits = DecompressionLimits {
+        max_output: Some(1000),
+        ..Default::default()
+    };
+    let mut zip = StreamingZipReader::new(&bytes[..]).with_limits(limits);
+    let entry = zip.next_entry().unwrap().unwrap();
+    let mut contents = Vec::new();
+    entry.read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, b"hello");
+    let entry = zip.next_entry().unwrap().unwrap();
+    assert_eq!(entry.entry().name, "zeros.bin");
+    let err = entry.read_to_end(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        zip_error(err),
+        Error::LimitExceeded(LimitExceeded::OutputSize { limit: 1000 })
+    ));
+}
+
+#[test]
+fn nested_archives() {
+    corpus::install_test_subscriber();
+
+    let innermost = corpus::stored_zip(&[("inner.txt", b"inner")]);
+    let inner = corpus::stored_zip(&[("deeper.zip", &innermost)]);
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("plugin.zip")).unwrap();
+    writer.write_all(&inner).unwrap();
+    let entry = NewEntry::new("packed.zip");
+    #[cfg(feature = "deflate")]
+    let entry = entry.with_method(Method::Deflate);
+    writer.start_entry(entry).unwrap();
+    writer.write_all(&innermost).unwrap();
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    // stored entries are read in place
+    let limits = DecompressionLimits {
+        max_depth: Some(1),
+        ..Default::default()
+    };
+    let plugin = archive
+        .by_name("plugin.zip")
+        .unwrap()
+        .read_zip(limits)
+        .unwrap();
+    assert!(matches!(plugin.file(), NestedFile::Stored(_)));
+    let deeper = plugin.by_name("deeper.zip").unwrap();
+    assert!(matches!(
+        deeper.read_zip(limits.nested().unwrap()),
+        Err(Error::LimitExceeded(LimitExceeded::Depth { limit: 1 }))
+    ));
+    let deeper = deeper.read_zip(Default::default()).unwrap();
+    assert_eq!(
+        deeper.by_name("inner.txt").unwrap().bytes().unwrap(),
+        b"inner"
+    );
+
+    let packed = archive.by_name("packed.zip").unwrap();
+    let nested = packed.read_zip(limits).unwrap();
+    #[cfg(feature = "deflate")]
+    assert!(matches!(nested.file(), NestedFile::Decompressed(_)));
+    assert_eq!(
+        nested.by_name("inner.txt").unwrap().bytes().unwrap(),
+        b"inner"
+    );
+
+    let limits = DecompressionLimits {
+        max_output: Some(10),
+        ..Default::default()
+    };
+    for name in ["plugin.zip", "packed.zip"] {
+        assert!(matches!(
+            archive.by_name(name).unwrap().read_zip(limits),
+            Err(Error::LimitExceeded(LimitExceeded::OutputSize {
+                limit: 10
+            }))
+        ));
+    }
+
+    // entries that aren't archives
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("hello.txt").unwrap();
+    assert!(entry.read_zip(Default::default()).is_err());
+}
+
+#[test]
+fn streaming_zip_reader() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+    // a data descriptor signature, but not the data descriptor
+    let tricky = b"PK\x07\x08 is not a data descriptor, PK\x07\x08\x00\x00\x00\x00 either";
+
+    for force_zip64 in [false, true] {
+        let mut writer = ArchiveWriter::new(Vec::new()).force_zip64(force_zip64);
+        writer.start_entry(NewEntry::new("tricky.txt")).unwrap();
+        writer.write_all(tricky).unwrap();
+        writer.start_entry(NewEntry::new("empty/")).unwrap();
+        let entry = NewEntry::new("data.bin");
+        #[cfg(feature = "deflate")]
+        let entry = entry.with_method(Method::Deflate);
+        writer.start_entry(entry).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.start_entry(NewEntry::new("skipped.bin")).unwrap();
+        writer.write_all(&data).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut zip = StreamingZipReader::new(TrickleReader(&bytes[..]));
+        let mut read = Vec::new();
+        while let Some(entry) = zip.next_entry().unwrap() {
+            let name = entry.entry().name.clone();
+            let mut contents = Vec::new();
+            if name != "skipped.bin" {
+                entry.read_to_end(&mut contents).unwrap();
+            }
+            read.push((name, contents));
+        }
+        assert!(zip.next_entry().unwrap().is_none());
+
+        assert_eq!(
+            read,
+            [
+                ("tricky.txt".to_owned(), tricky.to_vec()),
+                ("empty/".to_owned(), vec![]),
+                ("data.bin".to_owned(), data.clone()),
+                ("skipped.bin".to_owned(), vec![]),
+            ]
+        );
+    }
+
+    // an archive without entries
+    let bytes = ArchiveWriter::new(Vec::new()).finish().unwrap();
+    let mut zip = StreamingZipReader::new(&bytes[..]);
+    assert!(zip.next_entry().unwrap().is_none());
+
+    // a truncated one
+    let bytes = corpus::stored_zip(&[("a.txt", b"aaa")]);
+    let mut zip = StreamingZipReader::new(&bytes[..40]);
+    let entry = zip.next_entry().unwrap().unwrap();
+    let mut contents = Vec::new();
+    entry.read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, b"aaa");
+    assert!(zip.next_entry().is_err());
+}
+
+#[test]
+fn seekable_reader() {
+    use std::io::{Seek, SeekFrom};
+
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for (name, method) in [("stored", Method::Store), ("deflated", Method::Deflate)] {
+        writer
+            .start_entry(NewEntry::new(name).with_method(method))
+            .unwrap();
+        writer.write_all(&data).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    for name in ["stored", "deflated"] {
+        let entry = archive.by_name(name).unwrap();
+        let mut rd = entry.seekable_reader().unwrap();
+        let mut read_at = |pos: SeekFrom, len: usize| {
+            let start = rd.seek(pos).unwrap() as usize;
+            let mut buf = vec![0u8; len];
+            rd.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[start..start + len], "{name} at {pos:?}");
+            assert_eq!(rd.stream_position().unwrap() as usize, start + len);
+        };
+        read_at(SeekFrom::Start(300_000), 1000);
+        read_at(SeekFrom::Start(10), 20);
+        read_at(SeekFrom::Current(5000), 100);
+        read_at(SeekFrom::End(-16), 16);
+
+        assert_eq!(rd.seek(SeekFrom::End(10)).unwrap(), 400_010);
+        assert_eq!(rd.read(&mut [0u8; 16]).unwrap(), 0);
+        assert!(rd.seek(SeekFrom::Current(-500_000)).is_err());
+
+        rd.rewind().unwrap();
+        let mut all = Vec::new();
+        rd.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+    }
+}
+
+#[test]
+fn stored_entry_read_at() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for (name, method) in [("stored", Method::Store), ("deflated", Method::Deflate)] {
+        writer
+            .start_entry(NewEntry::new(name).with_method(method))
+            .unwrap();
+        writer.write_all(&data).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    assert!(archive
+        .by_name("deflated")
+        .unwrap()
+        .stored()
+        .unwrap()
+        .is_none());
+    let stored = archive
+        .by_name("stored")
+        .unwrap()
+        .stored()
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.len(), 10_000);
+    assert_eq!(&bytes[stored.data_offset() as usize..][..16], &data[..16]);
+
+    let mut buf = [0u8; 100];
+    stored.read_exact_at(5000, &mut buf).unwrap();
+    assert_eq!(buf, data[5000..5100]);
+    assert_eq!(stored.read_at(9_990, &mut buf).unwrap(), 10);
+    assert_eq!(buf[..10], data[9_990..]);
+    assert_eq!(stored.read_at(10_000, &mut buf).unwrap(), 0);
+    assert_eq!(
+        stored.read_exact_at(9_950, &mut buf).unwrap_err().kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+}
+
+#[test]
+fn reader_with_verification() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[14..18].copy_from_slice(&[0xef, 0xbe, 0xad, 0xde]);
+    bytes[central + 16..central + 20].copy_from_slice(&[0xef, 0xbe, 0xad, 0xde]);
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("hello.txt").unwrap();
+
+    assert!(entry.bytes().is_err());
+    let mut data = Vec::new();
+    entry
+        .reader_with_verification(Verification::Skip)
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, b"hello");
+}
+
+#[test]
+fn verify_archive() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("a.txt", b"hello"), ("b.txt", b"world"), ("c.txt", b"!")]);
+    let report = bytes.read_zip().unwrap().verify(Verification::IfPresent);
+    assert!(report.is_ok());
+    assert_eq!(report.entries.len(), 3);
+
+    // corrupt the data of b.txt, and the name in the local header of c.txt
+    let mut bytes = bytes;
+    let world = bytes.windows(5).position(|w| w == b"world").unwrap();
+    bytes[world] = b'W';
+    let local_c = bytes.windows(5).position(|w| w == b"c.txt").unwrap();
+    bytes[local_c] = b'C';
+
+    let report = bytes.read_zip().unwrap().verify(Verification::IfPresent);
+    assert!(!report.is_ok());
+    let failures: Vec<_> = report
+        .failures()
+        .map(|entry| (entry.name.as_str(), entry.result.as_ref().unwrap_err()))
+        .collect();
+    assert_eq!(failures.len(), 2, "{failures:?}");
+    assert!(matches!(
+        failures[0],
+        ("b.txt", Error::Format(FormatError::WrongChecksum { .. }))
+    ));
+    assert!(matches!(
+        failures[1],
+        (
+            "c.txt",
+            Error::Format(FormatError::LocalHeaderMismatch { .. })
+        )
+    ));
+    assert_eq!(
+        report.entries[2].mismatches,
+        vec![HeaderMismatch::Name {
+            local: b"C.txt".to_vec(),
+            central: b"c.txt".to_vec(),
+        }]
+    );
+}
+
+#[test]
+fn header_mismatches() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    assert!(bytes
+        .read_zip()
+        .unwrap()
+        .by_name("hello.txt")
+        .unwrap()
+        .header_mismatches()
+        .unwrap()
+        .is_empty());
+
+    // the local header claims another checksum and compression method
+    bytes[8..10].copy_from_slice(&8u16.to_le_bytes());
+    bytes[14..18].copy_from_slice(&0xdeadbeef_u32.to_le_bytes());
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("hello.txt").unwrap();
+    let mismatches = entry.header_mismatches().unwrap();
+    assert_eq!(
+        mismatches,
+        vec![
+            HeaderMismatch::Method {
+                local: Method::Deflate,
+                central: Method::Store,
+            },
+            HeaderMismatch::Crc32 {
+                local: 0xdeadbeef,
+                central: entry.crc32,
+            },
+        ]
+    );
+}
+
+#[test]
+fn generated_archives() {
+    use rc_zip::corpus::ZipBuilder;
+
+    corpus::install_test_subscriber();
+
+    // archives made from arbitrary bytes read back, but for wrong CRC-32s
+    let mut state = 0x2545_f491_u32;
+    for len in 0..300 {
+        let input: Vec<u8> = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let builder = ZipBuilder::from_bytes(&input);
+        let bytes = builder.build();
+        let archive = bytes.read_zip().unwrap();
+
+        for spec in &builder.entries {
+            // overlapping entries may overlap overlapping entries
+            let mut data_spec = spec;
+            while let Some(index) = data_spec.overlaps {
+                data_spec = &builder.entries[index];
+            }
+            let res = archive.by_name(&spec.name).unwrap().bytes();
+            if data_spec.wrong_crc32 {
+                let err = res.unwrap_err();
+                assert!(err.to_string().contains("checksum"), "{input:?}: {err}");
+            } else {
+                assert_eq!(res.unwrap(), data_spec.data, "{input:?}");
+            }
+        }
     }
 }
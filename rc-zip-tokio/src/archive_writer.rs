@@ -0,0 +1,192 @@
+use std::{io, pin::pin, pin::Pin, task};
+
+use futures_util::{Stream, StreamExt};
+
+use rc_zip::{
+    error::Error,
+    fsm::{ArchiveWriterFsm, Compression, NewEntry},
+    parse::{Archive, Entry, Version},
+};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// Writes a zip archive to any [AsyncWrite], see [ArchiveWriterFsm].
+///
+/// Start an entry with [Self::start_entry], then write its data through the
+/// [AsyncWrite] implementation. Call [Self::finish] once all entries are
+/// written, otherwise the archive is left without a central directory.
+pub struct ArchiveWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fsm: ArchiveWriterFsm,
+    wr: W,
+}
+
+impl<W> ArchiveWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Create a new archive writer, writing to `wr`
+    pub fn new(wr: W) -> Self {
+        Self {
+            fsm: ArchiveWriterFsm::new(),
+            wr,
+        }
+    }
+
+    /// Appends entries to an existing archive, see [ArchiveWriterFsm::append]:
+    /// `wr` is seeked to the start of its central directory, which is then
+    /// overwritten.
+    ///
+    /// The new archive may be shorter than the existing one, for example if
+    /// it had a zip64 end of central directory record: once finished, files
+    /// should be truncated to the writer's position, with
+    /// [tokio::fs::File::set_len].
+    pub async fn append(archive: &Archive, mut wr: W) -> Result<Self, Error>
+    where
+        W: AsyncSeek,
+    {
+        let fsm = ArchiveWriterFsm::append(archive)?;
+        wr.seek(io::SeekFrom::Start(archive.directory_offset()))
+            .await?;
+        Ok(Self { fsm, wr })
+    }
+
+    /// Writes zip64 records whether they're needed or not, see
+    /// [ArchiveWriterFsm::force_zip64]
+    pub fn force_zip64(mut self, force: bool) -> Self {
+        self.fsm = self.fsm.force_zip64(force);
+        self
+    }
+
+    /// Sets the "version made by" of new entries, see
+    /// [ArchiveWriterFsm::creator_version]
+    pub fn creator_version(mut self, version: Version) -> Self {
+        self.fsm = self.fsm.creator_version(version);
+        self
+    }
+
+    /// Lets `selector` pick each entry's compression method and level, from
+    /// the entry and its first bytes, see
+    /// [ArchiveWriterFsm::select_compression]
+    pub fn select_compression(
+        mut self,
+        selector: impl FnMut(&NewEntry, &[u8]) -> Compression + Send + Sync + 'static,
+    ) -> Self {
+        self.fsm = self.fsm.select_compression(selector);
+        self
+    }
+
+    /// Sets the archive comment, written when the archive is finished
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.fsm.set_comment(comment);
+    }
+
+    /// Finishes the current entry, if any, and starts a new one
+    pub async fn start_entry(&mut self, entry: NewEntry) -> Result<(), Error> {
+        self.fsm.start_entry(entry)?;
+        self.flush_output().await?;
+        Ok(())
+    }
+
+    /// Writes entries as they come out of `entries`, along with readers for
+    /// their data, finishing the current entry, if any. Each entry's data
+    /// is written as it's read, so archives can be made straight from async
+    /// pipelines, e.g. database exports or paginated API responses, without
+    /// buffering entries anywhere.
+    ///
+    /// More entries can be written afterwards, and [Self::finish] still has
+    /// to be called once they all are.
+    pub async fn write_entries<S, R>(&mut self, entries: S) -> Result<(), Error>
+    where
+        S: Stream<Item = (NewEntry, R)>,
+        R: AsyncRead,
+    {
+        let mut entries = pin!(entries);
+        while let Some((entry, rd)) = entries.next().await {
+            self.start_entry(entry).await?;
+            tokio::io::copy(&mut pin!(rd), self).await?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the current entry, if any, and copies `entry` from another
+    /// archive without recompressing it, see [ArchiveWriterFsm::start_raw_entry].
+    /// `rd` gives its data as stored, e.g. from
+    /// [EntryHandle::raw_entry](crate::EntryHandle::raw_entry).
+    pub async fn copy_raw_entry(
+        &mut self,
+        entry: &Entry,
+        mut rd: impl AsyncRead + Unpin,
+    ) -> Result<(), Error> {
+        self.fsm.start_raw_entry(entry)?;
+        self.flush_output().await?;
+        tokio::io::copy(&mut rd, self).await?;
+        self.fsm.finish_entry()?;
+        self.flush_output().await?;
+        Ok(())
+    }
+
+    /// Finishes the current entry, if any, and writes the central directory.
+    /// Returns the underlying writer, flushed.
+    pub async fn finish(mut self) -> Result<W, Error> {
+        self.fsm.finish()?;
+        self.flush_output().await?;
+        self.wr.flush().await?;
+        Ok(self.wr)
+    }
+
+    /// Writes all of the state machine's output to the underlying writer
+    async fn flush_output(&mut self) -> io::Result<()> {
+        let output = self.fsm.output();
+        let n = output.len();
+        self.wr.write_all(output).await?;
+        self.fsm.consume(n);
+        Ok(())
+    }
+
+    /// Like [Self::flush_output], for the [AsyncWrite] implementation
+    fn poll_flush_output(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        while !self.fsm.output().is_empty() {
+            let n = futures_util::ready!(Pin::new(&mut self.wr).poll_write(cx, self.fsm.output()))?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into()).into();
+            }
+            self.fsm.consume(n);
+        }
+        Ok(()).into()
+    }
+}
+
+impl<W> AsyncWrite for ArchiveWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        // output from the previous write is written out first, so at most
+        // one write's worth is ever buffered
+        futures_util::ready!(self.poll_flush_output(cx))?;
+        self.fsm.write(buf)?;
+        Ok(buf.len()).into()
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        futures_util::ready!(self.poll_flush_output(cx))?;
+        Pin::new(&mut self.wr).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        futures_util::ready!(self.poll_flush_output(cx))?;
+        Pin::new(&mut self.wr).poll_shutdown(cx)
+    }
+}
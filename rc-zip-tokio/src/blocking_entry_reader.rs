@@ -0,0 +1,199 @@
+use std::{future::Future, io, pin::Pin, sync::Arc, task};
+
+use rc_zip::{
+    error::Error,
+    fsm::{EntryFsm, FsmResult},
+    parse::Entry,
+    progress::Progress,
+};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    task::JoinHandle,
+};
+
+/// Size of the buffer decompressed data is written to on the blocking pool,
+/// before it's copied to the caller's buffer.
+const OUT_BUF_SIZE: usize = 128 * 1024;
+
+/// What a [EntryFsm::process] call on the blocking pool hands back: the
+/// state machine (unless it's done) along with the number of bytes it read
+/// and wrote, the output buffer, and the number of bytes filled before the call.
+type ProcessResult = (
+    Result<Option<(Box<EntryFsm>, usize, usize)>, Error>,
+    Vec<u8>,
+    usize,
+);
+
+#[derive(Default)]
+enum State {
+    Idle {
+        fsm: Box<EntryFsm>,
+        out: Vec<u8>,
+        out_pos: usize,
+        out_len: usize,
+    },
+    Processing(JoinHandle<ProcessResult>),
+    Done,
+
+    #[default]
+    Transitioning,
+}
+
+/// Like [crate::entry_reader::EntryReader], except reads from the underlying
+/// cursor happen on the current task, but decompression happens on tokio's
+/// blocking thread pool.
+pub(crate) struct BlockingEntryReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    rd: R,
+    state: State,
+}
+
+impl<R> BlockingEntryReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    pub(crate) fn new<F>(entry: &Entry, get_reader: F) -> Self
+    where
+        F: Fn(u64) -> R,
+    {
+        Self {
+            rd: get_reader(entry.header_offset),
+            state: State::Idle {
+                fsm: Box::new(EntryFsm::new(Some(entry.clone()), None)),
+                out: vec![0u8; OUT_BUF_SIZE],
+                out_pos: 0,
+                out_len: 0,
+            },
+        }
+    }
+
+    pub(crate) fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.state = match std::mem::take(&mut self.state) {
+            State::Idle {
+                fsm,
+                out,
+                out_pos,
+                out_len,
+            } => State::Idle {
+                fsm: Box::new(fsm.with_progress(progress)),
+                out,
+                out_pos,
+                out_len,
+            },
+            state => state,
+        };
+        self
+    }
+}
+
+impl<R> AsyncRead for BlockingEntryReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        let this = &mut *self;
+
+        loop {
+            match std::mem::take(&mut this.state) {
+                State::Idle {
+                    mut fsm,
+                    out,
+                    mut out_pos,
+                    out_len,
+                } => {
+                    if out_pos < out_len {
+                        let n = buf.remaining().min(out_len - out_pos);
+                        buf.put_slice(&out[out_pos..][..n]);
+                        out_pos += n;
+                        this.state = State::Idle {
+                            fsm,
+                            out,
+                            out_pos,
+                            out_len,
+                        };
+                        return Ok(()).into();
+                    }
+
+                    let mut filled_bytes = 0;
+                    if fsm.wants_read() {
+                        tracing::trace!(space_avail = fsm.space().len(), "fsm wants read");
+                        let mut read_buf = ReadBuf::new(fsm.space());
+                        match Pin::new(&mut this.rd).poll_read(cx, &mut read_buf) {
+                            task::Poll::Ready(res) => res?,
+                            task::Poll::Pending => {
+                                this.state = State::Idle {
+                                    fsm,
+                                    out,
+                                    out_pos,
+                                    out_len,
+                                };
+                                return task::Poll::Pending;
+                            }
+                        }
+                        filled_bytes = read_buf.filled().len();
+
+                        tracing::trace!("read {} bytes", filled_bytes);
+                        fsm.fill(filled_bytes);
+                    }
+
+                    let mut out = out;
+                    this.state = State::Processing(tokio::task::spawn_blocking(move || {
+                        let res = fsm.process(&mut out[..]).map(|res| match res {
+                            FsmResult::Continue((fsm, outcome)) => {
+                                Some((Box::new(fsm), outcome.bytes_read, outcome.bytes_written))
+                            }
+                            FsmResult::Done(_) => None,
+                        });
+                        (res, out, filled_bytes)
+                    }));
+                }
+                State::Processing(mut handle) => {
+                    let (res, out, filled_bytes) = match Pin::new(&mut handle).poll(cx) {
+                        task::Poll::Ready(res) => {
+                            res.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+                        }
+                        task::Poll::Pending => {
+                            this.state = State::Processing(handle);
+                            return task::Poll::Pending;
+                        }
+                    };
+
+                    match res? {
+                        Some((fsm, bytes_read, bytes_written)) => {
+                            if bytes_written == 0 && filled_bytes == 0 && bytes_read == 0 {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::Other,
+                                    "entry reader: no progress",
+                                ))
+                                .into();
+                            }
+
+                            tracing::trace!("wrote {} bytes", bytes_written);
+                            this.state = State::Idle {
+                                fsm,
+                                out,
+                                out_pos: 0,
+                                out_len: bytes_written,
+                            };
+                        }
+                        None => {
+                            this.state = State::Done;
+                            return Ok(()).into();
+                        }
+                    }
+                }
+                State::Done => {
+                    this.state = State::Done;
+                    return Ok(()).into();
+                }
+                State::Transitioning => unreachable!(),
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task,
+};
+
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::trace;
+
+use crate::HasCursor;
+
+/// Wraps a [HasCursor] with an LRU cache of fixed-size blocks, shared by all
+/// cursors created from it.
+///
+/// Reading a zip involves fetching the same ranges several times: the end of
+/// central directory and the central directory while opening the archive, and
+/// local headers whenever an entry is read. For remote resources, where each
+/// read is a round-trip, caching those avoids downloading the same bytes over
+/// and over, e.g. when browsing an archive interactively.
+///
+/// Consecutive blocks that aren't cached are fetched with the same inner
+/// cursor, so sequential reads don't result in one request per block.
+///
+/// [Cached::stats] reports how much was fetched from the inner resource
+/// compared to how much was actually read, which helps tuning the block size.
+pub struct Cached<F> {
+    inner: F,
+    block_size: usize,
+    max_blocks: usize,
+    blocks: Mutex<Blocks>,
+    stats: Counters,
+}
+
+impl<F> Cached<F>
+where
+    F: HasCursor,
+{
+    /// Default size of a cached block, in bytes.
+    pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+    /// Default number of blocks kept in the cache.
+    pub const DEFAULT_MAX_BLOCKS: usize = 64;
+
+    /// Wrap `inner`, with a cache of up to [Self::DEFAULT_MAX_BLOCKS] blocks
+    /// of [Self::DEFAULT_BLOCK_SIZE] bytes.
+    pub fn new(inner: F) -> Self {
+        Self::with_capacity(inner, Self::DEFAULT_BLOCK_SIZE, Self::DEFAULT_MAX_BLOCKS)
+    }
+
+    /// Wrap `inner`, with a cache of up to `max_blocks` blocks of `block_size`
+    /// bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `block_size` or `max_blocks` is zero.
+    pub fn with_capacity(inner: F, block_size: usize, max_blocks: usize) -> Self {
+        assert!(block_size > 0, "block size must be non-zero");
+        assert!(
+            max_blocks > 0,
+            "cache must be able to hold at least one block"
+        );
+
+        Self {
+            inner,
+            block_size,
+            max_blocks,
+            blocks: Default::default(),
+            stats: Default::default(),
+        }
+    }
+
+    /// Returns transfer statistics for all the cursors created so far.
+    pub fn stats(&self) -> TransferStats {
+        let s = &self.stats;
+        TransferStats {
+            requests: s.requests.load(Ordering::Relaxed),
+            bytes_fetched: s.bytes_fetched.load(Ordering::Relaxed),
+            bytes_consumed: s.bytes_consumed.load(Ordering::Relaxed),
+            cache_hits: s.cache_hits.load(Ordering::Relaxed),
+            cache_misses: s.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the wrapped resource.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    fn blocks(&self) -> MutexGuard<'_, Blocks> {
+        // the cache is never left in an inconsistent state, so a panic
+        // while it was locked doesn't matter.
+        self.blocks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<F> HasCursor for Cached<F>
+where
+    F: HasCursor,
+{
+    type Cursor<'a> = CachedCursor<'a, F>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        CachedCursor {
+            cache: self,
+            offset,
+            block: None,
+            fetch: None,
+            inner: None,
+        }
+    }
+}
+
+/// A snapshot of the transfer statistics of a [Cached] resource.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    /// How many cursors were created on the inner resource. For remote
+    /// resources, that's usually the number of requests issued.
+    pub requests: u64,
+
+    /// How many bytes were read from the inner resource.
+    pub bytes_fetched: u64,
+
+    /// How many bytes were read from the cache's cursors.
+    pub bytes_consumed: u64,
+
+    /// How many times a block was found in the cache.
+    pub cache_hits: u64,
+
+    /// How many times a block had to be fetched from the inner resource.
+    pub cache_misses: u64,
+}
+
+impl TransferStats {
+    /// The proportion of block lookups that were served from the cache,
+    /// between 0 and 1. Zero if there weren't any lookups yet.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / lookups as f64
+        }
+    }
+
+    /// How many bytes were fetched for each byte consumed: above 1 means
+    /// bytes were fetched and never read (or evicted and fetched again),
+    /// below 1 means the cache saved some transfers. Zero if nothing was
+    /// consumed yet.
+    pub fn amplification(&self) -> f64 {
+        if self.bytes_consumed == 0 {
+            0.0
+        } else {
+            self.bytes_fetched as f64 / self.bytes_consumed as f64
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    bytes_fetched: AtomicU64,
+    bytes_consumed: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Blocks {
+    /// block index => (data, last time it was used)
+    map: HashMap<u64, (Arc<[u8]>, u64)>,
+    clock: u64,
+}
+
+impl Blocks {
+    fn get(&mut self, index: u64) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let (data, last_used) = self.map.get_mut(&index)?;
+        *last_used = self.clock;
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, index: u64, data: Arc<[u8]>, max_blocks: usize) {
+        self.clock += 1;
+        if self.map.len() >= max_blocks && !self.map.contains_key(&index) {
+            let lru = self
+                .map
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(index, _)| *index);
+            if let Some(lru) = lru {
+                self.map.remove(&lru);
+            }
+        }
+        self.map.insert(index, (data, self.clock));
+    }
+}
+
+/// A block being fetched from the inner resource.
+struct Fetch {
+    index: u64,
+    buf: Vec<u8>,
+    filled: usize,
+}
+
+/// The cursor type for [Cached].
+pub struct CachedCursor<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    cache: &'a Cached<F>,
+    offset: u64,
+
+    /// the block `offset` is in, if we have it
+    block: Option<(u64, Arc<[u8]>)>,
+
+    /// the block `offset` is in, if we're fetching it
+    fetch: Option<Fetch>,
+
+    /// the inner cursor used for the last fetch, and the offset it's at
+    inner: Option<(u64, F::Cursor<'a>)>,
+}
+
+impl<F> AsyncRead for CachedCursor<'_, F>
+where
+    F: HasCursor,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+        let block_size = this.cache.block_size as u64;
+
+        loop {
+            let index = this.offset / block_size;
+
+            if let Some((block_index, data)) = &this.block {
+                if *block_index == index {
+                    // blocks are only shorter than `block_size` at the end
+                    // of the resource, so if we're past this one, it's EOF.
+                    let within = (this.offset % block_size) as usize;
+                    let n = data.len().saturating_sub(within).min(buf.remaining());
+                    if n > 0 {
+                        buf.put_slice(&data[within..][..n]);
+                        this.offset += n as u64;
+                        let stats = &this.cache.stats;
+                        stats.bytes_consumed.fetch_add(n as u64, Ordering::Relaxed);
+                    }
+                    return Ok(()).into();
+                }
+            }
+
+            let fetch = match &mut this.fetch {
+                Some(fetch) => fetch,
+                None => {
+                    if let Some(data) = this.cache.blocks().get(index) {
+                        trace!(%index, "cache hit");
+                        let stats = &this.cache.stats;
+                        stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+                        this.block = Some((index, data));
+                        continue;
+                    }
+
+                    trace!(%index, "cache miss");
+                    let stats = &this.cache.stats;
+                    stats.cache_misses.fetch_add(1, Ordering::Relaxed);
+                    this.fetch.insert(Fetch {
+                        index,
+                        buf: vec![0u8; this.cache.block_size],
+                        filled: 0,
+                    })
+                }
+            };
+
+            let fetch_offset = fetch.index * block_size + fetch.filled as u64;
+            let mut inner = match this.inner.take() {
+                Some((inner_offset, inner)) if inner_offset == fetch_offset => inner,
+                _ => {
+                    let stats = &this.cache.stats;
+                    stats.requests.fetch_add(1, Ordering::Relaxed);
+                    this.cache.inner.cursor_at(fetch_offset)
+                }
+            };
+
+            let mut read_buf = ReadBuf::new(&mut fetch.buf[fetch.filled..]);
+            match Pin::new(&mut inner).poll_read(cx, &mut read_buf) {
+                task::Poll::Pending => {
+                    this.inner = Some((fetch_offset, inner));
+                    return task::Poll::Pending;
+                }
+                task::Poll::Ready(Err(e)) => {
+                    this.fetch = None;
+                    return Err(e).into();
+                }
+                task::Poll::Ready(Ok(())) => {
+                    let n = read_buf.filled().len();
+                    fetch.filled += n;
+                    let stats = &this.cache.stats;
+                    stats.bytes_fetched.fetch_add(n as u64, Ordering::Relaxed);
+                    this.inner = Some((fetch_offset + n as u64, inner));
+
+                    if n == 0 || fetch.filled == fetch.buf.len() {
+                        let mut fetch = this.fetch.take().unwrap();
+                        fetch.buf.truncate(fetch.filled);
+                        let data: Arc<[u8]> = fetch.buf.into();
+                        this.cache.blocks().insert(
+                            fetch.index,
+                            data.clone(),
+                            this.cache.max_blocks,
+                        );
+                        this.block = Some((fetch.index, data));
+                    }
+                }
+            }
+        }
+    }
+}
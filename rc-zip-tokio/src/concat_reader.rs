@@ -0,0 +1,106 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Mutex, PoisonError},
+    task,
+};
+
+use rc_zip::parse::Entry;
+use tokio::io::{AsyncRead, ReadBuf, Take};
+
+use crate::{entry_reader::EntryReader, EntryHandle, HasCursor};
+
+/// A function returning bytes to insert before an entry, see
+/// [ConcatReader::with_separator].
+///
+/// It's only ever called through `&mut self`, so it doesn't need to be Sync
+/// for the reader to be: the mutex is never locked.
+type Separator<'a> = Mutex<Box<dyn FnMut(&Entry) -> Vec<u8> + Send + 'a>>;
+
+/// Reads the uncompressed contents of several entries back-to-back, as if
+/// they were a single file. Useful for payloads split into numbered entries.
+///
+/// Entries are read in the order they're given in, which doesn't need to be
+/// the order they appear in the archive.
+pub struct ConcatReader<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    entries: std::vec::IntoIter<EntryHandle<'a, F>>,
+    current: Option<EntryReader<Take<F::Cursor<'a>>>>,
+    separator: Option<Separator<'a>>,
+    pending: Vec<u8>,
+    pending_pos: usize,
+    started: bool,
+}
+
+impl<'a, F> ConcatReader<'a, F>
+where
+    F: HasCursor,
+{
+    /// Creates a reader for the given entries, in order.
+    pub fn new(entries: impl IntoIterator<Item = EntryHandle<'a, F>>) -> Self {
+        Self {
+            entries: entries.into_iter().collect::<Vec<_>>().into_iter(),
+            current: None,
+            separator: None,
+            pending: Vec::new(),
+            pending_pos: 0,
+            started: false,
+        }
+    }
+
+    /// Calls `separator` between entries, with the entry that's about to be
+    /// read, and inserts the bytes it returns.
+    pub fn with_separator(mut self, separator: impl FnMut(&Entry) -> Vec<u8> + Send + 'a) -> Self {
+        self.separator = Some(Mutex::new(Box::new(separator)));
+        self
+    }
+}
+
+impl<F> AsyncRead for ConcatReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+        if buf.remaining() == 0 {
+            return Ok(()).into();
+        }
+
+        loop {
+            if this.pending_pos < this.pending.len() {
+                let n = buf.remaining().min(this.pending.len() - this.pending_pos);
+                buf.put_slice(&this.pending[this.pending_pos..][..n]);
+                this.pending_pos += n;
+                return Ok(()).into();
+            }
+
+            if let Some(current) = &mut this.current {
+                let filled_before = buf.filled().len();
+                futures_util::ready!(Pin::new(current).poll_read(cx, buf))?;
+                if buf.filled().len() > filled_before {
+                    return Ok(()).into();
+                }
+                this.current = None;
+            }
+
+            let Some(next) = this.entries.next() else {
+                return Ok(()).into();
+            };
+            if this.started {
+                if let Some(separator) = &mut this.separator {
+                    let separator = separator.get_mut().unwrap_or_else(PoisonError::into_inner);
+                    this.pending = separator(&next);
+                    this.pending_pos = 0;
+                }
+            }
+            this.started = true;
+            this.current = Some(next.entry_reader());
+        }
+    }
+}
@@ -1,20 +1,24 @@
-use std::{io, pin::Pin, task};
+use std::{io, pin::Pin, sync::Arc, task};
 
 use pin_project_lite::pin_project;
 use rc_zip::{
-    fsm::{EntryFsm, FsmResult},
+    fsm::{DecompressionLimits, EntryFsm, EntryTrailer, FsmResult, Verification},
     parse::Entry,
+    progress::Progress,
 };
 use tokio::io::{AsyncRead, ReadBuf};
 
 pin_project! {
-    pub(crate) struct EntryReader<R>
+    /// Reads an entry's data, decompressing and checking it as it goes, see
+    /// [EntryHandle::reader](crate::EntryHandle::reader).
+    pub struct EntryReader<R>
     where
         R: AsyncRead,
     {
         #[pin]
         rd: R,
         fsm: Option<EntryFsm>,
+        trailer: Option<EntryTrailer>,
     }
 }
 
@@ -24,13 +28,42 @@ where
 {
     pub(crate) fn new<F>(entry: &Entry, get_reader: F) -> Self
     where
-        F: Fn(u64) -> R,
+        F: FnOnce(u64) -> R,
     {
         Self {
             rd: get_reader(entry.header_offset),
             fsm: Some(EntryFsm::new(Some(entry.clone()), None)),
+            trailer: None,
         }
     }
+
+    /// Returns the data descriptor that followed the entry's data, once it's
+    /// been read to the end: `None` if there wasn't any, or if the entry
+    /// isn't read yet.
+    pub fn trailer(&self) -> Option<&EntryTrailer> {
+        self.trailer.as_ref()
+    }
+
+    pub(crate) fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_limits(limits));
+        self
+    }
+
+    pub(crate) fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_progress(progress));
+        self
+    }
+
+    pub(crate) fn with_verification(mut self, verification: Verification) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_verification(verification));
+        self
+    }
+
+    #[cfg(feature = "aes")]
+    pub(crate) fn with_password(mut self, password: &[u8]) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_password(password));
+        self
+    }
 }
 
 impl<R> AsyncRead for EntryReader<R>
@@ -88,7 +121,8 @@ where
                         .into();
                     }
                 }
-                FsmResult::Done(_) => {
+                FsmResult::Done((_, trailer)) => {
+                    *this.trailer = trailer;
                     // neat!
                 }
             }
@@ -0,0 +1,278 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures_util::{StreamExt, TryStreamExt};
+use rc_zip::{
+    chrono::{DateTime, Utc},
+    error::{Error, FormatError},
+    parse::{Entry, EntryKind},
+    progress::Progress,
+};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncWriteExt},
+};
+use tracing::trace;
+
+use crate::{ArchiveHandle, EntryHandle, HasCursor};
+
+/// Options for [ArchiveHandle::extract_all].
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// How many files are written at the same time. Defaults to 8.
+    pub concurrency: usize,
+
+    /// Whether files get the [permissions](rc_zip::parse::Mode::permissions)
+    /// they have in the archive, off by default. This is only done on Unix,
+    /// and files whose permissions are 0 keep the default ones.
+    pub permissions: bool,
+
+    /// Whether files and directories get the modification time they have in
+    /// the archive, off by default. Directories only get it on Unix.
+    pub modified_times: bool,
+
+    /// What's told about progress as files are written, see [Progress]:
+    /// [Progress::entry_done] is called for every file, once it's been
+    /// read entirely.
+    pub progress: Option<Arc<dyn Progress>>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            permissions: false,
+            modified_times: false,
+            progress: None,
+        }
+    }
+}
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Extracts all entries into `dir`, several files at a time.
+    ///
+    /// All directories are created first, then files are written, at most
+    /// [ExtractOptions::concurrency] at a time, each decompressed on tokio's
+    /// blocking thread pool (see [EntryHandle::reader_on_blocking_pool]).
+    /// Metadata is applied last, so that writing files doesn't change the
+    /// modification time of their directories.
+    ///
+    /// Files are written to a temporary file next to them, renamed once
+    /// complete: dropping this future, for example when its task is
+    /// cancelled, doesn't leave partially written files behind.
+    ///
+    /// Like [crate::ReadZipStreaming::extract_streaming_to_dir], entries
+    /// without a [safe path](rc_zip::parse::Entry::safe_path) and symlinks
+    /// are skipped. Symlinks already in `dir` aren't followed: extraction
+    /// fails with [FormatError::UnsafePath] rather than go through one, and
+    /// one where an entry goes is replaced.
+    pub async fn extract_all(
+        &self,
+        dir: impl AsRef<Path>,
+        options: ExtractOptions,
+    ) -> Result<(), Error> {
+        let dir = dir.as_ref();
+
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        for entry in self.entries() {
+            let Some(name) = entry.safe_path() else {
+                trace!(name = %entry.name, "extract_all: skipping entry with unsafe name");
+                continue;
+            };
+
+            match entry.kind() {
+                EntryKind::Directory => {
+                    let path = prepare(dir, &name, &entry).await?;
+                    fs::create_dir_all(&path).await?;
+                    dirs.push((path, entry));
+                }
+                EntryKind::File => {
+                    let path = prepare(dir, &name, &entry).await?;
+                    files.push((path, entry));
+                }
+                EntryKind::Symlink => {
+                    trace!(name = %entry.name, "extract_all: skipping symlink");
+                }
+            }
+        }
+
+        futures_util::stream::iter(files.iter())
+            .map(|(path, entry)| extract_file(path, entry, options.progress.clone()))
+            .buffer_unordered(options.concurrency.max(1))
+            .try_collect::<()>()
+            .await?;
+
+        if !options.permissions && !options.modified_times {
+            return Ok(());
+        }
+        let metadata: Vec<Metadata> = files
+            .iter()
+            .map(|(path, entry)| Metadata {
+                path: path.clone(),
+                is_dir: false,
+                permissions: options.permissions.then(|| entry.mode.permissions()),
+                modified: options.modified_times.then_some(entry.modified),
+            })
+            .chain(dirs.iter().map(|(path, entry)| Metadata {
+                path: path.clone(),
+                is_dir: true,
+                permissions: None,
+                modified: options.modified_times.then_some(entry.modified),
+            }))
+            .collect();
+        tokio::task::spawn_blocking(move || metadata.iter().try_for_each(Metadata::apply))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+        Ok(())
+    }
+}
+
+/// Returns where the entry at `relative` goes in `dir`, creating the
+/// directories leading to it. Like the sync crate's `Extractor`, this
+/// doesn't go through symlinks that are already there, which could point
+/// outside of `dir`, and removes one at the entry's own path, so that it's
+/// replaced rather than written through.
+pub(crate) async fn prepare(dir: &Path, relative: &Path, entry: &Entry) -> Result<PathBuf, Error> {
+    fs::create_dir_all(dir).await?;
+
+    let mut path = dir.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        path.push(component);
+        let is_last = components.peek().is_none();
+
+        match fs::symlink_metadata(&path).await {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                if !is_last {
+                    trace!(name = %entry.name, "extract: refusing to go through symlink {}", path.display());
+                    return Err(FormatError::UnsafePath {
+                        name: entry.name.clone(),
+                    }
+                    .into());
+                }
+                fs::remove_file(&path).await?;
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if !is_last {
+                    fs::create_dir(&path).await?;
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(path)
+}
+
+async fn extract_file<F>(
+    path: &Path,
+    entry: &EntryHandle<'_, F>,
+    progress: Option<Arc<dyn Progress>>,
+) -> Result<(), Error>
+where
+    F: HasCursor,
+{
+    trace!(name = %entry.name, "extract_all: writing file");
+    let mut rd = entry.blocking_entry_reader();
+    if let Some(progress) = progress {
+        rd = rd.with_progress(progress);
+    }
+    write_file(path, &mut rd).await?;
+    Ok(())
+}
+
+/// Writes what `rd` yields to a new file at `path`, returning its size.
+///
+/// Data goes to a temporary file next to it first, which is renamed once
+/// complete: if this future is dropped before that, for example because the
+/// task it runs in was cancelled, the temporary file is removed, so no
+/// partially written file is left behind.
+pub(crate) async fn write_file<R>(path: &Path, rd: &mut R) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+{
+    let temp = TempPath::next_to(path);
+    let mut file = fs::File::create(&temp.path).await?;
+    let size = tokio::io::copy(rd, &mut file).await?;
+    file.flush().await?;
+    drop(file);
+    fs::rename(&temp.path, path).await?;
+    temp.keep();
+    Ok(size)
+}
+
+/// A temporary file, removed when this is dropped unless it was kept.
+struct TempPath {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempPath {
+    fn next_to(path: &Path) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self {
+            path: path.with_file_name(format!(
+                ".{name}.{}-{unique}.rc-zip-tmp",
+                std::process::id()
+            )),
+            keep: false,
+        }
+    }
+
+    fn keep(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if !self.keep {
+            trace!(path = %self.path.display(), "removing temporary file");
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Metadata applied to an extracted file or directory.
+struct Metadata {
+    path: PathBuf,
+    is_dir: bool,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    permissions: Option<u32>,
+    modified: Option<DateTime<Utc>>,
+}
+
+impl Metadata {
+    fn apply(&self) -> io::Result<()> {
+        // times first: files may be made read-only below
+        if let Some(modified) = self.modified {
+            if !self.is_dir {
+                let file = std::fs::OpenOptions::new().write(true).open(&self.path)?;
+                file.set_modified(modified.into())?;
+            } else if cfg!(unix) {
+                let dir = std::fs::File::open(&self.path)?;
+                dir.set_modified(modified.into())?;
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(permissions) = self.permissions.filter(|p| *p != 0) {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(permissions))?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
-//! A library for reading zip files asynchronously using tokio I/O traits,
-//! based on top of [rc-zip](https://crates.io/crates/rc-zip).
+//! A library for reading and writing zip files asynchronously using tokio
+//! I/O traits, based on top of [rc-zip](https://crates.io/crates/rc-zip).
 //!
 //! See also:
 //!
@@ -7,14 +7,48 @@
 
 #![warn(missing_docs)]
 
+mod archive_writer;
+pub use archive_writer::ArchiveWriter;
+
+mod blocking_entry_reader;
+mod cache;
+pub use cache::{Cached, CachedCursor, TransferStats};
+
+mod concat_reader;
+pub use concat_reader::ConcatReader;
+
 mod entry_reader;
+pub use entry_reader::EntryReader;
+
+mod extract;
+pub use extract::ExtractOptions;
+
 mod read_zip;
 
+mod prefetching_entry_reader;
+pub use prefetching_entry_reader::PrefetchingEntryReader;
+
+mod range_reader;
+pub use range_reader::{RangeReader, Ranged, RangedCursor};
+
+mod seekable_entry_reader;
+pub use seekable_entry_reader::SeekableEntryReader;
+
+mod retry;
+pub use retry::{RetryPolicy, Retrying, RetryingCursor};
+
 mod streaming_entry_reader;
 pub use streaming_entry_reader::StreamingEntryReader;
 
+mod streaming_extract;
+
+mod streaming_zip_reader;
+pub use streaming_zip_reader::StreamingZipReader;
+
 // re-exports
 pub use rc_zip;
 pub use read_zip::{
-    ArchiveHandle, EntryHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    ArchiveHandle, AsyncRandomAccessFileCursor, DynHasCursor, EntryHandle, HasCursor,
+    OwnedArchiveHandle, OwnedEntryHandle, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    SharedEntryHandle,
 };
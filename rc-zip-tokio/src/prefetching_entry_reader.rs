@@ -0,0 +1,181 @@
+use std::{io, pin::Pin, task};
+
+use rc_zip::parse::Entry;
+use tokio::io::{AsyncRead, ReadBuf, Take};
+use tracing::trace;
+
+use crate::{entry_reader::EntryReader, read_zip::Window, HasCursor};
+
+/// How many bytes of the next entry (local header and start of the data) are
+/// fetched while the current entry is being read.
+const PREFETCH_SIZE: usize = 64 * 1024;
+
+/// Reads the entries of an archive one after the other, in central directory
+/// order, fetching the start of the next entry while the current one is being
+/// read. This hides some of the round-trip latency of remote cursors.
+///
+/// It's obtained through [ArchiveHandle::read_entries_prefetching](crate::ArchiveHandle::read_entries_prefetching),
+/// and moves on to the next entry with [PrefetchingEntryReader::next_entry].
+pub struct PrefetchingEntryReader<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    file: &'a F,
+    window: Window,
+    entry: &'a Entry,
+    rest: Box<dyn Iterator<Item = &'a Entry> + Send + Sync + 'a>,
+    inner: EntryReader<PrefetchCursor<Take<F::Cursor<'a>>>>,
+    next: Option<(&'a Entry, PrefetchCursor<Take<F::Cursor<'a>>>)>,
+}
+
+impl<'a, F> PrefetchingEntryReader<'a, F>
+where
+    F: HasCursor,
+{
+    pub(crate) fn new(
+        file: &'a F,
+        window: Window,
+        mut entries: Box<dyn Iterator<Item = &'a Entry> + Send + Sync + 'a>,
+    ) -> Option<Self> {
+        let entry = entries.next()?;
+        let cursor = PrefetchCursor::new(window.cursor_at(file, entry.header_offset), 0);
+        Some(Self::with_cursor(file, window, entry, cursor, entries))
+    }
+
+    fn with_cursor(
+        file: &'a F,
+        window: Window,
+        entry: &'a Entry,
+        cursor: PrefetchCursor<Take<F::Cursor<'a>>>,
+        mut rest: Box<dyn Iterator<Item = &'a Entry> + Send + Sync + 'a>,
+    ) -> Self {
+        let next = rest.next().map(|next| {
+            let cursor = window.cursor_at(file, next.header_offset);
+            (next, PrefetchCursor::new(cursor, PREFETCH_SIZE))
+        });
+
+        Self {
+            file,
+            window,
+            entry,
+            rest,
+            inner: EntryReader::new(entry, |_| cursor),
+            next,
+        }
+    }
+
+    /// Return entry information for the entry being read.
+    pub fn entry(&self) -> &'a Entry {
+        self.entry
+    }
+
+    /// Moves on to the next entry, or returns `None` if this was the last one.
+    ///
+    /// The current entry doesn't need to have been read in full.
+    pub fn next_entry(self) -> Option<Self> {
+        let (entry, cursor) = self.next?;
+        Some(Self::with_cursor(
+            self.file,
+            self.window,
+            entry,
+            cursor,
+            self.rest,
+        ))
+    }
+}
+
+impl<F> AsyncRead for PrefetchingEntryReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Some((_, next)) = &mut this.next {
+            next.poll_prefetch(cx);
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+/// A cursor that can read ahead up to a fixed number of bytes into a buffer,
+/// which it returns first when read from.
+struct PrefetchCursor<C> {
+    cursor: C,
+    buf: Vec<u8>,
+    buf_pos: usize,
+    buf_len: usize,
+    prefetching: bool,
+    err: Option<io::Error>,
+}
+
+impl<C> PrefetchCursor<C>
+where
+    C: AsyncRead + Unpin,
+{
+    fn new(cursor: C, size: usize) -> Self {
+        Self {
+            cursor,
+            buf: vec![0u8; size],
+            buf_pos: 0,
+            buf_len: 0,
+            prefetching: size > 0,
+            err: None,
+        }
+    }
+
+    /// Makes as much progress prefetching as possible without blocking.
+    fn poll_prefetch(&mut self, cx: &mut task::Context<'_>) {
+        while self.prefetching && self.buf_len < self.buf.len() {
+            let mut read_buf = ReadBuf::new(&mut self.buf[self.buf_len..]);
+            match Pin::new(&mut self.cursor).poll_read(cx, &mut read_buf) {
+                task::Poll::Ready(Ok(())) => {
+                    let n = read_buf.filled().len();
+                    trace!(%n, "prefetched bytes");
+                    if n == 0 {
+                        self.prefetching = false;
+                    }
+                    self.buf_len += n;
+                }
+                task::Poll::Ready(Err(e)) => {
+                    self.err = Some(e);
+                    self.prefetching = false;
+                }
+                task::Poll::Pending => return,
+            }
+        }
+    }
+}
+
+impl<C> AsyncRead for PrefetchCursor<C>
+where
+    C: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+        this.prefetching = false;
+
+        if this.buf_pos < this.buf_len {
+            let n = buf.remaining().min(this.buf_len - this.buf_pos);
+            buf.put_slice(&this.buf[this.buf_pos..][..n]);
+            this.buf_pos += n;
+            if this.buf_pos == this.buf_len {
+                this.buf = Vec::new();
+            }
+            return Ok(()).into();
+        }
+
+        if let Some(e) = this.err.take() {
+            return Err(e).into();
+        }
+
+        Pin::new(&mut this.cursor).poll_read(cx, buf)
+    }
+}
@@ -0,0 +1,184 @@
+use std::{io, ops::Range, pin::Pin, task};
+
+use futures_util::future::BoxFuture;
+use rc_zip::{error::Error, fsm::ArchiveReadOptions};
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::trace;
+
+use crate::{ArchiveHandle, HasCursor, ReadZip, ReadZipWithSize};
+
+/// A resource that is read one range at a time, with a request for each:
+/// typically a file served over HTTP, read with range requests.
+///
+/// [Ranged] turns it into a [HasCursor], which can be read as a zip archive
+/// with [ReadZip].
+pub trait RangeReader {
+    /// Returns the size of the resource, in bytes: for HTTP, the
+    /// `Content-Length` of a `HEAD` request.
+    fn size(&self) -> BoxFuture<'_, io::Result<u64>>;
+
+    /// Returns the bytes of the resource in `range`. Fewer bytes may be
+    /// returned if the range goes past the end of the resource, and none if
+    /// it starts past the end, but more is an error: for HTTP, that's
+    /// usually a server that ignored the `Range` header.
+    fn read_range(&self, range: Range<u64>) -> BoxFuture<'_, io::Result<Vec<u8>>>;
+}
+
+/// Reads a zip archive from a [RangeReader].
+///
+/// Opening the archive takes a request for the size, then a few range
+/// requests for the end of central directory record and the central
+/// directory. Entries are then read on demand, with a request for the first
+/// [Self::request_size] bytes, and requests twice as large as the previous
+/// one after that, up to [Self::MAX_REQUEST_SIZE], so reading an entry of
+/// any size only takes a few requests.
+///
+/// Wrap it in [crate::Cached] to avoid fetching local headers several times,
+/// and in [crate::Retrying] if requests fail now and then.
+pub struct Ranged<R> {
+    inner: R,
+    request_size: u64,
+}
+
+impl<R> Ranged<R>
+where
+    R: RangeReader,
+{
+    /// Default size of the first request of each cursor, in bytes.
+    pub const DEFAULT_REQUEST_SIZE: u64 = 64 * 1024;
+
+    /// The size requests grow up to, in bytes.
+    pub const MAX_REQUEST_SIZE: u64 = 8 * 1024 * 1024;
+
+    /// Wrap `inner`, starting with requests of [Self::DEFAULT_REQUEST_SIZE]
+    /// bytes.
+    pub fn new(inner: R) -> Self {
+        Self::with_request_size(inner, Self::DEFAULT_REQUEST_SIZE)
+    }
+
+    /// Wrap `inner`, starting with requests of `request_size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `request_size` is zero.
+    pub fn with_request_size(inner: R, request_size: u64) -> Self {
+        assert!(request_size > 0, "request size must be non-zero");
+        Self {
+            inner,
+            request_size,
+        }
+    }
+
+    /// Returns the size of the first request of each cursor, in bytes.
+    pub fn request_size(&self) -> u64 {
+        self.request_size
+    }
+
+    /// Returns the wrapped resource.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> HasCursor for Ranged<R>
+where
+    R: RangeReader,
+{
+    type Cursor<'a> = RangedCursor<'a, R>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        RangedCursor {
+            ranged: self,
+            offset,
+            request_size: self.request_size,
+            data: Vec::new(),
+            pos: 0,
+            request: None,
+        }
+    }
+}
+
+impl<R> ReadZip for Ranged<R>
+where
+    R: RangeReader,
+{
+    type File = Self;
+
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self>, Error> {
+        let size = self.inner.size().await?;
+        self.read_zip_with_size_and_options(size, options).await
+    }
+}
+
+/// The cursor type for [Ranged].
+pub struct RangedCursor<'a, R> {
+    ranged: &'a Ranged<R>,
+
+    /// offset of the end of `data`
+    offset: u64,
+
+    /// size of the next request
+    request_size: u64,
+
+    /// what the last request returned, and how much of it was read
+    data: Vec<u8>,
+    pos: usize,
+
+    request: Option<BoxFuture<'a, io::Result<Vec<u8>>>>,
+}
+
+impl<R> AsyncRead for RangedCursor<'_, R>
+where
+    R: RangeReader,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        if this.pos == this.data.len() {
+            let request = this.request.get_or_insert_with(|| {
+                let range = this.offset..this.offset.saturating_add(this.request_size);
+                trace!(?range, "requesting range");
+                this.ranged.inner.read_range(range)
+            });
+            let data = futures_util::ready!(request.as_mut().poll(cx));
+            this.request = None;
+            let data = data?;
+
+            if data.len() as u64 > this.request_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "asked for {} bytes at offset {}, got {}",
+                        this.request_size,
+                        this.offset,
+                        data.len()
+                    ),
+                ))
+                .into();
+            }
+            this.offset += data.len() as u64;
+            this.request_size = this
+                .request_size
+                .saturating_mul(2)
+                .min(Ranged::<R>::MAX_REQUEST_SIZE)
+                .max(this.ranged.request_size);
+            this.data = data;
+            this.pos = 0;
+        }
+
+        // if the request returned nothing, that's EOF
+        let n = (this.data.len() - this.pos).min(buf.remaining());
+        buf.put_slice(&this.data[this.pos..][..n]);
+        this.pos += n;
+        Ok(()).into()
+    }
+}
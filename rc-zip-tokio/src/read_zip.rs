@@ -1,6 +1,7 @@
 use std::{
     cmp, io,
-    ops::Deref,
+    ops::{Deref, Range},
+    path::Path,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
@@ -8,16 +9,26 @@ use std::{
 
 use futures_util::future::BoxFuture;
 use positioned_io::{RandomAccessFile, ReadAt, Size};
-use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf, Take};
 
 use rc_zip::{
-    error::Error,
-    fsm::{ArchiveFsm, EntryFsm, FsmResult},
-    parse::{Archive, Entry},
+    error::{Error, FormatError},
+    fsm::{
+        ArchiveFsm, ArchiveReadOptions, DecompressionLimits, FsmResult, RecoveryFsm,
+        StreamingEntryFsm, Verification,
+    },
+    jar::{Manifest, MANIFEST_NAME},
+    parse::{Archive, Entry, HeaderMismatch, LocalFileHeader, Located, Method, Offsets},
+    progress::Progress,
 };
 use tracing::trace;
 
-use crate::{entry_reader::EntryReader, StreamingEntryReader};
+use crate::{
+    blocking_entry_reader::BlockingEntryReader,
+    entry_reader::EntryReader,
+    streaming_entry_reader::{next_entry, Next},
+    streaming_extract, PrefetchingEntryReader, SeekableEntryReader, StreamingEntryReader,
+};
 
 /// A trait for reading something as a zip archive.
 ///
@@ -28,7 +39,56 @@ pub trait ReadZipWithSize {
 
     /// Reads self as a zip archive.
     #[allow(async_fn_in_trait)]
-    async fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+    async fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(size, Default::default())
+            .await
+    }
+
+    /// Reads self as a zip archive, with the given options.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_size_and_options(
+        &self,
+        size: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_at_with_options(0, size, options).await
+    }
+
+    /// Reads a zip archive embedded in self, that is `len` bytes long and
+    /// starts at `offset`, as found in some container formats (game packs,
+    /// firmware images, etc.)
+    ///
+    /// Nothing outside of that window is read: the end of central directory
+    /// is looked for at the end of the window, and entry offsets are
+    /// relative to its start.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_at(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_at_with_options(offset, len, Default::default())
+            .await
+    }
+
+    /// Reads a zip archive embedded in self, with the given options. See
+    /// [ReadZipWithSize::read_zip_at].
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_at_with_options(
+        &self,
+        offset: u64,
+        len: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Salvages what can be found of a damaged zip archive, by scanning all
+    /// of self for local headers rather than reading the central directory,
+    /// see [RecoveryFsm].
+    #[allow(async_fn_in_trait)]
+    async fn recover_zip_with_size(
+        &self,
+        size: u64,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
 }
 
 /// A zip archive, read asynchronously from a file or other I/O resource.
@@ -42,7 +102,60 @@ pub trait ReadZip {
 
     /// Reads self as a zip archive.
     #[allow(async_fn_in_trait)]
-    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_options(Default::default()).await
+    }
+
+    /// Reads self as a zip archive, with the given options.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive that starts at `offset`, like one
+    /// embedded in a firmware image: offsets recorded in the archive are
+    /// relative to it, see [ArchiveReadOptions::base_offset]. Unlike
+    /// [ReadZipWithSize::read_zip_at], the archive's length needn't be
+    /// known, as long as it's near the end of self.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_offset(
+        &self,
+        offset: u64,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_options(ArchiveReadOptions::default().with_base_offset(offset))
+            .await
+    }
+
+    /// Reads self as a zip archive, which then owns self: unlike
+    /// [ArchiveHandle], it can be stored in structs, or moved to other
+    /// tasks.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_owned(self) -> Result<OwnedArchiveHandle<Self>, Error>
+    where
+        Self: ReadZip<File = Self> + HasCursor + Sized,
+    {
+        self.read_zip_owned_with_options(Default::default()).await
+    }
+
+    /// Like [ReadZip::read_zip_owned], with the given options.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_owned_with_options(
+        self,
+        options: ArchiveReadOptions,
+    ) -> Result<OwnedArchiveHandle<Self>, Error>
+    where
+        Self: ReadZip<File = Self> + HasCursor + Sized,
+    {
+        let ArchiveHandle {
+            window, archive, ..
+        } = self.read_zip_with_options(options).await?;
+        Ok(OwnedArchiveHandle {
+            file: Arc::new(self),
+            window,
+            archive,
+        })
+    }
 }
 
 impl<F> ReadZipWithSize for F
@@ -51,14 +164,20 @@ where
 {
     type File = F;
 
-    async fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+    async fn read_zip_at_with_options(
+        &self,
+        offset: u64,
+        len: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
         struct CursorState<'a, F: HasCursor + 'a> {
-            cursor: <F as HasCursor>::Cursor<'a>,
+            cursor: Take<<F as HasCursor>::Cursor<'a>>,
             offset: u64,
         }
         let mut cstate: Option<CursorState<'_, F>> = None;
 
-        let mut fsm = ArchiveFsm::new(size);
+        let window = Window { offset, len };
+        let mut fsm = ArchiveFsm::with_options(len, options);
         loop {
             if let Some(offset) = fsm.wants_read() {
                 let mut cstate_next = match cstate.take() {
@@ -69,7 +188,7 @@ where
                         } else {
                             trace!(%offset, %cstate.offset, "read_zip_with_size: making new cursor (had wrong offset)");
                             CursorState {
-                                cursor: self.cursor_at(offset),
+                                cursor: window.cursor_at(self, offset),
                                 offset,
                             }
                         }
@@ -77,7 +196,7 @@ where
                     None => {
                         trace!(%offset, "read_zip_with_size: making new cursor (had none)");
                         CursorState {
-                            cursor: self.cursor_at(offset),
+                            cursor: window.cursor_at(self, offset),
                             offset,
                         }
                     }
@@ -102,6 +221,43 @@ where
                 FsmResult::Done(archive) => {
                     return Ok(ArchiveHandle {
                         file: self,
+                        window,
+                        archive,
+                    })
+                }
+                FsmResult::Continue(fsm) => fsm,
+            }
+        }
+    }
+
+    async fn recover_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+        let window = Window {
+            offset: 0,
+            len: size,
+        };
+        let mut cursor = None;
+        let mut fsm = RecoveryFsm::new(size);
+        loop {
+            if let Some(offset) = fsm.wants_read() {
+                // data is read forward, the cursor is only replaced when
+                // parts of the file are skipped
+                if cursor.as_ref().map(|(cursor_offset, _)| *cursor_offset) != Some(offset) {
+                    cursor = Some((offset, window.cursor_at(self, offset)));
+                }
+                let (cursor_offset, rd) = cursor.as_mut().unwrap();
+                let read_bytes = rd.read(fsm.space()).await?;
+                if read_bytes == 0 {
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                *cursor_offset += read_bytes as u64;
+                fsm.fill(read_bytes);
+            }
+
+            fsm = match fsm.process()? {
+                FsmResult::Done(archive) => {
+                    return Ok(ArchiveHandle {
+                        file: self,
+                        window,
                         archive,
                     })
                 }
@@ -114,25 +270,36 @@ where
 impl ReadZip for &[u8] {
     type File = Self;
 
-    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
-        self.read_zip_with_size(self.len() as u64).await
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len() as u64, options)
+            .await
     }
 }
 
 impl ReadZip for Vec<u8> {
     type File = Self;
 
-    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
-        self.read_zip_with_size(self.len() as u64).await
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size_and_options(self.len() as u64, options)
+            .await
     }
 }
 
 impl ReadZip for Arc<RandomAccessFile> {
     type File = Self;
 
-    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+    async fn read_zip_with_options(
+        &self,
+        options: ArchiveReadOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
         let size = self.size()?.unwrap_or_default();
-        self.read_zip_with_size(size).await
+        self.read_zip_with_size_and_options(size, options).await
     }
 }
 
@@ -142,6 +309,7 @@ where
     F: HasCursor,
 {
     file: &'a F,
+    window: Window,
     archive: Archive,
 }
 
@@ -156,7 +324,7 @@ where
     }
 }
 
-impl<F> ArchiveHandle<'_, F>
+impl<'a, F> ArchiveHandle<'a, F>
 where
     F: HasCursor,
 {
@@ -164,26 +332,136 @@ where
     pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
         self.archive.entries().map(move |entry| EntryHandle {
             file: self.file,
+            window: self.window,
             entry,
         })
     }
 
+    /// Consumes the archive, returning handles that own their entry and only
+    /// borrow the underlying file. Unlike [EntryHandle], they can be kept
+    /// around, sorted, or moved to other tasks independently of the
+    /// archive.
+    pub fn into_entries(self) -> impl Iterator<Item = OwnedEntryHandle<'a, F>> {
+        let (file, window) = (self.file, self.window);
+        self.archive
+            .into_entries()
+            .map(move |entry| OwnedEntryHandle {
+                file,
+                window,
+                entry,
+            })
+    }
+
     /// Attempts to look up an entry by name. This is usually a bad idea,
     /// as names aren't necessarily normalized in zip archives.
     pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Looks up an entry by name ignoring case and how it's normalized, see
+    /// [Archive::by_name_ci].
+    pub fn by_name_ci<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name_ci(name).map(|entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Returns the AppleDouble entry holding `entry`'s macOS metadata, see
+    /// [Archive::apple_double_for].
+    pub fn apple_double_for(&self, entry: &Entry) -> Option<EntryHandle<'_, F>> {
         self.archive
-            .entries()
-            .find(|&x| x.name == name.as_ref())
+            .apple_double_for(entry)
             .map(|entry| EntryHandle {
                 file: self.file,
+                window: self.window,
+                entry,
+            })
+    }
+
+    /// Looks up an entry of a multi-release JAR, see
+    /// [Archive::by_name_for_release].
+    pub fn by_name_for_release<N: AsRef<str>>(
+        &self,
+        name: N,
+        release: u32,
+    ) -> Option<EntryHandle<'_, F>> {
+        self.archive
+            .by_name_for_release(name, release)
+            .map(|entry| EntryHandle {
+                file: self.file,
+                window: self.window,
+                entry,
+            })
+    }
+
+    /// Returns all entries named `name`, see [Archive::entries_by_name].
+    pub fn entries_by_name<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> impl Iterator<Item = EntryHandle<'b, F>> + 'b {
+        self.archive
+            .entries_by_name(name)
+            .map(move |entry| EntryHandle {
+                file: self.file,
+                window: self.window,
                 entry,
             })
     }
+
+    /// Returns the entries under the directory `dir`, see
+    /// [Archive::entries_under].
+    pub fn entries_under<'b>(&'b self, dir: &str) -> impl Iterator<Item = EntryHandle<'b, F>> + 'b {
+        self.archive
+            .entries_under(dir)
+            .map(move |entry| EntryHandle {
+                file: self.file,
+                window: self.window,
+                entry,
+            })
+    }
+
+    /// Returns the entries whose name matches the glob `pattern`, see
+    /// [Archive::glob].
+    #[cfg(feature = "glob")]
+    pub fn glob(
+        &self,
+        pattern: &str,
+    ) -> Result<impl Iterator<Item = EntryHandle<'_, F>>, rc_zip::globset::Error> {
+        Ok(self.archive.glob(pattern)?.map(move |entry| EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry,
+        }))
+    }
+
+    /// Reads and parses the manifest, if this is a JAR file.
+    pub async fn manifest(&self) -> Result<Option<Manifest>, Error> {
+        let Some(entry) = self.by_name(MANIFEST_NAME) else {
+            return Ok(None);
+        };
+        Ok(Some(Manifest::parse(&entry.bytes().await?)?))
+    }
+
+    /// Returns a reader for the first entry, which can then move on to the
+    /// following ones. While an entry is being read, the start of the next
+    /// one is fetched ahead of time.
+    ///
+    /// Returns `None` if the archive has no entries.
+    pub fn read_entries_prefetching(&self) -> Option<PrefetchingEntryReader<'_, F>> {
+        PrefetchingEntryReader::new(self.file, self.window, Box::new(self.archive.entries()))
+    }
 }
 
 /// A single entry in a zip archive, read asynchronously from a file or other I/O resource.
 pub struct EntryHandle<'a, F> {
     file: &'a F,
+    window: Window,
     entry: &'a Entry,
 }
 
@@ -196,12 +474,349 @@ impl<F> Deref for EntryHandle<'_, F> {
 }
 
 impl<'a, F> EntryHandle<'a, F>
+where
+    F: HasCursor,
+{
+    /// Returns a reader for the entry. Once it's read to the end,
+    /// [EntryReader::trailer] tells about the entry's data descriptor.
+    pub fn reader(&self) -> EntryReader<Take<F::Cursor<'a>>> {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which tells `progress` how far along
+    /// decompressing it is, see [Progress].
+    pub fn reader_with_progress(&self, progress: Arc<dyn Progress>) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_progress(progress)
+    }
+
+    /// Returns a reader for the entry, which checks its CRC-32 once it's
+    /// read according to `verification`, see [Verification].
+    pub fn reader_with_verification(
+        &self,
+        verification: Verification,
+    ) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_verification(verification)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_password(password)
+    }
+
+    pub(crate) fn entry_reader(&self) -> EntryReader<Take<F::Cursor<'a>>> {
+        EntryReader::new(self.entry, |offset| {
+            self.window.cursor_at(self.file, offset)
+        })
+    }
+
+    /// Returns a reader for the entry that can also seek, see
+    /// [SeekableEntryReader]. For entries stored without compression, this
+    /// reads their local header first.
+    pub async fn seekable_reader(&self) -> Result<SeekableEntryReader<'a, F>, Error> {
+        let data_offset = if self.entry.method == Method::Store && !self.entry.is_encrypted() {
+            Some(self.data_offset().await?)
+        } else {
+            None
+        };
+        let handle = EntryHandle {
+            file: self.file,
+            window: self.window,
+            entry: self.entry,
+        };
+        Ok(SeekableEntryReader::new(handle, data_offset))
+    }
+
+    /// Returns a cursor at `offset` in the archive.
+    pub(crate) fn cursor_at(&self, offset: u64) -> Take<F::Cursor<'a>> {
+        self.window.cursor_at(self.file, offset)
+    }
+
+    /// Returns a reader for the entry that decompresses on tokio's blocking
+    /// thread pool, via [tokio::task::spawn_blocking].
+    ///
+    /// Reads from the underlying cursor still happen on the current task.
+    /// This is slower than [EntryHandle::reader] for small entries, but keeps
+    /// large deflate/zstd/etc. entries from hogging the async runtime's worker
+    /// threads.
+    pub fn reader_on_blocking_pool(&self) -> impl AsyncRead + Unpin + '_ {
+        self.blocking_entry_reader()
+    }
+
+    pub(crate) fn blocking_entry_reader(&self) -> BlockingEntryReader<Take<F::Cursor<'a>>> {
+        BlockingEntryReader::new(self.entry, |offset| {
+            self.window.cursor_at(self.file, offset)
+        })
+    }
+
+    /// Reads this entry's local file header verbatim, and returns it along
+    /// with its offset. Comparing it with [Entry::raw_central_record] shows
+    /// discrepancies that are otherwise papered over.
+    pub async fn raw_local_header(&self) -> Result<Located<Vec<u8>>, Error> {
+        let offset = self.entry.header_offset;
+        let mut rd = self.window.cursor_at(self.file, offset);
+        let mut bytes = Vec::new();
+        loop {
+            match LocalFileHeader::measure(&bytes)? {
+                Some(len) if bytes.len() >= len => {
+                    bytes.truncate(len);
+                    return Ok(Located {
+                        offset,
+                        inner: bytes,
+                    });
+                }
+                _ => {}
+            }
+            if (&mut rd).take(256).read_to_end(&mut bytes).await? == 0 {
+                return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+
+    /// Reads this entry's local file header, and returns the fields it
+    /// disagrees on with the central directory, see
+    /// [LocalFileHeader::mismatches].
+    pub async fn header_mismatches(&self) -> Result<Vec<HeaderMismatch>, Error> {
+        let header = self.raw_local_header().await?;
+        Ok(LocalFileHeader::parse_raw(&header.inner)?.mismatches(self.entry))
+    }
+
+    /// Returns a reader for the entry's data as stored in the archive,
+    /// compressed and possibly encrypted, e.g. to copy it to another archive
+    /// with [ArchiveWriter::copy_raw_entry](crate::ArchiveWriter::copy_raw_entry).
+    pub async fn raw_entry(&self) -> Result<impl AsyncRead + Unpin + 'a, Error> {
+        Ok(self
+            .window
+            .cursor_at(self.file, self.data_offset().await?)
+            .take(self.entry.compressed_size))
+    }
+
+    /// Where the entry's data starts, relative to the start of the archive:
+    /// right after its local header, which this reads.
+    pub async fn data_offset(&self) -> Result<u64, Error> {
+        let header = self.raw_local_header().await?;
+        Ok(Offsets::add(header.offset, header.inner.len() as u64)?)
+    }
+
+    /// Where the entry's data, as stored in the archive, is in the file or
+    /// other I/O resource it's read from, e.g. to map it into memory or send
+    /// it over a socket directly. Unlike [Self::data_offset], this accounts
+    /// for archives embedded in a larger file, see
+    /// [ReadZipWithSize::read_zip_at]. This reads the local header.
+    pub async fn data_range(&self) -> Result<Range<u64>, Error> {
+        let start = self.window.offset.saturating_add(self.data_offset().await?);
+        Ok(start..start.saturating_add(self.entry.compressed_size))
+    }
+
+    /// Reads the entire entry into a vector.
+    pub async fn bytes(&self) -> io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        self.reader().read_to_end(&mut v).await?;
+        Ok(v)
+    }
+}
+
+/// A zip entry that owns its metadata, obtained through
+/// [ArchiveHandle::into_entries].
+pub struct OwnedEntryHandle<'a, F> {
+    file: &'a F,
+    window: Window,
+    entry: Entry,
+}
+
+impl<F> Clone for OwnedEntryHandle<'_, F> {
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file,
+            window: self.window,
+            entry: self.entry.clone(),
+        }
+    }
+}
+
+impl<F> Deref for OwnedEntryHandle<'_, F> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl<F> OwnedEntryHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Returns a reader for the entry.
+    pub fn reader(&self) -> EntryReader<Take<F::Cursor<'_>>> {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_password(password)
+    }
+
+    fn entry_reader(&self) -> EntryReader<Take<F::Cursor<'_>>> {
+        EntryReader::new(&self.entry, |offset| {
+            self.window.cursor_at(self.file, offset)
+        })
+    }
+
+    /// Returns a reader for the entry that decompresses on tokio's blocking
+    /// thread pool, see [EntryHandle::reader_on_blocking_pool].
+    pub fn reader_on_blocking_pool(&self) -> impl AsyncRead + Unpin + '_ {
+        BlockingEntryReader::new(&self.entry, |offset| {
+            self.window.cursor_at(self.file, offset)
+        })
+    }
+
+    /// Reads the entire entry into a vector.
+    pub async fn bytes(&self) -> io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        self.reader().read_to_end(&mut v).await?;
+        Ok(v)
+    }
+
+    /// Returns the entry's metadata, dropping the handle.
+    pub fn into_entry(self) -> Entry {
+        self.entry
+    }
+}
+
+/// A zip archive that owns the file or other I/O resource it's read from,
+/// obtained through [ReadZip::read_zip_owned].
+pub struct OwnedArchiveHandle<F> {
+    file: Arc<F>,
+    window: Window,
+    archive: Archive,
+}
+
+impl<F> Deref for OwnedArchiveHandle<F> {
+    type Target = Archive;
+
+    fn deref(&self) -> &Self::Target {
+        &self.archive
+    }
+}
+
+impl<F> OwnedArchiveHandle<F>
+where
+    F: HasCursor,
+{
+    /// Iterate over all files in this zip, read from the central directory.
+    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
+        self.archive.entries().map(move |entry| EntryHandle {
+            file: &*self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Consumes the archive, returning handles that own their entry and
+    /// share the underlying file: they can outlive the archive, and be
+    /// moved to other tasks independently of each other.
+    pub fn into_entries(self) -> impl Iterator<Item = SharedEntryHandle<F>> {
+        let (file, window) = (self.file, self.window);
+        self.archive
+            .into_entries()
+            .map(move |entry| SharedEntryHandle {
+                file: file.clone(),
+                window,
+                entry,
+            })
+    }
+
+    /// Attempts to look up an entry by name. This is usually a bad idea,
+    /// as names aren't necessarily normalized in zip archives.
+    pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| EntryHandle {
+            file: &*self.file,
+            window: self.window,
+            entry,
+        })
+    }
+
+    /// Returns the file or other I/O resource the archive is read from.
+    pub fn file(&self) -> &F {
+        &self.file
+    }
+}
+
+/// A zip entry that owns its metadata and shares the file it's read from,
+/// obtained through [OwnedArchiveHandle::into_entries].
+pub struct SharedEntryHandle<F> {
+    file: Arc<F>,
+    window: Window,
+    entry: Entry,
+}
+
+impl<F> Clone for SharedEntryHandle<F> {
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file.clone(),
+            window: self.window,
+            entry: self.entry.clone(),
+        }
+    }
+}
+
+impl<F> Deref for SharedEntryHandle<F> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl<F> SharedEntryHandle<F>
 where
     F: HasCursor,
 {
     /// Returns a reader for the entry.
-    pub fn reader(&self) -> impl AsyncRead + Unpin + '_ {
-        EntryReader::new(self.entry, |offset| self.file.cursor_at(offset))
+    pub fn reader(&self) -> EntryReader<Take<F::Cursor<'_>>> {
+        self.entry_reader()
+    }
+
+    /// Returns a reader for the entry, which fails if it decompresses to
+    /// more than `limits` allow.
+    pub fn reader_with_limits(&self, limits: DecompressionLimits) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_limits(limits)
+    }
+
+    /// Returns a reader for the entry, which decrypts it with the given
+    /// password if it's AES-encrypted.
+    #[cfg(feature = "aes")]
+    pub fn reader_with_password(&self, password: &[u8]) -> impl AsyncRead + Unpin + '_ {
+        self.entry_reader().with_password(password)
+    }
+
+    fn entry_reader(&self) -> EntryReader<Take<F::Cursor<'_>>> {
+        EntryReader::new(&self.entry, |offset| {
+            self.window.cursor_at(&*self.file, offset)
+        })
+    }
+
+    /// Returns a reader for the entry that decompresses on tokio's blocking
+    /// thread pool, see [EntryHandle::reader_on_blocking_pool].
+    pub fn reader_on_blocking_pool(&self) -> impl AsyncRead + Unpin + '_ {
+        BlockingEntryReader::new(&self.entry, |offset| {
+            self.window.cursor_at(&*self.file, offset)
+        })
     }
 
     /// Reads the entire entry into a vector.
@@ -210,26 +825,129 @@ where
         self.reader().read_to_end(&mut v).await?;
         Ok(v)
     }
+
+    /// Returns the entry's metadata, dropping the handle.
+    pub fn into_entry(self) -> Entry {
+        self.entry
+    }
+}
+
+/// Where an archive is within a [HasCursor]: usually all of it, but see
+/// [ReadZipWithSize::read_zip_at].
+#[derive(Clone, Copy)]
+pub(crate) struct Window {
+    offset: u64,
+    len: u64,
+}
+
+impl Window {
+    /// Returns a cursor at `offset` within the window, that stops at its end.
+    pub(crate) fn cursor_at<F: HasCursor>(self, file: &F, offset: u64) -> Take<F::Cursor<'_>> {
+        file.cursor_at(self.offset.saturating_add(offset))
+            .take(self.len.saturating_sub(offset))
+    }
 }
 
 /// A sliceable I/O resource: we can ask for an [AsyncRead] at a given offset.
+///
+/// This is the only thing rc-zip-tokio needs from a backend, so implementing
+/// it is all it takes to read zips from somewhere other than memory or local
+/// files (object stores, HTTP range requests, custom caches, etc.). For
+/// resources that are read with a request per range, see [crate::Ranged].
+///
+/// The contract is:
+///
+///   * [HasCursor::cursor_at] should be cheap and not perform any I/O: reads
+///     happen when the cursor is polled.
+///   * A cursor yields the bytes of the resource starting at `offset`, in
+///     order. Short reads are fine, and a read of zero bytes means the end of
+///     the resource was reached.
+///   * Cursors may be requested for any offset, in any order, and several of
+///     them may be alive at the same time (one per entry reader, for
+///     example), so they must not share a read position.
+///
+/// The size of the resource is not part of this trait: pass it to
+/// [ReadZipWithSize::read_zip_with_size], or implement [ReadZip].
 pub trait HasCursor {
     /// The type returned by [HasCursor::cursor_at].
     type Cursor<'a>: AsyncRead + Unpin + 'a
     where
         Self: 'a;
 
-    /// Returns an [AsyncRead] at the given offset.
+    /// Returns an [AsyncRead] that reads the resource from `offset` onwards.
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_>;
 }
 
+/// An object-safe form of [HasCursor], for when the type of the I/O resource
+/// isn't known statically, e.g. to keep files, in-memory buffers and network
+/// sources in one collection of `Box<dyn DynHasCursor>`.
+///
+/// It's implemented for every [HasCursor], and `dyn DynHasCursor` (as well as
+/// boxes of it) implements [HasCursor] in turn, by boxing every cursor.
+pub trait DynHasCursor {
+    /// Returns a boxed [AsyncRead] at the given offset.
+    fn dyn_cursor_at(&self, offset: u64) -> Box<dyn AsyncRead + Unpin + '_>;
+}
+
+impl<T> DynHasCursor for T
+where
+    T: HasCursor,
+{
+    fn dyn_cursor_at(&self, offset: u64) -> Box<dyn AsyncRead + Unpin + '_> {
+        Box::new(self.cursor_at(offset))
+    }
+}
+
+impl HasCursor for dyn DynHasCursor + '_ {
+    type Cursor<'a> = Box<dyn AsyncRead + Unpin + 'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.dyn_cursor_at(offset)
+    }
+}
+
+impl HasCursor for dyn DynHasCursor + Send + Sync + '_ {
+    type Cursor<'a> = Box<dyn AsyncRead + Unpin + 'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.dyn_cursor_at(offset)
+    }
+}
+
+impl<T> HasCursor for Box<T>
+where
+    T: HasCursor + ?Sized,
+{
+    type Cursor<'a> = T::Cursor<'a>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        (**self).cursor_at(offset)
+    }
+}
+
+/// Offsets past the end of a slice, including ones that don't even fit in
+/// a usize, result in an empty cursor, the same as reading past the end of a
+/// file would.
+fn slice_at(slice: &[u8], offset: u64) -> &[u8] {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| slice.get(offset..))
+        .unwrap_or_default()
+}
+
 impl HasCursor for &[u8] {
     type Cursor<'a> = &'a [u8]
     where
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        slice_at(self, offset)
     }
 }
 
@@ -239,7 +957,7 @@ impl HasCursor for Vec<u8> {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        slice_at(self, offset)
     }
 }
 
@@ -379,6 +1097,23 @@ where
     async fn stream_zip_entries_throwing_caution_to_the_wind(
         self,
     ) -> Result<StreamingEntryReader<R>, Error>;
+
+    /// Extracts all entries into `dir` as their local headers come in, then
+    /// reads the central directory that follows them, checks that it lists
+    /// the same entries, and returns it.
+    ///
+    /// This lets extraction overlap with, say, a download, but the checks
+    /// only happen at the end: if they fail, whatever was extracted should
+    /// be discarded. Since local headers don't carry file modes, symlinks
+    /// are extracted as regular files containing their target. Entries
+    /// without a [safe path](rc_zip::parse::Entry::safe_path) are
+    /// skipped.
+    ///
+    /// Symlinks already in `dir` aren't followed, as with
+    /// [ArchiveHandle::extract_all]: extraction fails rather than go through
+    /// one.
+    #[allow(async_fn_in_trait)]
+    async fn extract_streaming_to_dir(self, dir: &Path) -> Result<Archive, Error>;
 }
 
 impl<R> ReadZipStreaming<R> for R
@@ -386,21 +1121,15 @@ where
     R: AsyncRead + Unpin,
 {
     async fn stream_zip_entries_throwing_caution_to_the_wind(
-        mut self,
+        self,
     ) -> Result<StreamingEntryReader<Self>, Error> {
-        let mut fsm = EntryFsm::new(None, None);
-
-        loop {
-            if fsm.wants_read() {
-                let n = self.read(fsm.space()).await?;
-                trace!("read {} bytes into buf for first zip entry", n);
-                fsm.fill(n);
-            }
-
-            if let Some(entry) = fsm.process_till_header()? {
-                let entry = entry.clone();
-                return Ok(StreamingEntryReader::new(fsm, entry, self));
-            }
+        match next_entry(StreamingEntryFsm::new(None), self).await? {
+            Next::Entry(entry) => Ok(entry),
+            Next::Trailer { .. } => Err(FormatError::InvalidLocalHeader.into()),
         }
     }
+
+    async fn extract_streaming_to_dir(self, dir: &Path) -> Result<Archive, Error> {
+        streaming_extract::extract_to_dir(self, dir).await
+    }
 }
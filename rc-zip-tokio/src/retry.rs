@@ -0,0 +1,170 @@
+use std::{future::Future, io, pin::Pin, task, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    time::Sleep,
+};
+use tracing::trace;
+
+use crate::HasCursor;
+
+/// How [Retrying] handles errors from the underlying cursors.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// How many times in a row a read may be retried before the error is
+    /// returned to the caller. The count resets whenever a read succeeds.
+    pub max_retries: u32,
+
+    /// How long to wait before the first retry.
+    pub initial_backoff: Duration,
+
+    /// Upper bound for the wait between retries, which doubles each time.
+    pub max_backoff: Duration,
+
+    /// Decides whether an error is transient, and the read worth retrying.
+    /// Defaults to [Self::is_transient_kind].
+    pub is_transient: fn(&io::Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            is_transient: Self::is_transient_kind,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default for [Self::is_transient]: interruptions, timeouts, and
+    /// connections that were reset, aborted or dropped.
+    ///
+    /// Unexpected ends of file aren't retried: they usually mean the data
+    /// is truncated, which retrying can't fix.
+    pub fn is_transient_kind(e: &io::Error) -> bool {
+        matches!(
+            e.kind(),
+            io::ErrorKind::Interrupted
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+        )
+    }
+
+    /// Like [Self::is_transient_kind], but also retries unexpected ends of
+    /// file, for backends that report a response body cut short that way.
+    pub fn is_transient_kind_or_eof(e: &io::Error) -> bool {
+        Self::is_transient_kind(e) || e.kind() == io::ErrorKind::UnexpectedEof
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Wraps a [HasCursor] so that reads failing with a transient error are
+/// retried, with exponential backoff, according to a [RetryPolicy].
+///
+/// Retrying means asking the inner resource for a new cursor at the offset
+/// the failed one had reached, so a single failed request doesn't abort
+/// a long listing or extraction. This is mostly useful for remote backends.
+pub struct Retrying<F> {
+    inner: F,
+    policy: RetryPolicy,
+}
+
+impl<F> Retrying<F>
+where
+    F: HasCursor,
+{
+    /// Wrap `inner`, retrying reads according to `policy`.
+    pub fn new(inner: F, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Returns the wrapped resource.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F> HasCursor for Retrying<F>
+where
+    F: HasCursor,
+{
+    type Cursor<'a> = RetryingCursor<'a, F>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        RetryingCursor {
+            file: &self.inner,
+            policy: &self.policy,
+            offset,
+            cursor: self.inner.cursor_at(offset),
+            attempt: 0,
+            sleep: None,
+        }
+    }
+}
+
+/// The cursor type for [Retrying].
+pub struct RetryingCursor<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    file: &'a F,
+    policy: &'a RetryPolicy,
+    offset: u64,
+    cursor: F::Cursor<'a>,
+    attempt: u32,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<F> AsyncRead for RetryingCursor<'_, F>
+where
+    F: HasCursor,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(sleep) = &mut this.sleep {
+                futures_util::ready!(sleep.as_mut().poll(cx));
+                this.sleep = None;
+                trace!(offset = %this.offset, attempt = %this.attempt, "retrying read");
+                this.cursor = this.file.cursor_at(this.offset);
+            }
+
+            let filled_before = buf.filled().len();
+            match futures_util::ready!(Pin::new(&mut this.cursor).poll_read(cx, buf)) {
+                Ok(()) => {
+                    let read_bytes = buf.filled().len() - filled_before;
+                    if read_bytes > 0 {
+                        this.offset += read_bytes as u64;
+                        this.attempt = 0;
+                    }
+                    return Ok(()).into();
+                }
+                Err(e)
+                    if this.attempt < this.policy.max_retries && (this.policy.is_transient)(&e) =>
+                {
+                    let backoff = this.policy.backoff(this.attempt);
+                    trace!(offset = %this.offset, ?backoff, "transient read error: {e}");
+                    this.attempt += 1;
+                    this.sleep = Some(Box::pin(tokio::time::sleep(backoff)));
+                }
+                Err(e) => return Err(e).into(),
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    cmp, io,
+    io::SeekFrom,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::ready;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, ReadBuf, Take};
+
+use crate::{entry_reader::EntryReader, read_zip::EntryHandle, HasCursor};
+
+/// A reader for an entry that can also seek, see
+/// [EntryHandle::seekable_reader].
+///
+/// Entries stored without compression or encryption are read in place,
+/// wherever the reader seeks to. Others have to be decompressed from the
+/// start up to where the reader seeks to: seeking forward decompresses the
+/// data in between, and seeking backward starts over from the beginning.
+/// That happens on the next read, seeking itself never waits.
+///
+/// The checksum of stored entries isn't verified, and the checksum of other
+/// entries only when they're read to the end in one go.
+pub struct SeekableEntryReader<'a, F>
+where
+    F: HasCursor,
+{
+    handle: EntryHandle<'a, F>,
+    // where the data of stored entries starts
+    data_offset: Option<u64>,
+    // where the next read should happen
+    pos: u64,
+    current: Option<Current<'a, F>>,
+}
+
+/// The reader to read from, and where it is.
+struct Current<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    rd: Reader<'a, F>,
+    at: u64,
+}
+
+enum Reader<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    Stored(Take<Take<F::Cursor<'a>>>),
+    Decoded(Box<EntryReader<Take<F::Cursor<'a>>>>),
+}
+
+impl<'a, F> SeekableEntryReader<'a, F>
+where
+    F: HasCursor,
+{
+    pub(crate) fn new(handle: EntryHandle<'a, F>, data_offset: Option<u64>) -> Self {
+        Self {
+            handle,
+            data_offset,
+            pos: 0,
+            current: None,
+        }
+    }
+
+    /// Returns a reader at `self.pos`, or `None` if that's past the end.
+    fn poll_current(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<&mut Current<'a, F>>>> {
+        let at = self.current.as_ref().map(|current| current.at);
+        if at == Some(self.pos) {
+            return Poll::Ready(Ok(self.current.as_mut()));
+        }
+
+        if let Some(data_offset) = self.data_offset {
+            let len = self.handle.compressed_size.saturating_sub(self.pos);
+            let rd = self
+                .handle
+                .cursor_at(data_offset.saturating_add(self.pos))
+                .take(len);
+            return Poll::Ready(Ok(Some(self.current.insert(Current {
+                rd: Reader::Stored(rd),
+                at: self.pos,
+            }))));
+        }
+
+        // decompression only goes forward
+        if at.map_or(true, |at| at > self.pos) {
+            self.current = Some(Current {
+                rd: Reader::Decoded(Box::new(self.handle.entry_reader())),
+                at: 0,
+            });
+        }
+        let Some(Current {
+            rd: Reader::Decoded(rd),
+            at,
+        }) = &mut self.current
+        else {
+            unreachable!("entries that aren't stored are decoded");
+        };
+        let mut scratch = [0u8; 8192];
+        while *at < self.pos {
+            let len = cmp::min(scratch.len() as u64, self.pos - *at) as usize;
+            let mut skipped = ReadBuf::new(&mut scratch[..len]);
+            ready!(Pin::new(&mut **rd).poll_read(cx, &mut skipped))?;
+            if skipped.filled().is_empty() {
+                return Poll::Ready(Ok(None));
+            }
+            *at += skipped.filled().len() as u64;
+        }
+        Poll::Ready(Ok(self.current.as_mut()))
+    }
+}
+
+impl<F> AsyncRead for SeekableEntryReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(Current { rd, at }) = ready!(this.poll_current(cx))? else {
+            return Poll::Ready(Ok(()));
+        };
+        let before = buf.filled().len();
+        match rd {
+            Reader::Stored(rd) => ready!(Pin::new(rd).poll_read(cx, buf))?,
+            Reader::Decoded(rd) => ready!(Pin::new(&mut **rd).poll_read(cx, buf))?,
+        }
+        *at += (buf.filled().len() - before) as u64;
+        this.pos = *at;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<F> AsyncSeek for SeekableEntryReader<'_, F>
+where
+    F: HasCursor,
+{
+    /// Seeking relative to the end uses [Entry::uncompressed_size], which
+    /// comes from the archive and may be wrong.
+    ///
+    /// [Entry::uncompressed_size]: rc_zip::parse::Entry::uncompressed_size
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        let pos = match position {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(delta) => this.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => this.handle.uncompressed_size.checked_add_signed(delta),
+        };
+        this.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
@@ -1,8 +1,10 @@
 use oval::Buffer;
 use pin_project_lite::pin_project;
 use rc_zip::{
-    error::{Error, FormatError},
-    fsm::{EntryFsm, FsmResult},
+    error::Error,
+    fsm::{
+        DecompressionLimits, EntryFsm, EntryTrailer, FsmResult, StreamingEntryFsm, StreamingNext,
+    },
     parse::Entry,
 };
 use std::{io, pin::Pin, task};
@@ -14,12 +16,18 @@ pin_project! {
     /// not all name encodings may work, and only by reading it in its entirety
     /// can you move on to the next entry.
     ///
+    /// Moving on is done with [StreamingEntryReader::finish], which skips
+    /// whatever wasn't read of this entry. Dropping a reader doesn't touch the
+    /// underlying stream: if it was borrowed, it's left somewhere in the middle
+    /// of the entry, and can't be used to read the next one.
+    ///
     /// However, it only requires an [AsyncRead], and does not need to seek.
     pub struct StreamingEntryReader<R> {
         entry: Entry,
         #[pin]
         rd: R,
         state: State,
+        limits: DecompressionLimits,
     }
 }
 
@@ -32,6 +40,9 @@ enum State {
     Finished {
         /// remaining buffer for next entry
         remain: Buffer,
+
+        /// the entry's data descriptor, if any
+        trailer: Option<EntryTrailer>,
     },
     #[default]
     Transition,
@@ -46,6 +57,7 @@ where
             entry,
             rd,
             state: State::Reading { fsm },
+            limits: Default::default(),
         }
     }
 }
@@ -65,6 +77,7 @@ where
 
         match std::mem::take(this.state) {
             State::Reading { mut fsm } => {
+                let mut filled = 0;
                 if fsm.wants_read() {
                     trace!("fsm wants read");
                     let mut buf = ReadBuf::new(fsm.space());
@@ -78,7 +91,7 @@ where
                     let n = buf.filled().len();
 
                     trace!("giving fsm {} bytes from rd", n);
-                    fsm.fill(n);
+                    filled = fsm.fill(n);
                 } else {
                     trace!("fsm does not want read");
                 }
@@ -91,7 +104,7 @@ where
                         if outcome.bytes_written > 0 {
                             trace!("bytes have been written");
                             buf.advance(outcome.bytes_written);
-                        } else if outcome.bytes_read == 0 {
+                        } else if outcome.bytes_read == 0 && filled == 0 {
                             trace!("no bytes have been written or read");
                             // that's EOF, baby!
                         } else {
@@ -100,16 +113,16 @@ where
                             return self.poll_read(cx, buf);
                         }
                     }
-                    FsmResult::Done(remain) => {
-                        *this.state = State::Finished { remain };
+                    FsmResult::Done((remain, trailer)) => {
+                        *this.state = State::Finished { remain, trailer };
 
                         // neat!
                     }
                 }
             }
-            State::Finished { remain } => {
+            State::Finished { remain, trailer } => {
                 // wait for them to call finish
-                *this.state = State::Finished { remain };
+                *this.state = State::Finished { remain, trailer };
             }
             State::Transition => unreachable!(),
         }
@@ -127,53 +140,129 @@ where
         &self.entry
     }
 
+    /// Returns the data descriptor that followed the entry's data, once it's
+    /// been read to the end: `None` if there wasn't any, or if the entry
+    /// isn't read yet.
+    pub fn trailer(&self) -> Option<&EntryTrailer> {
+        match &self.state {
+            State::Finished { trailer, .. } => trailer.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Sets limits on what reading this entry, and the ones after it, may
+    /// produce, see [DecompressionLimits].
+    pub fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.limits = limits;
+        self.state = match std::mem::take(&mut self.state) {
+            State::Reading { fsm } => State::Reading {
+                fsm: fsm.with_limits(limits),
+            },
+            state => state,
+        };
+        self
+    }
+
+    /// Reads and discards the rest of this entry, returning how many
+    /// (uncompressed) bytes were skipped. The entry is still decompressed and
+    /// its checksum verified, since that's the only way to find where it ends
+    /// when sizes are only known from the data descriptor.
+    ///
+    /// Subsequent reads return no data.
+    pub async fn skip_rest(&mut self) -> Result<u64, Error> {
+        trace!("skipping rest of streaming entry");
+
+        let mut skipped = 0;
+        let mut scratch = vec![0u8; 16 * 1024];
+        while matches!(self.state, State::Reading { .. }) {
+            match self.read(&mut scratch).await? {
+                0 => break,
+                n => skipped += n as u64,
+            }
+        }
+
+        if matches!(self.state, State::Reading { .. }) {
+            return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(skipped)
+    }
+
     /// Finish reading this entry, returning the next streaming entry reader, if
-    /// any. This panics if the entry is not fully read.
+    /// any. The rest of this entry is skipped if it wasn't fully read, see
+    /// [StreamingEntryReader::skip_rest].
     ///
     /// If this returns None, there's no entries left.
-    pub async fn finish(mut self) -> Result<Option<StreamingEntryReader<R>>, Error> {
+    pub async fn finish(self) -> Result<Option<StreamingEntryReader<R>>, Error> {
+        match self.finish_or_trailer().await? {
+            Next::Entry(next) => Ok(Some(next)),
+            Next::Trailer { .. } => Ok(None),
+        }
+    }
+
+    /// Like [Self::finish], but returns what follows the last entry when
+    /// there are no entries left.
+    pub(crate) async fn finish_or_trailer(mut self) -> Result<Next<R>, Error> {
         trace!("finishing streaming entry reader");
 
-        if matches!(self.state, State::Reading { .. }) {
-            // this should transition to finished if there's no data
-            _ = self.read(&mut [0u8; 1]).await?;
-        }
+        self.skip_rest().await?;
 
         match self.state {
             State::Reading { .. } => {
-                panic!("entry not fully read");
+                unreachable!("skip_rest reads until the entry is finished");
             }
-            State::Finished { remain } => {
+            State::Finished { remain, .. } => {
                 // parse the next entry, if any
-                let mut fsm = EntryFsm::new(None, Some(remain));
+                Ok(
+                    match next_entry(StreamingEntryFsm::new(Some(remain)), self.rd).await? {
+                        Next::Entry(next) => Next::Entry(next.with_limits(self.limits)),
+                        trailer => trailer,
+                    },
+                )
+            }
+            State::Transition => unreachable!(),
+        }
+    }
+}
 
-                loop {
-                    if fsm.wants_read() {
-                        let n = self.rd.read(fsm.space()).await?;
-                        trace!("read {} bytes into buf for first zip entry", n);
-                        fsm.fill(n);
-                    }
+/// What follows a streaming entry.
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Next<R> {
+    /// Another entry.
+    Entry(StreamingEntryReader<R>),
 
-                    match fsm.process_till_header() {
-                        Ok(Some(entry)) => {
-                            let entry = entry.clone();
-                            return Ok(Some(StreamingEntryReader::new(fsm, entry, self.rd)));
-                        }
-                        Ok(None) => {
-                            // needs more turns
-                        }
-                        Err(e) => match e {
-                            Error::Format(FormatError::InvalidLocalHeader) => {
-                                // we probably reached the end of central directory!
-                                // TODO: we should probably check for the end of central directory
-                                return Ok(None);
-                            }
-                            _ => return Err(e),
-                        },
-                    }
+    /// The central directory: `buffered` holds what was read past the last
+    /// entry, and `rd` is positioned right after it.
+    Trailer { buffered: Buffer, rd: R },
+}
+
+/// Reads the next local header with `fsm`, or returns what follows the last
+/// entry if there isn't one.
+pub(crate) async fn next_entry<R>(mut fsm: StreamingEntryFsm, mut rd: R) -> Result<Next<R>, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        fsm = match fsm.process()? {
+            FsmResult::Continue(mut fsm) => {
+                let n = rd.read(fsm.space()).await?;
+                trace!("read {} bytes into buf for next zip entry", n);
+                if n == 0 {
+                    // neither another entry nor a central directory
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
                 }
+                fsm.fill(n);
+                fsm
             }
-            State::Transition => unreachable!(),
-        }
+            FsmResult::Done(StreamingNext::Entry(mut fsm)) => {
+                let entry = fsm
+                    .process_till_header()?
+                    .expect("local header was parsed")
+                    .clone();
+                return Ok(Next::Entry(StreamingEntryReader::new(fsm, entry, rd)));
+            }
+            FsmResult::Done(StreamingNext::Trailer(buffered)) => {
+                return Ok(Next::Trailer { buffered, rd });
+            }
+        };
     }
 }
@@ -0,0 +1,171 @@
+use std::{cmp, io, path::Path, pin::Pin, task};
+
+use rc_zip::{
+    encoding::Encoding,
+    error::{Error, FormatError},
+    fsm::{ArchiveFsm, FsmResult, StreamingEntryFsm},
+    parse::{Archive, EntryKind},
+};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, ReadBuf},
+};
+use tracing::trace;
+
+use crate::{
+    extract,
+    streaming_entry_reader::{next_entry, Next},
+    StreamingEntryReader,
+};
+
+/// See [crate::ReadZipStreaming::extract_streaming_to_dir].
+pub(crate) async fn extract_to_dir<R>(rd: R, dir: &Path) -> Result<Archive, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let rd = CountingReader {
+        inner: rd,
+        count: 0,
+    };
+
+    // name (from the local header) and uncompressed size of each entry
+    let mut extracted: Vec<(String, u64)> = Vec::new();
+    let mut next = next_entry(StreamingEntryFsm::new(None), rd).await?;
+    let (buffered, mut rd) = loop {
+        match next {
+            Next::Entry(mut entry) => {
+                let size = extract_entry(&mut entry, dir).await?;
+                extracted.push((entry.entry().name.clone(), size));
+                next = entry.finish_or_trailer().await?;
+            }
+            Next::Trailer { buffered, rd } => break (buffered, rd),
+        }
+    };
+
+    // everything left is the central directory and end of central
+    // directory record, which is where they'd be in the file.
+    let mut trailer = buffered.data().to_vec();
+    let trailer_offset = rd.count - trailer.len() as u64;
+    rd.read_to_end(&mut trailer).await?;
+    let archive = read_trailer(trailer_offset, &trailer)?;
+
+    check_entries(&archive, &extracted)?;
+
+    Ok(archive)
+}
+
+/// Checks that the central directory lists the entries that were read
+/// from local headers, in the same order.
+fn check_entries(archive: &Archive, extracted: &[(String, u64)]) -> Result<(), Error> {
+    // local headers don't know about the archive's encoding, so names can
+    // only be compared if it's UTF-8.
+    let compare_names = archive.encoding() == Encoding::Utf8;
+
+    let mut entries = archive.entries();
+    for (name, size) in extracted {
+        let matches = entries.next().is_some_and(|entry| {
+            entry.uncompressed_size == *size && (!compare_names || entry.name == *name)
+        });
+        if !matches {
+            return Err(FormatError::LocalHeaderMismatch { name: name.clone() }.into());
+        }
+    }
+    match entries.next() {
+        Some(entry) => Err(FormatError::LocalHeaderMismatch {
+            name: entry.name.clone(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Extracts a single entry, or skips it if it can't be extracted safely.
+/// Returns its uncompressed size either way.
+async fn extract_entry<R>(entry: &mut StreamingEntryReader<R>, dir: &Path) -> Result<u64, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let name = match entry.entry().safe_path() {
+        Some(name) => name,
+        None => {
+            trace!(name = %entry.entry().name, "extract_streaming_to_dir: skipping entry with unsafe name");
+            return entry.skip_rest().await;
+        }
+    };
+
+    match entry.entry().kind() {
+        EntryKind::Directory => {
+            let path = extract::prepare(dir, &name, entry.entry()).await?;
+            fs::create_dir_all(&path).await?;
+            entry.skip_rest().await
+        }
+        EntryKind::File => {
+            let path = extract::prepare(dir, &name, entry.entry()).await?;
+            Ok(extract::write_file(&path, entry).await?)
+        }
+        EntryKind::Symlink => {
+            trace!(name = %entry.entry().name, "extract_streaming_to_dir: skipping symlink");
+            entry.skip_rest().await
+        }
+    }
+}
+
+/// Reads the central directory from the end of a zip file, starting at
+/// `offset`: the fsm only looks for signatures before that, so it's fed
+/// zeroes there.
+fn read_trailer(offset: u64, trailer: &[u8]) -> Result<Archive, Error> {
+    let mut fsm = ArchiveFsm::new(offset + trailer.len() as u64);
+    loop {
+        if let Some(read_offset) = fsm.wants_read() {
+            let space = fsm.space();
+            let n = match read_offset.checked_sub(offset) {
+                Some(within) => {
+                    let within = usize::try_from(within)
+                        .map_or(trailer.len(), |within| cmp::min(within, trailer.len()));
+                    let available = &trailer[within..];
+                    let n = cmp::min(available.len(), space.len());
+                    space[..n].copy_from_slice(&available[..n]);
+                    n
+                }
+                None => {
+                    let n = usize::try_from(offset - read_offset)
+                        .map_or(space.len(), |n| cmp::min(n, space.len()));
+                    space[..n].fill(0);
+                    n
+                }
+            };
+            if n == 0 {
+                return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+            }
+            fsm.fill(n);
+        }
+
+        fsm = match fsm.process()? {
+            FsmResult::Continue(fsm) => fsm,
+            FsmResult::Done(archive) => return Ok(archive),
+        }
+    }
+}
+
+/// Keeps track of how many bytes were read from the stream, which is how
+/// we know where the central directory starts.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> AsyncRead for CountingReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        futures_util::ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.count += (buf.filled().len() - before) as u64;
+        Ok(()).into()
+    }
+}
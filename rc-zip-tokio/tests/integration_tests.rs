@@ -1,13 +1,28 @@
 use positioned_io::{RandomAccessFile, Size};
 use rc_zip::{
     corpus::{self, zips_dir, Case, Files},
-    error::Error,
-    parse::Archive,
+    error::{Error, FormatError, LimitExceeded},
+    fsm::{ArchiveReadOptions, DecompressionLimits, NewEntry},
+    parse::{Archive, Method},
 };
-use rc_zip_tokio::{ArchiveHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize};
-use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use rc_zip_tokio::{
+    ArchiveHandle, ArchiveWriter, Cached, ConcatReader, DynHasCursor, ExtractOptions, HasCursor,
+    RangeReader, Ranged, ReadZip, ReadZipStreaming, ReadZipWithSize, RetryPolicy, Retrying,
+    StreamingZipReader,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
 
-use std::{pin::Pin, sync::Arc, task};
+use futures_util::future::BoxFuture;
+use std::{
+    ops::Range,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task,
+    time::Duration,
+};
 
 async fn check_case<F: HasCursor>(test: &Case, archive: Result<ArchiveHandle<'_, F>, Error>) {
     corpus::check_case(test, archive.as_ref().map(|ar| -> &Archive { ar }));
@@ -46,6 +61,27 @@ async fn read_from_file() {
     assert_eq!(archive.entries().count(), 2);
 }
 
+#[tokio::test]
+async fn read_from_dyn_cursors() {
+    corpus::install_test_subscriber();
+
+    let path = zips_dir().join("test.zip");
+    let bytes = std::fs::read(&path).unwrap();
+    let size = bytes.len() as u64;
+    let sources: Vec<Box<dyn DynHasCursor + Send + Sync>> = vec![
+        Box::new(Arc::new(RandomAccessFile::open(&path).unwrap())),
+        Box::new(bytes),
+    ];
+
+    for source in sources {
+        let archive = source.read_zip_with_size(size).await.unwrap();
+        assert_eq!(archive.entries().count(), 2);
+        for entry in archive.entries() {
+            entry.bytes().await.unwrap();
+        }
+    }
+}
+
 #[tokio::test]
 async fn real_world_files() {
     corpus::install_test_subscriber();
@@ -68,6 +104,62 @@ async fn real_world_files() {
     }
 }
 
+#[tokio::test]
+async fn blocking_pool_decompression() {
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let Files::ExhaustiveList(files) = &case.files else {
+            continue;
+        };
+        let bytes = case.bytes();
+        let Ok(archive) = bytes.read_zip().await else {
+            continue;
+        };
+
+        for file in files {
+            let entry = archive.by_name(file.name).unwrap();
+            let mut v = Vec::new();
+            entry
+                .reader_on_blocking_pool()
+                .read_to_end(&mut v)
+                .await
+                .unwrap();
+            corpus::check_file_against(file, &entry, &v[..]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn prefetching() {
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let Files::ExhaustiveList(files) = &case.files else {
+            continue;
+        };
+        let bytes = case.bytes();
+        let Ok(archive) = bytes.read_zip().await else {
+            continue;
+        };
+
+        let mut num_read = 0;
+        let mut reader = archive.read_entries_prefetching();
+        while let Some(mut entry_reader) = reader {
+            let entry = entry_reader.entry();
+            let file = files.iter().find(|f| f.name == entry.name).unwrap();
+
+            let mut v = Vec::new();
+            entry_reader.read_to_end(&mut v).await.unwrap();
+            corpus::check_file_against(file, entry, &v[..]);
+
+            num_read += 1;
+            reader = entry_reader.next_entry();
+        }
+        assert_eq!(num_read, files.len());
+    }
+}
+
 #[tokio::test]
 async fn streaming() {
     corpus::install_test_subscriber();
@@ -95,6 +187,767 @@ async fn streaming() {
     }
 }
 
+#[tokio::test]
+async fn raw_local_header() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    let archive = bytes.read_zip().await.unwrap();
+    for entry in archive.entries() {
+        let raw = entry.raw_local_header().await.unwrap();
+        assert_eq!(raw.offset, entry.header_offset);
+        assert!(raw.inner.starts_with(b"PK\x03\x04"));
+        assert_eq!(raw.inner.len(), 30 + entry.name.len());
+    }
+}
+
+#[tokio::test]
+async fn extract_streaming() {
+    corpus::install_test_subscriber();
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_streaming");
+    let _ = std::fs::remove_dir_all(&dest);
+    std::fs::create_dir_all(&dest).unwrap();
+
+    let f = tokio::fs::File::open(zips_dir().join("test.zip"))
+        .await
+        .unwrap();
+    let archive = f.extract_streaming_to_dir(&dest).await.unwrap();
+    assert_eq!(archive.entries().count(), 2);
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let expected = bytes.read_zip().await.unwrap();
+    for entry in expected.entries() {
+        let on_disk = std::fs::read(dest.join(&entry.name)).unwrap();
+        assert_eq!(on_disk, entry.bytes().await.unwrap());
+    }
+
+    // the central directory says the second entry is bigger than it is
+    let mut bytes = corpus::stored_zip(&[("a.txt", b"a"), ("b.txt", b"b")]);
+    let eocd_offset = bytes.len() - 22;
+    let directory_offset =
+        u32::from_le_bytes(bytes[eocd_offset + 16..][..4].try_into().unwrap()) as usize;
+    let record = directory_offset + 46 + "a.txt".len();
+    bytes[record + 24..][..4].copy_from_slice(&2u32.to_le_bytes());
+
+    let dest = dest.join("mismatch");
+    std::fs::create_dir_all(&dest).unwrap();
+    let res = (&bytes[..]).extract_streaming_to_dir(&dest).await;
+    assert!(matches!(
+        res,
+        Err(Error::Format(FormatError::LocalHeaderMismatch { name })) if name == "b.txt"
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn extract_all() {
+    use rc_zip::{chrono::TimeZone, parse::UnixMode};
+
+    corpus::install_test_subscriber();
+
+    let modified = rc_zip::chrono::Utc
+        .with_ymd_and_hms(2001, 2, 3, 4, 5, 6)
+        .unwrap();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(
+            NewEntry::new("dir/")
+                .with_mode(UnixMode::IFDIR | UnixMode(0o755))
+                .with_modified(modified),
+        )
+        .await
+        .unwrap();
+    for i in 0..50 {
+        writer
+            .start_entry(
+                NewEntry::new(format!("dir/sub{}/file{i}.txt", i % 3))
+                    .with_mode(UnixMode::IFREG | UnixMode(0o640))
+                    .with_modified(modified),
+            )
+            .await
+            .unwrap();
+        writer
+            .write_all(i.to_string().repeat(i).as_bytes())
+            .await
+            .unwrap();
+    }
+    writer
+        .start_entry(NewEntry::new("../evil.txt"))
+        .await
+        .unwrap();
+    writer.write_all(b"evil").await.unwrap();
+    let bytes = writer.finish().await.unwrap();
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_all");
+    let _ = std::fs::remove_dir_all(&root);
+    let dest = root.join("dest");
+    let options = ExtractOptions {
+        concurrency: 4,
+        permissions: true,
+        modified_times: true,
+        ..Default::default()
+    };
+    bytes
+        .read_zip()
+        .await
+        .unwrap()
+        .extract_all(&dest, options)
+        .await
+        .unwrap();
+
+    for i in 0..50 {
+        let path = dest.join(format!("dir/sub{}/file{i}.txt", i % 3));
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            i.to_string().repeat(i).as_bytes()
+        );
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(
+            metadata.modified().unwrap(),
+            std::time::SystemTime::from(modified)
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        }
+    }
+    #[cfg(unix)]
+    assert_eq!(
+        std::fs::metadata(dest.join("dir"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        std::time::SystemTime::from(modified)
+    );
+    assert!(!root.join("evil.txt").exists());
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn extract_all_planted_symlinks() {
+    corpus::install_test_subscriber();
+
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_all_symlinks");
+    let _ = std::fs::remove_dir_all(&root);
+    let (dest, outside) = (root.join("dest"), root.join("outside"));
+    std::fs::create_dir_all(&dest).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(outside.join("file.txt"), "outside").unwrap();
+    std::os::unix::fs::symlink(&outside, dest.join("through")).unwrap();
+    std::os::unix::fs::symlink(outside.join("file.txt"), dest.join("replaced.txt")).unwrap();
+
+    // a symlink where an entry goes is replaced, not written through...
+    let bytes = corpus::stored_zip(&[("replaced.txt", b"replaced")]);
+    let archive = bytes.read_zip().await.unwrap();
+    archive
+        .extract_all(&dest, ExtractOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        std::fs::read(dest.join("replaced.txt")).unwrap(),
+        b"replaced"
+    );
+    assert_eq!(std::fs::read(outside.join("file.txt")).unwrap(), b"outside");
+
+    // ...and one on the way to it isn't gone through, for files and
+    // directories, whether extracting with the central directory or not
+    for name in ["through/evil.txt", "through/evil/"] {
+        let bytes = corpus::stored_zip(&[(name, b"")]);
+        let archive = bytes.read_zip().await.unwrap();
+        let res = archive.extract_all(&dest, ExtractOptions::default()).await;
+        assert!(
+            matches!(res, Err(Error::Format(FormatError::UnsafePath { .. }))),
+            "{name}: {res:?}"
+        );
+        let res = (&bytes[..]).extract_streaming_to_dir(&dest).await;
+        assert!(
+            matches!(res, Err(Error::Format(FormatError::UnsafePath { .. }))),
+            "{name}"
+        );
+    }
+    let names: Vec<_> = std::fs::read_dir(&outside)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["file.txt"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn extract_all_cancelled() {
+    use rc_zip::{parse::Entry, progress::Progress};
+    use std::sync::Barrier;
+
+    // holds up decompression until the extraction was dropped
+    struct Gate {
+        started: tokio::sync::Notify,
+        release: Barrier,
+        waited: AtomicUsize,
+    }
+
+    impl Progress for Gate {
+        fn entry(&self, _entry: &Entry, _compressed: u64, _uncompressed: u64) {
+            if self.waited.fetch_add(1, Ordering::SeqCst) == 0 {
+                self.started.notify_one();
+                self.release.wait();
+            }
+        }
+    }
+
+    corpus::install_test_subscriber();
+
+    let data = vec![7u8; 1024 * 1024];
+    let bytes = corpus::stored_zip(&[("big.bin", &data)]);
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("extract_all_cancelled");
+    let _ = std::fs::remove_dir_all(&dest);
+    std::fs::create_dir_all(&dest).unwrap();
+
+    let gate = Arc::new(Gate {
+        started: Default::default(),
+        release: Barrier::new(2),
+        waited: AtomicUsize::new(0),
+    });
+    let options = ExtractOptions {
+        progress: Some(gate.clone()),
+        ..Default::default()
+    };
+    let archive = bytes.read_zip().await.unwrap();
+    tokio::select! {
+        res = archive.extract_all(&dest, options) => panic!("extraction wasn't held up: {res:?}"),
+        _ = gate.started.notified() => {}
+    }
+    gate.release.wait();
+
+    let left: Vec<_> = std::fs::read_dir(&dest)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert!(left.is_empty(), "files left behind: {left:?}");
+}
+
+#[tokio::test]
+async fn readers_are_send_sync() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    let archive = bytes.read_zip().await.unwrap();
+    let entry = archive.by_name("hello.txt").unwrap();
+    assert_send_sync(&entry.reader());
+    assert_send_sync(&entry.reader_on_blocking_pool());
+    assert_send_sync(&archive.read_entries_prefetching().unwrap());
+    assert_send_sync(&ConcatReader::new(archive.entries()));
+    // a separator only needs to be Send
+    let count = std::cell::Cell::new(0);
+    assert_send_sync(
+        &ConcatReader::new(archive.entries()).with_separator(move |_| {
+            count.set(count.get() + 1);
+            vec![]
+        }),
+    );
+    let owned = archive.into_entries().next().unwrap();
+    assert_send_sync(&owned.reader());
+    assert_send_sync(&owned);
+
+    let streaming = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .await
+        .unwrap();
+    assert_send_sync(&streaming);
+}
+
+// decompressors keep their state in the reader: zstd's needs to be Send and
+// Sync too
+#[tokio::test]
+#[cfg(feature = "zstd")]
+async fn zstd_readers_in_spawned_tasks() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("found-me-zstd.zip")).unwrap();
+    let bytes: &'static Vec<u8> = Box::leak(Box::new(bytes));
+    let entry = bytes
+        .read_zip()
+        .await
+        .unwrap()
+        .into_entries()
+        .next()
+        .unwrap();
+    assert_eq!(entry.method, Method::Zstd);
+    let contents = tokio::spawn(async move {
+        let mut reader = entry.reader();
+        assert_send_sync(&reader);
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await.unwrap();
+        contents
+    })
+    .await
+    .unwrap();
+    assert_eq!(contents, "Oh no, you found me\n".repeat(5000).as_bytes());
+}
+
+#[tokio::test]
+#[cfg(feature = "aes")]
+async fn aes_encrypted_entries() {
+    corpus::install_test_subscriber();
+
+    let zip_error = |e: std::io::Error| *e.into_inner().unwrap().downcast::<Error>().unwrap();
+
+    let bytes = std::fs::read(zips_dir().join("aes.zip")).unwrap();
+    let archive = bytes.read_zip().await.unwrap();
+
+    // all entries are prefixes of the same text
+    let mut text = vec![];
+    let entry = archive.by_name("deflated.txt").unwrap();
+    entry
+        .reader_with_password(b"rc-zip")
+        .read_to_end(&mut text)
+        .await
+        .unwrap();
+    assert!(text.starts_with(b"line 0: "));
+    for entry in archive.entries() {
+        let mut v = vec![];
+        entry
+            .reader_with_password(b"rc-zip")
+            .read_to_end(&mut v)
+            .await
+            .unwrap();
+        assert_eq!(v, text[..entry.uncompressed_size as usize]);
+    }
+
+    let owned = archive.into_entries().next().unwrap();
+    let err = owned.bytes().await.unwrap_err();
+    assert!(matches!(zip_error(err), Error::PasswordRequired));
+    let err = owned
+        .reader_with_password(b"hunter2")
+        .read_to_end(&mut vec![])
+        .await
+        .unwrap_err();
+    assert!(matches!(zip_error(err), Error::WrongPassword));
+}
+
+#[tokio::test]
+async fn read_embedded() {
+    corpus::install_test_subscriber();
+
+    let zip = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    // the trailing zip would be found by a regular `read_zip`
+    let other_zip = corpus::stored_zip(&[("other.txt", b"other")]);
+    let mut container = vec![0xAAu8; 1234];
+    container.extend_from_slice(&zip);
+    container.extend_from_slice(&other_zip);
+
+    let archive = container.read_zip_at(1234, zip.len() as u64).await.unwrap();
+    assert_eq!(archive.size(), zip.len() as u64);
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, ["hello.txt", "world.txt"]);
+    for (name, contents) in [("hello.txt", b"hello"), ("world.txt", b"world")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().await.unwrap(), contents);
+
+        // data ranges are within the container, offsets within the archive
+        let range = entry.data_range().await.unwrap();
+        assert_eq!(
+            &container[range.start as usize..range.end as usize],
+            contents
+        );
+        assert_eq!(entry.data_offset().await.unwrap(), range.start - 1234);
+    }
+
+    // a window too short to contain the end of central directory
+    assert!(container.read_zip_at(1234, 100).await.is_err());
+}
+
+#[tokio::test]
+async fn owned_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("b.txt", b"bb"), ("c.txt", b"c"), ("a.txt", b"aaa")]);
+    let bytes: &'static Vec<u8> = Box::leak(Box::new(bytes));
+    let mut entries: Vec<_> = bytes.read_zip().await.unwrap().into_entries().collect();
+    entries.sort_by_key(|e| e.uncompressed_size);
+
+    let tasks: Vec<_> = entries
+        .into_iter()
+        .map(|entry| tokio::spawn(async move { entry.bytes().await.unwrap() }))
+        .collect();
+    let mut contents = vec![];
+    for task in tasks {
+        contents.push(task.await.unwrap());
+    }
+    assert_eq!(contents, [&b"c"[..], b"bb", b"aaa"]);
+}
+
+#[tokio::test]
+async fn owned_archive() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("a.txt", b"aaa"), ("b.txt", b"bb")]);
+    let archive = bytes.read_zip_owned().await.unwrap();
+    let entry = archive.by_name("b.txt").unwrap();
+    assert_eq!(entry.bytes().await.unwrap(), b"bb");
+
+    let tasks: Vec<_> = archive
+        .into_entries()
+        .map(|entry| tokio::spawn(async move { entry.bytes().await.unwrap() }))
+        .collect();
+    let mut contents = vec![];
+    for task in tasks {
+        contents.push(task.await.unwrap());
+    }
+    assert_eq!(contents, [&b"aaa"[..], b"bb"]);
+}
+
+#[tokio::test]
+async fn concatenated_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[
+        ("part.2", b"world"),
+        ("unrelated", b"nope"),
+        ("part.1", b"hello"),
+        ("part.3", b""),
+        ("part.4", b"!"),
+    ]);
+    let archive = bytes.read_zip().await.unwrap();
+    let parts = || (1..=4).map(|i| archive.by_name(format!("part.{i}")).unwrap());
+
+    let mut v = vec![];
+    ConcatReader::new(parts())
+        .read_to_end(&mut v)
+        .await
+        .unwrap();
+    assert_eq!(v, b"helloworld!");
+
+    let mut v = vec![];
+    ConcatReader::new(parts())
+        .with_separator(|entry| format!("<{}>", entry.name).into_bytes())
+        .read_to_end(&mut v)
+        .await
+        .unwrap();
+    assert_eq!(v, b"hello<part.2>world<part.3><part.4>!");
+}
+
+#[tokio::test]
+async fn streaming_skip() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[
+        ("partially-read.txt", b"abcdef"),
+        ("skipped.txt", b"ghijkl"),
+        ("untouched.txt", b"mnopqr"),
+        ("last.txt", b"stuvwx"),
+    ]);
+
+    let mut entry = (&bytes[..])
+        .stream_zip_entries_throwing_caution_to_the_wind()
+        .await
+        .unwrap();
+    assert_eq!(entry.entry().name, "partially-read.txt");
+    let mut buf = [0u8; 2];
+    entry.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ab");
+
+    let mut entry = entry.finish().await.unwrap().unwrap();
+    assert_eq!(entry.entry().name, "skipped.txt");
+    assert_eq!(entry.skip_rest().await.unwrap(), 6);
+    assert_eq!(entry.read(&mut buf).await.unwrap(), 0);
+
+    let entry = entry.finish().await.unwrap().unwrap();
+    assert_eq!(entry.entry().name, "untouched.txt");
+
+    let mut entry = entry.finish().await.unwrap().unwrap();
+    assert_eq!(entry.entry().name, "last.txt");
+    let mut v = vec![];
+    entry.read_to_end(&mut v).await.unwrap();
+    assert_eq!(v, b"stuvwx");
+    assert!(entry.finish().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn decompression_limits() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(NewEntry::new("zeros.bin"))
+        .await
+        .unwrap();
+    writer.write_all(&vec![0u8; 1 << 20]).await.unwrap();
+    let bytes = writer.finish().await.unwrap();
+
+    let archive = bytes.read_zip().await.unwrap();
+    let entry = archive.by_name("zeros.bin").unwrap();
+    let limits = DecompressionLimits {
+        max_output: Some(1000),
+        ..Default::default()
+    };
+    let err = entry
+        .reader_with_limits(limits)
+        .read_to_end(&mut Vec::new())
+        .await
+        .unwrap_err();
+    let err = *err.into_inner().unwrap().downcast::<Error>().unwrap();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(LimitExceeded::OutputSize { limit: 1000 })
+    ));
+
+    let mut zip = StreamingZipReader::new(&bytes[..]).with_limits(limits);
+    let entry = zip.next_entry().await.unwrap().unwrap();
+    assert!(entry.read_to_end(&mut Vec::new()).await.is_err());
+}
+
+#[tokio::test]
+async fn custom_backend() {
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let bytes = case.bytes();
+        let size = bytes.len() as u64;
+        let backend = SharedBytes(bytes.into());
+        let archive = backend.read_zip_with_size(size).await;
+        check_case(&case, archive).await;
+    }
+}
+
+#[tokio::test]
+async fn zip64_sparse() {
+    corpus::install_test_subscriber();
+
+    // just past what fits in 32 bits, so the next entry's offset doesn't
+    let big = u32::MAX as u64 + 10;
+    let sparse = Sparse(corpus::sparse_zip(&[big, 3], true));
+    let archive = sparse.read_zip_with_size(sparse.0.len()).await.unwrap();
+    let entries: Vec<_> = archive.entries().collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(
+        (entries[0].compressed_size, entries[0].uncompressed_size),
+        (big, big)
+    );
+    assert!(entries[1].header_offset > u32::MAX as u64);
+    assert_eq!(entries[1].bytes().await.unwrap(), [0; 3]);
+
+    let mut rd = entries[0].reader();
+    let copied = tokio::io::copy(&mut rd, &mut tokio::io::sink())
+        .await
+        .unwrap();
+    assert_eq!(copied, big);
+    let trailer = rd.trailer().unwrap();
+    assert!(trailer.is_zip64);
+    assert_eq!(trailer.descriptor.uncompressed_size, big);
+}
+
+#[tokio::test]
+async fn zip64_many_entries() {
+    corpus::install_test_subscriber();
+
+    let count = u16::MAX as usize + 10;
+    let sparse = Sparse(corpus::sparse_zip(&vec![1; count], false));
+    let archive = sparse.read_zip_with_size(sparse.0.len()).await.unwrap();
+    assert_eq!(archive.entries().count(), count);
+    let last = archive.by_name(format!("{}.bin", count - 1)).unwrap();
+    assert_eq!(last.bytes().await.unwrap(), [0]);
+}
+
+// A backend for archives too large to keep in memory, made by
+// corpus::sparse_zip
+
+struct Sparse(corpus::SparseZip);
+
+struct SparseCursor<'a> {
+    zip: &'a corpus::SparseZip,
+    offset: u64,
+}
+
+impl AsyncRead for SparseCursor<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        let n = self.zip.read_at(self.offset, buf.initialize_unfilled());
+        buf.advance(n);
+        self.offset += n as u64;
+        Ok(()).into()
+    }
+}
+
+impl HasCursor for Sparse {
+    type Cursor<'a> = SparseCursor<'a>;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        SparseCursor {
+            zip: &self.0,
+            offset,
+        }
+    }
+}
+
+// A backend that doesn't borrow from itself: its cursors own a handle to
+// the data and keep track of their own position.
+
+struct SharedBytes(Arc<[u8]>);
+
+struct SharedBytesCursor {
+    data: Arc<[u8]>,
+    pos: usize,
+}
+
+impl AsyncRead for SharedBytesCursor {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        let remaining = &self.data[self.pos.min(self.data.len())..];
+        let n = remaining.len().min(buf.remaining()).min(1000);
+        buf.put_slice(&remaining[..n]);
+        self.pos += n;
+        Ok(()).into()
+    }
+}
+
+impl HasCursor for SharedBytes {
+    type Cursor<'a> = SharedBytesCursor;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        SharedBytesCursor {
+            data: self.0.clone(),
+            pos: offset.try_into().unwrap(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn retries() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let size = bytes.len() as u64;
+    let bytes: Arc<[u8]> = bytes.into();
+    let flaky = FlakyBytes(bytes.clone(), std::io::ErrorKind::ConnectionReset);
+
+    // every cursor fails after 100 bytes, which nothing survives unless
+    // failed reads are resumed
+    assert!(flaky.read_zip_with_size(size).await.is_err());
+
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(1),
+        ..Default::default()
+    };
+    let retrying = Retrying::new(flaky, policy.clone());
+    let archive = retrying.read_zip_with_size(size).await.unwrap();
+    for entry in archive.entries() {
+        entry.bytes().await.unwrap();
+    }
+
+    // unexpected ends of file are only retried if asked to
+    let flaky = FlakyBytes(bytes, std::io::ErrorKind::UnexpectedEof);
+    let retrying = Retrying::new(flaky, policy.clone());
+    assert!(retrying.read_zip_with_size(size).await.is_err());
+    let policy = RetryPolicy {
+        is_transient: RetryPolicy::is_transient_kind_or_eof,
+        ..policy
+    };
+    let retrying = Retrying::new(retrying.into_inner(), policy);
+    let archive = retrying.read_zip_with_size(size).await.unwrap();
+    for entry in archive.entries() {
+        entry.bytes().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn range_cache() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let size = bytes.len() as u64;
+    let cursors = Arc::new(AtomicUsize::new(0));
+    let counting = CountingBytes {
+        inner: SharedBytes(bytes.into()),
+        cursors: cursors.clone(),
+    };
+    let cached = Cached::with_capacity(counting, 1024, 16);
+
+    let archive = cached.read_zip_with_size(size).await.unwrap();
+    for entry in archive.entries() {
+        entry.bytes().await.unwrap();
+    }
+    let cursors_after_first_read = cursors.load(Ordering::SeqCst);
+
+    // test.zip fits in the cache, so reading it again doesn't fetch anything
+    for entry in archive.entries() {
+        entry.bytes().await.unwrap();
+    }
+    assert_eq!(cursors.load(Ordering::SeqCst), cursors_after_first_read);
+
+    let stats = cached.stats();
+    assert_eq!(stats.requests, cursors_after_first_read as u64);
+    assert_eq!(stats.bytes_fetched, size);
+    assert!(stats.bytes_consumed > stats.bytes_fetched);
+    assert!(stats.cache_hits > 0);
+    assert_eq!(stats.cache_misses, size.div_ceil(1024));
+    assert!(stats.hit_rate() > 0.5);
+    assert!(stats.amplification() < 1.0);
+}
+
+struct CountingBytes {
+    inner: SharedBytes,
+    cursors: Arc<AtomicUsize>,
+}
+
+impl HasCursor for CountingBytes {
+    type Cursor<'a> = SharedBytesCursor;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.cursors.fetch_add(1, Ordering::SeqCst);
+        self.inner.cursor_at(offset)
+    }
+}
+
+// Cursors fail with the given error after 100 bytes
+struct FlakyBytes(Arc<[u8]>, std::io::ErrorKind);
+
+impl HasCursor for FlakyBytes {
+    type Cursor<'a> = FlakyBytesCursor;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        FlakyBytesCursor {
+            cursor: SharedBytes(self.0.clone()).cursor_at(offset),
+            budget: 100,
+            error: self.1,
+        }
+    }
+}
+
+struct FlakyBytesCursor {
+    cursor: SharedBytesCursor,
+    budget: usize,
+    error: std::io::ErrorKind,
+}
+
+impl AsyncRead for FlakyBytesCursor {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        if self.budget == 0 {
+            return Err(self.error.into()).into();
+        }
+
+        let len = buf.remaining().min(self.budget);
+        let mut inner_buf = ReadBuf::new(buf.initialize_unfilled_to(len));
+        futures_util::ready!(Pin::new(&mut self.cursor).poll_read(cx, &mut inner_buf))?;
+        let n = inner_buf.filled().len();
+        buf.advance(n);
+        self.budget -= n;
+        Ok(()).into()
+    }
+}
+
 // This helps find bugs in state machines!
 
 struct OneByteReadWrapper<R>(R);
@@ -128,3 +981,322 @@ where
         OneByteReadWrapper(self.0.cursor_at(offset))
     }
 }
+
+#[tokio::test]
+async fn write_archive() {
+    corpus::install_test_subscriber();
+
+    let method = if cfg!(feature = "deflate") {
+        Method::Deflate
+    } else {
+        Method::Store
+    };
+    let data: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(NewEntry::new("hello.txt"))
+        .await
+        .unwrap();
+    writer.write_all(b"hello").await.unwrap();
+    writer
+        .start_entry(NewEntry::new("data.bin").with_method(method))
+        .await
+        .unwrap();
+    for chunk in data.chunks(1000) {
+        writer.write_all(chunk).await.unwrap();
+    }
+    let bytes = writer.finish().await.unwrap();
+
+    let archive = bytes.read_zip().await.unwrap();
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, ["hello.txt", "data.bin"]);
+    let entry = archive.by_name("hello.txt").unwrap();
+    assert_eq!(entry.bytes().await.unwrap(), b"hello");
+    let entry = archive.by_name("data.bin").unwrap();
+    assert_eq!(entry.method, method);
+    assert_eq!(entry.bytes().await.unwrap(), data);
+}
+
+#[tokio::test]
+#[cfg(feature = "deflate")]
+async fn select_compression() {
+    use rc_zip::fsm::Compression;
+
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new()).select_compression(|entry, _| {
+        let method = if entry.name.ends_with(".jpg") {
+            Method::Store
+        } else {
+            Method::Deflate
+        };
+        Compression {
+            method,
+            level: None,
+        }
+    });
+    let text = "hello hello hello hello".repeat(100);
+    for name in ["photo.jpg", "notes.txt"] {
+        writer.start_entry(NewEntry::new(name)).await.unwrap();
+        writer.write_all(text.as_bytes()).await.unwrap();
+    }
+    let bytes = writer.finish().await.unwrap();
+
+    let archive = bytes.read_zip().await.unwrap();
+    let entry = archive.by_name("photo.jpg").unwrap();
+    assert_eq!(entry.method, Method::Store);
+    assert_eq!(entry.bytes().await.unwrap(), text.as_bytes());
+    let entry = archive.by_name("notes.txt").unwrap();
+    assert_eq!(entry.method, Method::Deflate);
+    assert_eq!(entry.bytes().await.unwrap(), text.as_bytes());
+}
+
+#[tokio::test]
+async fn write_entries_from_stream() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..25_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+
+    // entries come from another task, as they're produced
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let producer = {
+        let data = data.clone();
+        tokio::spawn(async move {
+            for i in 0..5 {
+                let mut entry = NewEntry::new(format!("page-{i}.bin"));
+                if cfg!(feature = "deflate") {
+                    entry = entry.with_method(Method::Deflate);
+                }
+                let page = data[i * 20_000..(i + 1) * 20_000].to_vec();
+                tx.send((entry, std::io::Cursor::new(page))).await.unwrap();
+            }
+        })
+    };
+    let entries = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    });
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer
+        .start_entry(NewEntry::new("first.txt"))
+        .await
+        .unwrap();
+    writer.write_all(b"first").await.unwrap();
+    writer.write_entries(entries).await.unwrap();
+    producer.await.unwrap();
+    let bytes = writer.finish().await.unwrap();
+
+    let archive = bytes.read_zip().await.unwrap();
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(
+        names,
+        [
+            "first.txt",
+            "page-0.bin",
+            "page-1.bin",
+            "page-2.bin",
+            "page-3.bin",
+            "page-4.bin"
+        ]
+    );
+    let entry = archive.by_name("first.txt").unwrap();
+    assert_eq!(entry.bytes().await.unwrap(), b"first");
+    for (i, page) in data.chunks(20_000).enumerate() {
+        let entry = archive.by_name(format!("page-{i}.bin")).unwrap();
+        assert_eq!(entry.bytes().await.unwrap(), page);
+    }
+}
+
+#[tokio::test]
+async fn append_to_archive() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("a.txt", b"aaa"), ("b.txt", b"bb")]);
+    let options = ArchiveReadOptions::default().with_keep_raw_records(true);
+    let archive = bytes.read_zip_with_options(options).await.unwrap();
+    let mut wr = std::io::Cursor::new(bytes.clone());
+    let mut writer = ArchiveWriter::append(&archive, &mut wr).await.unwrap();
+    writer.start_entry(NewEntry::new("c.txt")).await.unwrap();
+    writer.write_all(b"c").await.unwrap();
+    writer.finish().await.unwrap();
+
+    let bytes = wr.into_inner();
+    let archive = bytes.read_zip().await.unwrap();
+    let names: Vec<_> = archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, ["a.txt", "b.txt", "c.txt"]);
+    for (name, contents) in [("a.txt", &b"aaa"[..]), ("b.txt", b"bb"), ("c.txt", b"c")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().await.unwrap(), contents);
+    }
+}
+
+#[tokio::test]
+async fn write_zip64() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ArchiveWriter::new(Vec::new()).force_zip64(true);
+    writer.start_entry(NewEntry::new("a.txt")).await.unwrap();
+    writer.write_all(b"aaa").await.unwrap();
+    let bytes = writer.finish().await.unwrap();
+
+    let archive = bytes.read_zip().await.unwrap();
+    let entry = archive.by_name("a.txt").unwrap();
+    assert_eq!(entry.reader_version.version, 45);
+    assert_eq!(entry.bytes().await.unwrap(), b"aaa");
+}
+
+#[tokio::test]
+async fn copy_raw_entries() {
+    corpus::install_test_subscriber();
+
+    let source = corpus::stored_zip(&[("a.txt", b"aaa"), ("b.txt", b"bb")]);
+    let source = source.read_zip().await.unwrap();
+
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for entry in source.entries() {
+        let raw = entry.raw_entry().await.unwrap();
+        writer.copy_raw_entry(&entry, raw).await.unwrap();
+    }
+    let bytes = writer.finish().await.unwrap();
+
+    let archive = bytes.read_zip().await.unwrap();
+    for (name, contents) in [("a.txt", &b"aaa"[..]), ("b.txt", b"bb")] {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.bytes().await.unwrap(), contents);
+    }
+}
+
+#[tokio::test]
+async fn streaming_zip_reader() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..100_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("a.txt")).await.unwrap();
+    writer.write_all(b"aaa").await.unwrap();
+    let entry = NewEntry::new("data.bin");
+    #[cfg(feature = "deflate")]
+    let entry = entry.with_method(Method::Deflate);
+    writer.start_entry(entry).await.unwrap();
+    writer.write_all(&data).await.unwrap();
+    let bytes = writer.finish().await.unwrap();
+
+    let mut zip = StreamingZipReader::new(&bytes[..]);
+    let mut read = Vec::new();
+    while let Some(entry) = zip.next_entry().await.unwrap() {
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).await.unwrap();
+        read.push((entry.entry().name.clone(), contents));
+    }
+    assert_eq!(
+        read,
+        [
+            ("a.txt".to_owned(), b"aaa".to_vec()),
+            ("data.bin".to_owned(), data),
+        ]
+    );
+}
+
+/// Serves a buffer one range at a time, counting requests
+struct InMemoryRanges {
+    data: Vec<u8>,
+    requests: Arc<AtomicUsize>,
+}
+
+impl RangeReader for InMemoryRanges {
+    fn size(&self) -> BoxFuture<'_, std::io::Result<u64>> {
+        Box::pin(async move { Ok(self.data.len() as u64) })
+    }
+
+    fn read_range(&self, range: Range<u64>) -> BoxFuture<'_, std::io::Result<Vec<u8>>> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let start = (range.start as usize).min(self.data.len());
+        let end = (range.end as usize).min(self.data.len());
+        Box::pin(async move { Ok(self.data[start..end].to_vec()) })
+    }
+}
+
+#[tokio::test]
+async fn range_reader() {
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..1_000_000u32)
+        .flat_map(|i| (i % 251).to_le_bytes())
+        .collect();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    writer.start_entry(NewEntry::new("a.txt")).await.unwrap();
+    writer.write_all(b"aaa").await.unwrap();
+    writer.start_entry(NewEntry::new("data.bin")).await.unwrap();
+    writer.write_all(&data).await.unwrap();
+    let bytes = writer.finish().await.unwrap();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let ranged = Ranged::new(InMemoryRanges {
+        data: bytes,
+        requests: requests.clone(),
+    });
+    let archive = ranged.read_zip().await.unwrap();
+    let opening = requests.load(Ordering::SeqCst);
+    assert!(opening <= 4, "{opening} requests to open the archive");
+
+    let entry = archive.by_name("a.txt").unwrap();
+    assert_eq!(entry.bytes().await.unwrap(), b"aaa");
+
+    // 4 MB, read with requests of 64 KiB, 128 KiB, etc.
+    let entry = archive.by_name("data.bin").unwrap();
+    let before = requests.load(Ordering::SeqCst);
+    assert_eq!(entry.bytes().await.unwrap(), data);
+    let reading = requests.load(Ordering::SeqCst) - before;
+    assert!(reading <= 8, "{reading} requests to read 4 MB");
+}
+
+#[tokio::test]
+async fn seekable_reader() {
+    use std::io::SeekFrom;
+    use tokio::io::AsyncSeekExt;
+
+    corpus::install_test_subscriber();
+
+    let data: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    let mut writer = ArchiveWriter::new(Vec::new());
+    for (name, method) in [("stored", Method::Store), ("deflated", Method::Deflate)] {
+        writer
+            .start_entry(NewEntry::new(name).with_method(method))
+            .await
+            .unwrap();
+        writer.write_all(&data).await.unwrap();
+    }
+    let bytes = writer.finish().await.unwrap();
+    let archive = bytes.read_zip().await.unwrap();
+
+    for name in ["stored", "deflated"] {
+        let entry = archive.by_name(name).unwrap();
+        let mut rd = entry.seekable_reader().await.unwrap();
+        for (pos, len) in [
+            (SeekFrom::Start(300_000), 1000),
+            (SeekFrom::Start(10), 20),
+            (SeekFrom::Current(5000), 100),
+            (SeekFrom::End(-16), 16),
+        ] {
+            let start = rd.seek(pos).await.unwrap() as usize;
+            let mut buf = vec![0u8; len];
+            rd.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, data[start..start + len], "{name} at {pos:?}");
+        }
+
+        assert_eq!(rd.seek(SeekFrom::End(10)).await.unwrap(), 400_010);
+        assert_eq!(rd.read(&mut [0u8; 16]).await.unwrap(), 0);
+
+        rd.rewind().await.unwrap();
+        let mut all = Vec::new();
+        rd.read_to_end(&mut all).await.unwrap();
+        assert_eq!(all, data);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{io, ops::Deref, path::Path};
+
+use rc_zip::{
+    error::Error,
+    fsm::{ArchiveFsm, ArchiveReadOptions, DecompressionLimits, EntryFsm, FsmResult},
+    parse::{Archive, Entry},
+};
+use tokio_uring::{buf::IoBuf, fs::File};
+use tracing::trace;
+
+/// Size of the buffers used for positioned reads and writes.
+pub(crate) const BUFFER_SIZE: usize = 128 * 1024;
+
+/// A zip archive, read with io_uring from a file on disk.
+///
+/// This only contains metadata for the archive and its entries: entry data
+/// is read on-demand, with [UringArchive::bytes] or an
+/// [Extractor](crate::Extractor).
+pub struct UringArchive {
+    file: File,
+    archive: Archive,
+}
+
+impl Deref for UringArchive {
+    type Target = Archive;
+
+    fn deref(&self) -> &Self::Target {
+        &self.archive
+    }
+}
+
+impl UringArchive {
+    /// Opens the file at `path` and reads it as a zip archive.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open_with_options(path, Default::default()).await
+    }
+
+    /// Opens the file at `path` and reads it as a zip archive, with the given
+    /// options.
+    pub async fn open_with_options(
+        path: impl AsRef<Path>,
+        options: ArchiveReadOptions,
+    ) -> Result<Self, Error> {
+        let file = std::fs::File::open(path)?;
+        let size = file.metadata()?.len();
+        Self::from_file(File::from_std(file), size, options).await
+    }
+
+    /// Reads `file`, which is `size` bytes long, as a zip archive.
+    pub async fn from_file(
+        file: File,
+        size: u64,
+        options: ArchiveReadOptions,
+    ) -> Result<Self, Error> {
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        let mut fsm = ArchiveFsm::with_options(size, options);
+        loop {
+            if let Some(offset) = fsm.wants_read() {
+                let space = fsm.space();
+                let (res, slice) = file
+                    .read_at(buf.slice(..space.len().min(BUFFER_SIZE)), offset)
+                    .await;
+                buf = slice.into_inner();
+                let read_bytes = res?;
+
+                trace!(%offset, %read_bytes, "filling fsm");
+                if read_bytes == 0 {
+                    return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                }
+                space[..read_bytes].copy_from_slice(&buf[..read_bytes]);
+                fsm.fill(read_bytes);
+            }
+
+            fsm = match fsm.process()? {
+                FsmResult::Done(archive) => return Ok(Self { file, archive }),
+                FsmResult::Continue(fsm) => fsm,
+            }
+        }
+    }
+
+    /// Returns the file the archive is read from.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Reads the entire data of `entry`, which must belong to this archive,
+    /// into a vector.
+    pub async fn bytes(&self, entry: &Entry) -> Result<Vec<u8>, Error> {
+        self.bytes_with_limits(entry, Default::default()).await
+    }
+
+    /// Like [UringArchive::bytes], but fails if the entry decompresses to
+    /// more than `limits` allow.
+    pub async fn bytes_with_limits(
+        &self,
+        entry: &Entry,
+        limits: DecompressionLimits,
+    ) -> Result<Vec<u8>, Error> {
+        let mut decoder = EntryDecoder::new(self, entry).with_limits(limits);
+        let mut v = Vec::new();
+        let mut out = vec![0u8; BUFFER_SIZE];
+        loop {
+            let n = decoder.decode(&mut out).await?;
+            if n == 0 {
+                return Ok(v);
+            }
+            v.extend_from_slice(&out[..n]);
+        }
+    }
+}
+
+/// Decompresses an entry, whose data is read from the archive with
+/// positioned reads, from its local header onwards.
+pub(crate) struct EntryDecoder<'a> {
+    file: &'a File,
+    fsm: Option<EntryFsm>,
+    offset: u64,
+    buf: Vec<u8>,
+}
+
+impl<'a> EntryDecoder<'a> {
+    pub(crate) fn new(archive: &'a UringArchive, entry: &Entry) -> Self {
+        Self {
+            file: &archive.file,
+            fsm: Some(EntryFsm::new(Some(entry.clone()), None)),
+            offset: entry.header_offset,
+            buf: vec![0u8; BUFFER_SIZE],
+        }
+    }
+
+    pub(crate) fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_limits(limits));
+        self
+    }
+
+    /// Decompresses some data into `out`, returning how many bytes were
+    /// written to it: 0 means the end of the entry was reached.
+    pub(crate) async fn decode(&mut self, out: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            let mut fsm = match self.fsm.take() {
+                Some(fsm) => fsm,
+                None => return Ok(0),
+            };
+
+            let mut filled_bytes = 0;
+            if fsm.wants_read() {
+                let space = fsm.space();
+                let buf = std::mem::take(&mut self.buf);
+                let (res, slice) = self
+                    .file
+                    .read_at(buf.slice(..space.len().min(BUFFER_SIZE)), self.offset)
+                    .await;
+                self.buf = slice.into_inner();
+                let n = res?;
+                space[..n].copy_from_slice(&self.buf[..n]);
+                fsm.fill(n);
+                self.offset += n as u64;
+                filled_bytes = n;
+            }
+
+            match fsm.process(out)? {
+                FsmResult::Continue((fsm, outcome)) => {
+                    self.fsm = Some(fsm);
+
+                    if outcome.bytes_written > 0 {
+                        return Ok(outcome.bytes_written);
+                    } else if filled_bytes > 0 || outcome.bytes_read > 0 {
+                        // progress was made, keep reading
+                        continue;
+                    } else {
+                        return Err(Error::IO(io::Error::new(
+                            io::ErrorKind::Other,
+                            "entry decoder: no progress",
+                        )));
+                    }
+                }
+                FsmResult::Done(_) => return Ok(0),
+            }
+        }
+    }
+}
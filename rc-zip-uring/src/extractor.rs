@@ -0,0 +1,226 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use futures_util::future::try_join_all;
+use rc_zip::{
+    error::{Error, FormatError},
+    parse::{Entry, EntryKind},
+};
+use tokio_uring::{buf::IoBuf, fs::File};
+use tracing::trace;
+
+use crate::archive::{EntryDecoder, UringArchive, BUFFER_SIZE};
+
+/// Extracts entries into a directory with io_uring, making sure nothing is
+/// written outside of it.
+///
+/// This follows the same rules as rc-zip-sync's `Extractor`: entries are
+/// extracted to their [Entry::safe_path], what symlink entries may point to
+/// depends on [SymlinkBehavior], and existing symlinks are never followed.
+///
+/// Entries are handled in batches of [Extractor::with_concurrency] entries:
+/// within a batch, directories, symlinks and files are created in order, then
+/// the data of all files is decompressed and written concurrently. Creating
+/// directories and symlinks isn't done through io_uring.
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    dir: PathBuf,
+    symlinks: SymlinkBehavior,
+    permissions: bool,
+    concurrency: usize,
+}
+
+/// What [Extractor] does with symlink entries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SymlinkBehavior {
+    /// Symlink entries are rejected, whatever their target.
+    Deny,
+
+    /// Symlink entries are created if they point inside the destination
+    /// directory, see [Entry::safe_symlink_target], and rejected otherwise.
+    #[default]
+    AllowWithinRoot,
+
+    /// Symlink entries are created whatever their target, which is only
+    /// reasonable for trusted archives: a symlink to `/etc` is then fine.
+    /// Later entries still aren't extracted through them.
+    AllowAll,
+}
+
+impl Extractor {
+    /// Extract into `dir`, which is created if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            symlinks: Default::default(),
+            permissions: false,
+            concurrency: 16,
+        }
+    }
+
+    /// Sets whether files get the [permissions](rc_zip::parse::Mode::permissions)
+    /// they have in the archive, off by default. Directories keep their
+    /// default permissions, and files whose permissions are 0 keep the
+    /// default ones.
+    pub fn with_permissions(mut self, permissions: bool) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Sets what to do with symlink entries, see [SymlinkBehavior].
+    pub fn with_symlink_behavior(mut self, symlinks: SymlinkBehavior) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets how many files are written at the same time, 16 by default. Each
+    /// of them holds an open file and two buffers of 128 KiB.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Returns the destination directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Extracts all entries of `archive`, stopping at the first batch that
+    /// has an entry that can't be extracted.
+    pub async fn extract(&self, archive: &UringArchive) -> Result<(), Error> {
+        let entries: Vec<&Entry> = archive.entries().collect();
+        for batch in entries.chunks(self.concurrency) {
+            // files are opened in order, so that a later entry replacing
+            // one of them with a symlink can't redirect its data
+            let mut files = Vec::new();
+            for entry in batch {
+                if let Some(file) = self.create(archive, entry).await? {
+                    files.push((*entry, file));
+                }
+            }
+            try_join_all(
+                files
+                    .into_iter()
+                    .map(|(entry, file)| write_data(archive, entry, file)),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Creates what `entry` should be extracted to. Directories and
+    /// symlinks are done then, files are returned open to have their data
+    /// written.
+    async fn create(&self, archive: &UringArchive, entry: &Entry) -> Result<Option<File>, Error> {
+        if matches!(entry.kind(), EntryKind::Symlink) && self.symlinks == SymlinkBehavior::Deny {
+            trace!(name = %entry.name, "extract: denying symlink");
+            return Err(unsafe_path(entry));
+        }
+        let path = self.prepare(entry)?;
+
+        match entry.kind() {
+            EntryKind::Directory => {
+                fs::create_dir_all(&path)?;
+                Ok(None)
+            }
+            EntryKind::File => {
+                let file = fs::File::create(&path)?;
+
+                if self.permissions {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    let permissions = entry.mode.permissions();
+                    if permissions != 0 {
+                        file.set_permissions(fs::Permissions::from_mode(permissions))?;
+                    }
+                }
+                Ok(Some(File::from_std(file)))
+            }
+            EntryKind::Symlink => {
+                let target = String::from_utf8_lossy(&archive.bytes(entry).await?).into_owned();
+                let target = match self.symlinks {
+                    SymlinkBehavior::AllowAll => PathBuf::from(target),
+                    _ => entry
+                        .safe_symlink_target(&target)
+                        .ok_or_else(|| unsafe_path(entry))?,
+                };
+
+                if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_file()) {
+                    fs::remove_file(&path)?;
+                }
+                std::os::unix::fs::symlink(target, &path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Creates the parent directories of `entry`, and removes the symlink
+    /// where it should be extracted, if there's one. Returns the path to
+    /// extract it to.
+    fn prepare(&self, entry: &Entry) -> Result<PathBuf, Error> {
+        let relative = entry.safe_path().ok_or_else(|| unsafe_path(entry))?;
+        fs::create_dir_all(&self.dir)?;
+
+        let mut path = self.dir.clone();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            path.push(component);
+            let is_last = components.peek().is_none();
+
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    if !is_last {
+                        trace!(name = %entry.name, "extract: refusing to go through symlink {}", path.display());
+                        return Err(unsafe_path(entry));
+                    }
+                    fs::remove_file(&path)?;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    if !is_last {
+                        fs::create_dir(&path)?;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// Decompresses the data of `entry` into `file`, with positioned writes.
+async fn write_data(archive: &UringArchive, entry: &Entry, file: File) -> Result<(), Error> {
+    let mut decoder = EntryDecoder::new(archive, entry);
+    let mut out = vec![0u8; BUFFER_SIZE];
+    let mut pos = 0u64;
+    loop {
+        let n = decoder.decode(&mut out).await?;
+        if n == 0 {
+            break;
+        }
+
+        let mut written = 0;
+        while written < n {
+            let (res, slice) = file.write_at(out.slice(written..n), pos).await;
+            out = slice.into_inner();
+            let w = res?;
+            if w == 0 {
+                return Err(Error::IO(io::ErrorKind::WriteZero.into()));
+            }
+            written += w;
+            pos += w as u64;
+        }
+    }
+    trace!(name = %entry.name, %pos, "extract: wrote file");
+    file.close().await?;
+    Ok(())
+}
+
+fn unsafe_path(entry: &Entry) -> Error {
+    FormatError::UnsafePath {
+        name: entry.name.clone(),
+    }
+    .into()
+}
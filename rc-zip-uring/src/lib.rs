@@ -0,0 +1,28 @@
+//! A library for reading and extracting zip files from disk with io_uring,
+//! based on top of [rc-zip](https://crates.io/crates/rc-zip) and
+//! [tokio-uring](https://crates.io/crates/tokio-uring).
+//!
+//! Everything here must run within [tokio_uring::start]. Entry data is read
+//! with positioned reads and written out with positioned writes, straight
+//! from rc-zip's state machines, which saves a lot of syscalls over the
+//! `AsyncRead` loop of other crates when extracting many small files.
+//!
+//! This is Linux-only: on other platforms, this crate is empty.
+//!
+//! See also:
+//!
+//!   * [rc-zip-sync](https://crates.io/crates/rc-zip-sync) for using std I/O traits
+//!   * [rc-zip-tokio](https://crates.io/crates/rc-zip-tokio) for using tokio I/O traits
+
+#![cfg(target_os = "linux")]
+#![warn(missing_docs)]
+
+mod archive;
+pub use archive::UringArchive;
+
+mod extractor;
+pub use extractor::{Extractor, SymlinkBehavior};
+
+// re-exports
+pub use rc_zip;
+pub use tokio_uring;
@@ -0,0 +1,66 @@
+#![cfg(target_os = "linux")]
+
+use rc_zip::{
+    corpus::{self, zips_dir, Case, Files},
+    error::Error,
+    parse::Archive,
+};
+use rc_zip_uring::{Extractor, UringArchive};
+
+async fn check_case(test: &Case, archive: Result<UringArchive, Error>) {
+    corpus::check_case(test, archive.as_ref().map(|ar| -> &Archive { ar }));
+    let archive = match archive {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+
+    if let Files::ExhaustiveList(files) = &test.files {
+        for file in files {
+            let entry = archive
+                .by_name(file.name)
+                .unwrap_or_else(|| panic!("entry {} should exist", file.name));
+
+            corpus::check_file_against(file, entry, &archive.bytes(entry).await.unwrap()[..])
+        }
+    }
+}
+
+#[test]
+fn real_world_files() {
+    corpus::install_test_subscriber();
+
+    tokio_uring::start(async {
+        for case in corpus::test_cases() {
+            tracing::info!("============ testing {}", case.name);
+
+            let guarded_path = case.absolute_path();
+            let archive = UringArchive::open(&guarded_path.path).await;
+            check_case(&case, archive).await;
+            drop(guarded_path)
+        }
+    });
+}
+
+#[test]
+fn extractor() {
+    corpus::install_test_subscriber();
+
+    let dest = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("uring_extractor");
+    let _ = std::fs::remove_dir_all(&dest);
+
+    tokio_uring::start(async {
+        let archive = UringArchive::open(zips_dir().join("test.zip"))
+            .await
+            .unwrap();
+        Extractor::new(&dest)
+            .with_concurrency(1)
+            .extract(&archive)
+            .await
+            .unwrap();
+
+        for entry in archive.entries() {
+            let on_disk = std::fs::read(dest.join(&entry.name)).unwrap();
+            assert_eq!(on_disk, archive.bytes(entry).await.unwrap());
+        }
+    });
+}
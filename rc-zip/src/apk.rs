@@ -0,0 +1,91 @@
+//! Helpers for Android APKs, which are zip archives that `zipalign` has
+//! processed: the data of entries stored without compression starts at a
+//! multiple of 4 bytes, so it can be mapped into memory as is, and that of
+//! native libraries at a multiple of the page size, with `zipalign -p`.
+//!
+//! Checking an archive requires where each entry's data starts, which is
+//! only known after reading its local header: see rc-zip-sync's
+//! `ArchiveHandle::alignments`.
+
+use crate::parse::{Entry, Method};
+
+/// Alignment of stored entries' data, in bytes.
+pub const DEFAULT_ALIGNMENT: u64 = 4;
+
+/// Alignment of native libraries' data, in bytes, with `zipalign -p`.
+pub const PAGE_ALIGNMENT: u64 = 4096;
+
+/// The constraints `zipalign` enforces, see [ZipalignOptions::required_alignment].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ZipalignOptions {
+    /// Alignment of stored entries' data, in bytes: [DEFAULT_ALIGNMENT]
+    /// unless told otherwise.
+    pub alignment: u64,
+
+    /// Alignment of stored native libraries (`.so` entries), in bytes, if
+    /// they're page-aligned like with `zipalign -p`. [PAGE_ALIGNMENT] by
+    /// default, but newer Android versions support 16 KiB pages.
+    pub page_alignment: Option<u64>,
+}
+
+impl Default for ZipalignOptions {
+    fn default() -> Self {
+        Self {
+            alignment: DEFAULT_ALIGNMENT,
+            page_alignment: Some(PAGE_ALIGNMENT),
+        }
+    }
+}
+
+impl ZipalignOptions {
+    /// Returns the alignment `entry`'s data must have, if any: compressed
+    /// entries and directories don't need to be aligned.
+    pub fn required_alignment(&self, entry: &Entry) -> Option<u64> {
+        if entry.method != Method::Store || entry.name.ends_with('/') {
+            return None;
+        }
+        match self.page_alignment {
+            Some(page) if entry.name.ends_with(".so") => Some(page),
+            _ => Some(self.alignment),
+        }
+    }
+
+    /// Returns how the data of `entry`, which starts at `data_offset`, is
+    /// aligned.
+    pub fn entry_alignment(&self, entry: &Entry, data_offset: u64) -> EntryAlignment {
+        EntryAlignment {
+            name: entry.name.clone(),
+            data_offset,
+            required: self.required_alignment(entry),
+        }
+    }
+}
+
+/// Where an entry's data starts, and how it should be aligned, see
+/// [ZipalignOptions::entry_alignment].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryAlignment {
+    /// The entry's name
+    pub name: String,
+
+    /// Where the entry's data starts, relative to the start of the archive
+    pub data_offset: u64,
+
+    /// The alignment the entry's data must have, if any
+    pub required: Option<u64>,
+}
+
+impl EntryAlignment {
+    /// The largest power of two the data offset is a multiple of, or 2^63
+    /// for an offset of 0.
+    pub fn alignment(&self) -> u64 {
+        1u64.checked_shl(self.data_offset.trailing_zeros())
+            .unwrap_or(1 << 63)
+    }
+
+    /// Whether the data offset meets the required alignment, if any.
+    pub fn is_aligned(&self) -> bool {
+        self.required
+            .map_or(true, |required| self.data_offset % required.max(1) == 0)
+    }
+}
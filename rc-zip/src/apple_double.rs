@@ -0,0 +1,151 @@
+//! Helpers for the AppleDouble files macOS adds to the zip archives it makes:
+//! the resource fork and Finder metadata of `dir/file` are stored in an entry
+//! of their own, `__MACOSX/dir/._file`. Other tools make `dir/._file`
+//! entries, next to the file itself.
+//!
+//! See [RFC 1740](https://www.rfc-editor.org/rfc/rfc1740) for the format.
+
+use crate::{
+    error::{Error, FormatError},
+    parse::{Archive, Entry},
+};
+
+/// The directory macOS puts AppleDouble entries in.
+pub const MACOSX_DIR: &str = "__MACOSX/";
+
+/// Prefix of the base name of AppleDouble files.
+const PREFIX: &str = "._";
+
+/// Magic number AppleDouble files start with.
+const MAGIC: u32 = 0x0005_1607;
+
+/// Entry ids, see RFC 1740's "Definition of Entry IDs"
+const RESOURCE_FORK_ID: u32 = 2;
+const REAL_NAME_ID: u32 = 3;
+const FINDER_INFO_ID: u32 = 9;
+
+/// Returns true for the entries macOS adds: AppleDouble files, and the
+/// `__MACOSX/` directory they're in.
+pub fn is_apple_double(name: &str) -> bool {
+    name.starts_with(MACOSX_DIR)
+        || name == MACOSX_DIR.trim_end_matches('/')
+        || base_name(name).starts_with(PREFIX)
+}
+
+/// If `name` is the name of an AppleDouble file, returns the name of the
+/// entry it's for, without a trailing slash: both `__MACOSX/dir/._file` and
+/// `dir/._file` are for `dir/file`.
+pub fn apple_double_target(name: &str) -> Option<String> {
+    let name = name.strip_prefix(MACOSX_DIR).unwrap_or(name);
+    let (parent, base) = match name.rsplit_once('/') {
+        Some((parent, base)) => (Some(parent), base),
+        None => (None, name),
+    };
+    let base = base.strip_prefix(PREFIX).filter(|base| !base.is_empty())?;
+    Some(match parent {
+        Some(parent) => format!("{parent}/{base}"),
+        None => base.to_string(),
+    })
+}
+
+/// The part of `name` after its last slash, ignoring a trailing one.
+fn base_name(name: &str) -> &str {
+    let name = name.strip_suffix('/').unwrap_or(name);
+    name.rsplit_once('/').map_or(name, |(_, base)| base)
+}
+
+impl Archive {
+    /// Returns the AppleDouble entry holding `entry`'s resource fork and
+    /// Finder metadata, if there's one: see [AppleDouble::parse] to decode
+    /// its data.
+    pub fn apple_double_for(&self, entry: &Entry) -> Option<&Entry> {
+        let name = entry.name.strip_suffix('/').unwrap_or(&entry.name);
+        let apple_double = match name.rsplit_once('/') {
+            Some((parent, base)) => format!("{parent}/{PREFIX}{base}"),
+            None => format!("{PREFIX}{name}"),
+        };
+        self.by_name(format!("{MACOSX_DIR}{apple_double}"))
+            .or_else(|| self.by_name(apple_double))
+    }
+
+    /// Returns the entries that aren't AppleDouble files nor the
+    /// `__MACOSX/` directory, see [is_apple_double].
+    pub fn entries_without_apple_double(&self) -> impl Iterator<Item = &Entry> {
+        self.entries().filter(|entry| !is_apple_double(&entry.name))
+    }
+}
+
+/// The contents of an AppleDouble file, see [AppleDouble::parse].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppleDouble<'a> {
+    /// The file's Finder metadata, if any.
+    pub finder_info: Option<FinderInfo>,
+
+    /// The file's resource fork, if any.
+    pub resource_fork: Option<&'a [u8]>,
+
+    /// The file's original name, if recorded.
+    pub real_name: Option<&'a [u8]>,
+}
+
+/// The Finder metadata of a file, the start of its AppleDouble Finder Info
+/// entry. What follows, like macOS's extended attributes, isn't decoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FinderInfo {
+    /// Four-character file type, like `TEXT`
+    pub file_type: [u8; 4],
+
+    /// Four-character code of the application that made the file
+    pub creator: [u8; 4],
+
+    /// Finder flags, like whether the file is invisible or has a custom icon
+    pub flags: u16,
+}
+
+impl<'a> AppleDouble<'a> {
+    /// Parses the contents of an AppleDouble file, as found in the entry
+    /// returned by [Archive::apple_double_for].
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let invalid = || Error::from(FormatError::InvalidAppleDouble);
+        let u16_at = |offset: usize| -> Result<u16, Error> {
+            let bytes = data.get(offset..offset + 2).ok_or_else(invalid)?;
+            Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        let u32_at = |offset: usize| -> Result<u32, Error> {
+            let bytes = data.get(offset..offset + 4).ok_or_else(invalid)?;
+            Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+        };
+
+        // magic, version, 16 bytes of filler, then the number of entries
+        if u32_at(0)? != MAGIC {
+            return Err(invalid());
+        }
+        let count = u16_at(24)? as usize;
+
+        let mut apple_double = Self::default();
+        for i in 0..count {
+            let descriptor = 26 + i * 12;
+            let id = u32_at(descriptor)?;
+            let offset = u32_at(descriptor + 4)? as usize;
+            let len = u32_at(descriptor + 8)? as usize;
+            let contents = offset
+                .checked_add(len)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(invalid)?;
+
+            match id {
+                RESOURCE_FORK_ID => apple_double.resource_fork = Some(contents),
+                REAL_NAME_ID => apple_double.real_name = Some(contents),
+                FINDER_INFO_ID if contents.len() >= 10 => {
+                    apple_double.finder_info = Some(FinderInfo {
+                        file_type: contents[0..4].try_into().unwrap(),
+                        creator: contents[4..8].try_into().unwrap(),
+                        flags: u16::from_be_bytes([contents[8], contents[9]]),
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(apple_double)
+    }
+}
@@ -0,0 +1,397 @@
+//! Archives with controllable quirks, built in memory for tests and fuzzers:
+//! see [ZipBuilder].
+
+use std::borrow::Cow;
+
+use crate::parse::{
+    CentralDirectoryFileHeader, DataDescriptorRecord, EndOfCentralDirectory64Locator,
+    EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, ExtraFieldSettings,
+    ExtraZip64Field, HostSystem, LocalFileHeader, Method, MethodSpecific, MsdosTimestamp, Version,
+};
+
+/// Builds an archive entry by entry, each with its own quirks, see
+/// [ZipEntry]. With no quirks, the result is what [super::stored_zip] makes.
+///
+/// [ZipBuilder::from_bytes] makes one from arbitrary bytes, for fuzzers and
+/// property tests.
+#[derive(Clone, Debug, Default)]
+pub struct ZipBuilder {
+    /// The archive's entries, in order
+    pub entries: Vec<ZipEntry>,
+
+    /// Bytes before the first local header, like the stub of a
+    /// self-extracting archive. Offsets count from the start of the file.
+    pub prefix: Vec<u8>,
+
+    /// The archive comment
+    pub comment: Vec<u8>,
+
+    /// Whether to write zip64 end of central directory records, even though
+    /// nothing needs them, with the regular record's values masked
+    pub zip64_eocd: bool,
+}
+
+/// An entry of a [ZipBuilder] archive, and its quirks.
+#[derive(Clone, Debug)]
+pub struct ZipEntry {
+    /// Name of the entry
+    pub name: String,
+
+    /// Uncompressed contents of the entry
+    pub data: Vec<u8>,
+
+    /// How `data` is compressed: [Method::Store], or [Method::Deflate] with
+    /// the `deflate` feature
+    pub method: Method,
+
+    /// Whether the CRC-32 and sizes are in a data descriptor after the
+    /// data, with zeros in the local header
+    pub data_descriptor: bool,
+
+    /// Whether sizes and header offset are in zip64 extra fields, with
+    /// 0xFFFF_FFFF in their place, even though they'd fit
+    pub zip64: bool,
+
+    /// Raw extra fields, written after the zip64 one if any, in both the
+    /// local header and the central directory record: see [extra_field]
+    pub extra: Vec<u8>,
+
+    /// Whether the recorded CRC-32 is wrong, everywhere it's recorded
+    pub wrong_crc32: bool,
+
+    /// Index of an earlier entry whose local header and data this one's
+    /// central directory record points to: nothing else is written for it.
+    /// Its record is the other entry's, but for its name, so if that one
+    /// overlaps a third entry, this one does too.
+    pub overlaps: Option<usize>,
+}
+
+impl ZipBuilder {
+    /// An archive without entries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry.
+    pub fn with_entry(mut self, entry: ZipEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    /// Sets bytes to write before the first local header, see [Self::prefix].
+    pub fn with_prefix(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the archive comment.
+    pub fn with_comment(mut self, comment: impl Into<Vec<u8>>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Sets whether to write zip64 end of central directory records.
+    pub fn with_zip64_eocd(mut self, zip64_eocd: bool) -> Self {
+        self.zip64_eocd = zip64_eocd;
+        self
+    }
+
+    /// Makes a builder from arbitrary bytes, which is handy to fuzz or
+    /// property-test readers with archives that are valid apart from their
+    /// quirks: whatever `bytes` are, [Self::build] doesn't panic, and
+    /// similar bytes give similar archives.
+    ///
+    /// The first byte picks archive-wide quirks, then every entry takes a
+    /// byte of quirks, a name length and a data length, then its name and
+    /// data, until `bytes` run out.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut input = bytes.iter().copied();
+        let mut next = || input.next();
+
+        let mut builder = Self::new();
+        let Some(flags) = next() else {
+            return builder;
+        };
+        builder.zip64_eocd = flags & 0x1 != 0;
+        if flags & 0x2 != 0 {
+            builder.prefix = b"#!/bin/sh\nexit 0\n".to_vec();
+        }
+        if flags & 0x4 != 0 {
+            builder.comment = b"generated".to_vec();
+        }
+
+        while let (Some(quirks), Some(name_len), Some(data_len)) = (next(), next(), next()) {
+            // names stay printable, so they're valid UTF-8
+            let name: String = (0..name_len % 16)
+                .map_while(|_| next())
+                .map(|b| char::from(b'a' + b % 26))
+                .collect();
+            let data: Vec<u8> = (0..data_len).map_while(|_| next()).collect();
+
+            let index = builder.entries.len();
+            let mut entry = ZipEntry::new(format!("{index}-{name}"), data);
+            if cfg!(feature = "deflate") && quirks & 0x01 != 0 {
+                entry.method = Method::Deflate;
+            }
+            entry.data_descriptor = quirks & 0x02 != 0;
+            entry.zip64 = quirks & 0x04 != 0;
+            entry.wrong_crc32 = quirks & 0x08 != 0;
+            if quirks & 0x10 != 0 {
+                entry.extra = extra_field(0xcafe, &entry.data[..entry.data.len().min(8)]);
+            }
+            if quirks & 0x20 != 0 && index > 0 {
+                entry.overlaps = Some(quirks as usize % index);
+            }
+            builder.entries.push(entry);
+        }
+        builder
+    }
+
+    /// Writes the archive.
+    pub fn build(&self) -> Vec<u8> {
+        let modified = MsdosTimestamp {
+            time: 0,
+            date: (1 << 5) | 1,
+        };
+
+        let mut out = self.prefix.clone();
+        let mut directory = Vec::new();
+        // the central directory record of each entry, but for its name,
+        // which overlapping entries reuse
+        let mut records: Vec<CentralDirectoryFileHeader<'static>> = Vec::new();
+
+        for entry in &self.entries {
+            if let Some(other) = entry.overlaps {
+                let other = records
+                    .get(other)
+                    .expect("entries only overlap earlier ones");
+                let record = CentralDirectoryFileHeader {
+                    name: Cow::Owned(entry.name.clone().into_bytes()),
+                    extra: other.extra.clone(),
+                    comment: Cow::Borrowed(&[]),
+                    ..*other
+                };
+                record.write_to(&mut directory).unwrap();
+                records.push(record);
+                continue;
+            }
+
+            let compressed = entry.compressed();
+            let crc32 = crc32fast::hash(&entry.data) ^ u32::from(entry.wrong_crc32);
+            let size = entry.data.len() as u64;
+            let compressed_size = compressed.len() as u64;
+            let header_offset = out.len() as u64;
+            let version = Version {
+                host_system: HostSystem::Unix,
+                version: if entry.zip64 { 45 } else { 20 },
+            };
+            let flags = if entry.data_descriptor { 0x8 } else { 0 };
+
+            // values are masked in zip64 entries, and like Info-ZIP, sizes are
+            // in a zip64 extra field even if they're in the data descriptor,
+            // so readers know its form
+            let mut extra = Vec::new();
+            let (local_crc32, local_sizes) = if entry.data_descriptor {
+                (0, (0, 0))
+            } else {
+                (crc32, (size, compressed_size))
+            };
+            let u32_or_max = |value: u64| if entry.zip64 { u32::MAX } else { value as u32 };
+            if entry.zip64 {
+                ExtraZip64Field {
+                    uncompressed_size: local_sizes.0,
+                    compressed_size: local_sizes.1,
+                    header_offset: 0,
+                    disk_start: None,
+                }
+                .write_to(
+                    &mut extra,
+                    ExtraFieldSettings {
+                        uncompressed_size_u32: u32::MAX,
+                        compressed_size_u32: u32::MAX,
+                        header_offset_u32: 0,
+                    },
+                )
+                .unwrap();
+            }
+            extra.extend_from_slice(&entry.extra);
+            LocalFileHeader {
+                reader_version: version,
+                flags,
+                method: entry.method,
+                modified,
+                crc32: local_crc32,
+                compressed_size: u32_or_max(local_sizes.1),
+                uncompressed_size: u32_or_max(local_sizes.0),
+                name: Cow::Borrowed(entry.name.as_bytes()),
+                extra: Cow::Owned(extra),
+                method_specific: MethodSpecific::None,
+            }
+            .write_to(&mut out)
+            .unwrap();
+            out.extend_from_slice(&compressed);
+
+            if entry.data_descriptor {
+                DataDescriptorRecord {
+                    crc32,
+                    compressed_size,
+                    uncompressed_size: size,
+                }
+                .write_to(&mut out, entry.zip64)
+                .unwrap();
+            }
+
+            let settings = ExtraFieldSettings {
+                uncompressed_size_u32: u32_or_max(size),
+                compressed_size_u32: u32_or_max(compressed_size),
+                header_offset_u32: u32_or_max(header_offset),
+            };
+            let mut extra = Vec::new();
+            if entry.zip64 {
+                ExtraZip64Field {
+                    uncompressed_size: size,
+                    compressed_size,
+                    header_offset,
+                    disk_start: None,
+                }
+                .write_to(&mut extra, settings)
+                .unwrap();
+            }
+            extra.extend_from_slice(&entry.extra);
+            let record = CentralDirectoryFileHeader {
+                creator_version: version,
+                reader_version: version,
+                flags,
+                method: entry.method,
+                modified,
+                crc32,
+                compressed_size: settings.compressed_size_u32,
+                uncompressed_size: settings.uncompressed_size_u32,
+                disk_nbr_start: 0,
+                internal_attrs: 0,
+                external_attrs: 0o100644 << 16,
+                header_offset: settings.header_offset_u32,
+                name: Cow::Owned(entry.name.clone().into_bytes()),
+                extra: Cow::Owned(extra),
+                comment: Cow::Borrowed(&[]),
+            };
+            record.write_to(&mut directory).unwrap();
+            records.push(record);
+        }
+
+        let directory_offset = out.len() as u64;
+        let directory_records = self.entries.len() as u64;
+        let directory_size = directory.len() as u64;
+        out.extend_from_slice(&directory);
+
+        let mask = |value: u64, max: u64| if self.zip64_eocd { max } else { value };
+        if self.zip64_eocd {
+            let eocd64_offset = out.len() as u64;
+            EndOfCentralDirectory64Record {
+                record_size: 44,
+                creator_version: 45,
+                reader_version: 45,
+                disk_nbr: 0,
+                dir_disk_nbr: 0,
+                dir_records_this_disk: directory_records,
+                directory_records,
+                directory_size,
+                directory_offset,
+            }
+            .write_to(&mut out)
+            .unwrap();
+            EndOfCentralDirectory64Locator {
+                dir_disk_number: 0,
+                directory_offset: eocd64_offset,
+                total_disks: 1,
+            }
+            .write_to(&mut out)
+            .unwrap();
+        }
+        EndOfCentralDirectoryRecord {
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: mask(directory_records, u16::MAX as u64) as u16,
+            directory_records: mask(directory_records, u16::MAX as u64) as u16,
+            directory_size: mask(directory_size, u32::MAX as u64) as u32,
+            directory_offset: mask(directory_offset, u32::MAX as u64) as u32,
+            comment: Cow::Borrowed(&self.comment),
+        }
+        .write_to(&mut out)
+        .unwrap();
+
+        out
+    }
+}
+
+impl ZipEntry {
+    /// A stored entry without quirks.
+    pub fn new(name: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            data: data.into(),
+            method: Method::Store,
+            data_descriptor: false,
+            zip64: false,
+            extra: Vec::new(),
+            wrong_crc32: false,
+            overlaps: None,
+        }
+    }
+
+    /// Sets how the data is compressed, see [Self::method].
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets whether the entry has a data descriptor.
+    pub fn with_data_descriptor(mut self, data_descriptor: bool) -> Self {
+        self.data_descriptor = data_descriptor;
+        self
+    }
+
+    /// Sets whether the entry's sizes and offset are in zip64 extra fields.
+    pub fn with_zip64(mut self, zip64: bool) -> Self {
+        self.zip64 = zip64;
+        self
+    }
+
+    /// Sets the entry's raw extra fields, see [extra_field].
+    pub fn with_extra(mut self, extra: impl Into<Vec<u8>>) -> Self {
+        self.extra = extra.into();
+        self
+    }
+
+    /// Sets whether the entry's recorded CRC-32 is wrong.
+    pub fn with_wrong_crc32(mut self, wrong_crc32: bool) -> Self {
+        self.wrong_crc32 = wrong_crc32;
+        self
+    }
+
+    /// Makes the entry point at an earlier one's data, see [Self::overlaps].
+    pub fn with_overlaps(mut self, index: usize) -> Self {
+        self.overlaps = Some(index);
+        self
+    }
+
+    /// The entry's data, compressed with its method.
+    fn compressed(&self) -> Vec<u8> {
+        match self.method {
+            Method::Store => self.data.clone(),
+            #[cfg(feature = "deflate")]
+            Method::Deflate => miniz_oxide::deflate::compress_to_vec(&self.data, 6),
+            method => panic!("ZipBuilder can't compress with {method:?}"),
+        }
+    }
+}
+
+/// An extra field with the given tag and data, for [ZipEntry::extra]: the
+/// tag doesn't need to be a known one, and the data is written as is.
+pub fn extra_field(tag: u16, data: &[u8]) -> Vec<u8> {
+    let mut field = Vec::with_capacity(4 + data.len());
+    field.extend_from_slice(&tag.to_le_bytes());
+    field.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    field.extend_from_slice(data);
+    field
+}
@@ -2,7 +2,7 @@
 
 //! A corpus of zip files for testing.
 
-use std::{fs::File, path::PathBuf};
+use std::{borrow::Cow, fs::File, path::PathBuf};
 
 use chrono::{DateTime, FixedOffset, TimeZone, Timelike, Utc};
 use temp_dir::TempDir;
@@ -11,9 +11,19 @@ use tracing::span;
 use crate::{
     encoding::Encoding,
     error::Error,
-    parse::{Archive, Entry, EntryKind},
+    parse::{
+        Archive, CentralDirectoryFileHeader, EndOfCentralDirectoryRecord, Entry, EntryKind,
+        ExtraFieldSettings, ExtraZip64Field, HostSystem, LocalFileHeader, Method, MethodSpecific,
+        MsdosTimestamp, Version,
+    },
 };
 
+mod builder;
+pub use builder::*;
+
+mod sparse;
+pub use sparse::*;
+
 pub struct Case {
     pub name: &'static str,
     pub expected_encoding: Option<Encoding>,
@@ -201,6 +211,79 @@ pub fn test_cases() -> Vec<Case> {
             }]),
             ..Default::default()
         },
+        // NTFS extra field, with 100ns precision
+        Case {
+            name: "time-7zip.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "test.txt",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2017, 11, 1), (4, 11, 57), 244817900, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            name: "time-winrar.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "test.txt",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2017, 11, 1), (4, 11, 57), 244817900, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            name: "time-winzip.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "test.txt",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2017, 11, 1), (4, 11, 57), 244000000, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        // extended timestamp extra field, with 1s precision
+        Case {
+            name: "time-infozip.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "test.txt",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2017, 11, 1), (4, 11, 57), 0, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            name: "time-go.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "test.txt",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2017, 11, 1), (4, 11, 57), 0, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        // Info-ZIP Unix extra field, without uid/gid in the central directory
+        Case {
+            name: "time-osx.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "test.txt",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2017, 11, 1), (4, 11, 57), 0, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            name: "time-22738.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "file",
+                content: FileContent::Bytes(vec![]),
+                modified: Some(date((2000, 1, 1), (0, 0, 0), 0, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
         Case {
             name: "wine-zeroed.zip.bz2",
             expected_encoding: Some(Encoding::Utf8),
@@ -291,13 +374,18 @@ pub fn check_case(case: &Case, archive: Result<&Archive, &Error>) {
     assert_eq!(case_bytes.len() as u64, archive.size());
 
     if let Some(expected) = case.comment {
-        assert_eq!(expected, archive.comment())
+        assert_eq!(expected, archive.comment());
+        assert_eq!(expected.as_bytes(), archive.raw_comment());
     }
 
     if let Some(exp_encoding) = case.expected_encoding {
         assert_eq!(archive.encoding(), exp_encoding);
     }
 
+    for entry in archive.entries() {
+        assert_eq!(entry.name_decoding.encoding, archive.encoding());
+    }
+
     assert_eq!(
         case.files.len(),
         archive.entries().count(),
@@ -350,6 +438,126 @@ pub fn check_file_against(file: &CaseFile, entry: &Entry, actual_bytes: &[u8]) {
     }
 }
 
+/// Builds an archive of stored (uncompressed) entries in memory, for cases
+/// that are easier to describe than to check into `testdata`, like archives
+/// with duplicate names.
+pub fn stored_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|(name, data)| (*name, *data, &[][..]))
+        .collect();
+    stored_zip_with_extra(&entries)
+}
+
+/// Like [stored_zip], with an extra field for each entry, written to both
+/// its local header and central directory record.
+pub fn stored_zip_with_extra(entries: &[(&str, &[u8], &[u8])]) -> Vec<u8> {
+    build_stored_zip(entries, false)
+}
+
+/// Like [stored_zip], with every central directory record's header offset
+/// in a zip64 extra field and 0xFFFF_FFFF in its place, while sizes stay
+/// where they are: some writers do that for all entries once an offset
+/// needs it.
+pub fn stored_zip_with_zip64_offsets(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|(name, data)| (*name, *data, &[][..]))
+        .collect();
+    build_stored_zip(&entries, true)
+}
+
+fn build_stored_zip(entries: &[(&str, &[u8], &[u8])], zip64_offsets: bool) -> Vec<u8> {
+    let version = Version {
+        host_system: HostSystem::Unix,
+        version: 20,
+    };
+    let modified = MsdosTimestamp {
+        time: 0,
+        date: (1 << 5) | 1,
+    };
+
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data, extra) in entries {
+        let header_offset = out.len() as u32;
+        let crc32 = crc32fast::hash(data);
+        let size = data.len() as u32;
+
+        LocalFileHeader {
+            reader_version: version,
+            flags: 0,
+            method: Method::Store,
+            modified,
+            crc32,
+            compressed_size: size,
+            uncompressed_size: size,
+            name: Cow::Borrowed(name.as_bytes()),
+            extra: Cow::Borrowed(extra),
+            method_specific: MethodSpecific::None,
+        }
+        .write_to(&mut out)
+        .unwrap();
+        out.extend_from_slice(data);
+
+        let mut central_extra = extra.to_vec();
+        let settings = ExtraFieldSettings {
+            uncompressed_size_u32: size,
+            compressed_size_u32: size,
+            header_offset_u32: if zip64_offsets {
+                u32::MAX
+            } else {
+                header_offset
+            },
+        };
+        if zip64_offsets {
+            ExtraZip64Field {
+                uncompressed_size: size as u64,
+                compressed_size: size as u64,
+                header_offset: header_offset as u64,
+                disk_start: None,
+            }
+            .write_to(&mut central_extra, settings)
+            .unwrap();
+        }
+        CentralDirectoryFileHeader {
+            creator_version: version,
+            reader_version: version,
+            flags: 0,
+            method: Method::Store,
+            modified,
+            crc32,
+            compressed_size: size,
+            uncompressed_size: size,
+            disk_nbr_start: 0,
+            internal_attrs: 0,
+            external_attrs: 0o100644 << 16,
+            header_offset: settings.header_offset_u32,
+            name: Cow::Borrowed(name.as_bytes()),
+            extra: Cow::Owned(central_extra),
+            comment: Cow::Borrowed(&[]),
+        }
+        .write_to(&mut directory)
+        .unwrap();
+    }
+
+    let directory_offset = out.len() as u32;
+    out.extend_from_slice(&directory);
+    EndOfCentralDirectoryRecord {
+        disk_nbr: 0,
+        dir_disk_nbr: 0,
+        dir_records_this_disk: entries.len() as u16,
+        directory_records: entries.len() as u16,
+        directory_size: directory.len() as u32,
+        directory_offset,
+        comment: Cow::Borrowed(&[]),
+    }
+    .write_to(&mut out)
+    .unwrap();
+
+    out
+}
+
 // This test subscriber is used to suppress trace-level logs (yet executes
 // the code, for coverage reasons)
 pub fn install_test_subscriber() {
@@ -0,0 +1,249 @@
+//! Archives too large to keep in memory, for testing zip64 support: entries
+//! of 4 GiB or more, more than 65535 entries, offsets past 4 GiB.
+
+use std::borrow::Cow;
+
+use crate::parse::{
+    CentralDirectoryFileHeader, DataDescriptorRecord, EndOfCentralDirectory64Locator,
+    EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, ExtraFieldSettings,
+    ExtraZip64Field, HostSystem, LocalFileHeader, Method, MethodSpecific, MsdosTimestamp, Version,
+};
+
+/// A zip file that's mostly zeros, of which only the other bytes are kept
+/// in memory, see [sparse_zip].
+pub struct SparseZip {
+    len: u64,
+    /// Non-zero parts of the file and their offset, in order
+    chunks: Vec<(u64, Vec<u8>)>,
+}
+
+impl SparseZip {
+    /// The size of the zip file
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the zip file is empty, which it never is
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `chunk` to the file
+    fn push(&mut self, chunk: Vec<u8>) {
+        let start = self.len;
+        self.len += chunk.len() as u64;
+        self.chunks.push((start, chunk));
+    }
+
+    /// Reads the bytes at `offset` into `buf`, returning how many were read:
+    /// 0 past the end of the file. Reads stop where chunks start and end.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let next = self.chunks.partition_point(|(start, _)| *start <= offset);
+        if let Some((start, chunk)) = next.checked_sub(1).map(|i| &self.chunks[i]) {
+            let within = (offset - start) as usize;
+            if within < chunk.len() {
+                let n = buf.len().min(chunk.len() - within);
+                buf[..n].copy_from_slice(&chunk[within..within + n]);
+                return n;
+            }
+        }
+
+        // copying is much faster than filling in debug builds
+        static ZEROS: [u8; 64 * 1024] = [0; 64 * 1024];
+        let zeros_end = self.chunks.get(next).map_or(self.len, |(start, _)| *start);
+        let n = (buf.len().min(ZEROS.len()) as u64).min(zeros_end.saturating_sub(offset)) as usize;
+        buf[..n].copy_from_slice(&ZEROS[..n]);
+        n
+    }
+}
+
+/// Builds a zip file of stored entries filled with zeros, one per size in
+/// `sizes`, named after their index: `0.bin`, `1.bin`, etc. Entry data isn't
+/// kept in memory, so sizes can be well over 4 GiB.
+///
+/// Zip64 records are written where needed, like [crate::fsm::ArchiveWriterFsm]
+/// does. With `data_descriptors`, local headers have no CRC-32 or sizes, and
+/// entries are followed by data descriptors, in zip64 form for entries of
+/// 4 GiB or more.
+pub fn sparse_zip(sizes: &[u64], data_descriptors: bool) -> SparseZip {
+    let mut zip = SparseZip {
+        len: 0,
+        chunks: Vec::new(),
+    };
+    let mut pending = Vec::new();
+    let mut directory = Vec::new();
+    let modified = MsdosTimestamp {
+        time: 0,
+        date: (1 << 5) | 1,
+    };
+
+    for (i, &size) in sizes.iter().enumerate() {
+        let name = format!("{i}.bin");
+        let header_offset = zip.len + pending.len() as u64;
+        let crc32 = zeros_crc32(size);
+        let zip64 = size >= u32::MAX as u64;
+        let version = Version {
+            host_system: HostSystem::Unix,
+            version: if zip64 { 45 } else { 20 },
+        };
+
+        // like Info-ZIP, sizes are in a zip64 extra field even if they're
+        // in the data descriptor, so readers know it's in zip64 form
+        let local_size = match (zip64, data_descriptors) {
+            (true, _) => u32::MAX,
+            (false, true) => 0,
+            (false, false) => size as u32,
+        };
+        let mut extra = Vec::new();
+        if zip64 {
+            let known = if data_descriptors { 0 } else { size };
+            ExtraZip64Field {
+                uncompressed_size: known,
+                compressed_size: known,
+                header_offset: 0,
+                disk_start: None,
+            }
+            .write_to(
+                &mut extra,
+                ExtraFieldSettings {
+                    uncompressed_size_u32: u32::MAX,
+                    compressed_size_u32: u32::MAX,
+                    header_offset_u32: 0,
+                },
+            )
+            .unwrap();
+        }
+        LocalFileHeader {
+            reader_version: version,
+            flags: if data_descriptors { 0x8 } else { 0 },
+            method: Method::Store,
+            modified,
+            crc32: if data_descriptors { 0 } else { crc32 },
+            compressed_size: local_size,
+            uncompressed_size: local_size,
+            name: Cow::Borrowed(name.as_bytes()),
+            extra: Cow::Owned(extra),
+            method_specific: MethodSpecific::None,
+        }
+        .write_to(&mut pending)
+        .unwrap();
+
+        // the data itself is all zeros, so it's skipped
+        zip.push(std::mem::take(&mut pending));
+        zip.len += size;
+
+        if data_descriptors {
+            DataDescriptorRecord {
+                crc32,
+                compressed_size: size,
+                uncompressed_size: size,
+            }
+            .write_to(&mut pending, zip64)
+            .unwrap();
+        }
+
+        // values that don't fit are moved to a zip64 extra field
+        let settings = ExtraFieldSettings {
+            uncompressed_size_u32: u32_or_max(size),
+            compressed_size_u32: u32_or_max(size),
+            header_offset_u32: u32_or_max(header_offset),
+        };
+        let mut extra = Vec::new();
+        if zip64 || settings.header_offset_u32 == u32::MAX {
+            ExtraZip64Field {
+                uncompressed_size: size,
+                compressed_size: size,
+                header_offset,
+                disk_start: None,
+            }
+            .write_to(&mut extra, settings)
+            .unwrap();
+        }
+        CentralDirectoryFileHeader {
+            creator_version: version,
+            reader_version: version,
+            flags: if data_descriptors { 0x8 } else { 0 },
+            method: Method::Store,
+            modified,
+            crc32,
+            compressed_size: settings.compressed_size_u32,
+            uncompressed_size: settings.uncompressed_size_u32,
+            disk_nbr_start: 0,
+            internal_attrs: 0,
+            external_attrs: 0o100644 << 16,
+            header_offset: settings.header_offset_u32,
+            name: Cow::Owned(name.into_bytes()),
+            extra: Cow::Owned(extra),
+            comment: Cow::Borrowed(&[]),
+        }
+        .write_to(&mut directory)
+        .unwrap();
+    }
+
+    let directory_offset = zip.len + pending.len() as u64;
+    let directory_size = directory.len() as u64;
+    let directory_records = sizes.len() as u64;
+    pending.extend_from_slice(&directory);
+
+    if directory_records >= u16::MAX as u64 || directory_offset >= u32::MAX as u64 {
+        let eocd64_offset = zip.len + pending.len() as u64;
+        EndOfCentralDirectory64Record {
+            record_size: 44,
+            creator_version: 45,
+            reader_version: 45,
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: directory_records,
+            directory_records,
+            directory_size,
+            directory_offset,
+        }
+        .write_to(&mut pending)
+        .unwrap();
+        EndOfCentralDirectory64Locator {
+            dir_disk_number: 0,
+            directory_offset: eocd64_offset,
+            total_disks: 1,
+        }
+        .write_to(&mut pending)
+        .unwrap();
+    }
+
+    // values that don't fit are only in the zip64 record
+    EndOfCentralDirectoryRecord {
+        disk_nbr: 0,
+        dir_disk_nbr: 0,
+        dir_records_this_disk: u16::try_from(directory_records).unwrap_or(u16::MAX),
+        directory_records: u16::try_from(directory_records).unwrap_or(u16::MAX),
+        directory_size: u32_or_max(directory_size),
+        directory_offset: u32_or_max(directory_offset),
+        comment: Cow::Borrowed(&[]),
+    }
+    .write_to(&mut pending)
+    .unwrap();
+
+    zip.push(pending);
+    zip
+}
+
+/// The CRC-32 of `len` zeros, without hashing them all.
+pub fn zeros_crc32(len: u64) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    // the hash of 2^n zeros, for each bit of `len`
+    let mut zeros = crc32fast::Hasher::new();
+    zeros.update(&[0]);
+    let mut len = len;
+    while len > 0 {
+        if len & 1 == 1 {
+            hasher.combine(&zeros);
+        }
+        let same = zeros.clone();
+        zeros.combine(&same);
+        len >>= 1;
+    }
+    hasher.finalize()
+}
+
+fn u32_or_max(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
@@ -26,6 +26,22 @@ pub enum Encoding {
     ///
     /// Still in use by some Japanese users as of 2019.
     ShiftJis,
+
+    /// [GBK](https://en.wikipedia.org/wiki/GBK_(character_encoding)), the
+    /// usual encoding of archives made on Simplified Chinese Windows.
+    Gbk,
+
+    /// [Big5](https://en.wikipedia.org/wiki/Big5), the usual encoding of
+    /// archives made on Traditional Chinese Windows.
+    Big5,
+
+    /// [EUC-KR](https://en.wikipedia.org/wiki/Extended_Unix_Code#EUC-KR), the
+    /// usual encoding of archives made on Korean Windows.
+    EucKr,
+
+    /// [Codepage 866](https://en.wikipedia.org/wiki/Code_page_866), the DOS
+    /// encoding for Cyrillic, used by Russian versions of many zip tools.
+    Cp866,
 }
 
 impl fmt::Display for Encoding {
@@ -35,6 +51,185 @@ impl fmt::Display for Encoding {
             T::Utf8 => write!(f, "utf-8"),
             T::Cp437 => write!(f, "cp-437"),
             T::ShiftJis => write!(f, "shift-jis"),
+            T::Gbk => write!(f, "gbk"),
+            T::Big5 => write!(f, "big5"),
+            T::EucKr => write!(f, "euc-kr"),
+            T::Cp866 => write!(f, "cp-866"),
+        }
+    }
+}
+
+/// Why a given [Encoding] was used to decode an entry's name and comment,
+/// see [NameDecoding].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EncodingSource {
+    /// The entry has the language encoding flag (EFS, general purpose bit 11)
+    /// set, which means its name and comment are UTF-8.
+    Utf8Flag,
+
+    /// The entry doesn't have the language encoding flag set, but its name
+    /// and comment (and, when reading from the central directory, those of
+    /// all other entries) are valid UTF-8, so UTF-8 was assumed.
+    Utf8Assumed,
+
+    /// The encoding was guessed from the non-UTF-8 names and comments found
+    /// in the archive.
+    Detected,
+
+    /// The entry doesn't have the language encoding flag set, but has an
+    /// Info-ZIP Unicode Path extra field, which has its name in UTF-8. Its
+    /// comment is also taken from a Unicode Comment extra field, if it has
+    /// one; otherwise, it's decoded like other entries'.
+    UnicodeExtraField,
+
+    /// The encoding was given by [EncodingPolicy::Force], or by
+    /// [EncodingPolicy::Hint] and all names and comments were valid in it.
+    Given,
+
+    /// No supported encoding could be detected, so the fallback encoding
+    /// was used: by default, the zip format's original encoding, CP-437.
+    /// See [EncodingDetection].
+    Fallback,
+}
+
+/// How an entry's name and comment were decoded into text.
+///
+/// Decoding is never lossy: if the name or comment isn't valid in the chosen
+/// encoding, reading the archive fails with a [DecodingError] instead. However,
+/// a valid decoding can still be the wrong one, which is what [Self::confident]
+/// is for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NameDecoding {
+    /// The encoding that was used
+    pub encoding: Encoding,
+
+    /// Why that encoding was used
+    pub source: EncodingSource,
+
+    /// Whether we're confident this is the right encoding.
+    ///
+    /// This is always true for [EncodingSource::Utf8Flag],
+    /// [EncodingSource::Utf8Assumed], [EncodingSource::UnicodeExtraField]
+    /// and [EncodingSource::Given], and always false for
+    /// [EncodingSource::Fallback]. For [EncodingSource::Detected], it's false
+    /// if the detector couldn't clearly tell candidate encodings apart, in which
+    /// case the names may well be mangled.
+    pub confident: bool,
+}
+
+/// Which encoding is used for names and comments of entries that don't
+/// have the language encoding flag set, see
+/// [ArchiveReadOptions::encoding](crate::fsm::ArchiveReadOptions::encoding).
+///
+/// Entries that do have it set are always decoded as UTF-8, unless their
+/// name or comment isn't valid UTF-8.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EncodingPolicy {
+    /// Use UTF-8 if all names and comments are valid UTF-8, and detect the
+    /// encoding otherwise, as tuned by
+    /// [ArchiveReadOptions::encoding_detection](crate::fsm::ArchiveReadOptions::encoding_detection).
+    #[default]
+    Detect,
+
+    /// Like [Self::Detect], but try the given encoding before detecting
+    /// anything: it's used if all names and comments that aren't valid
+    /// UTF-8 are valid in it. Single-byte encodings like CP-437 always are.
+    Hint(Encoding),
+
+    /// Always use the given encoding, even if all names and comments are
+    /// valid UTF-8: for archives known to come from a given system.
+    /// `Force(Encoding::Cp437)` disables detection, and follows the zip
+    /// specification to the letter.
+    Force(Encoding),
+}
+
+/// Tunes how the encoding of names and comments is detected when entries
+/// don't have the language encoding flag set, and aren't all valid UTF-8.
+///
+/// Detection works well on large archives, but short names don't give the
+/// detector much to go on. Deployments that know which encodings they'll
+/// see can restrict detection to those, and pick what to use when the
+/// detector's guess isn't one of them.
+#[derive(Clone, Debug)]
+pub struct EncodingDetection {
+    /// Encodings detection is allowed to pick. A guess that isn't in this
+    /// list results in [Self::fallback] being used.
+    ///
+    /// By default, that's UTF-8, CP-437 and Shift-JIS: the other encodings
+    /// are more often guessed wrongly from short names, so they have to be
+    /// allowed explicitly.
+    pub allowed: Vec<Encoding>,
+
+    /// Whether to reject guesses the detector isn't confident about, see
+    /// [NameDecoding::confident], and use [Self::fallback] instead.
+    pub require_confident: bool,
+
+    /// The encoding used when nothing (allowed) could be detected.
+    pub fallback: Encoding,
+}
+
+impl Default for EncodingDetection {
+    fn default() -> Self {
+        Self {
+            allowed: vec![Encoding::Utf8, Encoding::Cp437, Encoding::ShiftJis],
+            require_confident: false,
+            fallback: Encoding::Cp437,
+        }
+    }
+}
+
+impl EncodingDetection {
+    /// Whether detection may pick `encoding`.
+    pub fn allows(&self, encoding: Encoding) -> bool {
+        self.allowed.contains(&encoding)
+    }
+
+    /// Turns the detector's guess into a [NameDecoding]. `cp437_suspicious`
+    /// is whether the input had characters that are unlikely in CP-437 file
+    /// names, which is used to tell it apart from Shift-JIS.
+    pub(crate) fn decide(
+        &self,
+        guess: &'static encoding_rs::Encoding,
+        confident: bool,
+        cp437_suspicious: bool,
+    ) -> NameDecoding {
+        let guess = if guess == encoding_rs::SHIFT_JIS {
+            // well hold on, sometimes Codepage 437 is detected as
+            // Shift-JIS by chardetng. If we have any characters
+            // that aren't valid DOS file names, then okay it's probably
+            // Shift-JIS. Otherwise, assume it's CP437.
+            if cp437_suspicious || !self.allows(Encoding::Cp437) {
+                Some(Encoding::ShiftJis)
+            } else {
+                Some(Encoding::Cp437)
+            }
+        } else if guess == encoding_rs::UTF_8 {
+            Some(Encoding::Utf8)
+        } else if guess == encoding_rs::GBK {
+            Some(Encoding::Gbk)
+        } else if guess == encoding_rs::BIG5 {
+            Some(Encoding::Big5)
+        } else if guess == encoding_rs::EUC_KR {
+            Some(Encoding::EucKr)
+        } else if guess == encoding_rs::IBM866 {
+            Some(Encoding::Cp866)
+        } else {
+            None
+        };
+
+        match guess {
+            Some(encoding) if self.allows(encoding) && (confident || !self.require_confident) => {
+                NameDecoding {
+                    encoding,
+                    source: EncodingSource::Detected,
+                    confident,
+                }
+            }
+            _ => NameDecoding {
+                encoding: self.fallback,
+                source: EncodingSource::Fallback,
+                confident: false,
+            },
         }
     }
 }
@@ -76,6 +271,10 @@ impl Encoding {
                 &oem_cp::code_table::DECODING_TABLE_CP437,
             )),
             Encoding::ShiftJis => self.decode_as(i, encoding_rs::SHIFT_JIS),
+            Encoding::Gbk => self.decode_as(i, encoding_rs::GBK),
+            Encoding::Big5 => self.decode_as(i, encoding_rs::BIG5),
+            Encoding::EucKr => self.decode_as(i, encoding_rs::EUC_KR),
+            Encoding::Cp866 => self.decode_as(i, encoding_rs::IBM866),
         }
     }
 
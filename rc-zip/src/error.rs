@@ -1,6 +1,7 @@
-//! All error types used in this crate
+//! All error types used in this crate, and warnings for problems that
+//! aren't errors.
 
-use crate::parse::Method;
+use crate::parse::{Method, Version};
 
 use super::encoding;
 
@@ -32,9 +33,38 @@ pub enum Error {
         msg: String,
     },
 
+    /// Compression-related error, while writing an archive
+    #[error("{method:?} compression error: {msg}")]
+    Compression {
+        /// The compression method that failed
+        method: Method,
+        /// Additional information
+        msg: String,
+    },
+
     /// Could not read as a zip because size could not be determined
     #[error("size must be known to open zip file")]
     UnknownSize,
+
+    /// The entry is encrypted, but no password was given.
+    #[error("entry is encrypted, a password is required")]
+    PasswordRequired,
+
+    /// The password given for an encrypted entry is wrong.
+    #[error("wrong password")]
+    WrongPassword,
+
+    /// Reading an entry was stopped by one of its
+    /// [DecompressionLimits](crate::fsm::DecompressionLimits), or reading
+    /// an archive by one of the limits in its
+    /// [ArchiveReadOptions](crate::fsm::ArchiveReadOptions).
+    #[error("limit exceeded: {0}")]
+    LimitExceeded(LimitExceeded),
+
+    /// Reading or extracting was cancelled before it was done, for example
+    /// with rc-zip-sync's `CancellationToken`.
+    #[error("cancelled")]
+    Cancelled,
 }
 
 impl Error {
@@ -49,6 +79,124 @@ impl Error {
     }
 }
 
+/// A central directory record that couldn't be turned into an [Entry], see
+/// [ArchiveReadOptions::skip_invalid_entries].
+///
+/// [Entry]: crate::parse::Entry
+/// [ArchiveReadOptions::skip_invalid_entries]: crate::fsm::ArchiveReadOptions::skip_invalid_entries
+#[derive(Debug, thiserror::Error)]
+#[error("central directory record #{index} ({name:?}) at offset {offset}: {error}")]
+pub struct EntryParseError {
+    /// Position of the record in the central directory, counting invalid
+    /// records too.
+    pub index: usize,
+
+    /// Offset of the record in the zip file.
+    pub offset: u64,
+
+    /// The record's name, decoded as lossy UTF-8, since decoding it
+    /// properly may be what failed.
+    pub name: String,
+
+    /// Why the record couldn't be turned into an entry.
+    #[source]
+    pub error: Error,
+}
+
+/// A problem with an archive that didn't keep it from being read, see
+/// [Archive::warnings](crate::parse::Archive::warnings).
+///
+/// Some of these are only warnings with
+/// [Strictness::Permissive](crate::parse::Strictness::Permissive), and make
+/// reading fail otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Warning {
+    /// An entry's extra field couldn't be parsed: it was ignored, along with
+    /// the extra fields after it. This is an error unless reading
+    /// permissively.
+    #[error("entry {name:?} has an invalid extra field, it was ignored")]
+    InvalidExtraField {
+        /// the name of the entry
+        name: String,
+    },
+
+    /// The end of central directory record doesn't have the number of
+    /// entries the central directory actually has: either it's wrong, or
+    /// some records couldn't be parsed and entries were lost. This is an
+    /// error unless reading permissively.
+    #[error("expected {expected} entries in the central directory, found {actual}")]
+    WrongEntryCount {
+        /// number of entries in the end of central directory record
+        expected: u64,
+        /// number of entries read from the central directory
+        actual: u64,
+    },
+
+    /// A non-empty entry has a CRC-32 of 0 in the central directory, so its
+    /// data can only be checked if it has a data descriptor with one.
+    #[error("entry {name:?} has no CRC-32 in the central directory")]
+    MissingChecksum {
+        /// the name of the entry
+        name: String,
+    },
+
+    /// An entry claims to need a version of the zip format that doesn't
+    /// exist (yet): the latest is 6.3.
+    #[error(
+        "entry {name:?} claims to need zip version {}.{}",
+        version.version / 10,
+        version.version % 10
+    )]
+    UnknownVersionNeeded {
+        /// the name of the entry
+        name: String,
+        /// the version it claims to need
+        version: Version,
+    },
+}
+
+/// Which limit was exceeded, see [Error::LimitExceeded].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum LimitExceeded {
+    /// The entry decompresses to more bytes than allowed.
+    #[error("entry decompresses to more than {limit} bytes")]
+    OutputSize {
+        /// the maximum number of bytes
+        limit: u64,
+    },
+
+    /// The entry decompresses to more bytes than allowed for its compressed
+    /// size.
+    #[error("entry decompresses to more than {limit} times its compressed size")]
+    Ratio {
+        /// the maximum compression ratio
+        limit: u64,
+    },
+
+    /// The entry is in an archive nested deeper than allowed.
+    #[error("archive is nested more than {limit} levels deep")]
+    Depth {
+        /// the maximum nesting depth
+        limit: u32,
+    },
+
+    /// The central directory has more entries than allowed, see
+    /// [ArchiveReadOptions::max_entries](crate::fsm::ArchiveReadOptions::max_entries).
+    #[error("archive has more than {limit} entries")]
+    EntryCount {
+        /// the maximum number of entries
+        limit: u64,
+    },
+
+    /// The central directory is larger than allowed, see
+    /// [ArchiveReadOptions::max_central_directory_size](crate::fsm::ArchiveReadOptions::max_central_directory_size).
+    #[error("central directory is larger than {limit} bytes")]
+    CentralDirectorySize {
+        /// the maximum size, in bytes
+        limit: u64,
+    },
+}
+
 /// Some part of the zip format is not supported by this crate.
 #[derive(Debug, thiserror::Error)]
 pub enum UnsupportedError {
@@ -69,6 +217,28 @@ pub enum UnsupportedError {
         minor: u8,
     },
 
+    /// The archive uses PKWARE strong encryption, see section 7 of the
+    /// appnote.
+    #[error("PKWARE strong encryption is not supported{}", match name {
+        Some(name) => format!(", for entry {name:?}"),
+        None => ", for the central directory".to_owned(),
+    })]
+    StrongEncryption {
+        /// the name of the entry whose data is encrypted, or None if it's
+        /// the central directory that is, in which case no entries can be
+        /// read at all
+        name: Option<String>,
+    },
+
+    /// An encrypted entry read from its local header only has its size in
+    /// its data descriptor, which comes after its data: where that data
+    /// ends can't be found.
+    #[error("encrypted entry {name:?} has no size in its local header, it can't be streamed")]
+    StreamingEncryptedEntry {
+        /// the name of the entry
+        name: String,
+    },
+
     /// The LZMA properties header is not the expected size.
     #[error("LZMA properties header wrong size: expected {expected} bytes, got {actual} bytes")]
     LzmaPropertiesHeaderWrongSize {
@@ -126,6 +296,12 @@ pub enum FormatError {
     #[error("invalid header offset")]
     InvalidHeaderOffset,
 
+    /// Offsets or sizes recorded in the archive don't add up: computing with
+    /// them overflows, or ends up before the start of the file. See
+    /// [Offsets](crate::parse::Offsets).
+    #[error("invalid offsets")]
+    InvalidOffsets,
+
     /// End of central directory record claims an impossible number of files.
     ///
     /// Each entry takes a minimum amount of size, so if the overall archive size is smaller than
@@ -138,10 +314,49 @@ pub enum FormatError {
         zip_size: u64,
     },
 
+    /// Several entries have the same name, and the archive was read with
+    /// [DuplicateNamePolicy::Error](crate::parse::DuplicateNamePolicy::Error).
+    #[error("duplicate entry name: {name}")]
+    DuplicateEntryName {
+        /// the name shared by several entries
+        name: String,
+    },
+
+    /// An entry can't be extracted safely: its name, or its target if it's a
+    /// symlink, would end up outside of the destination directory, see
+    /// [Entry::safe_path](crate::parse::Entry::safe_path). Or it's a symlink,
+    /// and those aren't allowed.
+    #[error("entry {name:?} can't be extracted safely")]
+    UnsafePath {
+        /// the name of the entry
+        name: String,
+    },
+
+    /// A JAR manifest could not be parsed, see [Manifest::parse](crate::jar::Manifest::parse).
+    #[error("invalid JAR manifest, at line {line}")]
+    InvalidManifest {
+        /// the line the problem was found on, starting from 1
+        line: usize,
+    },
+
+    /// An AppleDouble file could not be parsed, see
+    /// [AppleDouble::parse](crate::apple_double::AppleDouble::parse).
+    #[error("invalid AppleDouble file")]
+    InvalidAppleDouble,
+
     /// The local file header (before the file data) could not be parsed correctly.
     #[error("invalid local file header")]
     InvalidLocalHeader,
 
+    /// An entry read from its local file header doesn't match the central
+    /// directory, or there are more or fewer local headers than there are
+    /// central directory records.
+    #[error("local file header doesn't match the central directory, for entry {name:?}")]
+    LocalHeaderMismatch {
+        /// the name of the first entry that doesn't match
+        name: String,
+    },
+
     /// The data descriptor (after the file data) could not be parsed correctly.
     #[error("invalid data descriptor")]
     InvalidDataDescriptor,
@@ -163,6 +378,11 @@ pub enum FormatError {
         /// actual checksum (from decompressing the entry)
         actual: u32,
     },
+
+    /// The authentication code of an AES-encrypted entry didn't match: its
+    /// data is corrupted or was tampered with.
+    #[error("authentication code didn't match")]
+    WrongAuthenticationCode,
 }
 
 impl From<Error> for std::io::Error {
@@ -1,11 +1,15 @@
+use std::{collections::HashSet, sync::Arc};
+
 use super::FsmResult;
 use crate::{
-    encoding::Encoding,
-    error::{Error, FormatError},
+    encoding::{Encoding, EncodingDetection, EncodingPolicy, EncodingSource, NameDecoding},
+    error::{EntryParseError, Error, FormatError, LimitExceeded, UnsupportedError, Warning},
     parse::{
-        Archive, CentralDirectoryFileHeader, EndOfCentralDirectory, EndOfCentralDirectory64Locator,
-        EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, Entry, Located,
+        normalize_name, Archive, CentralDirectoryFileHeader, DuplicateNamePolicy,
+        EndOfCentralDirectory, EndOfCentralDirectory64Locator, EndOfCentralDirectory64Record,
+        EndOfCentralDirectoryRecord, Located, Offsets, Strictness,
     },
+    progress::Progress,
 };
 
 use ownable::traits::IntoOwned;
@@ -16,6 +20,198 @@ use winnow::{
     Parser, Partial,
 };
 
+/// Options that affect how an archive is read, see [ArchiveFsm::with_options].
+///
+/// More options may be added: start from [Default::default] and use the
+/// `with_*` setters.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct ArchiveReadOptions {
+    /// Which entry [Archive::by_name] returns when several entries share a
+    /// name, or whether reading such an archive should fail.
+    pub duplicate_names: DuplicateNamePolicy,
+
+    /// Whether the encoding of names and comments is detected, hinted at or
+    /// forced. Which one was used for each entry is given by
+    /// [Entry::name_decoding](crate::parse::Entry::name_decoding).
+    pub encoding: EncodingPolicy,
+
+    /// How the encoding of names and comments is detected, when it's not
+    /// UTF-8.
+    pub encoding_detection: EncodingDetection,
+
+    /// Whether to keep each entry's central directory record verbatim, in
+    /// [Entry::raw_central_record](crate::parse::Entry::raw_central_record),
+    /// for forensic purposes: zip readers disagree on many details, and
+    /// looking at the actual bytes is the only way to tell what a given
+    /// archive is up to.
+    pub keep_raw_records: bool,
+
+    /// Whether problems that can be worked around, like invalid extra
+    /// fields or a wrong number of entries in the end of central directory
+    /// record, make reading fail. Either way, the ones that didn't are
+    /// listed by [Archive::warnings].
+    pub strictness: Strictness,
+
+    /// Whether central directory records that can't be turned into entries,
+    /// for example because of invalid extra fields, are set aside rather
+    /// than failing the whole read. They're listed by
+    /// [Archive::entries_with_errors], and left out of [Archive::entries].
+    ///
+    /// This only covers records that could be parsed at all: the central
+    /// directory has no way to resynchronize after a truncated record.
+    pub skip_invalid_entries: bool,
+
+    /// Sizes of the parts of a split archive, in order, when it's read as
+    /// the concatenation of all of them: offsets in split archives are
+    /// relative to the part they point into, so they can't be resolved
+    /// without this. `MultiFileSource`, in rc-zip-sync, sets it.
+    ///
+    /// Empty for archives that aren't split, in which case the disk
+    /// numbers found in the archive are ignored.
+    pub disk_sizes: Vec<u64>,
+
+    /// Where the archive starts in the file, when it's known, like for a zip
+    /// file embedded in a firmware image: offsets recorded in the archive
+    /// are taken relative to it, rather than guessed from where the central
+    /// directory is found, see [Archive::global_offset].
+    ///
+    /// The end of central directory record is still looked for at the end
+    /// of the file, which may have some data after the archive.
+    pub base_offset: Option<u64>,
+
+    /// Maximum number of entries the central directory may have, whatever
+    /// the end of central directory record claims: the whole central
+    /// directory is kept in memory while it's read, so archives claiming
+    /// millions of entries use a lot of it.
+    pub max_entries: Option<u64>,
+
+    /// Maximum size of the central directory, in bytes, for the same
+    /// reason as [Self::max_entries].
+    pub max_central_directory_size: Option<u64>,
+
+    /// What's told about progress as the central directory is read, see
+    /// [Progress::central_directory].
+    pub progress: Option<Arc<dyn Progress>>,
+}
+
+impl ArchiveReadOptions {
+    /// Sets [Self::duplicate_names]
+    pub fn with_duplicate_names(mut self, duplicate_names: DuplicateNamePolicy) -> Self {
+        self.duplicate_names = duplicate_names;
+        self
+    }
+
+    /// Sets [Self::encoding]
+    pub fn with_encoding(mut self, encoding: EncodingPolicy) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets [Self::encoding_detection]
+    pub fn with_encoding_detection(mut self, encoding_detection: EncodingDetection) -> Self {
+        self.encoding_detection = encoding_detection;
+        self
+    }
+
+    /// Sets [Self::keep_raw_records]
+    pub fn with_keep_raw_records(mut self, keep_raw_records: bool) -> Self {
+        self.keep_raw_records = keep_raw_records;
+        self
+    }
+
+    /// Sets [Self::strictness]
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Sets [Self::skip_invalid_entries]
+    pub fn with_skip_invalid_entries(mut self, skip_invalid_entries: bool) -> Self {
+        self.skip_invalid_entries = skip_invalid_entries;
+        self
+    }
+
+    /// Sets [Self::disk_sizes]
+    pub fn with_disk_sizes(mut self, disk_sizes: Vec<u64>) -> Self {
+        self.disk_sizes = disk_sizes;
+        self
+    }
+
+    /// Sets [Self::base_offset]
+    pub fn with_base_offset(mut self, base_offset: u64) -> Self {
+        self.base_offset = Some(base_offset);
+        self
+    }
+
+    /// Sets [Self::max_entries]
+    pub fn with_max_entries(mut self, max_entries: u64) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Sets [Self::max_central_directory_size]
+    pub fn with_max_central_directory_size(mut self, max_central_directory_size: u64) -> Self {
+        self.max_central_directory_size = Some(max_central_directory_size);
+        self
+    }
+
+    /// Sets [Self::progress]
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Where the given disk starts in the concatenation of all parts, see
+    /// [Self::disk_sizes], or `None` if there's no such disk.
+    fn disk_start(&self, disk: u32) -> Option<u64> {
+        if self.disk_sizes.is_empty() {
+            return Some(0);
+        }
+        let disk = usize::try_from(disk).ok()?;
+        self.disk_sizes
+            .get(..disk)
+            .filter(|_| disk < self.disk_sizes.len())
+            .map(|sizes| sizes.iter().sum())
+    }
+
+    /// What to add to offsets recorded in the archive, relative to the start
+    /// of the given disk, to get offsets in the file: `global_offset` plus
+    /// where that disk starts. `None` if there's no such disk, or it
+    /// overflows.
+    ///
+    /// All recorded offsets go through this.
+    pub(crate) fn offset_adjustment(&self, disk: u32, global_offset: i64) -> Option<i64> {
+        i64::try_from(self.disk_start(disk)?)
+            .ok()?
+            .checked_add(global_offset)
+    }
+
+    /// The global offset given by [Self::base_offset], if any.
+    pub(crate) fn base_global_offset(&self) -> Option<i64> {
+        self.base_offset
+            .and_then(|offset| i64::try_from(offset).ok())
+    }
+
+    /// Checks the number of entries and the size of the central directory
+    /// against [Self::max_entries] and [Self::max_central_directory_size].
+    fn check_directory(&self, entries: u64, size: u64) -> Result<(), Error> {
+        if let Some(limit) = self.max_entries {
+            if entries > limit {
+                return Err(Error::LimitExceeded(LimitExceeded::EntryCount { limit }));
+            }
+        }
+        if let Some(limit) = self.max_central_directory_size {
+            if size > limit {
+                return Err(Error::LimitExceeded(LimitExceeded::CentralDirectorySize {
+                    limit,
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// [ArchiveFsm] parses a valid zip archive into an [Archive]. In particular, this struct finds
 /// an end of central directory record, parses the entire central directory, detects text encoding,
 /// and normalizes metadata.
@@ -41,6 +237,9 @@ pub struct ArchiveFsm {
 
     /// Buffer for reading data from the file
     buffer: Buffer,
+
+    /// Options passed to [Self::with_options]
+    options: ArchiveReadOptions,
 }
 
 #[derive(Default)]
@@ -61,6 +260,10 @@ enum State {
     /// Reading the zip64 end of central directory record.
     ReadEocd64 {
         eocdr64_offset: u64,
+        /// where the record would be if it's right before the locator, as it
+        /// usually is, tried if it's not at `eocdr64_offset`: the locator's
+        /// offset doesn't account for data prepended to the archive.
+        fallback_offset: Option<u64>,
         eocdr: Located<EndOfCentralDirectoryRecord<'static>>,
     },
 
@@ -68,6 +271,10 @@ enum State {
     ReadCentralDirectory {
         eocd: EndOfCentralDirectory<'static>,
         directory_headers: Vec<CentralDirectoryFileHeader<'static>>,
+        /// verbatim bytes of each header, if [ArchiveReadOptions::keep_raw_records]
+        raw_headers: Vec<Vec<u8>>,
+        /// size of the headers read so far
+        directory_len: u64,
     },
 
     #[default]
@@ -75,13 +282,24 @@ enum State {
 }
 
 impl ArchiveFsm {
-    /// This should be > 65KiB, because the section at the end of the
-    /// file that we check for end of central directory record is 65KiB.
+    /// This should be larger than the section at the end of the file that
+    /// we check for the end of central directory record, see
+    /// [Self::MAX_TRAILING_DATA].
     const DEFAULT_BUFFER_SIZE: usize = 256 * 1024;
 
+    /// How much data after the end of central directory record is tolerated:
+    /// the search for it goes back this far past the longest possible record.
+    const MAX_TRAILING_DATA: usize = 64 * 1024;
+
     /// Create a new archive reader with a specified file size.
     pub fn new(size: u64) -> Self {
-        let haystack_size: u64 = 65 * 1024;
+        Self::with_options(size, Default::default())
+    }
+
+    /// Create a new archive reader with a specified file size and options.
+    pub fn with_options(size: u64, options: ArchiveReadOptions) -> Self {
+        let haystack_size =
+            (EndOfCentralDirectoryRecord::MAX_LENGTH + Self::MAX_TRAILING_DATA) as u64;
         let haystack_size = if size < haystack_size {
             size
         } else {
@@ -92,6 +310,7 @@ impl ArchiveFsm {
             size,
             buffer: Buffer::with_capacity(Self::DEFAULT_BUFFER_SIZE),
             state: State::ReadEocd { haystack_size },
+            options,
         }
     }
 
@@ -141,16 +360,27 @@ impl ArchiveFsm {
                     EndOfCentralDirectoryRecord::find_in_block(haystack)
                 };
                 match res {
-                    None => Err(FormatError::DirectoryEndSignatureNotFound.into()),
+                    None => {
+                        trace!(
+                            searched = haystack_size,
+                            "ReadEocd | no end of central directory record"
+                        );
+                        Err(FormatError::DirectoryEndSignatureNotFound.into())
+                    }
                     Some(eocdr) => {
+                        let mut eocdr = eocdr.into_owned();
+                        self.buffer.reset();
+                        eocdr.offset += self.size - haystack_size;
+                        let record_len = EndOfCentralDirectoryRecord::MAX_LENGTH
+                            - u16::MAX as usize
+                            + eocdr.inner.comment.len();
                         trace!(
                             ?eocdr,
                             size = self.size,
+                            searched = self.size - eocdr.offset,
+                            trailing = self.size - eocdr.offset - record_len as u64,
                             "ReadEocd | found end of central directory record"
                         );
-                        let mut eocdr = eocdr.into_owned();
-                        self.buffer.reset();
-                        eocdr.offset += self.size - haystack_size;
 
                         if eocdr.offset < EndOfCentralDirectory64Locator::LENGTH as u64 {
                             // no room for an EOCD64 locator, definitely not a zip64 file
@@ -161,8 +391,10 @@ impl ArchiveFsm {
                             );
                             transition!(self.state => (S::ReadEocd { .. }) {
                                 S::ReadCentralDirectory {
-                                    eocd: EndOfCentralDirectory::new(self.size, eocdr, None)?,
+                                    eocd: EndOfCentralDirectory::new(self.size, eocdr, None, &self.options)?,
                                     directory_headers: vec![],
+                                    raw_headers: vec![],
+                                    directory_len: 0,
                                 }
                             });
                             Ok(FsmResult::Continue(self))
@@ -194,8 +426,10 @@ impl ArchiveFsm {
                         self.buffer.reset();
                         transition!(self.state => (S::ReadEocd64Locator { eocdr }) {
                             S::ReadCentralDirectory {
-                                eocd: EndOfCentralDirectory::new(self.size, eocdr, None)?,
+                                eocd: EndOfCentralDirectory::new(self.size, eocdr, None, &self.options)?,
                                 directory_headers: vec![],
+                                raw_headers: vec![],
+                                directory_len: 0,
                             }
                         });
                         Ok(FsmResult::Continue(self))
@@ -205,10 +439,22 @@ impl ArchiveFsm {
                             ?locator,
                             "ReadEocd64Locator | found zip64 end of central directory locator"
                         );
+                        let global_offset = self.options.base_global_offset().unwrap_or(0);
+                        let adjustment = self
+                            .options
+                            .offset_adjustment(locator.dir_disk_number, global_offset)
+                            .ok_or(FormatError::Directory64EndRecordInvalid)?;
+                        let eocdr64_offset = Offsets::adjust(locator.directory_offset, adjustment)?;
+                        let len = EndOfCentralDirectory64Locator::LENGTH
+                            + EndOfCentralDirectory64Record::LENGTH;
                         self.buffer.reset();
                         transition!(self.state => (S::ReadEocd64Locator { eocdr }) {
                             S::ReadEocd64 {
-                                eocdr64_offset: locator.directory_offset,
+                                eocdr64_offset,
+                                fallback_offset: eocdr
+                                    .offset
+                                    .checked_sub(len as u64)
+                                    .filter(|&offset| offset != eocdr64_offset),
                                 eocdr,
                             }
                         });
@@ -224,6 +470,26 @@ impl ArchiveFsm {
                         Ok(FsmResult::Continue(self))
                     }
                     Err(ErrMode::Backtrack(_)) | Err(ErrMode::Cut(_)) => {
+                        if let S::ReadEocd64 {
+                            fallback_offset: Some(offset),
+                            ..
+                        } = self.state
+                        {
+                            trace!(
+                                %offset,
+                                "ReadEocd64 | no record at the recorded offset, trying right before the locator"
+                            );
+                            self.buffer.reset();
+                            transition!(self.state => (S::ReadEocd64 { eocdr, .. }) {
+                                S::ReadEocd64 {
+                                    eocdr64_offset: offset,
+                                    fallback_offset: None,
+                                    eocdr,
+                                }
+                            });
+                            return Ok(FsmResult::Continue(self));
+                        }
+
                         // at this point, we really expected to have a zip64 end
                         // of central directory record, so, we want to propagate
                         // that error.
@@ -231,13 +497,15 @@ impl ArchiveFsm {
                     }
                     Ok((_, eocdr64)) => {
                         self.buffer.reset();
-                        transition!(self.state => (S::ReadEocd64 { eocdr, eocdr64_offset }) {
+                        transition!(self.state => (S::ReadEocd64 { eocdr, eocdr64_offset, .. }) {
                             S::ReadCentralDirectory {
                                 eocd: EndOfCentralDirectory::new(self.size, eocdr, Some(Located {
                                     offset: eocdr64_offset,
                                     inner: eocdr64
-                                }))?,
+                                }), &self.options)?,
                                 directory_headers: vec![],
+                                raw_headers: vec![],
+                                    directory_len: 0,
                             }
                         });
                         Ok(FsmResult::Continue(self))
@@ -247,7 +515,14 @@ impl ArchiveFsm {
             S::ReadCentralDirectory {
                 ref eocd,
                 ref mut directory_headers,
+                ref mut raw_headers,
+                ref mut directory_len,
             } => {
+                // the end of central directory record may not tell the
+                // truth, this is checked again as headers are read
+                self.options
+                    .check_directory(eocd.directory_records(), eocd.directory_size())?;
+
                 trace!(
                     "ReadCentralDirectory | process(), available: {}",
                     self.buffer.available_data()
@@ -260,6 +535,7 @@ impl ArchiveFsm {
                     "initial offset & len"
                 );
                 'read_headers: while !input.is_empty() {
+                    let start = input.as_bytes().offset_from(&self.buffer.data());
                     match CentralDirectoryFileHeader::parser.parse_next(&mut input) {
                         Ok(dh) => {
                             trace!(
@@ -269,7 +545,14 @@ impl ArchiveFsm {
                                 "ReadCentralDirectory | parsed directory header"
                             );
                             valid_consumed = input.as_bytes().offset_from(&self.buffer.data());
+                            if self.options.keep_raw_records {
+                                raw_headers
+                                    .push(self.buffer.data()[start..valid_consumed].to_vec());
+                            }
                             directory_headers.push(dh.into_owned());
+                            *directory_len += (valid_consumed - start) as u64;
+                            self.options
+                                .check_directory(directory_headers.len() as u64, *directory_len)?;
                         }
                         Err(ErrMode::Incomplete(_needed)) => {
                             // need more data to read the full header
@@ -285,7 +568,15 @@ impl ArchiveFsm {
                             let expected_records = directory_headers.len() as u16;
                             let actual_records = eocd.directory_records() as u16;
 
+                            let mut warnings = Vec::new();
                             if expected_records != actual_records {
+                                if directory_headers.is_empty() && eocd.requires_strong_encryption()
+                                {
+                                    return Err(
+                                        UnsupportedError::StrongEncryption { name: None }.into()
+                                    );
+                                }
+
                                 tracing::trace!(
                                     "error while reading central records: we read {} records, but EOCD announced {}. the last failed with: {err:?} (display: {err}). at that point, input had length {}",
                                     expected_records,
@@ -294,12 +585,18 @@ impl ArchiveFsm {
                                 );
 
                                 // if we read the wrong number of directory entries,
-                                // error out.
-                                return Err(FormatError::InvalidCentralRecord {
-                                    expected: expected_records,
-                                    actual: actual_records,
+                                // error out, unless told not to
+                                if self.options.strictness == Strictness::Strict {
+                                    return Err(FormatError::InvalidCentralRecord {
+                                        expected: expected_records,
+                                        actual: actual_records,
+                                    }
+                                    .into());
                                 }
-                                .into());
+                                warnings.push(Warning::WrongEntryCount {
+                                    expected: eocd.directory_records(),
+                                    actual: directory_headers.len() as u64,
+                                });
                             }
 
                             let mut detectorng = chardetng::EncodingDetector::new();
@@ -332,43 +629,117 @@ impl ArchiveFsm {
                                 }
                             }
 
-                            let encoding = {
-                                if all_utf8 {
-                                    Encoding::Utf8
-                                } else {
-                                    let encoding = detectorng.guess(None, true);
-                                    if encoding == encoding_rs::SHIFT_JIS {
-                                        // well hold on, sometimes Codepage 437 is detected as
-                                        // Shift-JIS by chardetng. If we have any characters
-                                        // that aren't valid DOS file names, then okay it's probably
-                                        // Shift-JIS. Otherwise, assume it's CP437.
-                                        if had_suspicious_chars_for_cp437 {
-                                            Encoding::ShiftJis
-                                        } else {
-                                            Encoding::Cp437
-                                        }
-                                    } else if encoding == encoding_rs::UTF_8 {
-                                        Encoding::Utf8
-                                    } else {
-                                        Encoding::Cp437
-                                    }
+                            let given = |encoding| NameDecoding {
+                                encoding,
+                                source: EncodingSource::Given,
+                                confident: true,
+                            };
+                            let decoding = match self.options.encoding {
+                                EncodingPolicy::Force(encoding) => given(encoding),
+                                _ if all_utf8 => NameDecoding {
+                                    encoding: Encoding::Utf8,
+                                    source: EncodingSource::Utf8Assumed,
+                                    confident: true,
+                                },
+                                EncodingPolicy::Hint(encoding)
+                                    if directory_headers
+                                        .iter()
+                                        .filter(|fh| fh.is_non_utf8())
+                                        .all(|fh| {
+                                            encoding.decode(&fh.name).is_ok()
+                                                && encoding.decode(&fh.comment).is_ok()
+                                        }) =>
+                                {
+                                    given(encoding)
+                                }
+                                _ => {
+                                    let detection = &self.options.encoding_detection;
+                                    let (guess, confident) = detectorng
+                                        .guess_assess(None, detection.allows(Encoding::Utf8));
+                                    detection.decide(
+                                        guess,
+                                        confident,
+                                        had_suspicious_chars_for_cp437,
+                                    )
                                 }
                             };
+                            let encoding = decoding.encoding;
+
+                            let global_offset = eocd.global_offset;
+                            let mut entries = Vec::with_capacity(directory_headers.len());
+                            let mut invalid_entries = Vec::new();
+                            let mut raw_headers = raw_headers.drain(..);
+                            let mut offset = eocd.directory_offset();
+                            for (index, dh) in directory_headers.iter().enumerate() {
+                                let record_offset = offset;
+                                offset = Offsets::add(offset, dh.record_len())?;
+                                let raw = raw_headers.next();
+
+                                // header offsets are relative to the start of
+                                // their own disk
+                                let entry = self
+                                    .options
+                                    .offset_adjustment(dh.disk_nbr_start.into(), global_offset)
+                                    .ok_or_else(|| FormatError::InvalidHeaderOffset.into())
+                                    .and_then(|adjustment| {
+                                        dh.as_entry_with(
+                                            decoding,
+                                            adjustment,
+                                            self.options.strictness,
+                                            &mut warnings,
+                                        )
+                                    });
+                                match entry {
+                                    Ok(mut entry) => {
+                                        entry.raw_central_record = raw.map(|inner| Located {
+                                            offset: record_offset,
+                                            inner,
+                                        });
+                                        entries.push(entry);
+                                    }
+                                    Err(error) if self.options.skip_invalid_entries => {
+                                        trace!(%index, %error, "skipping invalid entry");
+                                        invalid_entries.push(EntryParseError {
+                                            index,
+                                            offset: record_offset,
+                                            name: String::from_utf8_lossy(&dh.name).into_owned(),
+                                            error,
+                                        });
+                                    }
+                                    Err(error) => return Err(error),
+                                }
+                            }
 
-                            let global_offset = eocd.global_offset as u64;
-                            let entries: Result<Vec<Entry>, Error> = directory_headers
-                                .iter()
-                                .map(|x| x.as_entry(encoding, global_offset))
-                                .collect();
-                            let entries = entries?;
+                            if self.options.duplicate_names == DuplicateNamePolicy::Error {
+                                // names that only differ before normalization
+                                // are extracted to the same path
+                                let mut names = HashSet::new();
+                                for entry in &entries {
+                                    if !names.insert(normalize_name(&entry.name)) {
+                                        return Err(FormatError::DuplicateEntryName {
+                                            name: entry.name.clone(),
+                                        }
+                                        .into());
+                                    }
+                                }
+                            }
 
                             let comment = encoding.decode(eocd.comment())?;
+                            if let Some(progress) = &self.options.progress {
+                                progress.central_directory(*directory_len, eocd.directory_size());
+                            }
 
                             return Ok(FsmResult::Done(Archive {
                                 size: self.size,
+                                directory_offset: eocd.directory_offset(),
+                                global_offset,
                                 comment,
+                                raw_comment: eocd.comment().to_vec(),
                                 entries,
+                                invalid_entries,
                                 encoding,
+                                duplicate_names: self.options.duplicate_names,
+                                warnings,
                             }));
                         }
                     }
@@ -376,6 +747,9 @@ impl ArchiveFsm {
                 let consumed = valid_consumed;
                 tracing::trace!(%consumed, "ReadCentralDirectory total consumed");
                 self.buffer.consume(consumed);
+                if let Some(progress) = &self.options.progress {
+                    progress.central_directory(*directory_len, eocd.directory_size());
+                }
 
                 // need more data
                 Ok(FsmResult::Continue(self))
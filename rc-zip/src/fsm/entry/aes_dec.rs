@@ -0,0 +1,196 @@
+use std::cmp;
+
+use aes::{Aes128, Aes192, Aes256};
+use ctr::{
+    cipher::{KeyIvInit, StreamCipher},
+    Ctr128LE,
+};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::{
+    error::{Error, FormatError},
+    parse::{AesStrength, Method},
+};
+
+use super::{AnyDecompressor, DecompressOutcome, Decompressor, HasMoreInput};
+
+/// Length of the password verification value, which follows the salt
+const VERIFIER_LEN: usize = 2;
+
+/// Length of the authentication code, which follows the encrypted data
+const AUTH_CODE_LEN: usize = 10;
+
+/// How much decrypted data we hold on to, until the inner decompressor
+/// takes it
+const MAX_PENDING: usize = 64 * 1024;
+
+/// Decrypts WinZip AES-encrypted data (see Appendix E of the appnote), and
+/// feeds it to the decompressor for the method it was compressed with.
+pub(crate) struct AesDec {
+    inner: AnyDecompressor,
+    password: Vec<u8>,
+    key_len: usize,
+
+    /// Salt and password verification value, as they're read
+    header: Vec<u8>,
+
+    /// Known once the header has been read
+    keys: Option<Keys>,
+
+    /// Encrypted bytes left to read, not counting the authentication code
+    remaining: u64,
+
+    /// Decrypted data the inner decompressor hasn't taken yet
+    pending: Vec<u8>,
+
+    /// Authentication code, as it's read
+    auth_code: Vec<u8>,
+}
+
+struct Keys {
+    cipher: Box<dyn StreamCipher + Send + Sync>,
+    mac: Hmac<Sha1>,
+}
+
+impl AesDec {
+    pub(crate) fn new(
+        strength: AesStrength,
+        compressed_size: u64,
+        password: &[u8],
+        inner: AnyDecompressor,
+    ) -> Result<Self, Error> {
+        let key_len = match strength.key_bits() {
+            Some(bits) => bits as usize / 8,
+            None => return Err(Error::method_not_supported(Method::Aex)),
+        };
+        // the salt is half as long as the key
+        let header_len = key_len / 2 + VERIFIER_LEN;
+        let remaining = compressed_size
+            .checked_sub((header_len + AUTH_CODE_LEN) as u64)
+            .ok_or_else(|| {
+                dec_err(format!(
+                    "compressed size {compressed_size} too small for AES-{} header and trailer",
+                    key_len * 8
+                ))
+            })?;
+
+        Ok(Self {
+            inner,
+            password: password.to_vec(),
+            key_len,
+            header: Vec::with_capacity(header_len),
+            keys: None,
+            remaining,
+            pending: Vec::new(),
+            auth_code: Vec::with_capacity(AUTH_CODE_LEN),
+        })
+    }
+
+    fn header_len(&self) -> usize {
+        self.key_len / 2 + VERIFIER_LEN
+    }
+
+    /// Derives the encryption and authentication keys from the password and
+    /// salt, and checks the password against the verification value.
+    fn derive_keys(&self) -> Result<Keys, Error> {
+        let (salt, verifier) = self.header.split_at(self.key_len / 2);
+
+        let mut derived = [0u8; 2 * 32 + VERIFIER_LEN];
+        let derived = &mut derived[..2 * self.key_len + VERIFIER_LEN];
+        pbkdf2::pbkdf2_hmac::<Sha1>(&self.password, salt, 1000, derived);
+        let (enc_key, rest) = derived.split_at(self.key_len);
+        let (auth_key, expected_verifier) = rest.split_at(self.key_len);
+
+        if verifier != expected_verifier {
+            return Err(Error::WrongPassword);
+        }
+
+        // the counter is little-endian and starts at 1
+        let iv = 1u128.to_le_bytes();
+        let cipher: Box<dyn StreamCipher + Send + Sync> = match self.key_len {
+            16 => Box::new(Ctr128LE::<Aes128>::new(enc_key.into(), &iv.into())),
+            24 => Box::new(Ctr128LE::<Aes192>::new(enc_key.into(), &iv.into())),
+            _ => Box::new(Ctr128LE::<Aes256>::new(enc_key.into(), &iv.into())),
+        };
+        let mac = Hmac::<Sha1>::new_from_slice(auth_key).expect("HMAC takes keys of any size");
+
+        Ok(Keys { cipher, mac })
+    }
+}
+
+impl Decompressor for AesDec {
+    fn decompress(
+        &mut self,
+        mut in_buf: &[u8],
+        out: &mut [u8],
+        _has_more_input: HasMoreInput,
+    ) -> Result<DecompressOutcome, Error> {
+        let mut bytes_read = 0;
+
+        let keys = match &mut self.keys {
+            Some(keys) => keys,
+            None => {
+                let n = cmp::min(self.header_len() - self.header.len(), in_buf.len());
+                self.header.extend_from_slice(&in_buf[..n]);
+                in_buf = &in_buf[n..];
+                bytes_read += n;
+
+                if self.header.len() < self.header_len() {
+                    return Ok(DecompressOutcome {
+                        bytes_read,
+                        bytes_written: 0,
+                    });
+                }
+                self.keys.insert(self.derive_keys()?)
+            }
+        };
+
+        // the authentication code is computed over the encrypted data
+        let n = usize::try_from(self.remaining)
+            .map_or(in_buf.len(), |remaining| cmp::min(remaining, in_buf.len()));
+        let n = cmp::min(n, MAX_PENDING.saturating_sub(self.pending.len()));
+        let start = self.pending.len();
+        self.pending.extend_from_slice(&in_buf[..n]);
+        keys.mac.update(&self.pending[start..]);
+        keys.cipher.apply_keystream(&mut self.pending[start..]);
+        self.remaining -= n as u64;
+        in_buf = &in_buf[n..];
+        bytes_read += n;
+
+        let mut inner_has_more_input = HasMoreInput::Yes;
+        if self.remaining == 0 {
+            let had = self.auth_code.len();
+            let n = cmp::min(AUTH_CODE_LEN - had, in_buf.len());
+            self.auth_code.extend_from_slice(&in_buf[..n]);
+            bytes_read += n;
+
+            if self.auth_code.len() == AUTH_CODE_LEN {
+                if had < AUTH_CODE_LEN {
+                    let expected = keys.mac.clone().finalize().into_bytes();
+                    if self.auth_code[..] != expected[..AUTH_CODE_LEN] {
+                        return Err(FormatError::WrongAuthenticationCode.into());
+                    }
+                }
+                inner_has_more_input = HasMoreInput::No;
+            }
+        }
+
+        let outcome = self
+            .inner
+            .decompress(&self.pending, out, inner_has_more_input)?;
+        self.pending.drain(..outcome.bytes_read);
+
+        Ok(DecompressOutcome {
+            bytes_read,
+            bytes_written: outcome.bytes_written,
+        })
+    }
+}
+
+fn dec_err(msg: String) -> Error {
+    Error::Decompression {
+        method: Method::Aex,
+        msg,
+    }
+}
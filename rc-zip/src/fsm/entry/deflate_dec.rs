@@ -3,7 +3,10 @@ use std::cmp;
 use miniz_oxide::inflate::{
     core::{
         decompress,
-        inflate_flags::{TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_IGNORE_ADLER32},
+        inflate_flags::{
+            TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_IGNORE_ADLER32,
+            TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        },
         DecompressorOxide,
     },
     TINFLStatus,
@@ -33,6 +36,11 @@ pub(crate) struct DeflateDec {
     /// need to call miniz_oxide again to get more data.
     remain_in_internal_buffer: usize,
 
+    /// Whether nothing was decompressed yet, in which case a large enough
+    /// output buffer is decompressed into directly: miniz_oxide needs no
+    /// earlier data then, see [Self::decompress_direct].
+    direct: bool,
+
     /// The miniz_oxide decompressor state
     state: DecompressorOxide,
 }
@@ -44,6 +52,7 @@ impl Default for DeflateDec {
             out_pos: 0,
             state: DecompressorOxide::new(),
             remain_in_internal_buffer: 0,
+            direct: true,
         }
     }
 }
@@ -80,6 +89,11 @@ impl Decompressor for DeflateDec {
             flags |= TINFL_FLAG_HAS_MORE_INPUT;
         }
 
+        if self.direct && out.len() >= Self::INTERNAL_BUFFER_LENGTH {
+            return self.decompress_direct(in_buf, out, flags);
+        }
+        self.direct = false;
+
         let (status, bytes_read, bytes_written) = decompress(
             &mut self.state,
             in_buf,
@@ -90,7 +104,53 @@ impl Decompressor for DeflateDec {
         trace!(%bytes_read, %bytes_written, ?status, "decompress returned");
         outcome.bytes_read += bytes_read;
         self.remain_in_internal_buffer += bytes_written;
+        Self::check_status(status)?;
+
+        trace!("calling copy_to_out");
+        self.copy_to_out(out, &mut outcome);
+        Ok(outcome)
+    }
+}
+
+impl DeflateDec {
+    const INTERNAL_BUFFER_LENGTH: usize = 64 * 1024;
+
+    /// How far back deflate streams may refer to earlier data
+    const WINDOW_LENGTH: usize = 32 * 1024;
+
+    /// Decompresses straight into `out`, saving a copy through the internal
+    /// buffer. Later calls go through the internal buffer, which is given
+    /// the end of what was written, since the stream may refer to it.
+    fn decompress_direct(
+        &mut self,
+        in_buf: &[u8],
+        out: &mut [u8],
+        flags: u32,
+    ) -> Result<DecompressOutcome, Error> {
+        let (status, bytes_read, bytes_written) = decompress(
+            &mut self.state,
+            in_buf,
+            out,
+            0,
+            flags | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        trace!(%bytes_read, %bytes_written, ?status, "direct decompress returned");
+        Self::check_status(status)?;
+
+        if bytes_written > 0 {
+            let window = cmp::min(bytes_written, Self::WINDOW_LENGTH);
+            self.internal_buffer[..window]
+                .copy_from_slice(&out[bytes_written - window..][..window]);
+            self.out_pos = window;
+            self.direct = false;
+        }
+        Ok(DecompressOutcome {
+            bytes_read,
+            bytes_written,
+        })
+    }
 
+    fn check_status(status: TINFLStatus) -> Result<(), Error> {
         match status {
             TINFLStatus::FailedCannotMakeProgress => {
                 return Err(Error::Decompression { method: Method::Deflate, msg: "Failed to make progress: more input data was expected, but the caller indicated there was no more data, so the input stream is likely truncated".to_string() })
@@ -115,15 +175,8 @@ impl Decompressor for DeflateDec {
 				// the caller will keep calling
 			},
         }
-
-        trace!("calling copy_to_out");
-        self.copy_to_out(out, &mut outcome);
-        Ok(outcome)
+        Ok(())
     }
-}
-
-impl DeflateDec {
-    const INTERNAL_BUFFER_LENGTH: usize = 64 * 1024;
 
     fn copy_to_out(&mut self, mut out: &mut [u8], outcome: &mut DecompressOutcome) {
         // as long as there's room in out_buf and we have remaining data in the
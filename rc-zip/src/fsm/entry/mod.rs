@@ -1,4 +1,4 @@
-use std::cmp;
+use std::{cmp, sync::Arc};
 
 use oval::Buffer;
 use tracing::trace;
@@ -25,9 +25,13 @@ mod lzma_dec;
 #[cfg(feature = "zstd")]
 mod zstd_dec;
 
+#[cfg(feature = "aes")]
+mod aes_dec;
+
 use crate::{
-    error::{Error, FormatError, UnsupportedError},
-    parse::{DataDescriptorRecord, Entry, LocalFileHeader, Method},
+    error::{Error, FormatError, LimitExceeded, UnsupportedError},
+    parse::{DataDescriptorRecord, Entry, HeaderMismatch, LocalFileHeader, Method, MethodDetails},
+    progress::Progress,
 };
 
 use super::FsmResult;
@@ -48,6 +52,10 @@ enum State {
         /// Whether the entry is zip64 (because its compressed size or uncompressed size is u32::MAX)
         is_zip64: bool,
 
+        /// Whether the compressed size is still unknown: the data descriptor
+        /// has to be found to know where the data ends
+        find_data_descriptor: bool,
+
         /// Amount of bytes we've fed to the decompressor
         compressed_bytes: u64,
 
@@ -74,38 +82,220 @@ enum State {
         metrics: EntryReadMetrics,
 
         /// The data descriptor for this entry, if any
-        descriptor: Option<DataDescriptorRecord>,
+        descriptor: Option<EntryTrailer>,
     },
 
     #[default]
     Transition,
 }
 
+/// Limits on reading an entry, to protect against zip bombs: archives that
+/// are small, but decompress to far more data than expected, possibly by
+/// nesting archives in archives. See [EntryFsm::with_limits].
+///
+/// Exceeding any of them fails with [Error::LimitExceeded]. None are set by
+/// default.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DecompressionLimits {
+    /// Maximum number of bytes an entry may decompress to, whatever its
+    /// uncompressed size claims.
+    pub max_output: Option<u64>,
+
+    /// Maximum ratio of an entry's decompressed size to its compressed size.
+    /// For entries whose compressed size is only known from their data
+    /// descriptor, it's checked against the compressed data read so far.
+    pub max_ratio: Option<u64>,
+
+    /// Maximum [Self::depth] of the archives entries are read from.
+    pub max_depth: Option<u32>,
+
+    /// How deeply the archive being read is nested in other archives, 0 for
+    /// the outermost one: when scanning archives found in entries, read them
+    /// with [Self::nested].
+    pub depth: u32,
+}
+
+impl DecompressionLimits {
+    /// Returns the limits to read an archive found in an entry read with
+    /// these ones, one level deeper. Fails if that's deeper than
+    /// [Self::max_depth].
+    pub fn nested(self) -> Result<Self, Error> {
+        let nested = Self {
+            depth: self.depth + 1,
+            ..self
+        };
+        nested.check_depth()?;
+        Ok(nested)
+    }
+
+    fn check_depth(&self) -> Result<(), Error> {
+        match self.max_depth {
+            Some(limit) if self.depth > limit => {
+                Err(Error::LimitExceeded(LimitExceeded::Depth { limit }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks what was decompressed so far against the limits
+    fn check(&self, uncompressed_bytes: u64, compressed_size: u64) -> Result<(), Error> {
+        if let Some(limit) = self.max_output {
+            if uncompressed_bytes > limit {
+                return Err(Error::LimitExceeded(LimitExceeded::OutputSize { limit }));
+            }
+        }
+        if let Some(limit) = self.max_ratio {
+            if uncompressed_bytes > compressed_size.saturating_mul(limit) {
+                return Err(Error::LimitExceeded(LimitExceeded::Ratio { limit }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the CRC-32 of an entry is checked once it's read, see
+/// [EntryFsm::with_verification].
+///
+/// Sizes are checked whatever this is. AE-2 encrypted entries, which don't
+/// store their CRC-32, are checked with their authentication code instead,
+/// unless this is [Verification::Skip].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Verification {
+    /// The CRC-32 must match the one recorded in the archive, even if that's
+    /// zero: only empty entries are then expected to have that checksum.
+    Require,
+
+    /// The CRC-32 must match the one recorded in the archive, unless that's
+    /// zero, as left by some writers that don't compute it.
+    #[default]
+    IfPresent,
+
+    /// The CRC-32 isn't computed nor checked, e.g. because the data is
+    /// already covered by another checksum.
+    Skip,
+}
+
+/// How the CRC-32 of an entry was verified, as told to
+/// [Progress::entry_done].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VerificationStatus {
+    /// The CRC-32 matched the one recorded in the archive, or, for AE-2
+    /// entries, the authentication code did.
+    Verified,
+
+    /// No CRC-32 was recorded in the archive, so it couldn't be checked,
+    /// with [Verification::IfPresent].
+    NoChecksum,
+
+    /// The CRC-32 wasn't checked, with [Verification::Skip].
+    Skipped,
+}
+
+/// The data descriptor that followed an entry's data, as found once the
+/// entry is read, see [EntryFsm::process].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EntryTrailer {
+    /// The CRC-32 and sizes it recorded, as is: they may disagree with the
+    /// entry's, see [Entry::crc32] and [Entry::uncompressed_size].
+    pub descriptor: DataDescriptorRecord,
+
+    /// Whether it started with its optional signature, `PK\x07\x08`
+    pub has_signature: bool,
+
+    /// Whether its sizes were 64-bit
+    pub is_zip64: bool,
+}
+
 /// A state machine that can parse a zip entry
 pub struct EntryFsm {
     state: State,
     entry: Option<Entry>,
+    /// Whether the entry's CRC-32 and sizes are only known from its data
+    /// descriptor, see [Self::process_till_header]
+    sizes_from_descriptor: bool,
+    /// How the local header differs from the central directory, once it's
+    /// been read, see [Self::header_mismatches]
+    mismatches: Option<Vec<HeaderMismatch>>,
     buffer: Buffer,
+    limits: DecompressionLimits,
+    verification: Verification,
+    progress: Option<Arc<dyn Progress>>,
+    #[cfg(feature = "aes")]
+    password: Option<Vec<u8>>,
 }
 
 impl EntryFsm {
-    /// Create a new state machine for decompressing a zip entry
-    pub fn new(entry: Option<Entry>, buffer: Option<Buffer>) -> Self {
-        const BUF_CAPACITY: usize = 256 * 1024;
+    /// Size of the buffer [Self::new] allocates when it isn't given one
+    pub const DEFAULT_BUFFER_SIZE: usize = 256 * 1024;
+
+    /// Smallest buffer [Self::new] accepts. Buffers grow when a local header
+    /// or data descriptor doesn't fit in them, so this is only a floor.
+    pub const MIN_BUFFER_SIZE: usize = 1024;
+
+    /// Smallest output buffer entry data is read straight into, see
+    /// [Self::direct_read_len]
+    pub const DIRECT_READ_MIN: usize = 64 * 1024;
 
+    /// Create a new state machine for decompressing a zip entry. It reads
+    /// into `buffer` if given, which may hold data already read from the
+    /// start of the entry, or allocates one of [Self::DEFAULT_BUFFER_SIZE].
+    ///
+    /// A buffer can be reused across entries, see [Self::into_buffer]: that
+    /// saves an allocation per entry, which adds up for many small ones.
+    pub fn new(entry: Option<Entry>, buffer: Option<Buffer>) -> Self {
         Self {
             state: State::ReadLocalHeader,
             entry,
+            sizes_from_descriptor: false,
+            mismatches: None,
             buffer: match buffer {
                 Some(buffer) => {
-                    assert!(buffer.capacity() >= BUF_CAPACITY, "buffer too small");
+                    assert!(
+                        buffer.capacity() >= Self::MIN_BUFFER_SIZE,
+                        "buffer too small"
+                    );
                     buffer
                 }
-                None => Buffer::with_capacity(BUF_CAPACITY),
+                None => Buffer::with_capacity(Self::DEFAULT_BUFFER_SIZE),
             },
+            limits: Default::default(),
+            verification: Default::default(),
+            progress: None,
+            #[cfg(feature = "aes")]
+            password: None,
         }
     }
 
+    /// Sets the password used to decrypt the entry, if it's AES-encrypted
+    /// (see [MethodDetails::Aes]). Without one, reading such an entry fails
+    /// with [Error::PasswordRequired].
+    #[cfg(feature = "aes")]
+    pub fn with_password(mut self, password: &[u8]) -> Self {
+        self.password = Some(password.to_vec());
+        self
+    }
+
+    /// Sets limits on what reading the entry may produce, see
+    /// [DecompressionLimits].
+    pub fn with_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets whether the entry's CRC-32 is checked, see [Verification]. It's
+    /// checked only if the archive records one by default.
+    pub fn with_verification(mut self, verification: Verification) -> Self {
+        self.verification = verification;
+        self
+    }
+
+    /// Sets what's told about the entry's progress as it's decompressed,
+    /// see [Progress].
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
     /// If this returns true, the caller should read data from into
     /// [Self::space] — without forgetting to call [Self::fill] with the number
     /// of bytes written.
@@ -125,6 +315,12 @@ impl EntryFsm {
     /// Like `process`, but only processes the header. If this returns
     /// `Ok(None)`, the caller should read more data and call this function
     /// again.
+    ///
+    /// Without an entry from the central directory, the entry is read from
+    /// its local header. If that has a data descriptor and no sizes, as
+    /// written by streaming writers, the end of the data is found by looking
+    /// for a data descriptor whose compressed size matches, and the CRC-32
+    /// and sizes of the entry returned here are left to zero.
     pub fn process_till_header(&mut self) -> Result<Option<&Entry>, Error> {
         match &self.state {
             State::ReadLocalHeader => {
@@ -139,6 +335,16 @@ impl EntryFsm {
         Ok(self.entry.as_ref())
     }
 
+    /// Returns the fields the entry's local header and its central directory
+    /// record disagree on, see [LocalFileHeader::mismatches].
+    ///
+    /// This is `None` until the local header has been read, see
+    /// [Self::process_till_header], and when there's no central directory
+    /// record to compare it with.
+    pub fn header_mismatches(&self) -> Option<&[HeaderMismatch]> {
+        self.mismatches.as_deref()
+    }
+
     fn internal_process_local_header(&mut self) -> Result<bool, Error> {
         assert!(
             matches!(self.state, State::ReadLocalHeader),
@@ -150,18 +356,42 @@ impl EntryFsm {
             Ok(header) => {
                 let consumed = input.as_bytes().offset_from(&self.buffer.data());
                 tracing::trace!(local_file_header = ?header, consumed, "parsed local file header");
-                let decompressor = AnyDecompressor::new(
-                    header.method,
-                    self.entry.as_ref().map(|entry| entry.uncompressed_size),
-                )?;
-
-                if self.entry.is_none() {
-                    self.entry = Some(header.as_entry()?);
+                let uncompressed_size = self.entry.as_ref().map(|entry| entry.uncompressed_size);
+                if let Some(entry) = &self.entry {
+                    let mismatches = header.mismatches(entry);
+                    if !mismatches.is_empty() {
+                        trace!(name = %entry.name, ?mismatches, "local header mismatches");
+                    }
+                    self.mismatches = Some(mismatches);
+                } else {
+                    let entry = header.as_entry()?;
+                    self.sizes_from_descriptor =
+                        header.has_data_descriptor() && entry.compressed_size == 0;
+                    if self.sizes_from_descriptor && entry.is_encrypted() {
+                        // the size is needed to find where the authentication
+                        // code starts
+                        return Err(
+                            UnsupportedError::StreamingEncryptedEntry { name: entry.name }.into(),
+                        );
+                    }
+                    self.entry = Some(entry);
                 }
+                let decompressor = self.decompressor(header.method, uncompressed_size)?;
+
+                // some writers only tell from the central directory that the
+                // data descriptor has 64-bit sizes, others with a zip64
+                // extra field whose sizes are left to the data descriptor,
+                // see APPNOTE 4.3.9.2
+                let entry = self.entry.as_ref().unwrap();
+                let is_zip64 = header.compressed_size == u32::MAX
+                    || header.uncompressed_size == u32::MAX
+                    || header.has_zip64_field()
+                    || entry.compressed_size >= u32::MAX as u64
+                    || entry.uncompressed_size >= u32::MAX as u64;
 
                 self.state = State::ReadData {
-                    is_zip64: header.compressed_size == u32::MAX
-                        || header.uncompressed_size == u32::MAX,
+                    is_zip64,
+                    find_data_descriptor: self.sizes_from_descriptor,
                     has_data_descriptor: header.has_data_descriptor(),
                     compressed_bytes: 0,
                     uncompressed_bytes: 0,
@@ -176,6 +406,42 @@ impl EntryFsm {
         }
     }
 
+    /// Picks the decompressor for the entry's data, decrypting it first if
+    /// needed.
+    fn decompressor(
+        &self,
+        method: Method,
+        uncompressed_size: Option<u64>,
+    ) -> Result<AnyDecompressor, Error> {
+        let entry = self.entry.as_ref().unwrap();
+        if entry.is_strongly_encrypted() {
+            return Err(UnsupportedError::StrongEncryption {
+                name: Some(entry.name.clone()),
+            }
+            .into());
+        }
+
+        match entry.method_details {
+            #[cfg(feature = "aes")]
+            MethodDetails::Aes {
+                strength, method, ..
+            } => {
+                if method == Method::Lzma {
+                    // the LZMA properties header would be encrypted along
+                    // with the data, and only the local header parser reads it
+                    return Err(Error::method_not_supported(method));
+                }
+                let password = self.password.as_deref().ok_or(Error::PasswordRequired)?;
+                let inner = AnyDecompressor::new(method, uncompressed_size)?;
+                let dec = aes_dec::AesDec::new(strength, entry.compressed_size, password, inner)?;
+                Ok(AnyDecompressor::Aes(Box::new(dec)))
+            }
+            #[cfg(not(feature = "aes"))]
+            MethodDetails::Aes { .. } => Err(Error::method_not_enabled(Method::Aex)),
+            _ => AnyDecompressor::new(method, uncompressed_size),
+        }
+    }
+
     /// Process the input and write the output to the given buffer
     ///
     /// This function will return `FsmResult::Continue` if it needs more input
@@ -185,11 +451,14 @@ impl EntryFsm {
     ///
     /// Also, after writing all the output, process will read the data
     /// descriptor (if any), and make sur the CRC32 hash and the uncompressed
-    /// size match the expected values.
+    /// size match the expected values. `FsmResult::Done` holds the buffer,
+    /// with whatever was read past the entry, and the data descriptor as
+    /// found, see [EntryTrailer].
+    #[allow(clippy::type_complexity)]
     pub fn process(
         mut self,
         out: &mut [u8],
-    ) -> Result<FsmResult<(Self, DecompressOutcome), Buffer>, Error> {
+    ) -> Result<FsmResult<(Self, DecompressOutcome), (Buffer, Option<EntryTrailer>)>, Error> {
         tracing::trace!(
             state = match &self.state {
                 State::ReadLocalHeader => "ReadLocalHeader",
@@ -201,6 +470,8 @@ impl EntryFsm {
             "process"
         );
 
+        self.limits.check_depth()?;
+
         use State as S;
         'process_state: loop {
             return match &mut self.state {
@@ -222,9 +493,43 @@ impl EntryFsm {
                     uncompressed_bytes,
                     hasher,
                     decompressor,
+                    is_zip64,
+                    find_data_descriptor,
                     ..
                 } => {
                     let in_buf = self.buffer.data();
+
+                    // what's buffered is entry data until a data descriptor
+                    // is found, if we're still looking for one
+                    let mut unknown_size_max_len = None;
+                    if *find_data_descriptor {
+                        // no more than `out` can take is consumed at once,
+                        // so there's no use searching further than that:
+                        // the rest would be searched again next time
+                        let searched = cmp::min(in_buf.len(), out.len() + MAX_DESCRIPTOR_LEN);
+                        match find_data_descriptor_in(
+                            &in_buf[..searched],
+                            *compressed_bytes,
+                            *is_zip64,
+                        ) {
+                            DescriptorSearch::Found {
+                                offset,
+                                is_zip64: found_zip64,
+                            } => {
+                                let entry = self.entry.as_mut().unwrap();
+                                entry.compressed_size = *compressed_bytes + offset as u64;
+                                trace!(compressed_size = %entry.compressed_size, "found data descriptor");
+                                *is_zip64 = found_zip64;
+                                *find_data_descriptor = false;
+                            }
+                            DescriptorSearch::NotFound { data_len } => {
+                                if data_len == 0 {
+                                    return Ok(FsmResult::Continue((self, Default::default())));
+                                }
+                                unknown_size_max_len = Some(data_len);
+                            }
+                        }
+                    }
                     let entry = self.entry.as_ref().unwrap();
 
                     // do we have more input to feed to the decompressor?
@@ -233,20 +538,28 @@ impl EntryFsm {
                         return Ok(FsmResult::Continue((self, Default::default())));
                     }
 
-                    // don't feed the decompressor bytes beyond the entry's compressed size
-                    let in_buf_max_len = cmp::min(
-                        in_buf.len(),
-                        entry.compressed_size as usize - *compressed_bytes as usize,
-                    );
+                    let (in_buf_max_len, has_more_input) = match unknown_size_max_len {
+                        Some(max_len) => (max_len, HasMoreInput::Yes),
+                        None => {
+                            // don't feed the decompressor bytes beyond the entry's compressed size
+                            // (what's left may not fit in a usize on 32-bit targets, but
+                            // then it's more than what's buffered anyway)
+                            let remaining = entry.compressed_size - *compressed_bytes;
+                            let max_len = usize::try_from(remaining)
+                                .map_or(in_buf.len(), |remaining| {
+                                    cmp::min(in_buf.len(), remaining)
+                                });
+                            let fed_bytes_after_this = *compressed_bytes + max_len as u64;
+                            if fed_bytes_after_this == entry.compressed_size {
+                                (max_len, HasMoreInput::No)
+                            } else {
+                                (max_len, HasMoreInput::Yes)
+                            }
+                        }
+                    };
                     let in_buf = &in_buf[..in_buf_max_len];
                     let bytes_fed_this_turn = in_buf.len();
-
                     let fed_bytes_after_this = *compressed_bytes + in_buf.len() as u64;
-                    let has_more_input = if fed_bytes_after_this == entry.compressed_size as _ {
-                        HasMoreInput::No
-                    } else {
-                        HasMoreInput::Yes
-                    };
 
                     trace!(
                         compressed_bytes = *compressed_bytes,
@@ -268,7 +581,10 @@ impl EntryFsm {
                         "decompressed"
                     );
 
-                    if outcome.bytes_written == 0 && *compressed_bytes == entry.compressed_size {
+                    if outcome.bytes_written == 0
+                        && !*find_data_descriptor
+                        && *compressed_bytes == entry.compressed_size
+                    {
                         trace!("eof and no bytes written, we're done");
 
                         // we're done, let's read the data descriptor (if there's one)
@@ -299,9 +615,18 @@ impl EntryFsm {
                     }
 
                     // write the decompressed data to the hasher
-                    hasher.update(&out[..outcome.bytes_written]);
+                    if self.verification != Verification::Skip {
+                        hasher.update(&out[..outcome.bytes_written]);
+                    }
                     // update the number of bytes we've decompressed
                     *uncompressed_bytes += outcome.bytes_written as u64;
+                    self.limits.check(
+                        *uncompressed_bytes,
+                        cmp::max(*compressed_bytes, entry.compressed_size),
+                    )?;
+                    if let Some(progress) = &self.progress {
+                        progress.entry(entry, *compressed_bytes, *uncompressed_bytes);
+                    }
 
                     trace!(
                         compressed_bytes = *compressed_bytes,
@@ -316,11 +641,24 @@ impl EntryFsm {
 
                     match DataDescriptorRecord::mk_parser(*is_zip64).parse_next(&mut input) {
                         Ok(descriptor) => {
+                            let trailer = EntryTrailer {
+                                descriptor,
+                                has_signature: self
+                                    .buffer
+                                    .data()
+                                    .starts_with(DataDescriptorRecord::SIGNATURE.as_bytes()),
+                                is_zip64: *is_zip64,
+                            };
                             self.buffer
                                 .consume(input.as_bytes().offset_from(&self.buffer.data()));
-                            trace!("data descriptor = {:#?}", descriptor);
+                            trace!("data descriptor = {:#?}", trailer);
+                            if self.sizes_from_descriptor {
+                                let entry = self.entry.as_mut().unwrap();
+                                entry.crc32 = descriptor.crc32;
+                                entry.uncompressed_size = descriptor.uncompressed_size;
+                            }
                             transition!(self.state => (S::ReadDataDescriptor { metrics, .. }) {
-                                S::Validate { metrics, descriptor: Some(descriptor) }
+                                S::Validate { metrics, descriptor: Some(trailer) }
                             });
                             self.process(out)
                         }
@@ -338,8 +676,8 @@ impl EntryFsm {
 
                     let expected_crc32 = if entry.crc32 != 0 {
                         entry.crc32
-                    } else if let Some(descriptor) = descriptor.as_ref() {
-                        descriptor.crc32
+                    } else if let Some(trailer) = descriptor.as_ref() {
+                        trailer.descriptor.crc32
                     } else {
                         0
                     };
@@ -351,14 +689,32 @@ impl EntryFsm {
                         }));
                     }
 
-                    if expected_crc32 != 0 && expected_crc32 != metrics.crc32 {
-                        return Err(Error::Format(FormatError::WrongChecksum {
-                            expected: expected_crc32,
-                            actual: metrics.crc32,
-                        }));
-                    }
+                    // AE-2 entries don't store their CRC-32: their data is
+                    // authenticated by the HMAC checked as it's decrypted
+                    let authenticated =
+                        matches!(entry.method_details, MethodDetails::Aes { version: 2, .. });
+
+                    let status = match self.verification {
+                        Verification::Skip => VerificationStatus::Skipped,
+                        _ if authenticated => VerificationStatus::Verified,
+                        Verification::IfPresent if expected_crc32 == 0 && metrics.crc32 != 0 => {
+                            VerificationStatus::NoChecksum
+                        }
+                        Verification::Require | Verification::IfPresent => {
+                            if expected_crc32 != metrics.crc32 {
+                                return Err(Error::Format(FormatError::WrongChecksum {
+                                    expected: expected_crc32,
+                                    actual: metrics.crc32,
+                                }));
+                            }
+                            VerificationStatus::Verified
+                        }
+                    };
 
-                    Ok(FsmResult::Done(self.buffer))
+                    if let Some(progress) = &self.progress {
+                        progress.entry_done(entry, status);
+                    }
+                    Ok(FsmResult::Done((self.buffer, *descriptor)))
                 }
                 S::Transition => {
                     unreachable!("the state machine should never be in the transition state")
@@ -375,6 +731,11 @@ impl EntryFsm {
         if self.buffer.available_space() == 0 {
             self.buffer.shift();
         }
+        if self.buffer.available_space() == 0 {
+            // something that doesn't fit, like a header with long extra fields
+            let capacity = self.buffer.capacity();
+            self.buffer.grow(capacity * 2);
+        }
         self.buffer.space()
     }
 
@@ -384,6 +745,137 @@ impl EntryFsm {
     pub fn fill(&mut self, count: usize) -> usize {
         self.buffer.fill(count)
     }
+
+    /// For entries stored without compression or encryption, returns how
+    /// many bytes of data can be read straight into an output buffer of
+    /// `out_len` bytes, rather than into [Self::space] and then copied over
+    /// by [Self::process]. After reading them, call [Self::fill_direct].
+    ///
+    /// Returns `Some(0)` while something is still buffered: [Self::process]
+    /// should be called without reading more, until it's all processed.
+    /// Returns `None` when data has to go through our buffer: for other
+    /// methods, when the entry's size is only known from its data
+    /// descriptor, once all of the data is read, or when `out_len` is less
+    /// than [Self::DIRECT_READ_MIN], since smaller reads are better batched.
+    pub fn direct_read_len(&self, out_len: usize) -> Option<usize> {
+        let State::ReadData {
+            find_data_descriptor: false,
+            compressed_bytes,
+            decompressor: AnyDecompressor::Store(_),
+            ..
+        } = &self.state
+        else {
+            return None;
+        };
+        let remaining = self.entry.as_ref()?.compressed_size - compressed_bytes;
+        if out_len < Self::DIRECT_READ_MIN || remaining == 0 {
+            return None;
+        }
+        if self.buffer.available_data() > 0 {
+            return Some(0);
+        }
+        Some(usize::try_from(remaining).map_or(out_len, |remaining| out_len.min(remaining)))
+    }
+
+    /// Accounts for `data`, read straight into the caller's output buffer as
+    /// allowed by [Self::direct_read_len]: it's checked and counted as if
+    /// [Self::process] had written it.
+    pub fn fill_direct(&mut self, data: &[u8]) -> Result<(), Error> {
+        let State::ReadData {
+            compressed_bytes,
+            uncompressed_bytes,
+            hasher,
+            ..
+        } = &mut self.state
+        else {
+            panic!("fill_direct called outside of entry data");
+        };
+        let entry = self.entry.as_ref().unwrap();
+
+        *compressed_bytes += data.len() as u64;
+        *uncompressed_bytes += data.len() as u64;
+        if self.verification != Verification::Skip {
+            hasher.update(data);
+        }
+        self.limits.check(
+            *uncompressed_bytes,
+            cmp::max(*compressed_bytes, entry.compressed_size),
+        )?;
+        if let Some(progress) = &self.progress {
+            progress.entry(entry, *compressed_bytes, *uncompressed_bytes);
+        }
+        Ok(())
+    }
+
+    /// Returns what was read but not processed yet
+    pub(crate) fn buffered(&self) -> &[u8] {
+        self.buffer.data()
+    }
+
+    /// Consumes the state machine, returning its buffer, which holds
+    /// whatever was read but not processed yet: for example, what follows
+    /// the last entry of an archive, once [Self::process_till_header] fails
+    /// to find another local header.
+    pub fn into_buffer(self) -> Buffer {
+        self.buffer
+    }
+}
+
+/// Where the data descriptor of an entry whose size is unknown is, see
+/// [find_data_descriptor_in]
+enum DescriptorSearch {
+    /// The data descriptor starts at `offset` in the buffer
+    Found { offset: usize, is_zip64: bool },
+
+    /// The first `data_len` bytes of the buffer are entry data, and the
+    /// rest may be the start of a data descriptor
+    NotFound { data_len: usize },
+}
+
+/// Size of a zip64 data descriptor: signature, crc32, then 64-bit
+/// compressed and uncompressed sizes
+const MAX_DESCRIPTOR_LEN: usize = 24;
+
+/// Looks for the data descriptor that ends an entry in `data`, given how
+/// many bytes of the entry's data came before it. That's a data descriptor
+/// signature followed by the compressed size, 32-bit or 64-bit (the one
+/// given by `is_zip64` is tried first), which has to match.
+///
+/// Data descriptors without a signature aren't found.
+fn find_data_descriptor_in(data: &[u8], compressed_bytes: u64, is_zip64: bool) -> DescriptorSearch {
+    const SIGNATURE: &[u8] = b"PK\x07\x08";
+
+    let mut start = 0;
+    while let Some(pos) = data[start..]
+        .windows(SIGNATURE.len())
+        .position(|window| window == SIGNATURE)
+    {
+        let offset = start + pos;
+        let record = &data[offset..];
+        if record.len() < MAX_DESCRIPTOR_LEN {
+            // can't tell yet, wait for more input
+            return DescriptorSearch::NotFound { data_len: offset };
+        }
+
+        let expected = compressed_bytes + offset as u64;
+        let size32 = u32::from_le_bytes(record[8..12].try_into().unwrap()) as u64;
+        let size64 = u64::from_le_bytes(record[8..16].try_into().unwrap());
+        let found_zip64 = match (size32 == expected, size64 == expected) {
+            (true, true) => Some(is_zip64),
+            (true, false) => Some(false),
+            (false, true) => Some(true),
+            (false, false) => None,
+        };
+        if let Some(is_zip64) = found_zip64 {
+            return DescriptorSearch::Found { offset, is_zip64 };
+        }
+        start = offset + 1;
+    }
+
+    // a signature may be cut off at the end
+    DescriptorSearch::NotFound {
+        data_len: data.len().saturating_sub(SIGNATURE.len() - 1),
+    }
 }
 
 enum AnyDecompressor {
@@ -398,6 +890,8 @@ enum AnyDecompressor {
     Lzma(Box<lzma_dec::LzmaDec>),
     #[cfg(feature = "zstd")]
     Zstd(zstd_dec::ZstdDec),
+    #[cfg(feature = "aes")]
+    Aes(Box<aes_dec::AesDec>),
 }
 
 #[derive(Default, Debug)]
@@ -500,6 +994,8 @@ impl Decompressor for AnyDecompressor {
             Self::Lzma(dec) => dec.decompress(in_buf, out, has_more_input),
             #[cfg(feature = "zstd")]
             Self::Zstd(dec) => dec.decompress(in_buf, out, has_more_input),
+            #[cfg(feature = "aes")]
+            Self::Aes(dec) => dec.decompress(in_buf, out, has_more_input),
         }
     }
 }
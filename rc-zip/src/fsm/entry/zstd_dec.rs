@@ -1,4 +1,8 @@
-use std::{cmp, io::Write};
+use std::{
+    cmp,
+    io::Write,
+    sync::{Mutex, PoisonError},
+};
 
 use crate::{error::Error, parse::Method};
 
@@ -9,7 +13,9 @@ use zstd::stream::write::Decoder;
 
 #[derive(Default)]
 enum State {
-    Writing(Box<Decoder<'static, Vec<u8>>>),
+    /// The decoder isn't Sync, which would make the entry readers using it
+    /// not Sync either, see [Exclusive].
+    Writing(Box<Exclusive<Decoder<'static, Vec<u8>>>>),
     Draining(Vec<u8>),
 
     #[default]
@@ -23,7 +29,7 @@ pub(crate) struct ZstdDec {
 impl ZstdDec {
     pub fn new() -> Result<Self, Error> {
         Ok(Self {
-            state: State::Writing(Box::new(Decoder::new(vec![])?)),
+            state: State::Writing(Box::new(Exclusive::new(Decoder::new(vec![])?))),
         })
     }
 }
@@ -57,7 +63,7 @@ impl Decompressor for ZstdDec {
 
             match &mut self.state {
                 State::Writing(stream) => {
-                    let n = stream.write(in_buf).map_err(dec_err)?;
+                    let n = stream.get_mut().write(in_buf).map_err(dec_err)?;
                     trace!(
                         "wrote {} bytes to decompressor (of {} available)",
                         n,
@@ -97,7 +103,8 @@ impl Decompressor for ZstdDec {
                             }
 
                             match std::mem::take(&mut self.state) {
-                                State::Writing(mut stream) => {
+                                State::Writing(stream) => {
+                                    let mut stream = stream.into_inner();
                                     trace!("finishing...");
                                     stream.flush().map_err(dec_err)?;
                                     self.state = State::Draining(stream.into_inner());
@@ -133,7 +140,7 @@ impl ZstdDec {
     #[inline(always)]
     fn internal_buf_mut(&mut self) -> &mut Vec<u8> {
         match &mut self.state {
-            State::Writing(stream) => stream.get_mut(),
+            State::Writing(stream) => stream.get_mut().get_mut(),
             State::Draining(buf) => buf,
             State::Transition => unreachable!(),
         }
@@ -157,3 +164,23 @@ impl ZstdDec {
         }
     }
 }
+
+/// Makes a value that's Send but not Sync, like the zstd decoder, Sync, by
+/// only ever giving out `&mut` access to it: that's exclusive, so sharing
+/// `&Exclusive` between threads is harmless.
+struct Exclusive<T>(Mutex<T>);
+
+impl<T> Exclusive<T> {
+    fn new(inner: T) -> Self {
+        Self(Mutex::new(inner))
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        // the mutex is never locked, so it can't be poisoned
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn into_inner(self) -> T {
+        self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -8,6 +8,13 @@
 //! data (while calculating the CRC32), then the data descriptor, and then
 //! checking whether the uncompressed size and CRC32 match the values in the
 //! central directory.
+//!
+//! Without the central directory, [StreamingEntryFsm] finds entries one
+//! after the other, from their local headers. When the central directory is
+//! damaged, [RecoveryFsm] scans the whole file for them.
+//!
+//! Going the other way, [ArchiveWriterFsm] writes entries one after the
+//! other, then the central directory that lists them.
 
 macro_rules! transition {
     ($state: expr => ($pattern: pat) $body: expr) => {
@@ -19,11 +26,23 @@ macro_rules! transition {
     };
 }
 
+/// The buffer state machines read into, see [EntryFsm::new]
+pub use oval::Buffer;
+
 mod archive;
-pub use archive::ArchiveFsm;
+pub use archive::{ArchiveFsm, ArchiveReadOptions};
 
 mod entry;
-pub use entry::EntryFsm;
+pub use entry::{DecompressionLimits, EntryFsm, EntryTrailer, Verification, VerificationStatus};
+
+mod streaming;
+pub use streaming::{StreamingEntryFsm, StreamingNext};
+
+mod recovery;
+pub use recovery::RecoveryFsm;
+
+mod writer;
+pub use writer::{ArchiveWriterFsm, Compression, NewEntry};
 
 /// Indicates whether or not the state machine has completed its work
 pub enum FsmResult<M, R> {
@@ -0,0 +1,293 @@
+use oval::Buffer;
+use tracing::trace;
+use winnow::{error::ErrMode, Parser, Partial};
+
+use super::FsmResult;
+use crate::{
+    encoding::Encoding,
+    error::{EntryParseError, Error},
+    parse::{
+        Archive, CentralDirectoryFileHeader, DataDescriptorRecord, EndOfCentralDirectoryRecord,
+        EntryTrust, LocalFileHeader,
+    },
+};
+
+/// [RecoveryFsm] salvages what it can of an archive whose end of central
+/// directory record or central directory is missing or damaged, typically
+/// because it was truncated. Rather than reading the central directory, it
+/// scans the whole file for local file headers, and builds an [Archive]
+/// from those: each entry's [Entry::trust](crate::parse::Entry::trust)
+/// tells how far its metadata can be trusted.
+///
+/// Local headers don't have everything the central directory has: entries
+/// have no comments or permissions, and the sizes of entries written in a
+/// streaming fashion have to be found in their data descriptor, or guessed.
+///
+/// The loop is the same as [ArchiveFsm](super::ArchiveFsm)'s, except data is
+/// only ever read forward.
+pub struct RecoveryFsm {
+    /// Size of the entire zip file
+    size: u64,
+
+    buffer: Buffer,
+
+    /// Offset in the file of the start of the buffer's data
+    offset: u64,
+
+    archive: Archive,
+
+    /// Local headers found so far, valid or not
+    records: usize,
+
+    /// Where the data of the last entry starts, if it didn't have its sizes
+    /// in its local header and its end hasn't been found yet, and whether its
+    /// local header had a zip64 extra field
+    pending: Option<(u64, bool)>,
+}
+
+/// What [RecoveryFsm::process_record] did
+enum Step {
+    /// The record was handled, and skipped over
+    Done,
+    /// More data is needed to parse the record
+    NeedData,
+}
+
+impl RecoveryFsm {
+    /// Local headers with their name and extra field are at most 128KiB,
+    /// this leaves room for reading more while parsing one.
+    const BUFFER_SIZE: usize = 256 * 1024;
+
+    /// Create a new recovery state machine, for a file of the given size.
+    pub fn new(size: u64) -> Self {
+        Self {
+            size,
+            buffer: Buffer::with_capacity(Self::BUFFER_SIZE),
+            offset: 0,
+            archive: Archive {
+                size,
+                directory_offset: size,
+                global_offset: 0,
+                encoding: Encoding::Utf8,
+                entries: Vec::new(),
+                invalid_entries: Vec::new(),
+                comment: String::new(),
+                raw_comment: Vec::new(),
+                duplicate_names: Default::default(),
+                warnings: Vec::new(),
+            },
+            records: 0,
+            pending: None,
+        }
+    }
+
+    /// If this returns `Some(offset)`, the caller should read data from
+    /// `offset` into [Self::space] — without forgetting to call
+    /// [Self::fill] with the number of bytes read.
+    pub fn wants_read(&self) -> Option<u64> {
+        let offset = self.offset + self.buffer.available_data() as u64;
+        (offset < self.size).then_some(offset)
+    }
+
+    /// Returns a mutable slice with all the available space to write to.
+    ///
+    /// After writing to this, call [Self::fill] with the number of bytes written.
+    #[inline]
+    pub fn space(&mut self) -> &mut [u8] {
+        if self.buffer.available_space() == 0 {
+            self.buffer.shift();
+        }
+        self.buffer.space()
+    }
+
+    /// After having written data to [Self::space], call this to indicate how
+    /// many bytes were written.
+    #[inline]
+    pub fn fill(&mut self, count: usize) -> usize {
+        self.buffer.fill(count)
+    }
+
+    /// Process buffered data, looking for records.
+    ///
+    /// A result of [FsmResult::Continue] gives back ownership of the state
+    /// machine and indicates the I/O loop should continue, starting with
+    /// [Self::wants_read]. A result of [FsmResult::Done] means the whole
+    /// file was scanned.
+    pub fn process(mut self) -> Result<FsmResult<Self, Archive>, Error> {
+        let eof = self.wants_read().is_none();
+        loop {
+            let data = self.buffer.data();
+            let Some(pos) = find_signature(data) else {
+                // the last few bytes may be the start of a signature
+                let keep = if eof { 0 } else { data.len().min(3) };
+                self.skip((data.len() - keep) as u64);
+                if eof {
+                    return Ok(FsmResult::Done(self.finish()));
+                }
+                return Ok(FsmResult::Continue(self));
+            };
+            self.skip(pos as u64);
+
+            match self.process_record(eof)? {
+                Step::Done => {}
+                Step::NeedData if eof => {
+                    // the file ends in the middle of the record
+                    self.skip(4);
+                }
+                Step::NeedData => return Ok(FsmResult::Continue(self)),
+            }
+        }
+    }
+
+    /// Handles the record at the start of the buffer, whose signature was
+    /// found by [find_signature]
+    fn process_record(&mut self, eof: bool) -> Result<Step, Error> {
+        let data = self.buffer.data();
+        if data.len() < 4 {
+            return Ok(Step::NeedData);
+        }
+
+        let signature = &data[..4];
+        if signature == LocalFileHeader::SIGNATURE.as_bytes() {
+            let (entry, has_data_descriptor, has_zip64_field, header_len, name) =
+                match LocalFileHeader::parser.parse_peek(Partial::new(data)) {
+                    Err(ErrMode::Incomplete(_)) => return Ok(Step::NeedData),
+                    Err(_) => {
+                        trace!(offset = self.offset, "RecoveryFsm | not a local header");
+                        self.skip(4);
+                        return Ok(Step::Done);
+                    }
+                    Ok((_, header)) => (
+                        header.as_entry(),
+                        header.has_data_descriptor(),
+                        header.has_zip64_field(),
+                        LocalFileHeader::measure(data)?.unwrap_or_default() as u64,
+                        String::from_utf8_lossy(&header.name).into_owned(),
+                    ),
+                };
+
+            let header_offset = self.offset;
+            self.end_pending(header_offset);
+            let index = self.records;
+            self.records += 1;
+
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    trace!(%index, %error, "RecoveryFsm | invalid local header");
+                    self.archive.invalid_entries.push(EntryParseError {
+                        index,
+                        offset: header_offset,
+                        name,
+                        error,
+                    });
+                    self.skip(4);
+                    return Ok(Step::Done);
+                }
+            };
+            trace!(name = %entry.name, %header_offset, "RecoveryFsm | found entry");
+            entry.header_offset = header_offset;
+
+            let data_start = header_offset + header_len;
+            let data_end = data_start.saturating_add(entry.compressed_size);
+            let skip = if has_data_descriptor {
+                entry.trust = EntryTrust::Guessed;
+                self.pending = Some((data_start, has_zip64_field));
+                header_len
+            } else if data_end > self.size {
+                entry.trust = EntryTrust::Truncated;
+                header_len
+            } else {
+                data_end - header_offset
+            };
+            self.archive.entries.push(entry);
+            self.skip(skip);
+            return Ok(Step::Done);
+        }
+
+        if signature == DataDescriptorRecord::SIGNATURE.as_bytes() {
+            if let Some((data_start, has_zip64_field)) = self.pending {
+                let compressed_size = self.offset - data_start;
+                // a zip64 descriptor for small sizes also parses as a 32-bit
+                // one, so try the one the local header hints at first
+                let attempts = if has_zip64_field {
+                    [true, false]
+                } else {
+                    [false, true]
+                };
+                for is_zip64 in attempts {
+                    match DataDescriptorRecord::mk_parser(is_zip64).parse_peek(Partial::new(data)) {
+                        Err(ErrMode::Incomplete(_)) if !eof => return Ok(Step::NeedData),
+                        Ok((rest, descriptor)) if descriptor.compressed_size == compressed_size => {
+                            let len = (data.len() - rest.len()) as u64;
+                            if let Some(entry) = self.archive.entries.last_mut() {
+                                entry.crc32 = descriptor.crc32;
+                                entry.compressed_size = descriptor.compressed_size;
+                                entry.uncompressed_size = descriptor.uncompressed_size;
+                                entry.trust = EntryTrust::DataDescriptor;
+                            }
+                            self.pending = None;
+                            self.skip(len);
+                            return Ok(Step::Done);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        } else if signature == CentralDirectoryFileHeader::SIGNATURE.as_bytes()
+            || signature == EndOfCentralDirectoryRecord::SIGNATURE.as_bytes()
+        {
+            let offset = self.offset;
+            self.end_pending(offset);
+            if self.archive.directory_offset == self.size {
+                self.archive.directory_offset = offset;
+            }
+        }
+
+        // a record we don't need, or something that looks like one in an
+        // entry's data
+        self.skip(4);
+        Ok(Step::Done)
+    }
+
+    /// Ends the data of the pending entry, if any, at `offset`
+    fn end_pending(&mut self, offset: u64) {
+        if let Some((data_start, _)) = self.pending.take() {
+            if let Some(entry) = self.archive.entries.last_mut() {
+                entry.compressed_size = offset - data_start;
+            }
+        }
+    }
+
+    /// Skips `n` bytes, which may not have been read yet
+    fn skip(&mut self, n: u64) {
+        match usize::try_from(n) {
+            Ok(n) if n <= self.buffer.available_data() => {
+                self.buffer.consume(n);
+            }
+            _ => self.buffer.reset(),
+        }
+        self.offset += n;
+    }
+
+    fn finish(mut self) -> Archive {
+        self.end_pending(self.size);
+        if let Some(entry) = self
+            .archive
+            .entries
+            .iter()
+            .find(|entry| entry.name_decoding.encoding != Encoding::Utf8)
+        {
+            self.archive.encoding = entry.name_decoding.encoding;
+        }
+        self.archive
+    }
+}
+
+/// Returns the position of the first thing in `data` that looks like the
+/// signature of a record [RecoveryFsm] cares about.
+fn find_signature(data: &[u8]) -> Option<usize> {
+    data.windows(4).position(|window| {
+        window.starts_with(b"PK") && matches!(window[2..], [3, 4] | [7, 8] | [1, 2] | [5, 6])
+    })
+}
@@ -0,0 +1,107 @@
+use oval::Buffer;
+
+use crate::{
+    error::{Error, FormatError},
+    parse::{
+        CentralDirectoryFileHeader, EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord,
+        LocalFileHeader,
+    },
+};
+
+use super::{EntryFsm, FsmResult};
+
+/// A state machine that finds what comes next in a zip archive read from
+/// start to end, without the central directory: either another entry, or
+/// the central directory, which means there are no entries left.
+///
+/// Entries written in a streaming fashion only have their sizes in their
+/// data descriptor, see [EntryFsm::process_till_header]. Once an entry's
+/// [EntryFsm] is done, the buffer it returns is given to a new
+/// [StreamingEntryFsm] to find the next one.
+pub struct StreamingEntryFsm {
+    fsm: EntryFsm,
+}
+
+/// What follows an entry, see [StreamingEntryFsm::process]
+#[allow(clippy::large_enum_variant)]
+pub enum StreamingNext {
+    /// Another entry, whose local header was parsed: [EntryFsm::process_till_header]
+    /// returns it right away.
+    Entry(EntryFsm),
+
+    /// The central directory, or the end of central directory record if
+    /// there are no entries at all. The buffer holds what was read of it.
+    Trailer(Buffer),
+}
+
+impl StreamingEntryFsm {
+    /// Create a new state machine, starting with `buffer` if what was read
+    /// of the previous entry went past its end
+    pub fn new(buffer: Option<Buffer>) -> Self {
+        Self {
+            fsm: EntryFsm::new(None, buffer),
+        }
+    }
+
+    /// If this returns true, the caller should read data from into
+    /// [Self::space] — without forgetting to call [Self::fill] with the number
+    /// of bytes written. If reading returns no data, the stream was
+    /// truncated.
+    pub fn wants_read(&self) -> bool {
+        self.fsm.wants_read()
+    }
+
+    /// Returns a mutable slice with all the available space to write to.
+    ///
+    /// After writing to this, call [Self::fill] with the number of bytes written.
+    #[inline]
+    pub fn space(&mut self) -> &mut [u8] {
+        self.fsm.space()
+    }
+
+    /// After having written data to [Self::space], call this to indicate how
+    /// many bytes were written.
+    #[inline]
+    pub fn fill(&mut self, count: usize) -> usize {
+        self.fsm.fill(count)
+    }
+
+    /// Process the input, looking at the signature of the next record.
+    ///
+    /// Returns [FsmResult::Continue] if more input is needed. Anything other
+    /// than a local file header or the start of the central directory is an
+    /// error.
+    pub fn process(mut self) -> Result<FsmResult<Self, StreamingNext>, Error> {
+        let data = self.fsm.buffered();
+        if data.len() < 4 {
+            return Ok(FsmResult::Continue(self));
+        }
+
+        let signature = &data[..4];
+        if signature == LocalFileHeader::SIGNATURE.as_bytes() {
+            return Ok(match self.fsm.process_till_header()? {
+                Some(_) => FsmResult::Done(StreamingNext::Entry(self.fsm)),
+                None => FsmResult::Continue(self),
+            });
+        }
+
+        let trailer_signatures = [
+            CentralDirectoryFileHeader::SIGNATURE,
+            EndOfCentralDirectory64Record::SIGNATURE,
+            EndOfCentralDirectoryRecord::SIGNATURE,
+            // the archive extra data record comes first when the central
+            // directory is encrypted
+            "PK\x06\x08",
+        ];
+        if trailer_signatures
+            .iter()
+            .any(|trailer| signature == trailer.as_bytes())
+        {
+            return Ok(FsmResult::Done(StreamingNext::Trailer(
+                self.fsm.into_buffer(),
+            )));
+        }
+
+        Err(FormatError::InvalidLocalHeader.into())
+    }
+}
@@ -0,0 +1,72 @@
+use miniz_oxide::deflate::core::{
+    compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+
+use crate::{error::Error, parse::Method};
+
+use super::Compressor;
+
+/// Compression level used by default, same as zlib's
+const DEFAULT_LEVEL: u32 = 6;
+
+pub(crate) struct DeflateEnc {
+    /// The miniz_oxide compressor state, boxed because it's pretty large
+    state: Box<CompressorOxide>,
+}
+
+impl Default for DeflateEnc {
+    fn default() -> Self {
+        Self::new(DEFAULT_LEVEL)
+    }
+}
+
+impl DeflateEnc {
+    /// A compressor for the given level, from 0 to 9. miniz_oxide also has
+    /// a level 10, which is slower and only rarely smaller.
+    pub(crate) fn new(level: u32) -> Self {
+        // negative window bits means raw deflate, without a zlib header
+        let flags = create_comp_flags_from_zip_params(level.min(9) as i32, -15, 0);
+        Self {
+            state: Box::new(CompressorOxide::new(flags)),
+        }
+    }
+
+    fn run(
+        &mut self,
+        mut in_buf: &[u8],
+        out: &mut Vec<u8>,
+        flush: TDEFLFlush,
+    ) -> Result<(), Error> {
+        loop {
+            let (status, bytes_read) = compress_to_output(&mut self.state, in_buf, flush, |buf| {
+                out.extend_from_slice(buf);
+                true
+            });
+            in_buf = &in_buf[bytes_read..];
+
+            match status {
+                TDEFLStatus::Okay if in_buf.is_empty() => return Ok(()),
+                TDEFLStatus::Okay => {
+                    // keep going until all of the input is taken
+                }
+                TDEFLStatus::Done => return Ok(()),
+                TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                    return Err(Error::Compression {
+                        method: Method::Deflate,
+                        msg: format!("miniz_oxide failed with {status:?}"),
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl Compressor for DeflateEnc {
+    fn compress(&mut self, in_buf: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        self.run(in_buf, out, TDEFLFlush::None)
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.run(&[], out, TDEFLFlush::Finish)
+    }
+}
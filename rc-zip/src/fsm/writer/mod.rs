@@ -0,0 +1,830 @@
+use std::{borrow::Cow, io};
+
+use chrono::{DateTime, Utc};
+use tracing::trace;
+use winnow::{Parser, Partial};
+
+mod store_enc;
+
+#[cfg(feature = "deflate")]
+mod deflate_enc;
+
+use crate::{
+    error::{Error, FormatError},
+    parse::{
+        Archive, CentralDirectoryFileHeader, DataDescriptorRecord, EndOfCentralDirectory64Locator,
+        EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, Entry, ExtraFieldSettings,
+        ExtraZip64Field, HostSystem, LocalFileHeader, Method, MethodSpecific, MsdosTimestamp,
+        UnixMode, Version,
+    },
+};
+
+/// Version needed to extract entries: 2.0 is enough for directories,
+/// deflate and data descriptors.
+const VERSION: u8 = 20;
+
+/// Version needed to extract entries that use zip64, and archives that have
+/// a zip64 end of central directory record
+const VERSION_ZIP64: u8 = 45;
+
+/// Version made by, unless set with [ArchiveWriterFsm::creator_version]:
+/// readers go by its host system to interpret external attributes, which
+/// only have Unix modes on Unix.
+const DEFAULT_CREATOR_VERSION: Version = Version {
+    host_system: if cfg!(windows) {
+        HostSystem::MsDos
+    } else {
+        HostSystem::Unix
+    },
+    version: VERSION,
+};
+
+/// Bit 3: sizes and CRC-32 are in the data descriptor that follows the data
+const FLAG_DATA_DESCRIPTOR: u16 = 0x8;
+
+/// Bit 11: the name and comment are UTF-8
+const FLAG_UTF8: u16 = 0x800;
+
+/// MS-DOS directory attribute, set in the low byte of the external attributes
+const MSDOS_DIR: u32 = 0x10;
+
+/// How many of an entry's first bytes are given to the callback set with
+/// [ArchiveWriterFsm::select_compression]
+const LEADING_BYTES: usize = 512;
+
+/// An entry to add to an archive with [ArchiveWriterFsm::start_entry].
+#[derive(Debug, Clone)]
+pub struct NewEntry {
+    /// Name of the entry, with `/` as a path separator. Directory names
+    /// end with a `/`.
+    pub name: String,
+
+    /// How the entry's data is compressed: [Method::Store] and, with the
+    /// `deflate` feature, [Method::Deflate] are supported.
+    pub method: Method,
+
+    /// Compression level, from 0 (fastest) to 9 (smallest), for methods
+    /// that have one. Defaults to the method's default level, 6 for
+    /// deflate.
+    pub level: Option<u32>,
+
+    /// Last modification time, stored with 2-second precision. Dates
+    /// before 1980 or after 2107 are stored as 1980-01-01.
+    pub modified: DateTime<Utc>,
+
+    /// Unix permission and file type bits
+    pub mode: UnixMode,
+
+    /// Entry comment
+    pub comment: String,
+
+    /// How many bytes of data the entry is expected to have, uncompressed.
+    ///
+    /// Entries are written before their size is known, so unless they have
+    /// a hint under 4 GiB, their local header gets a zip64 extra field:
+    /// readers that go by local headers then know their data descriptor
+    /// has 64-bit sizes. Entries with a smaller hint don't get one, for
+    /// readers that don't support zip64. If their data turns out to be
+    /// 4 GiB or more anyway, it's still written, but only readers that go
+    /// by the central directory can find where it ends.
+    pub size_hint: Option<u64>,
+
+    /// Whether to set the language encoding flag, which tells readers the
+    /// name and comment are UTF-8. They're always written as UTF-8; by
+    /// default, the flag is only set if either isn't ASCII, for readers
+    /// that don't know about it.
+    pub utf8_flag: Option<bool>,
+}
+
+impl NewEntry {
+    /// A stored entry, modified now, with mode `0o644` — or `0o755` and the
+    /// directory bit, if the name ends with a `/`.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let mode = if name.ends_with('/') {
+            UnixMode::IFDIR | UnixMode(0o755)
+        } else {
+            UnixMode::IFREG | UnixMode(0o644)
+        };
+        Self {
+            name,
+            method: Method::Store,
+            level: None,
+            modified: Utc::now(),
+            mode,
+            comment: String::new(),
+            size_hint: None,
+            utf8_flag: None,
+        }
+    }
+
+    /// Sets the compression method
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the compression level
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Sets the last modification time
+    pub fn with_modified(mut self, modified: DateTime<Utc>) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    /// Sets the Unix permission and file type bits
+    pub fn with_mode(mut self, mode: UnixMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the entry comment
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Sets how many bytes of data the entry is expected to have, see
+    /// [Self::size_hint]
+    pub fn with_size_hint(mut self, size_hint: u64) -> Self {
+        self.size_hint = Some(size_hint);
+        self
+    }
+
+    /// Sets whether to set the language encoding flag, or leaves it to the
+    /// default with `None`, see [Self::utf8_flag]
+    pub fn with_utf8_flag(mut self, utf8_flag: Option<bool>) -> Self {
+        self.utf8_flag = utf8_flag;
+        self
+    }
+}
+
+/// An entry's compression method and level, as picked by the callback given
+/// to [ArchiveWriterFsm::select_compression].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    /// See [NewEntry::method]
+    pub method: Method,
+
+    /// See [NewEntry::level]
+    pub level: Option<u32>,
+}
+
+type CompressionSelector = Box<dyn FnMut(&NewEntry, &[u8]) -> Compression + Send + Sync>;
+
+/// Compresses an entry's data as it's written.
+pub(crate) trait Compressor {
+    /// Compresses `in_buf` entirely, appending whatever output is ready to `out`
+    fn compress(&mut self, in_buf: &[u8], out: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Appends the rest of the output to `out`, once all the data was given
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+enum AnyCompressor {
+    Store(store_enc::StoreEnc),
+    #[cfg(feature = "deflate")]
+    Deflate(Box<deflate_enc::DeflateEnc>),
+}
+
+impl AnyCompressor {
+    #[cfg_attr(not(feature = "deflate"), allow(unused_variables))]
+    fn new(method: Method, level: Option<u32>) -> Result<Self, Error> {
+        match method {
+            Method::Store => Ok(Self::Store(Default::default())),
+            #[cfg(feature = "deflate")]
+            Method::Deflate => Ok(Self::Deflate(Box::new(match level {
+                Some(level) => deflate_enc::DeflateEnc::new(level),
+                None => Default::default(),
+            }))),
+            #[cfg(not(feature = "deflate"))]
+            Method::Deflate => Err(Error::method_not_enabled(method)),
+            _ => Err(Error::method_not_supported(method)),
+        }
+    }
+}
+
+impl Compressor for AnyCompressor {
+    fn compress(&mut self, in_buf: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Self::Store(enc) => enc.compress(in_buf, out),
+            #[cfg(feature = "deflate")]
+            Self::Deflate(enc) => enc.compress(in_buf, out),
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Self::Store(enc) => enc.finish(out),
+            #[cfg(feature = "deflate")]
+            Self::Deflate(enc) => enc.finish(out),
+        }
+    }
+}
+
+/// An entry started while a compression selector is set, whose local header
+/// waits for its first bytes
+struct PendingEntry {
+    entry: NewEntry,
+    leading: Vec<u8>,
+}
+
+/// The entry being written, its central directory header is completed once
+/// its data is.
+struct CurrentEntry {
+    header: CentralDirectoryFileHeader<'static>,
+    header_offset: u64,
+    /// Whether the local header has a zip64 extra field
+    zip64: bool,
+    /// For entries copied from another archive, the CRC-32 and sizes they
+    /// should end up with
+    raw: Option<DataDescriptorRecord>,
+    compressor: AnyCompressor,
+    hasher: crc32fast::Hasher,
+    compressed_size: u64,
+    uncompressed_size: u64,
+}
+
+/// A state machine that writes a zip archive, sans-io: everything it
+/// produces is buffered until the caller takes it with [Self::output] and
+/// [Self::consume].
+///
+/// Entries are written one after the other: a local file header, the
+/// compressed data, then a data descriptor with the CRC-32 and sizes, which
+/// aren't known until the data is all written. [Self::finish] then writes
+/// the central directory and the end of central directory record.
+///
+/// Since sizes only come after the data, streaming readers have to look for
+/// the data descriptor to tell where the data ends, see
+/// [EntryFsm::process_till_header](crate::fsm::EntryFsm::process_till_header).
+///
+/// Zip64 records are written as needed: for entries of 4 GiB or more, and
+/// for archives with more than 65535 entries or that are larger than 4 GiB.
+/// Since an entry's size isn't known when its local header is written, that
+/// header gets a zip64 extra field unless the entry has a size hint under
+/// 4 GiB, see [NewEntry::size_hint], or unless zip64 is forced, see
+/// [Self::force_zip64].
+pub struct ArchiveWriterFsm {
+    output: Vec<u8>,
+
+    /// Offset of the next byte of output, relative to the start of the
+    /// archive
+    offset: u64,
+
+    current: Option<CurrentEntry>,
+    pending: Option<PendingEntry>,
+    selector: Option<CompressionSelector>,
+    directory: Vec<CentralDirectoryFileHeader<'static>>,
+    comment: String,
+    force_zip64: bool,
+    creator_version: Version,
+    finished: bool,
+}
+
+impl Default for ArchiveWriterFsm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveWriterFsm {
+    /// Create a new state machine for writing an archive
+    pub fn new() -> Self {
+        Self {
+            output: Vec::new(),
+            offset: 0,
+            current: None,
+            pending: None,
+            selector: None,
+            directory: Vec::new(),
+            comment: String::new(),
+            force_zip64: false,
+            creator_version: DEFAULT_CREATOR_VERSION,
+            finished: false,
+        }
+    }
+
+    /// If true, zip64 records are written for all entries and for the end
+    /// of central directory, whether they're needed or not: the layout
+    /// doesn't depend on entry sizes, and readers that only go by local
+    /// headers know that data descriptors have 64-bit sizes. Defaults to
+    /// false.
+    pub fn force_zip64(mut self, force: bool) -> Self {
+        self.force_zip64 = force;
+        self
+    }
+
+    /// Sets the "version made by" of new entries and of the zip64 end of
+    /// central directory record. Its host system also goes in entries'
+    /// "version needed to extract".
+    ///
+    /// Readers only take [NewEntry::mode] into account with a Unix (or
+    /// macOS) host system: with the MS-DOS one, they only get whether the
+    /// entry is a directory. Defaults to MS-DOS on Windows, Unix elsewhere,
+    /// and version 2.0.
+    pub fn creator_version(mut self, version: Version) -> Self {
+        self.creator_version = version;
+        self
+    }
+
+    /// Lets `selector` pick the compression method and level of each entry
+    /// started with [Self::start_entry], overriding [NewEntry::method] and
+    /// [NewEntry::level], so that already compressed data, like JPEG
+    /// images or videos, is stored rather than compressed again.
+    ///
+    /// It's given the entry, along with its [size hint](NewEntry::size_hint)
+    /// if it has one, and the first 512 bytes of its data, or all of it if
+    /// it's shorter: entries' local headers are only written once
+    /// those bytes are, and they're kept in memory until then.
+    pub fn select_compression(
+        mut self,
+        selector: impl FnMut(&NewEntry, &[u8]) -> Compression + Send + Sync + 'static,
+    ) -> Self {
+        self.selector = Some(Box::new(selector));
+        self
+    }
+
+    /// Create a state machine that appends entries to an existing archive,
+    /// without rewriting the data of its entries: the output is meant to be
+    /// written at [Archive::directory_offset], and replaces the central
+    /// directory with one that lists both the existing and the new entries.
+    ///
+    /// The archive's comment is kept, but records that couldn't be read
+    /// (see [ArchiveReadOptions::skip_invalid_entries]) are dropped.
+    ///
+    /// The archive must have been read with
+    /// [ArchiveReadOptions::keep_raw_records], so that the existing central
+    /// directory records can be carried over as they are.
+    ///
+    /// [ArchiveReadOptions::skip_invalid_entries]: super::ArchiveReadOptions::skip_invalid_entries
+    /// [ArchiveReadOptions::keep_raw_records]: super::ArchiveReadOptions::keep_raw_records
+    pub fn append(archive: &Archive) -> Result<Self, Error> {
+        let mut directory = Vec::new();
+        for entry in archive.entries() {
+            let raw = entry.raw_central_record.as_ref().ok_or_else(|| {
+                Error::IO(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "appending requires the archive to be read with keep_raw_records",
+                ))
+            })?;
+            let mut header = CentralDirectoryFileHeader::parser
+                .parse_next(&mut Partial::new(&raw.inner[..]))
+                .map_err(|_| FormatError::InvalidCentralRecord {
+                    expected: archive.entries.len() as u16,
+                    actual: directory.len() as u16,
+                })?
+                .into_owned();
+
+            // the archive may have had data prepended, which the entry's
+            // offset accounts for. zip64 offsets are in an extra field,
+            // they're left as-is.
+            if header.header_offset != u32::MAX {
+                header.header_offset = u32::try_from(entry.header_offset)
+                    .ok()
+                    .filter(|&offset| offset != u32::MAX)
+                    .ok_or_else(|| {
+                        Error::IO(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "header offset doesn't fit in the existing central directory record",
+                        ))
+                    })?;
+            }
+            directory.push(header);
+        }
+
+        trace!(
+            entries = directory.len(),
+            directory_offset = archive.directory_offset(),
+            "appending to archive"
+        );
+        Ok(Self {
+            offset: archive.directory_offset(),
+            directory,
+            comment: archive.comment().to_owned(),
+            ..Self::new()
+        })
+    }
+
+    /// Sets the archive comment, written when the archive is finished
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.comment = comment.into();
+    }
+
+    /// Writes the local file header of a new entry, finishing the current
+    /// one if any. Its data is then given with [Self::write].
+    ///
+    /// Panics if the archive is already finished.
+    pub fn start_entry(&mut self, entry: NewEntry) -> Result<(), Error> {
+        if self.selector.is_some() {
+            self.finish_entry()?;
+            self.pending = Some(PendingEntry {
+                entry,
+                leading: Vec::new(),
+            });
+            return Ok(());
+        }
+        let compressor = AnyCompressor::new(entry.method, entry.level)?;
+        self.start(entry, compressor, 0, None)
+    }
+
+    /// Starts the pending entry with the compression the selector picks,
+    /// then compresses its first bytes.
+    fn start_pending(&mut self) -> Result<(), Error> {
+        let Some(PendingEntry { mut entry, leading }) = self.pending.take() else {
+            return Ok(());
+        };
+        let selector = self
+            .selector
+            .as_mut()
+            .expect("entries are only pending with a selector");
+        let compression = selector(&entry, &leading);
+        trace!(name = %entry.name, ?compression, "selected compression");
+        entry.method = compression.method;
+        entry.level = compression.level;
+
+        let compressor = AnyCompressor::new(entry.method, entry.level)?;
+        self.start(entry, compressor, 0, None)?;
+        self.write(&leading)
+    }
+
+    /// Like [Self::start_entry], for an entry whose data is copied as-is
+    /// from another archive, see [Entry::compressed_size]: no matter its
+    /// method, that data is then given with [Self::write], and isn't
+    /// recompressed.
+    ///
+    /// The entry's name, method, timestamp, mode, comment, CRC-32 and sizes
+    /// are carried over. Since its sizes are known, it gets no data
+    /// descriptor. Encrypted entries can't be copied.
+    ///
+    /// Panics if the archive is already finished.
+    pub fn start_raw_entry(&mut self, entry: &Entry) -> Result<(), Error> {
+        if entry.is_encrypted() {
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("entry {:?} is encrypted, it can't be copied", entry.name),
+            )));
+        }
+
+        let new_entry = NewEntry {
+            name: entry.name.clone(),
+            method: entry.method,
+            level: None,
+            modified: entry.modified,
+            mode: entry.mode.into(),
+            comment: entry.comment.clone(),
+            size_hint: Some(entry.uncompressed_size),
+            utf8_flag: None,
+        };
+        let expected = DataDescriptorRecord {
+            crc32: entry.crc32,
+            compressed_size: entry.compressed_size,
+            uncompressed_size: entry.uncompressed_size,
+        };
+        // bits 1 and 2 are method-specific, see [MethodDetails]
+        let method_flags = entry.flags & 0b110;
+        self.start(
+            new_entry,
+            AnyCompressor::Store(Default::default()),
+            method_flags,
+            Some(expected),
+        )
+    }
+
+    fn start(
+        &mut self,
+        entry: NewEntry,
+        compressor: AnyCompressor,
+        method_flags: u16,
+        raw: Option<DataDescriptorRecord>,
+    ) -> Result<(), Error> {
+        self.finish_entry()?;
+
+        let header_offset = self.offset;
+        let zip64 = self.force_zip64
+            || match &raw {
+                Some(raw) => needs_zip64(raw.compressed_size) || needs_zip64(raw.uncompressed_size),
+                // 64-bit sizes in the data descriptor may be needed
+                None => entry.size_hint.map_or(true, needs_zip64),
+            };
+
+        let mut flags = method_flags;
+        if raw.is_none() {
+            flags |= FLAG_DATA_DESCRIPTOR;
+        }
+        let utf8 = entry
+            .utf8_flag
+            .unwrap_or(!entry.name.is_ascii() || !entry.comment.is_ascii());
+        if utf8 {
+            flags |= FLAG_UTF8;
+        }
+
+        let mut external_attrs = entry.mode.0 << 16;
+        if entry.mode & UnixMode::IFMT == UnixMode::IFDIR {
+            external_attrs |= MSDOS_DIR;
+        }
+
+        let modified = MsdosTimestamp::from_datetime(&entry.modified).unwrap_or(
+            // 1980-01-01 00:00:00
+            MsdosTimestamp {
+                time: 0,
+                date: 1 << 5 | 1,
+            },
+        );
+
+        // unless the entry is copied, sizes are in the data descriptor, and
+        // the zip64 extra field only tells readers it has 64-bit sizes
+        let mut local = LocalFileHeader {
+            reader_version: self.reader_version(VERSION),
+            flags,
+            method: entry.method,
+            modified,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            name: Cow::Borrowed(entry.name.as_bytes()),
+            extra: Cow::Borrowed(&[]),
+            method_specific: MethodSpecific::None,
+        };
+        let mut zip64_field = ExtraZip64Field::default();
+        if let Some(raw) = &raw {
+            local.crc32 = raw.crc32;
+            local.compressed_size = raw.compressed_size as u32;
+            local.uncompressed_size = raw.uncompressed_size as u32;
+            zip64_field.compressed_size = raw.compressed_size;
+            zip64_field.uncompressed_size = raw.uncompressed_size;
+        }
+        if zip64 {
+            local.reader_version = self.reader_version(VERSION_ZIP64);
+            local.compressed_size = u32::MAX;
+            local.uncompressed_size = u32::MAX;
+            let settings = ExtraFieldSettings {
+                uncompressed_size_u32: u32::MAX,
+                compressed_size_u32: u32::MAX,
+                header_offset_u32: 0,
+            };
+            let mut extra = Vec::new();
+            zip64_field.write_to(&mut extra, settings)?;
+            local.extra = Cow::Owned(extra);
+        }
+        trace!(name = %entry.name, header_offset, raw = raw.is_some(), "starting entry");
+        self.write_record(|w| local.write_to(w))?;
+
+        self.current = Some(CurrentEntry {
+            header: CentralDirectoryFileHeader {
+                creator_version: self.creator_version,
+                reader_version: self.reader_version(VERSION),
+                flags,
+                method: entry.method,
+                modified,
+                crc32: 0,
+                compressed_size: 0,
+                uncompressed_size: 0,
+                disk_nbr_start: 0,
+                internal_attrs: 0,
+                external_attrs,
+                header_offset: 0,
+                name: Cow::Owned(entry.name.into_bytes()),
+                extra: Cow::Borrowed(&[]),
+                comment: Cow::Owned(entry.comment.into_bytes()),
+            },
+            header_offset,
+            zip64,
+            raw,
+            compressor,
+            hasher: crc32fast::Hasher::new(),
+            compressed_size: 0,
+            uncompressed_size: 0,
+        });
+        Ok(())
+    }
+
+    /// Compresses data for the current entry.
+    ///
+    /// Panics if no entry was started, or if the archive is already finished.
+    pub fn write(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        if let Some(pending) = &mut self.pending {
+            let n = data.len().min(LEADING_BYTES - pending.leading.len());
+            pending.leading.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if pending.leading.len() < LEADING_BYTES {
+                return Ok(());
+            }
+            self.start_pending()?;
+        }
+
+        let current = self
+            .current
+            .as_mut()
+            .expect("write called without starting an entry");
+
+        let before = self.output.len();
+        current.compressor.compress(data, &mut self.output)?;
+        let written = (self.output.len() - before) as u64;
+
+        if current.raw.is_none() {
+            current.hasher.update(data);
+        }
+        current.uncompressed_size += data.len() as u64;
+        current.compressed_size += written;
+        self.offset += written;
+        Ok(())
+    }
+
+    /// Finishes the current entry, if any, by flushing the compressor and
+    /// writing the data descriptor. This is done implicitly by
+    /// [Self::start_entry] and [Self::finish].
+    pub fn finish_entry(&mut self) -> Result<(), Error> {
+        assert!(!self.finished, "archive is already finished");
+        self.start_pending()?;
+
+        let Some(mut current) = self.current.take() else {
+            return Ok(());
+        };
+
+        let before = self.output.len();
+        current.compressor.finish(&mut self.output)?;
+        let written = (self.output.len() - before) as u64;
+        current.compressed_size += written;
+        self.offset += written;
+
+        let descriptor = match current.raw {
+            Some(expected) => {
+                if current.compressed_size != expected.compressed_size {
+                    return Err(Error::IO(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "copied {} bytes of entry data, expected {}",
+                            current.compressed_size, expected.compressed_size
+                        ),
+                    )));
+                }
+                expected
+            }
+            None => {
+                let descriptor = DataDescriptorRecord {
+                    crc32: current.hasher.finalize(),
+                    compressed_size: current.compressed_size,
+                    uncompressed_size: current.uncompressed_size,
+                };
+                let zip64 = current.zip64
+                    || needs_zip64(descriptor.compressed_size)
+                    || needs_zip64(descriptor.uncompressed_size);
+                self.write_record(|w| descriptor.write_to(w, zip64))?;
+                descriptor
+            }
+        };
+
+        // values that don't fit are moved to a zip64 extra field
+        let mut header = current.header;
+        header.crc32 = descriptor.crc32;
+        let zip64_field = ExtraZip64Field {
+            uncompressed_size: descriptor.uncompressed_size,
+            compressed_size: descriptor.compressed_size,
+            header_offset: current.header_offset,
+            disk_start: None,
+        };
+        let u32_or_max = |value: u64| {
+            if self.force_zip64 || needs_zip64(value) {
+                u32::MAX
+            } else {
+                value as u32
+            }
+        };
+        let settings = ExtraFieldSettings {
+            uncompressed_size_u32: u32_or_max(zip64_field.uncompressed_size),
+            compressed_size_u32: u32_or_max(zip64_field.compressed_size),
+            header_offset_u32: u32_or_max(zip64_field.header_offset),
+        };
+        header.uncompressed_size = settings.uncompressed_size_u32;
+        header.compressed_size = settings.compressed_size_u32;
+        header.header_offset = settings.header_offset_u32;
+        if [
+            settings.uncompressed_size_u32,
+            settings.compressed_size_u32,
+            settings.header_offset_u32,
+        ]
+        .contains(&u32::MAX)
+        {
+            let mut extra = Vec::new();
+            zip64_field.write_to(&mut extra, settings)?;
+            header.extra = Cow::Owned(extra);
+            header.reader_version = self.reader_version(VERSION_ZIP64);
+        }
+        self.directory.push(header);
+        Ok(())
+    }
+
+    /// Finishes the current entry, then writes the central directory and
+    /// the end of central directory record. Once the output is consumed,
+    /// the archive is complete.
+    ///
+    /// Panics if the archive is already finished.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        self.finish_entry()?;
+
+        let directory_offset = self.offset;
+        let directory_records = self.directory.len() as u64;
+
+        let directory = std::mem::take(&mut self.directory);
+        for header in &directory {
+            self.write_record(|w| header.write_to(w))?;
+        }
+        let directory_size = self.offset - directory_offset;
+
+        let zip64 = self.force_zip64
+            || directory_records >= u16::MAX as u64
+            || needs_zip64(directory_size)
+            || needs_zip64(directory_offset);
+        if zip64 {
+            let eocd64_offset = self.offset;
+            let eocd64 = EndOfCentralDirectory64Record {
+                // the size of the record, not counting the signature and
+                // this field
+                record_size: 44,
+                creator_version: u16::from_le_bytes([
+                    self.creator_version.version,
+                    self.creator_version.host_system.into(),
+                ]),
+                reader_version: VERSION_ZIP64 as u16,
+                disk_nbr: 0,
+                dir_disk_nbr: 0,
+                dir_records_this_disk: directory_records,
+                directory_records,
+                directory_size,
+                directory_offset,
+            };
+            self.write_record(|w| eocd64.write_to(w))?;
+
+            let locator = EndOfCentralDirectory64Locator {
+                dir_disk_number: 0,
+                directory_offset: eocd64_offset,
+                total_disks: 1,
+            };
+            self.write_record(|w| locator.write_to(w))?;
+        }
+
+        // values that don't fit are only in the zip64 record
+        let eocd = EndOfCentralDirectoryRecord {
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: u16::try_from(directory_records).unwrap_or(u16::MAX),
+            directory_records: u16::try_from(directory_records).unwrap_or(u16::MAX),
+            directory_size: u32::try_from(directory_size).unwrap_or(u32::MAX),
+            directory_offset: u32::try_from(directory_offset).unwrap_or(u32::MAX),
+            comment: Cow::Owned(self.comment.as_bytes().to_vec()),
+        };
+        self.write_record(|w| eocd.write_to(w))?;
+
+        trace!(directory_records, directory_size, zip64, "finished archive");
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Returns true once [Self::finish] was called
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Output that's ready to be written out. Once it is (even partially),
+    /// call [Self::consume] with the number of bytes written.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Marks `n` bytes of [Self::output] as written
+    pub fn consume(&mut self, n: usize) {
+        self.output.drain(..n);
+    }
+
+    /// Serializes a record to the output, keeping track of the offset
+    fn write_record(
+        &mut self,
+        write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>,
+    ) -> Result<(), Error> {
+        let before = self.output.len();
+        if let Err(e) = write(&mut self.output) {
+            self.output.truncate(before);
+            return Err(e.into());
+        }
+        self.offset += (self.output.len() - before) as u64;
+        Ok(())
+    }
+
+    /// The "version needed to extract" for `version`, on the configured host
+    fn reader_version(&self, version: u8) -> Version {
+        Version {
+            host_system: self.creator_version.host_system,
+            version,
+        }
+    }
+}
+
+/// Whether a size or offset doesn't fit in a u32 field, where 0xFFFF_FFFF
+/// means the value is in a zip64 record.
+fn needs_zip64(value: u64) -> bool {
+    value >= u32::MAX as u64
+}
@@ -0,0 +1,17 @@
+use crate::error::Error;
+
+use super::Compressor;
+
+#[derive(Default)]
+pub(crate) struct StoreEnc;
+
+impl Compressor for StoreEnc {
+    fn compress(&mut self, in_buf: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        out.extend_from_slice(in_buf);
+        Ok(())
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Helpers for JAR files, which are zip archives with a few Java-specific
+//! conventions: a manifest at [MANIFEST_NAME], and, for multi-release JARs,
+//! entries overriding the base ones for specific Java releases, under
+//! `META-INF/versions/N/`.
+//!
+//! See the [JAR File Specification](https://docs.oracle.com/en/java/javase/21/docs/specs/jar/jar.html).
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{Error, FormatError},
+    parse::{Archive, Entry},
+};
+
+/// The name of the manifest entry in a JAR file.
+pub const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+
+/// Where multi-release JARs keep their versioned entries.
+const VERSIONS_PREFIX: &str = "META-INF/versions/";
+
+/// Versioned entries for releases older than this are ignored by the JVM.
+const MIN_RELEASE: u32 = 9;
+
+/// A parsed `META-INF/MANIFEST.MF`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Attributes of the main section, like `Main-Class` or `Multi-Release`.
+    pub main_attributes: Attributes,
+
+    /// Per-entry sections, in the order they appear in.
+    pub sections: Vec<Section>,
+}
+
+/// A per-entry section of a [Manifest].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Section {
+    /// The value of the section's `Name` attribute, usually the name of an
+    /// entry or a package directory.
+    pub name: String,
+
+    /// The section's attributes, not including `Name`.
+    pub attributes: Attributes,
+}
+
+/// A list of manifest attributes, in the order they appear in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pairs: Vec<(String, String)>,
+}
+
+impl Attributes {
+    /// Returns the value of an attribute. Attribute names are compared
+    /// case-insensitively, as the JVM does.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over all attributes, as `(name, value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The number of attributes.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Whether there are no attributes at all.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Parses the contents of a manifest.
+    ///
+    /// Lines may end with CRLF, LF or CR, and values may be continued on
+    /// the next line by starting it with a single space.
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+        let text = std::str::from_utf8(data).map_err(|e| {
+            let line = 1 + data[..e.valid_up_to()]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
+            FormatError::InvalidManifest { line }
+        })?;
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+        let mut manifest = Manifest::default();
+        let mut in_main = true;
+        let mut section_start = 1;
+        let mut pairs: Vec<(String, String)> = Vec::new();
+
+        for (i, line) in text.split('\n').chain(std::iter::once("")).enumerate() {
+            let line_nr = i + 1;
+
+            if line.is_empty() {
+                // end of a section
+                let pairs = std::mem::take(&mut pairs);
+                if in_main {
+                    manifest.main_attributes = Attributes { pairs };
+                    in_main = false;
+                } else if !pairs.is_empty() {
+                    let mut pairs = pairs.into_iter();
+                    let name = match pairs.next() {
+                        Some((k, v)) if k.eq_ignore_ascii_case("Name") => v,
+                        _ => {
+                            return Err(FormatError::InvalidManifest {
+                                line: section_start,
+                            }
+                            .into())
+                        }
+                    };
+                    manifest.sections.push(Section {
+                        name,
+                        attributes: Attributes {
+                            pairs: pairs.collect(),
+                        },
+                    });
+                }
+                section_start = line_nr + 1;
+                continue;
+            }
+
+            if let Some(continued) = line.strip_prefix(' ') {
+                match pairs.last_mut() {
+                    Some((_, value)) => value.push_str(continued),
+                    None => return Err(FormatError::InvalidManifest { line: line_nr }.into()),
+                }
+                continue;
+            }
+
+            match line.split_once(": ") {
+                Some((name, value)) if !name.is_empty() => {
+                    pairs.push((name.to_string(), value.to_string()))
+                }
+                _ => return Err(FormatError::InvalidManifest { line: line_nr }.into()),
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Returns the per-entry section with the given name, if any.
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Whether the main section has `Multi-Release: true`, in which case
+    /// entries under `META-INF/versions/N/` override the base ones when
+    /// running on Java release `N` or later.
+    pub fn is_multi_release(&self) -> bool {
+        self.main_attributes
+            .get("Multi-Release")
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+    }
+}
+
+/// Splits the name of a versioned entry of a multi-release JAR, like
+/// `META-INF/versions/11/com/example/Foo.class`, into its release and the
+/// name of the entry it overrides, like `(11, "com/example/Foo.class")`.
+///
+/// Returns `None` for names that aren't under `META-INF/versions/N/`.
+pub fn split_versioned_name(name: &str) -> Option<(u32, &str)> {
+    let rest = name.strip_prefix(VERSIONS_PREFIX)?;
+    let (release, name) = rest.split_once('/')?;
+    // `u32::from_str` accepts a leading `+`, the JVM doesn't
+    if !release.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((release.parse().ok()?, name))
+}
+
+impl Archive {
+    /// Returns the manifest entry, if this is a JAR file.
+    pub fn manifest_entry(&self) -> Option<&Entry> {
+        self.by_name(MANIFEST_NAME)
+    }
+
+    /// Looks up an entry by name the way the JVM does for a multi-release
+    /// JAR running on the given Java release: the entry from the highest
+    /// `META-INF/versions/N/` with `N <= release` wins, and the base entry
+    /// is returned if there's none.
+    ///
+    /// This doesn't check [Manifest::is_multi_release]: for JARs that
+    /// aren't multi-release, use [Archive::by_name] instead.
+    pub fn by_name_for_release<N: AsRef<str>>(&self, name: N, release: u32) -> Option<&Entry> {
+        let name = name.as_ref();
+        self.entries()
+            .filter_map(|e| {
+                let (r, n) = split_versioned_name(&e.name)?;
+                (n == name && (MIN_RELEASE..=release).contains(&r)).then_some((r, e))
+            })
+            .max_by_key(|&(r, _)| r)
+            .map(|(_, e)| e)
+            .or_else(|| self.by_name(name))
+    }
+
+    /// Lists the entries a multi-release JAR exposes on the given Java
+    /// release, as `(name, entry)` pairs: versioned entries that apply
+    /// replace the base entry of the same name, and ones that don't apply
+    /// are left out.
+    ///
+    /// Names are listed in the order they first appear in the central
+    /// directory, whether that's as a base or a versioned entry.
+    pub fn entries_for_release(&self, release: u32) -> Vec<(&str, &Entry)> {
+        // name => (index in `out`, release of the entry there)
+        let mut seen: HashMap<&str, (usize, u32)> = HashMap::new();
+        let mut out: Vec<(&str, &Entry)> = Vec::new();
+
+        for entry in self.entries() {
+            let (entry_release, name) = match split_versioned_name(&entry.name) {
+                Some((r, n)) if (MIN_RELEASE..=release).contains(&r) && !n.is_empty() => (r, n),
+                Some(_) => continue,
+                None => (0, entry.name.as_str()),
+            };
+
+            match seen.get_mut(name) {
+                Some((index, seen_release)) => {
+                    if entry_release > *seen_release {
+                        *seen_release = entry_release;
+                        out[*index] = (name, entry);
+                    }
+                }
+                None => {
+                    seen.insert(name, (out.len(), entry_release));
+                    out.push((name, entry));
+                }
+            }
+        }
+
+        out
+    }
+}
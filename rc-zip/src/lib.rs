@@ -12,13 +12,20 @@
 //!   * [rc-zip-sync](https://crates.io/crates/rc-zip-sync) for using std I/O traits
 //!   * [rc-zip-tokio](https://crates.io/crates/rc-zip-tokio) for using tokio I/O traits
 
+pub mod apk;
+pub mod apple_double;
 pub mod encoding;
 pub mod error;
 pub mod fsm;
+pub mod jar;
 pub mod parse;
+pub mod progress;
+pub mod verify;
 
 #[cfg(any(test, feature = "corpus"))]
 pub mod corpus;
 
 // dependencies re-exports
 pub use chrono;
+#[cfg(feature = "glob")]
+pub use globset;
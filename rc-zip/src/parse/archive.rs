@@ -1,14 +1,21 @@
+use std::{collections::HashMap, path::PathBuf};
+
 use chrono::{offset::Utc, DateTime, TimeZone};
 use num_enum::{FromPrimitive, IntoPrimitive};
 use ownable::{IntoOwned, ToOwned};
-use winnow::{binary::le_u16, PResult, Partial};
+use tracing::trace;
+use winnow::{binary::le_u16, PResult, Parser, Partial};
 
 use crate::{
-    encoding::Encoding,
-    parse::{Mode, Version},
+    encoding::{Encoding, EncodingSource, NameDecoding},
+    error::{EntryParseError, Error, FormatError, Warning},
+    parse::{Located, MethodDetails, Mode, MsdosMode, Version},
 };
 
-use super::{zero_datetime, ExtraField, NtfsAttr};
+use super::{
+    normalize_name, zero_datetime, ExtraField, ExtraFieldSettings, ExtraFields, ExtraUnicodeField,
+    NtfsAttr,
+};
 
 /// An Archive contains general information about a zip files, along with a list
 /// of [entries][Entry].
@@ -20,9 +27,15 @@ use super::{zero_datetime, ExtraField, NtfsAttr};
 /// [rc-zip-tokio](https://crates.io/crates/rc-zip-tokio).
 pub struct Archive {
     pub(crate) size: u64,
+    pub(crate) directory_offset: u64,
+    pub(crate) global_offset: i64,
     pub(crate) encoding: Encoding,
     pub(crate) entries: Vec<Entry>,
+    pub(crate) invalid_entries: Vec<EntryParseError>,
     pub(crate) comment: String,
+    pub(crate) raw_comment: Vec<u8>,
+    pub(crate) duplicate_names: DuplicateNamePolicy,
+    pub(crate) warnings: Vec<Warning>,
 }
 
 impl Archive {
@@ -32,15 +45,125 @@ impl Archive {
         self.size
     }
 
+    /// Where the central directory starts, in bytes: this is right after
+    /// the data of the last entry, and where entries are appended, see
+    /// [ArchiveWriterFsm::append](crate::fsm::ArchiveWriterFsm::append).
+    #[inline(always)]
+    pub fn directory_offset(&self) -> u64 {
+        self.directory_offset
+    }
+
+    /// How far off the offsets recorded in the archive were: this is the
+    /// size of data prepended to the archive after it was written, like the
+    /// executable stub of a self-extracting archive, and is negative for
+    /// archives truncated at the start. It's already applied to
+    /// [Entry::header_offset] and [Self::directory_offset].
+    ///
+    /// This is zero for self-extracting archives made by tools that adjust
+    /// offsets, like `zip -A`: see [Self::prepended_data_len] for those.
+    #[inline(always)]
+    pub fn global_offset(&self) -> i64 {
+        self.global_offset
+    }
+
+    /// How many bytes come before the first entry, or before the central
+    /// directory for archives without entries, whether recorded offsets
+    /// account for them or not, see [Self::global_offset]. This is the size
+    /// of the executable stub of self-extracting archives, and zero for
+    /// most other archives.
+    pub fn prepended_data_len(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.header_offset)
+            .chain(std::iter::once(self.directory_offset))
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Problems found while reading the archive that weren't errors, see
+    /// [Strictness].
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     /// Iterate over all files in this zip, read from the central directory.
     pub fn entries(&self) -> impl Iterator<Item = &Entry> {
         self.entries.iter()
     }
 
+    /// Iterate over all records of the central directory, in order: valid
+    /// ones as entries, and ones that couldn't be turned into entries as
+    /// errors.
+    ///
+    /// Unless the archive was read with
+    /// [ArchiveReadOptions::skip_invalid_entries](crate::fsm::ArchiveReadOptions::skip_invalid_entries),
+    /// this is the same as [Self::entries], since reading it would have
+    /// failed on the first invalid record.
+    pub fn entries_with_errors(&self) -> impl Iterator<Item = Result<&Entry, &EntryParseError>> {
+        let mut entries = self.entries.iter();
+        let mut invalid = self.invalid_entries.iter().peekable();
+        (0..).map_while(move |index| match invalid.next_if(|e| e.index == index) {
+            Some(e) => Some(Err(e)),
+            None => entries.next().map(Ok),
+        })
+    }
+
+    /// Consumes the archive, returning all its entries.
+    pub fn into_entries(self) -> impl Iterator<Item = Entry> {
+        self.entries.into_iter()
+    }
+
     /// Attempts to look up an entry by name. This is usually a bad idea,
     /// as names aren't necessarily normalized in zip archives.
+    ///
+    /// This goes through all entries: for many lookups, or to match names
+    /// regardless of how they're normalized, see [Archive::build_index], or
+    /// [Archive::by_name_ci] for a single one.
+    ///
+    /// If several entries have that name, which one is returned depends on
+    /// the [DuplicateNamePolicy] the archive was read with: see
+    /// [Self::entries_by_name] to get all of them.
     pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<&Entry> {
-        self.entries.iter().find(|&x| x.name == name.as_ref())
+        let mut matching = self.entries.iter().filter(|&x| x.name == name.as_ref());
+        match self.duplicate_names {
+            DuplicateNamePolicy::FirstWins => matching.next(),
+            // with `Error`, there's at most one match anyway
+            DuplicateNamePolicy::LastWins | DuplicateNamePolicy::Error => matching.last(),
+        }
+    }
+
+    /// Returns all entries named `name`, in the order of the central
+    /// directory, whatever the [DuplicateNamePolicy] the archive was read
+    /// with. There's usually at most one, but archives can have several
+    /// entries with the same name, for example when files were appended to
+    /// an existing archive.
+    pub fn entries_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.entries.iter().filter(move |entry| entry.name == name)
+    }
+
+    /// Computes totals over all entries, from the central directory only:
+    /// nothing is decompressed, so sizes are as advertised by the archive.
+    pub fn stats(&self) -> ArchiveStats {
+        let mut stats = ArchiveStats::default();
+        for entry in &self.entries {
+            stats.entries += 1;
+            // sizes come straight from the archive, they may be bogus
+            stats.compressed_size = stats.compressed_size.saturating_add(entry.compressed_size);
+            stats.uncompressed_size = stats
+                .uncompressed_size
+                .saturating_add(entry.uncompressed_size);
+            *stats.methods.entry(entry.method).or_default() += 1;
+            if entry.is_encrypted() {
+                stats.encrypted += 1;
+            }
+            if stats.oldest.map_or(true, |oldest| entry.modified < oldest) {
+                stats.oldest = Some(entry.modified);
+            }
+            if stats.newest.map_or(true, |newest| entry.modified > newest) {
+                stats.newest = Some(entry.modified);
+            }
+        }
+        stats
     }
 
     /// Returns the detected character encoding for text fields
@@ -50,12 +173,84 @@ impl Archive {
         self.encoding
     }
 
-    /// Returns the comment for this archive, if any. When reading
-    /// a zip file with an empty comment field, this will return None.
+    /// Returns the comment for this archive, decoded with [Self::encoding]
+    /// like entry names. This is empty if the archive has no comment.
     #[inline(always)]
     pub fn comment(&self) -> &str {
         &self.comment
     }
+
+    /// Returns [Self::comment] as stored in the end of central directory
+    /// record, before decoding.
+    #[inline(always)]
+    pub fn raw_comment(&self) -> &[u8] {
+        &self.raw_comment
+    }
+}
+
+/// Totals over the entries of an [Archive], see [Archive::stats].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    /// Number of entries, including directories and symlinks.
+    pub entries: usize,
+
+    /// Sum of all entries' [compressed sizes](Entry::compressed_size).
+    pub compressed_size: u64,
+
+    /// Sum of all entries' [uncompressed sizes](Entry::uncompressed_size).
+    pub uncompressed_size: u64,
+
+    /// Number of entries using each compression method.
+    pub methods: HashMap<Method, usize>,
+
+    /// Number of [encrypted](Entry::is_encrypted) entries.
+    pub encrypted: usize,
+
+    /// The oldest [modification time](Entry::modified), if there are entries.
+    pub oldest: Option<DateTime<Utc>>,
+
+    /// The newest [modification time](Entry::modified), if there are entries.
+    pub newest: Option<DateTime<Utc>>,
+}
+
+/// Which entry [Archive::by_name] returns when several entries have the same
+/// name. [Archive::entries_by_name] returns all of them.
+///
+/// Zip archives can legitimately contain several entries with the same name,
+/// for example when files were appended to an existing archive. However,
+/// different tools resolve these differently, which can be abused to show
+/// one tool something else than what another one sees (think config files
+/// vetted by one and used by the other).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DuplicateNamePolicy {
+    /// The first entry in the central directory wins.
+    #[default]
+    FirstWins,
+
+    /// The last entry in the central directory wins. This is what most unzip
+    /// tools effectively do, since later entries overwrite earlier ones when
+    /// extracted.
+    LastWins,
+
+    /// Reading an archive with duplicate names fails with
+    /// [FormatError::DuplicateEntryName](crate::error::FormatError::DuplicateEntryName).
+    /// Names are compared once [normalized](normalize_name), since names
+    /// like `a/b.txt` and `./a//b.txt` would be extracted to the same path.
+    Error,
+}
+
+/// What to do with problems in an archive that can be worked around, see
+/// [Warning](crate::error::Warning).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Strictness {
+    /// Reading the archive fails.
+    #[default]
+    Strict,
+
+    /// The problem is worked around, and reported by [Archive::warnings].
+    /// Problems that aren't errors in the first place are reported either
+    /// way.
+    Permissive,
 }
 
 /// Describes a zip archive entry (a file, a directory, a symlink)
@@ -67,16 +262,42 @@ pub struct Entry {
     /// files in the wild with all sorts of evil variants, so, be conservative
     /// in what you accept.
     ///
-    /// See also [Self::sanitized_name], which returns a sanitized version of
-    /// the name, working around zip slip vulnerabilities.
+    /// See also [Self::safe_path], which returns the path to extract the
+    /// entry to, if it's safe to do so.
     pub name: String,
 
+    /// How [Self::name] and [Self::comment] were decoded, for telling
+    /// whether they might be mangled.
+    pub name_decoding: NameDecoding,
+
     /// Compression method: Store, Deflate, Bzip2, etc.
     pub method: Method,
 
-    /// Comment is any arbitrary user-defined string shorter than 64KiB
+    /// Parameters of [Self::method], like the Deflate compression option or
+    /// the strength of AES encryption.
+    pub method_details: MethodDetails,
+
+    /// Comment is any arbitrary user-defined string shorter than 64KiB.
+    ///
+    /// It's decoded like [Self::name], see [Self::name_decoding], unless
+    /// it's taken from a Unicode Comment extra field.
     pub comment: String,
 
+    /// [Self::name] as stored in the zip file, before decoding. If it was
+    /// taken from a Unicode Path extra field, this is still the header's.
+    pub raw_name: Vec<u8>,
+
+    /// [Self::comment] as stored in the zip file, before decoding. This is
+    /// empty for entries read from their local header, which has no comment.
+    pub raw_comment: Vec<u8>,
+
+    /// The extra field as stored in the header the entry was read from,
+    /// before parsing: see [Self::extra_fields] to go through its fields.
+    pub raw_extra: Vec<u8>,
+
+    /// How the zip64 extra field of [Self::raw_extra] is laid out
+    pub(crate) extra_settings: ExtraFieldSettings,
+
     /// This entry's "last modified" timestamp - with caveats
     ///
     /// Due to the history of the ZIP file format, this may be inaccurate. It may be offset
@@ -85,6 +306,8 @@ pub struct Entry {
     /// epoch, if something went really wrong.
     ///
     /// If you're reading this after the year 2038, or after the year 2108, godspeed.
+    ///
+    /// See [Self::timestamp_source] for where it comes from.
     pub modified: DateTime<Utc>,
 
     /// This entry's "created" timestamp, if available.
@@ -97,6 +320,12 @@ pub struct Entry {
     /// See [Self::accessed] for caveats.
     pub accessed: Option<DateTime<Utc>>,
 
+    /// Where [Self::modified] comes from. When there are several extra
+    /// fields with timestamps, the most precise one is used, whatever their
+    /// order: [Self::created] and [Self::accessed] may come from a less
+    /// precise one, if that's the only one that has them.
+    pub timestamp_source: TimestampSource,
+
     /// Offset of the local file header in the zip file
     ///
     /// ```text
@@ -118,20 +347,21 @@ pub struct Entry {
     /// General purpose bit flag
     ///
     /// In the zip format, the most noteworthy flag (bit 11) is for UTF-8 names.
-    /// Other flags can indicate: encryption (unsupported), various compression
-    /// settings (depending on the [Method] used).
+    /// Other flags can indicate: encryption (see [Self::is_encrypted]),
+    /// various compression settings (depending on the [Method] used).
     ///
     /// For LZMA, general-purpose bit 1 denotes the EOS marker.
     pub flags: u16,
 
     /// Unix user ID
     ///
-    /// Only present if a Unix extra field or New Unix extra field was found.
+    /// Only present if a Unix extra field (0x000d or Info-ZIP's 0x5855 and
+    /// 0x7855) or New Unix extra field (0x7875) was found: Info-ZIP's old
+    /// ones only have ids in local headers. The New Unix field, which has
+    /// 32-bit ids, is preferred.
     pub uid: Option<u32>,
 
-    /// Unix group ID
-    ///
-    /// Only present if a Unix extra field or New Unix extra field was found.
+    /// Unix group ID, found like [Self::uid]
     pub gid: Option<u32>,
 
     /// CRC-32 hash as found in the central directory.
@@ -150,9 +380,92 @@ pub struct Entry {
 
     /// File mode.
     pub mode: Mode,
+
+    /// External file attributes as found in the central directory, whose
+    /// meaning depends on the system the entry was made on: see
+    /// [Self::mode] for them decoded, and [Self::msdos_mode]. This is 0 for
+    /// entries read from their local header, which has none.
+    pub external_attrs: u32,
+
+    /// The central directory record this entry was read from, verbatim, and
+    /// its offset in the zip file.
+    ///
+    /// Only present if the archive was read with
+    /// [ArchiveReadOptions::keep_raw_records](crate::fsm::ArchiveReadOptions::keep_raw_records).
+    pub raw_central_record: Option<Located<Vec<u8>>>,
+
+    /// Where this entry's metadata comes from, and so how far it can be
+    /// trusted: anything other than [EntryTrust::CentralDirectory] and
+    /// [EntryTrust::LocalHeader] comes from
+    /// [RecoveryFsm](crate::fsm::RecoveryFsm).
+    pub trust: EntryTrust,
+}
+
+/// Where an entry's timestamps come from, see [Entry::timestamp_source].
+/// Later variants are more precise, and preferred over earlier ones.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TimestampSource {
+    /// The MS-DOS timestamp of the entry's header, which has no time zone:
+    /// it's taken as UTC, but is usually the local time of whoever made
+    /// the archive. It only has a modification time, to 2 seconds.
+    Msdos,
+
+    /// A Unix extra field, PKWARE's or Info-ZIP's old one.
+    Unix,
+
+    /// An extended timestamp extra field, in UTC, to the second.
+    ExtendedTimestamp,
+
+    /// An NTFS extra field, in UTC, to 100 nanoseconds.
+    Ntfs,
+}
+
+/// Where an entry's metadata comes from, see [Entry::trust].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryTrust {
+    /// The central directory, as is normally the case.
+    CentralDirectory,
+
+    /// The entry's local header, which had its sizes, when reading an
+    /// archive without its central directory.
+    LocalHeader,
+
+    /// The entry's local header, with sizes from a data descriptor that was
+    /// found right where the data ended.
+    DataDescriptor,
+
+    /// The entry's local header, which didn't have its sizes: its data is
+    /// assumed to end where the next record starts. Its CRC-32 and sizes
+    /// are unknown, so reading it may well fail.
+    Guessed,
+
+    /// The entry's local header, but its data goes past the end of the
+    /// file: reading it fails.
+    Truncated,
 }
 
 impl Entry {
+    /// Whether the entry is encrypted (general purpose bit 0). WinZip AES
+    /// entries (see [MethodDetails::Aes]) can be read with a password, with
+    /// the `aes` feature; traditional PKWARE encryption and strong
+    /// encryption (see [Self::is_strongly_encrypted]) can't be read.
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    /// Whether the entry is encrypted with PKWARE strong encryption (general
+    /// purpose bit 6), rather than traditional PKWARE encryption or WinZip AES.
+    pub fn is_strongly_encrypted(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    /// Returns the MS-DOS attributes of the entry, like whether it's hidden
+    /// or read-only: the low byte of [Self::external_attrs]. Most tools set
+    /// them whatever system they run on, but some leave them all unset.
+    pub fn msdos_mode(&self) -> MsdosMode {
+        MsdosMode(self.external_attrs & 0xff)
+    }
+
     /// Returns a sanitized version of the entry's name, if it
     /// seems safe. In particular, if this method feels like the
     /// entry name is trying to do a zip slip (cf.
@@ -189,8 +502,192 @@ impl Entry {
         }
     }
 
+    /// Returns the path this entry should be extracted to, relative to the
+    /// destination directory, or None if it's not safe to extract: if its
+    /// name is absolute, starts with a drive letter, or has a `..`
+    /// component (cf. <https://snyk.io/research/zip-slip-vulnerability>).
+    ///
+    /// The name is [normalized](normalize_name) first, like when
+    /// looking entries up, so both `/` and `\\` are treated as separators,
+    /// wherever the archive was made. `.` components are dropped too, so
+    /// `a//./b` is extracted to `a/b`, and names with nothing left, like
+    /// `./`, are None.
+    ///
+    /// This is stricter than [Self::sanitized_name], and is what
+    /// extraction helpers in the higher-level crates use.
+    pub fn safe_path(&self) -> Option<PathBuf> {
+        let name = normalize_name(&self.name);
+        let mut path = PathBuf::new();
+        for component in relative_components(&name)? {
+            match component {
+                "" | "." => {}
+                ".." => return None,
+                component => path.push(component),
+            }
+        }
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Returns `target`, the target of this symlink entry, as a path to
+    /// create the symlink with, or None if it's not safe to: if it's
+    /// absolute, starts with a drive letter, or if following it from
+    /// [Self::safe_path] would leave the destination directory.
+    ///
+    /// `..` components are allowed at the start of the target, as long as
+    /// they stay within it, so `a/link` may point to `../b`, but not to
+    /// `../../b`. They aren't allowed after other components: `b/..` isn't
+    /// necessarily where it seems to be if `b` is itself a symlink.
+    pub fn safe_symlink_target(&self, target: &str) -> Option<PathBuf> {
+        // how many levels up the target may go: the depth of the symlink's
+        // parent directory.
+        let mut up = self.safe_path()?.components().count() - 1;
+        let mut went_down = false;
+
+        let mut path = PathBuf::new();
+        for component in relative_components(target)? {
+            match component {
+                "" | "." => continue,
+                ".." if went_down => return None,
+                ".." => up = up.checked_sub(1)?,
+                _ => went_down = true,
+            }
+            path.push(component);
+        }
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Returns the fields of [Self::raw_extra], including the ones this
+    /// crate doesn't know about, e.g. to carry them over when repacking.
+    pub fn extra_fields(&self) -> ExtraFields<'_> {
+        ExtraFields::new(&self.raw_extra, self.extra_settings)
+    }
+
+    /// Parses the extra fields in `extra` and applies them to the entry,
+    /// except for the Unicode Path and Comment extra fields, which are
+    /// returned for [Self::set_text].
+    ///
+    /// Timestamps are taken from the most precise field that has them,
+    /// whatever the order of the fields: NTFS (100ns), then extended
+    /// timestamp, then Unix (seconds), then the MS-DOS timestamp the entry
+    /// started with (2s, local time).
+    ///
+    /// Sizes and offsets that `settings` has as 0xFFFF_FFFF are taken from
+    /// the zip64 extra field, which has to be there.
+    ///
+    /// With [Strictness::Permissive], an invalid extra field and those after
+    /// it are ignored rather than failing, in which case the returned bool
+    /// is true. So is a missing zip64 extra field, leaving those values
+    /// masked.
+    pub(crate) fn set_extra_fields<'a>(
+        &mut self,
+        extra: &'a [u8],
+        settings: ExtraFieldSettings,
+        strictness: Strictness,
+    ) -> Result<(UnicodeFields<'a>, bool), Error> {
+        self.extra_settings = settings;
+        let mut fields = Vec::new();
+        let mut slice = Partial::new(extra);
+        let mut invalid = false;
+        while !slice.is_empty() {
+            match ExtraField::mk_parser(settings).parse_next(&mut slice) {
+                Ok(ef) => fields.push(ef),
+                Err(e) => {
+                    trace!("extra field error: {:#?}", e);
+                    if strictness == Strictness::Strict {
+                        return Err(FormatError::InvalidExtraField.into());
+                    }
+                    invalid = true;
+                    break;
+                }
+            }
+        }
+
+        // values masked with 0xFFFF_FFFF are only known from a zip64 field
+        let masked = [
+            settings.uncompressed_size_u32,
+            settings.compressed_size_u32,
+            settings.header_offset_u32,
+        ]
+        .contains(&u32::MAX);
+        if masked && !fields.iter().any(|ef| matches!(ef, ExtraField::Zip64(_))) {
+            trace!("masked values but no zip64 extra field");
+            if strictness == Strictness::Strict {
+                return Err(FormatError::InvalidExtraField.into());
+            }
+            invalid = true;
+        }
+
+        // least precise first, so more precise fields overwrite them
+        fields.sort_by_key(|ef| match ef {
+            ExtraField::Unix(_) => Some(TimestampSource::Unix),
+            ExtraField::Timestamp(_) => Some(TimestampSource::ExtendedTimestamp),
+            ExtraField::Ntfs(_) => Some(TimestampSource::Ntfs),
+            _ => None,
+        });
+
+        let mut unicode = UnicodeFields::default();
+        for ef in fields {
+            match ef {
+                ExtraField::UnicodePath(field) => unicode.path = Some(field),
+                ExtraField::UnicodeComment(field) => unicode.comment = Some(field),
+                ef => self.set_extra_field(&ef),
+            }
+        }
+        Ok((unicode, invalid))
+    }
+
+    /// Sets the entry's name and comment from those in its header, decoded
+    /// with `decoding`.
+    ///
+    /// Entries without the language encoding flag may have Unicode Path and
+    /// Comment extra fields, made by tools that wrote the header in some
+    /// other encoding. Those are used instead, if they were made from the
+    /// same header text: the name is then [EncodingSource::UnicodeExtraField].
+    pub(crate) fn set_text(
+        &mut self,
+        decoding: NameDecoding,
+        name: &[u8],
+        comment: &[u8],
+        unicode: &UnicodeFields<'_>,
+    ) -> Result<(), Error> {
+        let has_utf8_flag = self.flags & 0x800 != 0;
+        let unicode_text = |field: &Option<ExtraUnicodeField<'_>>, text: &[u8]| {
+            field
+                .as_ref()
+                .filter(|_| !has_utf8_flag)
+                .and_then(|field| field.text_for(text))
+                .map(str::to_owned)
+        };
+
+        self.raw_name = name.to_vec();
+        self.raw_comment = comment.to_vec();
+        self.name_decoding = decoding;
+        self.name = match unicode_text(&unicode.path, name) {
+            Some(name) => {
+                self.name_decoding = NameDecoding {
+                    encoding: Encoding::Utf8,
+                    source: EncodingSource::UnicodeExtraField,
+                    confident: true,
+                };
+                name
+            }
+            None => decoding.encoding.decode(name)?,
+        };
+        self.comment = match unicode_text(&unicode.comment, comment) {
+            Some(comment) => comment,
+            None => decoding.encoding.decode(comment)?,
+        };
+        Ok(())
+    }
+
     /// Apply the extra field to the entry, updating its metadata.
-    pub(crate) fn set_extra_field(&mut self, ef: &ExtraField) {
+    fn set_extra_field(&mut self, ef: &ExtraField) {
         match &ef {
             ExtraField::Zip64(z64) => {
                 self.uncompressed_size = z64.uncompressed_size;
@@ -198,10 +695,16 @@ impl Entry {
                 self.header_offset = z64.header_offset;
             }
             ExtraField::Timestamp(ts) => {
-                self.modified = Utc
-                    .timestamp_opt(ts.mtime as i64, 0)
-                    .single()
-                    .unwrap_or_else(zero_datetime);
+                if let Some(mtime) = ts.mtime {
+                    self.modified = unix_datetime(mtime).unwrap_or_else(zero_datetime);
+                    self.timestamp_source = TimestampSource::ExtendedTimestamp;
+                }
+                if let Some(atime) = ts.atime {
+                    self.accessed = unix_datetime(atime);
+                }
+                if let Some(ctime) = ts.ctime {
+                    self.created = unix_datetime(ctime);
+                }
             }
             ExtraField::Ntfs(nf) => {
                 for attr in &nf.attrs {
@@ -210,32 +713,87 @@ impl Entry {
                         self.modified = attr.mtime.to_datetime().unwrap_or_else(zero_datetime);
                         self.created = attr.ctime.to_datetime();
                         self.accessed = attr.atime.to_datetime();
+                        self.timestamp_source = TimestampSource::Ntfs;
                     }
                 }
             }
             ExtraField::Unix(uf) => {
-                self.modified = Utc
-                    .timestamp_opt(uf.mtime as i64, 0)
-                    .single()
-                    .unwrap_or_else(zero_datetime);
+                self.modified = unix_datetime(uf.mtime).unwrap_or_else(zero_datetime);
+                self.accessed = unix_datetime(uf.atime);
+                self.timestamp_source = TimestampSource::Unix;
 
                 if self.uid.is_none() {
-                    self.uid = Some(uf.uid as u32);
+                    self.uid = uf.uid.map(u32::from);
                 }
 
                 if self.gid.is_none() {
-                    self.gid = Some(uf.gid as u32);
+                    self.gid = uf.gid.map(u32::from);
+                }
+            }
+            ExtraField::Unix2(uf) => {
+                if self.uid.is_none() {
+                    self.uid = uf.uid.map(u32::from);
+                }
+
+                if self.gid.is_none() {
+                    self.gid = uf.gid.map(u32::from);
                 }
             }
             ExtraField::NewUnix(uf) => {
                 self.uid = Some(uf.uid as u32);
-                self.gid = Some(uf.uid as u32);
+                self.gid = Some(uf.gid as u32);
+            }
+            ExtraField::Aes(aes) => {
+                if self.method == Method::Aex {
+                    self.method_details = MethodDetails::Aes {
+                        version: aes.version,
+                        strength: aes.strength.into(),
+                        method: aes.method,
+                    };
+                }
             }
             _ => {}
         };
     }
 }
 
+/// Converts seconds since the Unix epoch, as found in extra fields, to a
+/// date time.
+fn unix_datetime(secs: u32) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(secs as i64, 0).single()
+}
+
+/// Splits a name or symlink target into its components, separated by `/` or
+/// `\\`. Returns None if it's absolute, starts with a drive letter, or
+/// contains a NUL byte.
+fn relative_components(path: &str) -> Option<impl Iterator<Item = &str>> {
+    if path.contains('\0') || path.starts_with(['/', '\\']) {
+        return None;
+    }
+
+    let mut components = path.split(['/', '\\']).peekable();
+    let first = components.peek()?.as_bytes();
+    if first.len() >= 2 && first[0].is_ascii_alphabetic() && first[1] == b':' {
+        return None;
+    }
+
+    // on Windows, colons elsewhere name alternate data streams
+    #[cfg(windows)]
+    if path.contains(':') {
+        return None;
+    }
+
+    Some(components)
+}
+
+/// The Info-ZIP Unicode Path and Comment extra fields of an entry, see
+/// [Entry::set_extra_fields].
+#[derive(Default)]
+pub(crate) struct UnicodeFields<'a> {
+    path: Option<ExtraUnicodeField<'a>>,
+    comment: Option<ExtraUnicodeField<'a>>,
+}
+
 /// The entry's file type: a directory, a file, or a symbolic link.
 #[derive(Debug)]
 pub enum EntryKind {
@@ -1,7 +1,6 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, io};
 
 use ownable::{IntoOwned, ToOwned};
-use tracing::trace;
 use winnow::{
     binary::{le_u16, le_u32},
     prelude::PResult,
@@ -11,15 +10,15 @@ use winnow::{
 
 use crate::{
     encoding::detect_utf8,
-    encoding::Encoding,
-    error::{Error, FormatError},
+    encoding::{Encoding, EncodingSource, NameDecoding},
+    error::{Error, Warning},
     parse::{
-        zero_datetime, Entry, ExtraField, ExtraFieldSettings, HostSystem, Mode, MsdosMode,
-        MsdosTimestamp, UnixMode, Version,
+        field_len_u16, zero_datetime, Entry, EntryTrust, ExtraFieldSettings, HostSystem, Mode,
+        MsdosMode, MsdosTimestamp, Offsets, Strictness, TimestampSource, UnixMode, Version,
     },
 };
 
-use super::Method;
+use super::{Method, MethodDetails};
 
 /// 4.3.12 Central directory structure: File header
 #[derive(IntoOwned, ToOwned)]
@@ -71,7 +70,7 @@ pub struct CentralDirectoryFileHeader<'a> {
 }
 
 impl<'a> CentralDirectoryFileHeader<'a> {
-    const SIGNATURE: &'static str = "PK\x01\x02";
+    pub(crate) const SIGNATURE: &'static str = "PK\x01\x02";
 
     /// Parser for the central directory file header
     pub fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
@@ -114,9 +113,46 @@ impl<'a> CentralDirectoryFileHeader<'a> {
             comment: Cow::Borrowed(comment),
         })
     }
+
+    /// Serialize this header, in the same layout [Self::parser] expects.
+    ///
+    /// Returns an error if the name, extra field or comment are longer than
+    /// what a u16 length field can express.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        let name_len = field_len_u16("name", self.name.len())?;
+        let extra_len = field_len_u16("extra field", self.extra.len())?;
+        let comment_len = field_len_u16("comment", self.comment.len())?;
+
+        w.write_all(Self::SIGNATURE.as_bytes())?;
+        self.creator_version.write_to(w)?;
+        self.reader_version.write_to(w)?;
+        w.write_all(&self.flags.to_le_bytes())?;
+        w.write_all(&u16::from(self.method).to_le_bytes())?;
+        self.modified.write_to(w)?;
+        w.write_all(&self.crc32.to_le_bytes())?;
+        w.write_all(&self.compressed_size.to_le_bytes())?;
+        w.write_all(&self.uncompressed_size.to_le_bytes())?;
+        w.write_all(&name_len.to_le_bytes())?;
+        w.write_all(&extra_len.to_le_bytes())?;
+        w.write_all(&comment_len.to_le_bytes())?;
+        w.write_all(&self.disk_nbr_start.to_le_bytes())?;
+        w.write_all(&self.internal_attrs.to_le_bytes())?;
+        w.write_all(&self.external_attrs.to_le_bytes())?;
+        w.write_all(&self.header_offset.to_le_bytes())?;
+        w.write_all(&self.name)?;
+        w.write_all(&self.extra)?;
+        w.write_all(&self.comment)
+    }
 }
 
 impl CentralDirectoryFileHeader<'_> {
+    /// The size of this header as laid out in the central directory, name,
+    /// extra field and comment included.
+    pub fn record_len(&self) -> u64 {
+        // signature and fixed-size fields
+        46 + (self.name.len() + self.extra.len() + self.comment.len()) as u64
+    }
+
     /// Returns true if the name or comment is not valid UTF-8
     pub fn is_non_utf8(&self) -> bool {
         let (valid1, require1) = detect_utf8(&self.name[..]);
@@ -140,17 +176,55 @@ impl CentralDirectoryFileHeader<'_> {
 
     /// Converts the directory header into a entry: this involves
     /// parsing the extra fields and converting the timestamps.
-    pub fn as_entry(&self, encoding: Encoding, global_offset: u64) -> Result<Entry, Error> {
+    ///
+    /// `decoding` is how text fields are decoded archive-wide. Entries that
+    /// have the language encoding flag set, and whose name and comment are
+    /// valid UTF-8, are decoded as UTF-8 instead, with
+    /// [EncodingSource::Utf8Flag].
+    ///
+    /// `global_offset` is added to the header offset, and is negative when
+    /// the archive was truncated at the start rather than prepended to.
+    pub fn as_entry(&self, decoding: NameDecoding, global_offset: i64) -> Result<Entry, Error> {
+        self.as_entry_with(decoding, global_offset, Strictness::Strict, &mut Vec::new())
+    }
+
+    /// Like [Self::as_entry], but problems that can be worked around with
+    /// `strictness` are, and those that aren't errors are pushed to
+    /// `warnings`.
+    pub(crate) fn as_entry_with(
+        &self,
+        decoding: NameDecoding,
+        global_offset: i64,
+        strictness: Strictness,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Entry, Error> {
+        let mut name_decoding = decoding;
+        if self.flags & 0x800 != 0
+            && std::str::from_utf8(&self.name).is_ok()
+            && std::str::from_utf8(&self.comment).is_ok()
+        {
+            name_decoding = NameDecoding {
+                encoding: Encoding::Utf8,
+                source: EncodingSource::Utf8Flag,
+                confident: true,
+            };
+        }
+
         let mut entry = Entry {
-            name: encoding.decode(&self.name[..])?,
+            name: Default::default(),
+            name_decoding,
             method: self.method,
-            comment: encoding.decode(&self.comment[..])?,
+            method_details: MethodDetails::from_flags(self.method, self.flags),
+            comment: Default::default(),
+            raw_name: Default::default(),
+            raw_comment: Default::default(),
+            raw_extra: self.extra.to_vec(),
+            extra_settings: Default::default(),
             modified: self.modified.to_datetime().unwrap_or_else(zero_datetime),
             created: None,
             accessed: None,
-            header_offset: (self.header_offset as u64)
-                .checked_add(global_offset)
-                .ok_or(FormatError::InvalidHeaderOffset)?,
+            timestamp_source: TimestampSource::Msdos,
+            header_offset: self.header_offset as u64,
             reader_version: self.reader_version,
             flags: self.flags,
             uid: None,
@@ -159,9 +233,46 @@ impl CentralDirectoryFileHeader<'_> {
             compressed_size: self.compressed_size as _,
             uncompressed_size: self.uncompressed_size as _,
             mode: Mode(0),
+            external_attrs: self.external_attrs,
+            raw_central_record: None,
+            trust: EntryTrust::CentralDirectory,
+        };
+
+        let settings = ExtraFieldSettings {
+            uncompressed_size_u32: self.uncompressed_size,
+            compressed_size_u32: self.compressed_size,
+            header_offset_u32: self.header_offset,
         };
+        let (unicode, invalid_extra_field) =
+            entry.set_extra_fields(&self.extra, settings, strictness)?;
+        entry.set_text(name_decoding, &self.name, &self.comment, &unicode)?;
+
+        if invalid_extra_field {
+            warnings.push(Warning::InvalidExtraField {
+                name: entry.name.clone(),
+            });
+        }
+        // AE-2 encrypted entries have no CRC-32 on purpose
+        if entry.crc32 == 0 && entry.uncompressed_size > 0 && !entry.is_encrypted() {
+            warnings.push(Warning::MissingChecksum {
+                name: entry.name.clone(),
+            });
+        }
+        if self.reader_version.version > 63 {
+            warnings.push(Warning::UnknownVersionNeeded {
+                name: entry.name.clone(),
+                version: self.reader_version,
+            });
+        }
+
+        // the zip64 extra field may have had the actual offset
+        entry.header_offset = Offsets::adjust(entry.header_offset, global_offset)?;
 
         entry.mode = match self.creator_version.host_system {
+            // some tools claim to be Unix but only set the MS-DOS attributes
+            HostSystem::Unix | HostSystem::Osx if self.external_attrs >> 16 == 0 => {
+                MsdosMode(self.external_attrs).into()
+            }
             HostSystem::Unix | HostSystem::Osx => UnixMode(self.external_attrs >> 16).into(),
             HostSystem::WindowsNtfs | HostSystem::Vfat | HostSystem::MsDos => {
                 MsdosMode(self.external_attrs).into()
@@ -173,25 +284,6 @@ impl CentralDirectoryFileHeader<'_> {
             entry.mode |= Mode::DIR
         };
 
-        let settings = ExtraFieldSettings {
-            uncompressed_size_u32: self.uncompressed_size,
-            compressed_size_u32: self.compressed_size,
-            header_offset_u32: self.header_offset,
-        };
-
-        let mut slice = Partial::new(&self.extra[..]);
-        while !slice.is_empty() {
-            match ExtraField::mk_parser(settings).parse_next(&mut slice) {
-                Ok(ef) => {
-                    entry.set_extra_field(&ef);
-                }
-                Err(e) => {
-                    trace!("extra field error: {:#?}", e);
-                    return Err(FormatError::InvalidExtraField.into());
-                }
-            }
-        }
-
         Ok(entry)
     }
 }
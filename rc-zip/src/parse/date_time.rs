@@ -1,9 +1,9 @@
 use chrono::{
     offset::{LocalResult, TimeZone, Utc},
-    DateTime, Timelike,
+    DateTime, Datelike, Timelike,
 };
 use ownable::{IntoOwned, ToOwned};
-use std::fmt;
+use std::{fmt, io};
 use winnow::{
     binary::{le_u16, le_u64},
     seq, PResult, Parser, Partial,
@@ -40,6 +40,12 @@ impl MsdosTimestamp {
         .parse_next(i)
     }
 
+    /// Serialize this timestamp, in the same layout [Self::parser] expects
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_all(&self.time.to_le_bytes())?;
+        w.write_all(&self.date.to_le_bytes())
+    }
+
     /// Attempts to convert to a chrono UTC date time
     pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
         // see https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-dosdatetimetofiletime
@@ -66,6 +72,18 @@ impl MsdosTimestamp {
         let h = (self.time >> 11) as u32;
         date.with_hour(h)?.with_minute(m)?.with_second(s)
     }
+
+    /// Converts a chrono UTC date time, rounding seconds down to an even
+    /// number. Returns `None` if the year is outside of 1980..=2107.
+    pub fn from_datetime(dt: &DateTime<Utc>) -> Option<Self> {
+        let y = u16::try_from(dt.year().checked_sub(1980)?).ok()?;
+        if y > 0b111_1111 {
+            return None;
+        }
+        let date = y << 9 | (dt.month() as u16) << 5 | dt.day() as u16;
+        let time = (dt.hour() as u16) << 11 | (dt.minute() as u16) << 5 | (dt.second() as u16 / 2);
+        Some(Self { time, date })
+    }
 }
 
 /// A timestamp in NTFS format.
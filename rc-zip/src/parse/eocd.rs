@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, io};
 
 use ownable::{traits as ownable_traits, IntoOwned, ToOwned};
 use tracing::trace;
@@ -9,7 +9,11 @@ use winnow::{
     PResult, Parser, Partial,
 };
 
-use crate::error::{Error, FormatError};
+use crate::{
+    error::{Error, FormatError},
+    fsm::ArchiveReadOptions,
+    parse::{field_len_u16, CentralDirectoryFileHeader, Offsets},
+};
 
 /// 4.3.16  End of central directory record:
 #[derive(Debug, ToOwned, IntoOwned, Clone)]
@@ -39,20 +43,44 @@ pub struct EndOfCentralDirectoryRecord<'a> {
 impl<'a> EndOfCentralDirectoryRecord<'a> {
     /// Does not include comment size & comment data
     const MIN_LENGTH: usize = 20;
-    const SIGNATURE: &'static str = "PK\x05\x06";
-
-    /// Find the end of central directory record in a block of data
+    pub(crate) const SIGNATURE: &'static str = "PK\x05\x06";
+
+    /// Length of the longest possible record, with a 65535-byte comment
+    pub const MAX_LENGTH: usize = Self::MIN_LENGTH + 2 + u16::MAX as usize;
+
+    /// Find the end of central directory record in a block of data, usually
+    /// the end of the file.
+    ///
+    /// The block may have trailing data after the record, and its comment
+    /// may contain what looks like another record: of all the candidates,
+    /// this picks the one that's most consistent with the block. One whose
+    /// central directory (or zip64 locator) is found right before it, within
+    /// the block, is preferred, then one that ends exactly where the block
+    /// does, then the one closest to the end of the block.
     pub fn find_in_block(b: &'a [u8]) -> Option<Located<Self>> {
-        for i in (0..(b.len().saturating_sub(Self::MIN_LENGTH + 1))).rev() {
-            let mut input = Partial::new(&b[i..]);
-            if let Ok(directory) = Self::parser.parse_next(&mut input) {
-                return Some(Located {
+        Self::candidates_in_block(b).max_by_key(|candidate| {
+            let end =
+                candidate.offset as usize + Self::MIN_LENGTH + 2 + candidate.inner.comment.len();
+            // ties go to the record closest to the end: the others may well
+            // be those of stored archives nested in this one
+            (candidate.consistency(b), end == b.len(), candidate.offset)
+        })
+    }
+
+    /// All the records that can be parsed in a block of data, from the last
+    /// one to the first one.
+    pub fn candidates_in_block(b: &'a [u8]) -> impl Iterator<Item = Located<Self>> {
+        (0..(b.len().saturating_sub(Self::MIN_LENGTH + 1)))
+            .rev()
+            .filter(move |&i| b[i..].starts_with(Self::SIGNATURE.as_bytes()))
+            .filter_map(move |i| {
+                let mut input = Partial::new(&b[i..]);
+                let directory = Self::parser.parse_next(&mut input).ok()?;
+                Some(Located {
                     offset: i as u64,
                     inner: directory,
-                });
-            }
-        }
-        None
+                })
+            })
     }
 
     /// Parser for the end of central directory record
@@ -69,6 +97,66 @@ impl<'a> EndOfCentralDirectoryRecord<'a> {
         }}
         .parse_next(i)
     }
+
+    /// Serialize this record, in the same layout [Self::parser] expects.
+    ///
+    /// Returns an error if the comment is longer than what a u16 length
+    /// field can express.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        let comment_len = field_len_u16("comment", self.comment.len())?;
+
+        w.write_all(Self::SIGNATURE.as_bytes())?;
+        w.write_all(&self.disk_nbr.to_le_bytes())?;
+        w.write_all(&self.dir_disk_nbr.to_le_bytes())?;
+        w.write_all(&self.dir_records_this_disk.to_le_bytes())?;
+        w.write_all(&self.directory_records.to_le_bytes())?;
+        w.write_all(&self.directory_size.to_le_bytes())?;
+        w.write_all(&self.directory_offset.to_le_bytes())?;
+        w.write_all(&comment_len.to_le_bytes())?;
+        w.write_all(&self.comment)
+    }
+}
+
+/// How well an end of central directory record candidate agrees with the
+/// data before it, see [EndOfCentralDirectoryRecord::find_in_block]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Consistency {
+    /// The central directory can't fit before the record
+    Inconsistent,
+    /// What's before the record is outside the block, or the central
+    /// directory is empty
+    Unknown,
+    /// The central directory, or a zip64 locator, is right before the record
+    Consistent,
+}
+
+impl Located<EndOfCentralDirectoryRecord<'_>> {
+    fn consistency(&self, b: &[u8]) -> Consistency {
+        let offset = self.offset as usize;
+
+        // zip64 archives have their locator right before the record, and
+        // may have placeholders instead of the directory's size
+        if offset >= EndOfCentralDirectory64Locator::LENGTH
+            && b[offset - EndOfCentralDirectory64Locator::LENGTH..]
+                .starts_with(EndOfCentralDirectory64Locator::SIGNATURE.as_bytes())
+        {
+            return Consistency::Consistent;
+        }
+
+        let size = self.inner.directory_size as usize;
+        match offset.checked_sub(size) {
+            _ if size == 0 => Consistency::Unknown,
+            // with prepended data, the directory's offset may be wrong, but
+            // it's still right before the record
+            Some(start)
+                if b[start..].starts_with(CentralDirectoryFileHeader::SIGNATURE.as_bytes()) =>
+            {
+                Consistency::Consistent
+            }
+            Some(_) => Consistency::Inconsistent,
+            None => Consistency::Unknown,
+        }
+    }
 }
 
 /// 4.3.15 Zip64 end of central directory locator
@@ -97,6 +185,14 @@ impl EndOfCentralDirectory64Locator {
         }}
         .parse_next(i)
     }
+
+    /// Serialize this locator, in the same layout [Self::parser] expects.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_all(Self::SIGNATURE.as_bytes())?;
+        w.write_all(&self.dir_disk_number.to_le_bytes())?;
+        w.write_all(&self.directory_offset.to_le_bytes())?;
+        w.write_all(&self.total_disks.to_le_bytes())
+    }
 }
 
 /// 4.3.14  Zip64 end of central directory record
@@ -132,7 +228,9 @@ pub struct EndOfCentralDirectory64Record {
 }
 
 impl EndOfCentralDirectory64Record {
-    const SIGNATURE: &'static str = "PK\x06\x06";
+    /// Length of the record, without the zip64 extensible data sector
+    pub const LENGTH: usize = 56;
+    pub(crate) const SIGNATURE: &'static str = "PK\x06\x06";
 
     /// Parser for the zip64 end of central directory record
     pub fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
@@ -150,6 +248,23 @@ impl EndOfCentralDirectory64Record {
         }}
         .parse_next(i)
     }
+
+    /// Serialize this record, in the same layout [Self::parser] expects.
+    ///
+    /// Note that the zip64 extensible data sector is not supported, so
+    /// [Self::record_size] is written as-is.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_all(Self::SIGNATURE.as_bytes())?;
+        w.write_all(&self.record_size.to_le_bytes())?;
+        w.write_all(&self.creator_version.to_le_bytes())?;
+        w.write_all(&self.reader_version.to_le_bytes())?;
+        w.write_all(&self.disk_nbr.to_le_bytes())?;
+        w.write_all(&self.dir_disk_nbr.to_le_bytes())?;
+        w.write_all(&self.dir_records_this_disk.to_le_bytes())?;
+        w.write_all(&self.directory_records.to_le_bytes())?;
+        w.write_all(&self.directory_size.to_le_bytes())?;
+        w.write_all(&self.directory_offset.to_le_bytes())
+    }
 }
 
 /// A zip structure and its location in the input file
@@ -200,7 +315,9 @@ pub struct EndOfCentralDirectory<'a> {
     pub dir64: Option<Located<EndOfCentralDirectory64Record>>,
 
     /// Zip files may be prepended by arbitrary data, this is how much
-    /// data is at the beginning of the file that isn't part of the zip
+    /// data is at the beginning of the file that isn't part of the zip,
+    /// either given by [ArchiveReadOptions::base_offset] or guessed from
+    /// where the directory is found
     pub global_offset: i64,
 }
 
@@ -209,6 +326,7 @@ impl<'a> EndOfCentralDirectory<'a> {
         size: u64,
         dir: Located<EndOfCentralDirectoryRecord<'a>>,
         dir64: Option<Located<EndOfCentralDirectory64Record>>,
+        options: &ArchiveReadOptions,
     ) -> Result<Self, Error> {
         let mut res = Self {
             dir,
@@ -245,19 +363,34 @@ impl<'a> EndOfCentralDirectory<'a> {
         // 0                   directory_offset - woops!                   directory_end_offset
         // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 
-        let computed_directory_offset = res
-            .located_directory_offset()
-            .checked_sub(res.directory_size())
+        // in split archives, the directory's offset is relative to the start
+        // of the disk it's on
+        let directory_disk_start = options
+            .offset_adjustment(res.directory_disk(), 0)
             .ok_or(FormatError::DirectoryOffsetPointsOutsideFile)?;
 
-        // did we find a valid offset?
-        if (0..size).contains(&computed_directory_offset) {
-            // that's different from the recorded one?
-            if computed_directory_offset != res.directory_offset() {
-                // then assume the whole file is offset
-                res.global_offset =
-                    computed_directory_offset as i64 - res.directory_offset() as i64;
-                res.set_directory_offset(computed_directory_offset);
+        if let Some(global_offset) = options.base_global_offset() {
+            // the caller told us where the archive starts, no need to guess
+            res.global_offset = global_offset;
+            let adjustment = Offsets::combine(directory_disk_start, global_offset)?;
+            let directory_offset = Offsets::adjust(res.directory_offset(), adjustment)?;
+            res.set_directory_offset(directory_offset);
+        } else {
+            let computed_directory_offset =
+                Offsets::sub(res.located_directory_offset(), res.directory_size())?;
+
+            // did we find a valid offset?
+            if (0..size).contains(&computed_directory_offset) {
+                // that's different from the recorded one?
+                if computed_directory_offset != res.directory_offset() {
+                    // then assume the whole file is offset. the recorded
+                    // offset can be anything, so this may not fit.
+                    res.global_offset =
+                        Offsets::delta(res.directory_offset(), computed_directory_offset)?
+                            .checked_sub(directory_disk_start)
+                            .ok_or(FormatError::InvalidOffsets)?;
+                    res.set_directory_offset(computed_directory_offset);
+                }
             }
         }
 
@@ -290,6 +423,14 @@ impl<'a> EndOfCentralDirectory<'a> {
         }
     }
 
+    #[inline]
+    pub(crate) fn directory_disk(&self) -> u32 {
+        match self.dir64.as_ref() {
+            Some(d64) => d64.inner.dir_disk_nbr,
+            None => self.dir.inner.dir_disk_nbr as u32,
+        }
+    }
+
     #[inline]
     pub(crate) fn directory_size(&self) -> u64 {
         match self.dir64.as_ref() {
@@ -314,6 +455,16 @@ impl<'a> EndOfCentralDirectory<'a> {
         }
     }
 
+    /// Whether the zip64 end of central directory record is version 2, which
+    /// is only used when the central directory is encrypted or compressed with
+    /// PKWARE strong encryption (see section 7.3.4 of the appnote): version
+    /// needed to extract is 6.2 or later.
+    pub(crate) fn requires_strong_encryption(&self) -> bool {
+        self.dir64
+            .as_ref()
+            .is_some_and(|d64| d64.inner.reader_version & 0xff >= 62)
+    }
+
     #[inline]
     pub(crate) fn comment(&self) -> &[u8] {
         &self.dir.inner.comment
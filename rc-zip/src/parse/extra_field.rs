@@ -1,16 +1,20 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, io};
 
 use ownable::{IntoOwned, ToOwned};
 use winnow::{
     binary::{le_u16, le_u32, le_u64, le_u8, length_take},
-    combinator::{opt, preceded, repeat_till},
+    combinator::{opt, repeat_till},
     error::{ErrMode, ErrorKind, ParserError, StrContext},
     seq,
+    stream::Stream,
     token::{literal, take},
     PResult, Parser, Partial,
 };
 
-use crate::parse::NtfsTimestamp;
+use crate::{
+    error::{Error, FormatError},
+    parse::{Method, NtfsTimestamp},
+};
 
 /// 4.4.28 extra field: (Variable)
 pub(crate) struct ExtraFieldRecord<'a> {
@@ -36,7 +40,7 @@ impl<'a> ExtraFieldRecord<'a> {
 /// is created. The order of the fields in the zip64 extended information record
 /// is fixed, but the fields MUST only appear if the corresponding Local or
 /// Central directory record field is set to 0xFFFF or 0xFFFFFFFF.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct ExtraFieldSettings {
     /// The uncompressed size field read from a local or central directory record
     /// If this is 0xFFFF_FFFF, then the zip64 extra field uncompressed size
@@ -67,14 +71,24 @@ pub enum ExtraField<'a> {
     Timestamp(ExtraTimestampField),
     /// UNIX & Info-Zip UNIX
     Unix(ExtraUnixField<'a>),
+    /// Info-ZIP UNIX extra field, type 2
+    Unix2(ExtraUnix2Field),
     /// New UNIX extra field
     NewUnix(ExtraNewUnixField),
     /// NTFS (Win9x/WinNT FileTimes)
     Ntfs(ExtraNtfsField),
-    /// Unknown extra field, with tag
+    /// WinZip AES encryption
+    Aes(ExtraAesField),
+    /// Info-ZIP Unicode Path
+    UnicodePath(ExtraUnicodeField<'a>),
+    /// Info-ZIP Unicode Comment
+    UnicodeComment(ExtraUnicodeField<'a>),
+    /// Unknown extra field, or known one that couldn't be parsed, with tag
     Unknown {
         /// tag of the extra field
         tag: u16,
+        /// data of the extra field, as is
+        data: Cow<'a, [u8]>,
     },
 }
 
@@ -102,18 +116,78 @@ impl<'a> ExtraField<'a> {
                 ExtraUnixField::TAG | ExtraUnixField::TAG_INFOZIP => {
                     opt(ExtraUnixField::parser.map(EF::Unix)).parse_next(payload)?
                 }
+                ExtraUnix2Field::TAG => {
+                    opt(ExtraUnix2Field::parser.map(EF::Unix2)).parse_next(payload)?
+                }
                 ExtraNewUnixField::TAG => {
                     opt(ExtraNewUnixField::parser.map(EF::NewUnix)).parse_next(payload)?
                 }
+                ExtraAesField::TAG => {
+                    opt(ExtraAesField::parser.map(EF::Aes)).parse_next(payload)?
+                }
+                ExtraUnicodeField::TAG_PATH => {
+                    opt(ExtraUnicodeField::parser.map(EF::UnicodePath)).parse_next(payload)?
+                }
+                ExtraUnicodeField::TAG_COMMENT => {
+                    opt(ExtraUnicodeField::parser.map(EF::UnicodeComment)).parse_next(payload)?
+                }
                 _ => None,
             }
-            .unwrap_or(EF::Unknown { tag: rec.tag });
+            .unwrap_or(EF::Unknown {
+                tag: rec.tag,
+                data: Cow::Borrowed(rec.payload),
+            });
 
             Ok(variant)
         }
     }
 }
 
+/// The extra fields of an entry, in the order they're stored in, see
+/// [Entry::extra_fields](crate::parse::Entry::extra_fields).
+///
+/// Known fields are parsed, and others are yielded as [ExtraField::Unknown]
+/// along with their data, so they can be written back as is. A field that
+/// can't be delimited ends the iteration with
+/// [FormatError::InvalidExtraField].
+#[derive(Clone)]
+pub struct ExtraFields<'a> {
+    rest: &'a [u8],
+    settings: ExtraFieldSettings,
+}
+
+impl<'a> ExtraFields<'a> {
+    /// Iterates over the extra fields in `extra`, given the settings for the
+    /// zip64 extra field, see [ExtraField::mk_parser].
+    pub fn new(extra: &'a [u8], settings: ExtraFieldSettings) -> Self {
+        Self {
+            rest: extra,
+            settings,
+        }
+    }
+}
+
+impl<'a> Iterator for ExtraFields<'a> {
+    type Item = Result<ExtraField<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let mut input = Partial::new(self.rest);
+        match ExtraField::mk_parser(self.settings).parse_next(&mut input) {
+            Ok(field) => {
+                self.rest = input.into_inner();
+                Some(Ok(field))
+            }
+            Err(_) => {
+                self.rest = &[];
+                Some(Err(FormatError::InvalidExtraField.into()))
+            }
+        }
+    }
+}
+
 /// 4.5.3 -Zip64 Extended Information Extra Field (0x0001)
 #[derive(Clone, Default)]
 pub struct ExtraZip64Field {
@@ -162,39 +236,80 @@ impl ExtraZip64Field {
             })
         }
     }
+
+    /// Serialize this field, tag and size included. Like when parsing, only
+    /// the values whose u32 counterpart in `settings` is 0xFFFF_FFFF are
+    /// written, then the disk start number if there is one.
+    pub fn write_to(&self, w: &mut impl io::Write, settings: ExtraFieldSettings) -> io::Result<()> {
+        let fields = [
+            (settings.uncompressed_size_u32, self.uncompressed_size),
+            (settings.compressed_size_u32, self.compressed_size),
+            (settings.header_offset_u32, self.header_offset),
+        ];
+        let fields = fields
+            .iter()
+            .filter(|(u32_value, _)| *u32_value == 0xFFFF_FFFF);
+
+        let size = fields.clone().count() * 8 + self.disk_start.map_or(0, |_| 4);
+        w.write_all(&Self::TAG.to_le_bytes())?;
+        w.write_all(&(size as u16).to_le_bytes())?;
+        for (_, value) in fields {
+            w.write_all(&value.to_le_bytes())?;
+        }
+        if let Some(disk_start) = self.disk_start {
+            w.write_all(&disk_start.to_le_bytes())?;
+        }
+        Ok(())
+    }
 }
 
-/// Extended timestamp extra field
+/// Extended timestamp extra field (0x5455), with times in seconds since the
+/// Unix epoch.
 #[derive(Clone)]
 pub struct ExtraTimestampField {
-    /// number of seconds since epoch
-    pub mtime: u32,
+    /// last modification time
+    pub mtime: Option<u32>,
+    /// last access time, only ever in local headers
+    pub atime: Option<u32>,
+    /// creation time, only ever in local headers
+    pub ctime: Option<u32>,
 }
 
 impl ExtraTimestampField {
     const TAG: u16 = 0x5455;
 
     fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
-        preceded(
-            // 1 byte of flags, if bit 0 is set, modification time is present
-            le_u8.verify(|x| x & 0b1 != 0),
-            seq! {Self { mtime: le_u32 }},
-        )
-        .parse_next(i)
+        // 1 byte of flags: bits 0, 1 and 2 tell whether the modification,
+        // access and creation times are present. In the central directory,
+        // only the modification time is, whatever the flags say.
+        let flags = le_u8.parse_next(i)?;
+        let mut time = |bit: u8| {
+            if flags & (1 << bit) != 0 && i.eof_offset() >= 4 {
+                le_u32.map(Some).parse_next(i)
+            } else {
+                Ok(None)
+            }
+        };
+        Ok(Self {
+            mtime: time(0)?,
+            atime: time(1)?,
+            ctime: time(2)?,
+        })
     }
 }
 
-/// 4.5.7 -UNIX Extra Field (0x000d):
+/// 4.5.7 -UNIX Extra Field (0x000d), and the Info-ZIP UNIX Extra Field
+/// (0x5855) it's based on.
 #[derive(Clone, ToOwned, IntoOwned)]
 pub struct ExtraUnixField<'a> {
     /// file last access time
     pub atime: u32,
     /// file last modification time
     pub mtime: u32,
-    /// file user id
-    pub uid: u16,
-    /// file group id
-    pub gid: u16,
+    /// file user id, not in the central directory for the Info-ZIP variant
+    pub uid: Option<u16>,
+    /// file group id, not in the central directory for the Info-ZIP variant
+    pub gid: Option<u16>,
     /// variable length data field
     pub data: Cow<'a, [u8]>,
 }
@@ -204,19 +319,47 @@ impl<'a> ExtraUnixField<'a> {
     const TAG_INFOZIP: u16 = 0x5855;
 
     fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
-        let t_size = le_u16.parse_next(i)?;
+        let atime = le_u32.parse_next(i)?;
+        let mtime = le_u32.parse_next(i)?;
+        let (uid, gid) = if i.eof_offset() >= 4 {
+            (Some(le_u16.parse_next(i)?), Some(le_u16.parse_next(i)?))
+        } else {
+            (None, None)
+        };
+        let data = take(i.eof_offset()).map(Cow::Borrowed).parse_next(i)?;
+        Ok(Self {
+            atime,
+            mtime,
+            uid,
+            gid,
+            data,
+        })
+    }
+}
 
-        // t_size includes the size of the atime .. gid fields, totalling 12 bytes.
-        let t_size = t_size
-            .checked_sub(12)
-            .ok_or(ErrMode::from_error_kind(i, ErrorKind::Verify))?;
+/// Info-ZIP Unix Extra Field (type 2) (0x7855), which has 16-bit user and
+/// group ids in the local header, and nothing in the central directory.
+#[derive(Clone)]
+pub struct ExtraUnix2Field {
+    /// file user id, only in the local header
+    pub uid: Option<u16>,
+    /// file group id, only in the local header
+    pub gid: Option<u16>,
+}
 
+impl ExtraUnix2Field {
+    const TAG: u16 = 0x7855;
+
+    fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
+        if i.eof_offset() == 0 {
+            return Ok(Self {
+                uid: None,
+                gid: None,
+            });
+        }
         seq! {Self {
-            atime: le_u32,
-            mtime: le_u32,
-            uid: le_u16,
-            gid: le_u16,
-            data: take(t_size).map(Cow::Borrowed),
+            uid: le_u16.map(Some),
+            gid: le_u16.map(Some),
         }}
         .parse_next(i)
     }
@@ -276,6 +419,76 @@ impl ExtraNewUnixField {
     }
 }
 
+/// WinZip AES extra data field (0x9901), see Appendix E of the appnote
+#[derive(Clone)]
+pub struct ExtraAesField {
+    /// 1 for AE-1, 2 for AE-2
+    pub version: u16,
+
+    /// 1, 2 or 3 for 128, 192 or 256-bit keys
+    pub strength: u8,
+
+    /// the method the data was compressed with, before being encrypted
+    pub method: Method,
+}
+
+impl ExtraAesField {
+    const TAG: u16 = 0x9901;
+
+    fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
+        let version = le_u16.parse_next(i)?;
+        let _ = literal("AE").parse_next(i)?; // vendor ID
+        let strength = le_u8.parse_next(i)?;
+        let method = Method::parser.parse_next(i)?;
+        Ok(Self {
+            version,
+            strength,
+            method,
+        })
+    }
+}
+
+/// Info-ZIP Unicode Path (0x7075) and Unicode Comment (0x6375) extra
+/// fields: the UTF-8 version of an entry's name or comment, for archives
+/// whose headers are in another encoding.
+///
+/// They're only valid for the header text they were made from, which tools
+/// that don't know about them may have changed: see [Self::text_for].
+#[derive(Clone, ToOwned, IntoOwned)]
+pub struct ExtraUnicodeField<'a> {
+    /// version of the field, only 1 is defined
+    pub version: u8,
+    /// CRC-32 of the name or comment in the header
+    pub crc32: u32,
+    /// the UTF-8 name or comment
+    pub text: Cow<'a, str>,
+}
+
+impl<'a> ExtraUnicodeField<'a> {
+    const TAG_PATH: u16 = 0x7075;
+    const TAG_COMMENT: u16 = 0x6375;
+
+    fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
+        let version = le_u8.parse_next(i)?;
+        let crc32 = le_u32.parse_next(i)?;
+        let text = take(i.eof_offset())
+            .try_map(std::str::from_utf8)
+            .map(Cow::Borrowed)
+            .parse_next(i)?;
+        Ok(Self {
+            version,
+            crc32,
+            text,
+        })
+    }
+
+    /// Returns the UTF-8 text if this field was made from `header_text`,
+    /// the name or comment as found in the header, and None otherwise.
+    pub fn text_for(&self, header_text: &[u8]) -> Option<&str> {
+        (self.version == 1 && self.crc32 == crc32fast::hash(header_text)).then_some(&*self.text)
+    }
+}
+
 /// 4.5.5 -NTFS Extra Field (0x000a):
 #[derive(Clone)]
 pub struct ExtraNtfsField {
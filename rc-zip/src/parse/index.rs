@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+use super::{Archive, DuplicateNamePolicy, Entry};
+
+/// Normalizes an entry name, for looking entries up and extracting them:
+///
+///   * backslashes, which archives made on Windows sometimes use, are
+///     turned into forward slashes,
+///   * leading `./` components are stripped, so `./docs/a.md` is `docs/a.md`,
+///   * runs of slashes are collapsed into one, so `docs//a.md` is
+///     `docs/a.md`,
+///   * the name is put in Unicode Normalization Form C (NFC), so that for
+///     example an `é` written as `e` followed by a combining accent matches
+///     a precomposed `é`.
+///
+/// Nothing else is done: names aren't lowercased (see [Archive::by_name_ci]
+/// for that), `..` components and a leading slash are kept as they are, and
+/// so is a trailing slash, which marks directories.
+///
+/// This is what [EntryIndex], [Archive::by_name_ci] and
+/// [Entry::safe_path] go through, so a name found by one is extracted to
+/// the same path.
+///
+/// ```
+/// use rc_zip::parse::normalize_name;
+///
+/// assert_eq!(normalize_name(".\\docs\\\\README.md"), "docs/README.md");
+/// assert_eq!(normalize_name("./././assets//"), "assets/");
+/// assert_eq!(normalize_name("/etc/../passwd"), "/etc/../passwd");
+/// ```
+pub fn normalize_name(name: &str) -> String {
+    let is_separator = |c| c == '/' || c == '\\';
+    let mut normalized = String::with_capacity(name.len());
+    if name.starts_with(is_separator) {
+        normalized.push('/');
+    }
+    let mut components = name
+        .split(is_separator)
+        .filter(|component| !component.is_empty())
+        .skip_while(|&component| component == ".")
+        .peekable();
+    let is_empty = components.peek().is_none();
+    for (i, component) in components.enumerate() {
+        if i > 0 {
+            normalized.push('/');
+        }
+        normalized.push_str(component);
+    }
+    if !is_empty && name.ends_with(is_separator) {
+        normalized.push('/');
+    }
+    match is_nfc_quick(normalized.chars()) {
+        IsNormalized::Yes => normalized,
+        _ => normalized.nfc().collect(),
+    }
+}
+
+/// An index of an archive's entries by name, for looking up many of them,
+/// see [Archive::build_index].
+///
+/// Names are [normalized](normalize_name), both in the index and when looking
+/// them up. When several entries have the same name, the one found is the one
+/// [Archive::by_name] would return, following the [DuplicateNamePolicy] the
+/// archive was read with.
+pub struct EntryIndex<'a> {
+    entries: &'a [Entry],
+    exact: HashMap<String, usize>,
+    folded: HashMap<String, usize>,
+}
+
+impl<'a> EntryIndex<'a> {
+    fn new(archive: &'a Archive) -> Self {
+        let mut index = Self {
+            entries: &archive.entries,
+            exact: HashMap::with_capacity(archive.entries.len()),
+            folded: HashMap::with_capacity(archive.entries.len()),
+        };
+        let first_wins = archive.duplicate_names == DuplicateNamePolicy::FirstWins;
+        for (i, entry) in archive.entries.iter().enumerate() {
+            let name = normalize_name(&entry.name);
+            for (map, key) in [
+                (&mut index.folded, name.to_lowercase()),
+                (&mut index.exact, name),
+            ] {
+                if first_wins {
+                    map.entry(key).or_insert(i);
+                } else {
+                    map.insert(key, i);
+                }
+            }
+        }
+        index
+    }
+
+    /// Looks up an entry by name, after [normalizing](normalize_name) it.
+    pub fn get(&self, name: &str) -> Option<&'a Entry> {
+        self.exact
+            .get(&normalize_name(name))
+            .map(|&i| &self.entries[i])
+    }
+
+    /// Looks up an entry by name ignoring case, after
+    /// [normalizing](normalize_name) it: names are compared once lowercased
+    /// with [str::to_lowercase].
+    ///
+    /// Different entries may then match, like `README` and `readme`: which
+    /// one is returned follows the [DuplicateNamePolicy] too.
+    pub fn get_ignore_case(&self, name: &str) -> Option<&'a Entry> {
+        self.folded
+            .get(&normalize_name(name).to_lowercase())
+            .map(|&i| &self.entries[i])
+    }
+
+    /// Returns the number of distinct normalized names.
+    pub fn len(&self) -> usize {
+        self.exact.len()
+    }
+
+    /// Returns true if the archive has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty()
+    }
+}
+
+impl Archive {
+    /// Builds an index of entries by name, for looking up many of them in
+    /// constant time, where [Archive::by_name] goes through all entries
+    /// every time. Names are [normalized](normalize_name), which also makes
+    /// lookups more predictable.
+    ///
+    /// This goes through all entries once, so it's only worth it for more
+    /// than a handful of lookups.
+    pub fn build_index(&self) -> EntryIndex<'_> {
+        EntryIndex::new(self)
+    }
+}
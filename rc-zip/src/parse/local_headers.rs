@@ -1,13 +1,13 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, io};
 
 use crate::{
-    encoding::{detect_utf8, Encoding},
+    encoding::{detect_utf8, Encoding, EncodingSource, NameDecoding},
     error::{Error, FormatError, UnsupportedError},
-    parse::{Method, MsdosTimestamp, Version},
+    parse::{field_len_u16, Method, MethodDetails, MsdosTimestamp, Strictness, Version},
 };
 
+use chrono::{DateTime, Utc};
 use ownable::{IntoOwned, ToOwned};
-use tracing::trace;
 use winnow::{
     binary::{le_u16, le_u32, le_u64, le_u8},
     combinator::opt,
@@ -17,7 +17,10 @@ use winnow::{
     PResult, Parser, Partial,
 };
 
-use super::{zero_datetime, Entry, ExtraField, ExtraFieldSettings, Mode};
+use super::{
+    zero_datetime, Entry, EntryTrust, ExtraField, ExtraFieldSettings, ExtraFields, Mode,
+    TimestampSource,
+};
 
 #[derive(Debug, ToOwned, IntoOwned)]
 /// 4.3.7 Local file header
@@ -69,6 +72,32 @@ impl<'a> LocalFileHeader<'a> {
 
     /// Parser for the local file header
     pub fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
+        let mut header = Self::fields_parser.parse_next(i)?;
+        if header.method == Method::Lzma {
+            let lzma_properties = LzmaProperties::parser.parse_next(i)?;
+            if let Err(e) = lzma_properties.error_if_unsupported() {
+                return Err(ErrMode::Cut(ContextError::from_external_error(
+                    i,
+                    ErrorKind::Verify,
+                    e,
+                )));
+            }
+            header.method_specific = MethodSpecific::Lzma(lzma_properties);
+        }
+        Ok(header)
+    }
+
+    /// Parses a local file header read verbatim, e.g. with the length given
+    /// by [Self::measure]: unlike [Self::parser], this doesn't read the
+    /// method-specific properties, which are left to [MethodSpecific::None].
+    pub fn parse_raw(data: &'a [u8]) -> Result<Self, Error> {
+        Self::fields_parser
+            .parse_next(&mut Partial::new(data))
+            .map_err(|_| FormatError::InvalidLocalHeader.into())
+    }
+
+    /// Parses the header up to its extra field
+    fn fields_parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
         let _ = literal(Self::SIGNATURE).parse_next(i)?;
 
         let reader_version = Version::parser.parse_next(i)?;
@@ -85,21 +114,6 @@ impl<'a> LocalFileHeader<'a> {
         let name = take(name_len).parse_next(i).map(Cow::Borrowed)?;
         let extra = take(extra_len).parse_next(i).map(Cow::Borrowed)?;
 
-        let method_specific = match method {
-            Method::Lzma => {
-                let lzma_properties = LzmaProperties::parser.parse_next(i)?;
-                if let Err(e) = lzma_properties.error_if_unsupported() {
-                    return Err(ErrMode::Cut(ContextError::from_external_error(
-                        i,
-                        ErrorKind::Verify,
-                        e,
-                    )));
-                }
-                MethodSpecific::Lzma(lzma_properties)
-            }
-            _ => MethodSpecific::None,
-        };
-
         Ok(Self {
             reader_version,
             flags,
@@ -110,10 +124,64 @@ impl<'a> LocalFileHeader<'a> {
             uncompressed_size,
             name,
             extra,
-            method_specific,
+            method_specific: MethodSpecific::None,
         })
     }
 
+    /// Serialize this header, in the same layout [Self::parser] expects.
+    ///
+    /// Like the parser, this includes the method-specific properties header
+    /// (e.g. for LZMA) that precedes the entry's data.
+    ///
+    /// Returns an error if the name or extra field are longer than what a u16
+    /// length field can express.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        let name_len = field_len_u16("name", self.name.len())?;
+        let extra_len = field_len_u16("extra field", self.extra.len())?;
+
+        w.write_all(Self::SIGNATURE.as_bytes())?;
+        self.reader_version.write_to(w)?;
+        w.write_all(&self.flags.to_le_bytes())?;
+        w.write_all(&u16::from(self.method).to_le_bytes())?;
+        self.modified.write_to(w)?;
+        w.write_all(&self.crc32.to_le_bytes())?;
+        w.write_all(&self.compressed_size.to_le_bytes())?;
+        w.write_all(&self.uncompressed_size.to_le_bytes())?;
+        w.write_all(&name_len.to_le_bytes())?;
+        w.write_all(&extra_len.to_le_bytes())?;
+        w.write_all(&self.name)?;
+        w.write_all(&self.extra)?;
+
+        match &self.method_specific {
+            MethodSpecific::None => Ok(()),
+            MethodSpecific::Lzma(props) => props.write_to(w),
+        }
+    }
+
+    /// Given the start of a local file header, returns its full length in
+    /// bytes, name and extra field included, or `None` if more data is needed
+    /// to tell. This is how the header can be read verbatim, see
+    /// [Entry::raw_central_record] for the central directory counterpart.
+    ///
+    /// Unlike [Self::parser], this doesn't include method-specific
+    /// properties, which are really the start of the entry's data.
+    pub fn measure(data: &[u8]) -> Result<Option<usize>, Error> {
+        // signature, then fixed-size fields up to the name & extra lengths
+        const FIXED_LEN: usize = 30;
+
+        let signature = Self::SIGNATURE.as_bytes();
+        if !data.starts_with(&signature[..signature.len().min(data.len())]) {
+            return Err(FormatError::InvalidLocalHeader.into());
+        }
+        if data.len() < FIXED_LEN {
+            return Ok(None);
+        }
+
+        let name_len = u16::from_le_bytes([data[26], data[27]]) as usize;
+        let extra_len = u16::from_le_bytes([data[28], data[29]]) as usize;
+        Ok(Some(FIXED_LEN + name_len + extra_len))
+    }
+
     /// Check for the presence of the bit flag that indicates a data descriptor
     /// is present after the file data.
     pub fn has_data_descriptor(&self) -> bool {
@@ -122,24 +190,49 @@ impl<'a> LocalFileHeader<'a> {
         self.flags & 0b1000 != 0
     }
 
+    /// Whether the header has a zip64 extra field, in which case sizes in
+    /// the entry's data descriptor are 64-bit.
+    pub fn has_zip64_field(&self) -> bool {
+        let settings = ExtraFieldSettings::default();
+        ExtraFields::new(&self.extra, settings)
+            .any(|field| matches!(field, Ok(ExtraField::Zip64(_))))
+    }
+
     /// Converts the local file header into an entry.
     pub fn as_entry(&self) -> Result<Entry, Error> {
         // see APPNOTE 4.4.4: Bit 11 is the language encoding flag (EFS)
-        let has_utf8_flag = self.flags & 0x800 == 0;
-        let encoding = if has_utf8_flag && detect_utf8(&self.name[..]).0 {
-            Encoding::Utf8
+        let has_utf8_flag = self.flags & 0x800 != 0;
+        let name_decoding = if detect_utf8(&self.name[..]).0 {
+            NameDecoding {
+                encoding: Encoding::Utf8,
+                source: if has_utf8_flag {
+                    EncodingSource::Utf8Flag
+                } else {
+                    EncodingSource::Utf8Assumed
+                },
+                confident: true,
+            }
         } else {
-            Encoding::Cp437
+            NameDecoding {
+                encoding: Encoding::Cp437,
+                source: EncodingSource::Fallback,
+                confident: false,
+            }
         };
-        let name = encoding.decode(&self.name[..])?;
-
         let mut entry = Entry {
-            name,
+            name: Default::default(),
+            name_decoding,
             method: self.method,
+            method_details: MethodDetails::from_flags(self.method, self.flags),
             comment: Default::default(),
+            raw_name: Default::default(),
+            raw_comment: Default::default(),
+            raw_extra: self.extra.to_vec(),
+            extra_settings: Default::default(),
             modified: self.modified.to_datetime().unwrap_or_else(zero_datetime),
             created: None,
             accessed: None,
+            timestamp_source: TimestampSource::Msdos,
             header_offset: 0,
             reader_version: self.reader_version,
             flags: self.flags,
@@ -149,38 +242,150 @@ impl<'a> LocalFileHeader<'a> {
             compressed_size: self.compressed_size as _,
             uncompressed_size: self.uncompressed_size as _,
             mode: Mode(0),
+            external_attrs: 0,
+            raw_central_record: None,
+            trust: EntryTrust::LocalHeader,
         };
 
-        if entry.name.ends_with('/') {
-            // believe it or not, this is straight from the APPNOTE
-            entry.mode |= Mode::DIR
-        };
-
-        let mut slice = Partial::new(&self.extra[..]);
         let settings = ExtraFieldSettings {
             compressed_size_u32: self.compressed_size,
             uncompressed_size_u32: self.uncompressed_size,
             header_offset_u32: 0,
         };
+        let (unicode, _) = entry.set_extra_fields(&self.extra, settings, Strictness::Strict)?;
+        entry.set_text(name_decoding, &self.name, &[], &unicode)?;
 
-        while !slice.is_empty() {
-            match ExtraField::mk_parser(settings).parse_next(&mut slice) {
-                Ok(ef) => {
-                    entry.set_extra_field(&ef);
-                }
-                Err(e) => {
-                    trace!("extra field error: {:#?}", e);
-                    return Err(FormatError::InvalidExtraField.into());
-                }
-            }
+        if entry.name.ends_with('/') {
+            // believe it or not, this is straight from the APPNOTE
+            entry.mode |= Mode::DIR
+        };
+
+        if let (
+            MethodSpecific::Lzma(props),
+            MethodDetails::Lzma {
+                ref mut properties, ..
+            },
+        ) = (&self.method_specific, &mut entry.method_details)
+        {
+            *properties = Some(*props);
         }
 
         Ok(entry)
     }
+
+    /// Compares this header with `central`, the entry's record in the central
+    /// directory, and returns the fields they disagree on: readers, including
+    /// this crate, go by the central directory, but other tools may use the
+    /// local header instead, and archives crafted to be read differently by
+    /// both are a known way to smuggle content past scanners.
+    ///
+    /// The CRC-32 and sizes are only compared if this header records them,
+    /// i.e. if the entry doesn't have a data descriptor. Fields recorded in
+    /// the extra field, like zip64 sizes and precise timestamps, are only
+    /// compared if it can be parsed.
+    pub fn mismatches(&self, central: &Entry) -> Vec<HeaderMismatch> {
+        let mut mismatches = Vec::new();
+        let local = self.as_entry().ok();
+
+        if self.name[..] != central.raw_name[..] {
+            mismatches.push(HeaderMismatch::Name {
+                local: self.name.to_vec(),
+                central: central.raw_name.clone(),
+            });
+        }
+        if self.method != central.method {
+            mismatches.push(HeaderMismatch::Method {
+                local: self.method,
+                central: central.method,
+            });
+        }
+        if let Some(local) = local.as_ref().filter(|_| !self.has_data_descriptor()) {
+            if local.crc32 != central.crc32 {
+                mismatches.push(HeaderMismatch::Crc32 {
+                    local: local.crc32,
+                    central: central.crc32,
+                });
+            }
+            if local.compressed_size != central.compressed_size {
+                mismatches.push(HeaderMismatch::CompressedSize {
+                    local: local.compressed_size,
+                    central: central.compressed_size,
+                });
+            }
+            if local.uncompressed_size != central.uncompressed_size {
+                mismatches.push(HeaderMismatch::UncompressedSize {
+                    local: local.uncompressed_size,
+                    central: central.uncompressed_size,
+                });
+            }
+        }
+        if let Some(local) = &local {
+            if local.modified != central.modified {
+                mismatches.push(HeaderMismatch::Modified {
+                    local: local.modified,
+                    central: central.modified,
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+/// A field an entry's local file header and central directory record
+/// disagree on, see [LocalFileHeader::mismatches].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeaderMismatch {
+    /// The file names differ, as raw bytes
+    Name {
+        /// name in the local header
+        local: Vec<u8>,
+        /// name in the central directory
+        central: Vec<u8>,
+    },
+
+    /// The compression methods differ
+    Method {
+        /// method in the local header
+        local: Method,
+        /// method in the central directory
+        central: Method,
+    },
+
+    /// The CRC-32 checksums differ
+    Crc32 {
+        /// checksum in the local header
+        local: u32,
+        /// checksum in the central directory
+        central: u32,
+    },
+
+    /// The compressed sizes differ
+    CompressedSize {
+        /// size in the local header
+        local: u64,
+        /// size in the central directory
+        central: u64,
+    },
+
+    /// The uncompressed sizes differ
+    UncompressedSize {
+        /// size in the local header
+        local: u64,
+        /// size in the central directory
+        central: u64,
+    },
+
+    /// The modification times differ
+    Modified {
+        /// time in the local header
+        local: DateTime<Utc>,
+        /// time in the central directory
+        central: DateTime<Utc>,
+    },
 }
 
 /// 4.3.9  Data descriptor:
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct DataDescriptorRecord {
     /// CRC32 checksum
     pub crc32: u32,
@@ -191,7 +396,7 @@ pub struct DataDescriptorRecord {
 }
 
 impl DataDescriptorRecord {
-    const SIGNATURE: &'static str = "PK\x07\x08";
+    pub(crate) const SIGNATURE: &'static str = "PK\x07\x08";
 
     /// Create a parser for the data descriptor record.
     pub fn mk_parser(is_zip64: bool) -> impl FnMut(&mut Partial<&'_ [u8]>) -> PResult<Self> {
@@ -223,10 +428,35 @@ impl DataDescriptorRecord {
             }
         }
     }
+
+    /// Serialize this data descriptor, always including the optional
+    /// signature. If `is_zip64` is false, the sizes are written as u32 and
+    /// an error is returned if they don't fit.
+    pub fn write_to(&self, w: &mut impl io::Write, is_zip64: bool) -> io::Result<()> {
+        w.write_all(Self::SIGNATURE.as_bytes())?;
+        w.write_all(&self.crc32.to_le_bytes())?;
+        if is_zip64 {
+            w.write_all(&self.compressed_size.to_le_bytes())?;
+            w.write_all(&self.uncompressed_size.to_le_bytes())
+        } else {
+            let too_large = |field: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{field} doesn't fit in a non-zip64 data descriptor"),
+                )
+            };
+            let compressed_size =
+                u32::try_from(self.compressed_size).map_err(|_| too_large("compressed size"))?;
+            let uncompressed_size = u32::try_from(self.uncompressed_size)
+                .map_err(|_| too_large("uncompressed size"))?;
+            w.write_all(&compressed_size.to_le_bytes())?;
+            w.write_all(&uncompressed_size.to_le_bytes())
+        }
+    }
 }
 
 /// 5.8.5 LZMA Properties header
-#[derive(Debug, ToOwned, IntoOwned)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ToOwned, IntoOwned)]
 pub struct LzmaProperties {
     /// major version
     pub major: u8,
@@ -251,6 +481,12 @@ impl LzmaProperties {
         .parse_next(i)
     }
 
+    /// Serialize this properties header, in the same layout [Self::parser] expects.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_all(&[self.major, self.minor])?;
+        w.write_all(&self.properties_size.to_le_bytes())
+    }
+
     /// Check if the LZMA version is supported.
     pub fn error_if_unsupported(&self) -> Result<(), Error> {
         if (self.major, self.minor) != (2, 0) {
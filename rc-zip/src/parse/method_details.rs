@@ -0,0 +1,129 @@
+use crate::parse::{LzmaProperties, Method};
+
+/// How an entry's data was compressed or encrypted, beyond its [Method]:
+/// parameters found in the general purpose bit flag and extra fields, see
+/// section 4.4.4 of the appnote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MethodDetails {
+    /// The method has no parameters that this crate knows of.
+    None,
+
+    /// Deflate and Deflate64: the compression option the data was
+    /// compressed with, from bits 1 and 2.
+    Deflate(DeflateOption),
+
+    /// Implode (method 6), from bits 1 and 2.
+    Implode {
+        /// Size of the sliding dictionary: 4K or 8K.
+        dictionary_size: u32,
+
+        /// Number of Shannon-Fano trees used to encode the data: 2 or 3.
+        trees: u8,
+    },
+
+    /// LZMA
+    Lzma {
+        /// Whether the data ends with an end-of-stream marker, from bit 1.
+        eos_marker: bool,
+
+        /// The LZMA properties header, which comes right before the data:
+        /// only known if the entry was read from its local file header.
+        properties: Option<LzmaProperties>,
+    },
+
+    /// WinZip AES encryption, from the AES extra field (0x9901), see
+    /// Appendix E of the appnote.
+    Aes {
+        /// 1 for AE-1, 2 for AE-2. With AE-2, the CRC-32 isn't stored.
+        version: u16,
+
+        /// The size of the encryption key.
+        strength: AesStrength,
+
+        /// The method the data was compressed with, before being encrypted.
+        method: Method,
+    },
+}
+
+impl MethodDetails {
+    /// The details that can be told from an entry's method and general
+    /// purpose bit flag alone.
+    pub(crate) fn from_flags(method: Method, flags: u16) -> Self {
+        let bit1 = flags & 0b010 != 0;
+        let bit2 = flags & 0b100 != 0;
+
+        match method {
+            Method::Deflate | Method::Deflate64 => Self::Deflate(match (bit2, bit1) {
+                (false, false) => DeflateOption::Normal,
+                (false, true) => DeflateOption::Maximum,
+                (true, false) => DeflateOption::Fast,
+                (true, true) => DeflateOption::SuperFast,
+            }),
+            Method::Unrecognized(6) => Self::Implode {
+                dictionary_size: if bit1 { 8192 } else { 4096 },
+                trees: if bit2 { 3 } else { 2 },
+            },
+            Method::Lzma => Self::Lzma {
+                eos_marker: bit1,
+                properties: None,
+            },
+            _ => Self::None,
+        }
+    }
+}
+
+/// The compression option used for Deflate and Deflate64, see
+/// [MethodDetails::Deflate].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeflateOption {
+    /// Normal (-en) compression option
+    Normal,
+
+    /// Maximum (-exx/-ex) compression option
+    Maximum,
+
+    /// Fast (-ef) compression option
+    Fast,
+
+    /// Super Fast (-es) compression option
+    SuperFast,
+}
+
+/// The key size used for WinZip AES encryption, see [MethodDetails::Aes].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AesStrength {
+    /// 128-bit key
+    Aes128,
+
+    /// 192-bit key
+    Aes192,
+
+    /// 256-bit key
+    Aes256,
+
+    /// A strength this crate doesn't know of
+    Unknown(u8),
+}
+
+impl AesStrength {
+    /// The size of the key in bits, if the strength is known.
+    pub fn key_bits(self) -> Option<u16> {
+        match self {
+            Self::Aes128 => Some(128),
+            Self::Aes192 => Some(192),
+            Self::Aes256 => Some(256),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+impl From<u8> for AesStrength {
+    fn from(strength: u8) -> Self {
+        match strength {
+            1 => Self::Aes128,
+            2 => Self::Aes192,
+            3 => Self::Aes256,
+            other => Self::Unknown(other),
+        }
+    }
+}
@@ -10,6 +10,14 @@
 mod archive;
 pub use archive::*;
 
+mod index;
+pub use index::*;
+
+mod query;
+
+mod overlap;
+pub use overlap::*;
+
 mod extra_field;
 pub use extra_field::*;
 
@@ -30,3 +38,20 @@ pub use eocd::*;
 
 mod local_headers;
 pub use local_headers::*;
+
+mod method_details;
+pub use method_details::*;
+
+mod offset;
+pub use offset::*;
+
+/// Converts the length of a variable-length field (name, extra field,
+/// comment) to the u16 that precedes it in most zip records.
+pub(crate) fn field_len_u16(field: &'static str, len: usize) -> std::io::Result<u16> {
+    u16::try_from(len).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{field} is {len} bytes long, which doesn't fit in a u16"),
+        )
+    })
+}
@@ -35,6 +35,65 @@ impl Mode {
     pub const STICKY: Self = Self(1 << 20);
     /// ?: non-regular file; nothing else is known
     pub const IRREGULAR: Self = Self(1 << 19);
+
+    /// Returns the permission bits, like `0o644`. Those are 0 for entries
+    /// made on systems that don't have permissions, other than MS-DOS
+    /// and Windows, whose read-only attribute is mapped to them.
+    pub fn permissions(&self) -> u32 {
+        self.0 & 0o777
+    }
+
+    /// Returns the type of file this mode describes.
+    pub fn file_type(&self) -> FileType {
+        if self.has(Self::DIR) {
+            FileType::Directory
+        } else if self.has(Self::SYMLINK) {
+            FileType::Symlink
+        } else if self.has(Self::NAMED_PIPE) {
+            FileType::NamedPipe
+        } else if self.has(Self::SOCKET) {
+            FileType::Socket
+        } else if self.has(Self::DEVICE) && self.has(Self::CHAR_DEVICE) {
+            FileType::CharDevice
+        } else if self.has(Self::DEVICE) {
+            FileType::BlockDevice
+        } else if self.has(Self::IRREGULAR) {
+            FileType::Irregular
+        } else {
+            FileType::File
+        }
+    }
+}
+
+/// The type of file a [Mode] describes, see [Mode::file_type].
+///
+/// This is finer-grained than [EntryKind](crate::parse::EntryKind), which
+/// treats everything that isn't a directory or a symlink as a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    /// A regular file
+    File,
+
+    /// A directory
+    Directory,
+
+    /// A symbolic link
+    Symlink,
+
+    /// A named pipe (FIFO)
+    NamedPipe,
+
+    /// A Unix domain socket
+    Socket,
+
+    /// A block device
+    BlockDevice,
+
+    /// A character device
+    CharDevice,
+
+    /// Some other non-regular file
+    Irregular,
 }
 
 impl fmt::Display for Mode {
@@ -115,7 +174,7 @@ impl From<UnixMode> for Mode {
 
         match m & UnixMode::IFMT {
             UnixMode::IFBLK => mode |= Mode::DEVICE,
-            UnixMode::IFCHR => mode |= Mode::DEVICE & Mode::CHAR_DEVICE,
+            UnixMode::IFCHR => mode |= Mode::DEVICE | Mode::CHAR_DEVICE,
             UnixMode::IFDIR => mode |= Mode::DIR,
             UnixMode::IFIFO => mode |= Mode::NAMED_PIPE,
             UnixMode::IFLNK => mode |= Mode::SYMLINK,
@@ -146,7 +205,7 @@ impl From<MsdosMode> for Mode {
             Mode(0o666)
         };
         if m.has(MsdosMode::READ_ONLY) {
-            mode &= Mode(0o222);
+            mode &= Mode(!0o222);
         }
 
         mode
@@ -204,6 +263,40 @@ impl From<u32> for UnixMode {
     }
 }
 
+impl From<Mode> for UnixMode {
+    fn from(m: Mode) -> Self {
+        let mut mode = UnixMode(m.0 & 0o777);
+
+        mode |= if m.has(Mode::DIR) {
+            UnixMode::IFDIR
+        } else if m.has(Mode::SYMLINK) {
+            UnixMode::IFLNK
+        } else if m.has(Mode::NAMED_PIPE) {
+            UnixMode::IFIFO
+        } else if m.has(Mode::SOCKET) {
+            UnixMode::IFSOCK
+        } else if m.has(Mode::DEVICE) && m.has(Mode::CHAR_DEVICE) {
+            UnixMode::IFCHR
+        } else if m.has(Mode::DEVICE) {
+            UnixMode::IFBLK
+        } else {
+            UnixMode::IFREG
+        };
+
+        if m.has(Mode::SETGID) {
+            mode |= UnixMode::ISGID
+        }
+        if m.has(Mode::SETUID) {
+            mode |= UnixMode::ISUID
+        }
+        if m.has(Mode::STICKY) {
+            mode |= UnixMode::ISVTX
+        }
+
+        mode
+    }
+}
+
 /// MsdosMode represents the file mode and permission bits for MS-DOS
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MsdosMode(pub u32);
@@ -214,6 +307,18 @@ impl MsdosMode {
 
     /// the file is read-only
     pub const READ_ONLY: Self = Self(0x01);
+
+    /// the file is hidden
+    pub const HIDDEN: Self = Self(0x02);
+
+    /// the file is used by the operating system
+    pub const SYSTEM: Self = Self(0x04);
+
+    /// the entry is the volume label
+    pub const VOLUME_LABEL: Self = Self(0x08);
+
+    /// the file has changed since it was last backed up
+    pub const ARCHIVE: Self = Self(0x20);
 }
 
 impl From<u32> for MsdosMode {
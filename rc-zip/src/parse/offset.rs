@@ -0,0 +1,53 @@
+//! Checked arithmetic for offsets read from archives.
+
+use crate::error::FormatError;
+
+/// Checked arithmetic for offsets and sizes read from archives.
+///
+/// Offsets, sizes and disk numbers are whatever the archive says they are,
+/// so computing with them can overflow, which would panic in debug builds
+/// and wrap around in release builds. The parsers go through these instead,
+/// and malformed archives get a [FormatError::InvalidOffsets].
+///
+/// ```
+/// use rc_zip::{error::FormatError, parse::Offsets};
+///
+/// assert_eq!(Offsets::add(30, 12).unwrap(), 42);
+/// assert!(matches!(Offsets::add(u64::MAX, 1), Err(FormatError::InvalidOffsets)));
+/// assert_eq!(Offsets::adjust(100, -30).unwrap(), 70);
+/// assert!(matches!(Offsets::adjust(10, -30), Err(FormatError::InvalidOffsets)));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Offsets;
+
+impl Offsets {
+    /// `offset + len`, e.g. where a record that starts at `offset` ends.
+    pub fn add(offset: u64, len: u64) -> Result<u64, FormatError> {
+        offset.checked_add(len).ok_or(FormatError::InvalidOffsets)
+    }
+
+    /// `offset - len`, e.g. where a record of length `len` that ends at
+    /// `offset` starts.
+    pub fn sub(offset: u64, len: u64) -> Result<u64, FormatError> {
+        offset.checked_sub(len).ok_or(FormatError::InvalidOffsets)
+    }
+
+    /// Moves `offset` by `adjustment`, which is negative for archives that
+    /// were cut out of a larger file.
+    pub fn adjust(offset: u64, adjustment: i64) -> Result<u64, FormatError> {
+        offset
+            .checked_add_signed(adjustment)
+            .ok_or(FormatError::InvalidOffsets)
+    }
+
+    /// Adds two adjustments, see [Self::adjust].
+    pub fn combine(a: i64, b: i64) -> Result<i64, FormatError> {
+        a.checked_add(b).ok_or(FormatError::InvalidOffsets)
+    }
+
+    /// The signed distance from `from` to `to`, i.e. the adjustment that
+    /// moves `from` to `to`.
+    pub fn delta(from: u64, to: u64) -> Result<i64, FormatError> {
+        i64::try_from(i128::from(to) - i128::from(from)).map_err(|_| FormatError::InvalidOffsets)
+    }
+}
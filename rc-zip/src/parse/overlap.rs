@@ -0,0 +1,103 @@
+use super::Archive;
+
+/// Two entries whose data overlaps, found by [Archive::detect_overlaps].
+///
+/// `first` and `second` are indices into [Archive::entries], `first` being
+/// the entry whose range starts first in the file, or that comes first in
+/// the central directory when they start at the same offset.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Overlap {
+    /// The entry whose local header comes first.
+    pub first: usize,
+
+    /// The entry whose local header is within `first`'s range, or at the
+    /// same offset.
+    pub second: usize,
+
+    /// How the two entries overlap.
+    pub kind: OverlapKind,
+}
+
+/// How two entries overlap, see [Overlap].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OverlapKind {
+    /// Both entries point at the same local header, so they have the same
+    /// data. Entries of an archive made this way can all be extracted from
+    /// a single, highly compressed, stream.
+    SharedHeader,
+
+    /// The second entry's local header is within the first one's data, as
+    /// in zip bombs made of nested entries, each one's data containing the
+    /// next one.
+    Nested,
+}
+
+impl Archive {
+    /// Finds entries whose data overlaps, or that point at the same local
+    /// header: each entry normally has its own local header, followed by its
+    /// data, and no two entries share any byte. Zip bombs break that rule,
+    /// to get a lot of output from a small archive, so archives from
+    /// untrusted sources can be rejected if this returns anything, before
+    /// extracting any of their entries.
+    ///
+    /// Each entry's range starts at its [header
+    /// offset](super::Entry::header_offset) and spans at least its local
+    /// header's fixed fields, its name and its [compressed
+    /// size](super::Entry::compressed_size). Local headers aren't read, so
+    /// their extra fields aren't accounted for.
+    ///
+    /// An entry overlapping several others is reported once: along with the
+    /// first entry at the same header offset if there's one, or else with
+    /// the earlier one whose range reaches the furthest. That keeps this
+    /// linear in the number of entries (after sorting them), which matters
+    /// for archives with millions of entries all sharing the same header.
+    pub fn detect_overlaps(&self) -> Vec<Overlap> {
+        // signature, then fixed-size fields up to the name & extra lengths
+        const LOCAL_HEADER_FIXED_LEN: u64 = 30;
+
+        let mut ranges: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                // sizes come straight from the archive, they may be bogus
+                let end = entry
+                    .header_offset
+                    .saturating_add(LOCAL_HEADER_FIXED_LEN + entry.raw_name.len() as u64)
+                    .saturating_add(entry.compressed_size);
+                (entry.header_offset, index, end)
+            })
+            .collect();
+        ranges.sort_unstable();
+
+        let mut overlaps = Vec::new();
+        // the first entry at the current header offset
+        let mut header: Option<(u64, usize)> = None;
+        // the range reaching the furthest so far, and its entry
+        let mut furthest: Option<(u64, usize)> = None;
+        for (start, index, end) in ranges {
+            match (header, furthest) {
+                (Some((header_offset, first)), _) if start == header_offset => {
+                    overlaps.push(Overlap {
+                        first,
+                        second: index,
+                        kind: OverlapKind::SharedHeader,
+                    });
+                }
+                (_, Some((furthest_end, first))) if start < furthest_end => {
+                    header = Some((start, index));
+                    overlaps.push(Overlap {
+                        first,
+                        second: index,
+                        kind: OverlapKind::Nested,
+                    });
+                }
+                _ => header = Some((start, index)),
+            }
+            if furthest.map_or(true, |(furthest_end, _)| end > furthest_end) {
+                furthest = Some((end, index));
+            }
+        }
+        overlaps
+    }
+}
@@ -0,0 +1,61 @@
+use super::{normalize_name, Archive, DuplicateNamePolicy, Entry};
+
+impl Archive {
+    /// Looks up an entry by name ignoring case, after
+    /// [normalizing](normalize_name) both names, so `Docs\\README.md` is
+    /// found as `docs/readme.md`: names are compared once lowercased with
+    /// [str::to_lowercase]. Archives made on Windows often differ from what
+    /// one would expect in both ways.
+    ///
+    /// Like [Archive::by_name], this goes through all entries: see
+    /// [EntryIndex::get_ignore_case](super::EntryIndex::get_ignore_case)
+    /// for many lookups. Different entries may match, like `README` and
+    /// `readme`: which one is returned depends on the [DuplicateNamePolicy]
+    /// the archive was read with.
+    pub fn by_name_ci<N: AsRef<str>>(&self, name: N) -> Option<&Entry> {
+        let name = normalize_name(name.as_ref()).to_lowercase();
+        let mut matching = self
+            .entries
+            .iter()
+            .filter(|entry| normalize_name(&entry.name).to_lowercase() == name);
+        match self.duplicate_names {
+            DuplicateNamePolicy::FirstWins => matching.next(),
+            DuplicateNamePolicy::LastWins | DuplicateNamePolicy::Error => matching.last(),
+        }
+    }
+
+    /// Returns the entries under the directory `dir`, in the order of the
+    /// central directory. Whether `dir` ends with a slash doesn't matter, and
+    /// the entry for the directory itself, if there's one, isn't returned.
+    /// With an empty `dir`, all entries are returned.
+    ///
+    /// Names are compared as they are, see [Archive::build_index] for how
+    /// they may differ.
+    pub fn entries_under<'a>(&'a self, dir: &str) -> impl Iterator<Item = &'a Entry> + 'a {
+        let mut prefix = dir.trim_end_matches('/').to_owned();
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        self.entries
+            .iter()
+            .filter(move |entry| entry.name.len() > prefix.len() && entry.name.starts_with(&prefix))
+    }
+
+    /// Returns the entries whose name matches the glob `pattern`, in the
+    /// order of the central directory, or an error if the pattern is invalid.
+    ///
+    /// `*` and `?` don't match `/`, while `**` matches any number of
+    /// directories, so `assets/**/*.png` matches `assets/a.png` and
+    /// `assets/ui/icons/b.png`. See [globset::Glob] for the full syntax.
+    #[cfg(feature = "glob")]
+    pub fn glob(&self, pattern: &str) -> Result<impl Iterator<Item = &Entry> + '_, globset::Error> {
+        let matcher = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        Ok(self
+            .entries
+            .iter()
+            .filter(move |entry| matcher.is_match(&entry.name)))
+    }
+}
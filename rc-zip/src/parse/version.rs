@@ -1,6 +1,6 @@
 use num_enum::{FromPrimitive, IntoPrimitive};
 use ownable::{IntoOwned, ToOwned};
-use std::fmt;
+use std::{fmt, io};
 use winnow::{binary::le_u8, seq, PResult, Parser, Partial};
 
 /// A zip version (either created by, or required when reading an archive).
@@ -34,6 +34,11 @@ impl Version {
         }}
         .parse_next(i)
     }
+
+    /// Serialize this version, in the same layout [Self::parser] expects
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_all(&[self.version, self.host_system.into()])
+    }
 }
 
 /// System on which an archive was created, as encoded into a version u16.
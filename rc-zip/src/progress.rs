@@ -0,0 +1,48 @@
+//! Progress reporting, for showing progress bars while an archive is read
+//! and its entries are decompressed, without wrapping readers.
+//!
+//! A [Progress] is set with [ArchiveReadOptions::progress] for reading the
+//! central directory, and [EntryFsm::with_progress] for decompressing
+//! entries: the state machines call it, so it works the same with every
+//! I/O model.
+//!
+//! [ArchiveReadOptions::progress]: crate::fsm::ArchiveReadOptions::progress
+//! [EntryFsm::with_progress]: crate::fsm::EntryFsm::with_progress
+
+use std::fmt;
+
+use crate::{fsm::VerificationStatus, parse::Entry};
+
+/// Receives progress updates. All methods do nothing by default.
+///
+/// Methods take `&self`, since several entries may be decompressed at the
+/// same time, on different threads: implementations typically use atomics
+/// or a mutex.
+pub trait Progress: Send + Sync {
+    /// Called as the central directory is read, with how many bytes of it
+    /// were parsed so far, and its size as given by the end of central
+    /// directory record.
+    fn central_directory(&self, read: u64, total: u64) {
+        let _ = (read, total);
+    }
+
+    /// Called as `entry` is decompressed, with how many compressed bytes
+    /// were consumed and uncompressed bytes produced so far: compare them
+    /// to [Entry::compressed_size] and [Entry::uncompressed_size].
+    fn entry(&self, entry: &Entry, compressed: u64, uncompressed: u64) {
+        let _ = (entry, compressed, uncompressed);
+    }
+
+    /// Called once `entry` was entirely decompressed and its checksum
+    /// verified, with how it was: see
+    /// [Verification](crate::fsm::Verification).
+    fn entry_done(&self, entry: &Entry, status: VerificationStatus) {
+        let _ = (entry, status);
+    }
+}
+
+impl fmt::Debug for dyn Progress + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("dyn Progress")
+    }
+}
@@ -0,0 +1,52 @@
+//! Reports of verifying a whole archive: reading every entry, checking its
+//! checksum and sizes, and that its local header agrees with the central
+//! directory. This is done by the I/O crates, e.g. with rc-zip-sync's
+//! `ArchiveHandle::verify`.
+
+use crate::{error::Error, fsm::VerificationStatus, parse::HeaderMismatch};
+
+/// What verifying an archive found, for each of its entries.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// One report per entry, in the order of the central directory.
+    pub entries: Vec<EntryReport>,
+}
+
+impl VerifyReport {
+    /// Returns true if all entries passed.
+    pub fn is_ok(&self) -> bool {
+        self.entries.iter().all(|entry| entry.result.is_ok())
+    }
+
+    /// Returns the reports of the entries that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &EntryReport> {
+        self.entries.iter().filter(|entry| entry.result.is_err())
+    }
+}
+
+/// What verifying an entry found, see [VerifyReport].
+#[derive(Debug)]
+pub struct EntryReport {
+    /// The entry's name, from the central directory.
+    pub name: String,
+
+    /// Where the entry's local header is, see
+    /// [Entry::header_offset](crate::parse::Entry::header_offset).
+    pub header_offset: u64,
+
+    /// The fields the entry's local header and central directory record
+    /// disagree on: if there are any, the entry's data isn't read, and it
+    /// fails with [FormatError::LocalHeaderMismatch].
+    ///
+    /// [FormatError::LocalHeaderMismatch]: crate::error::FormatError::LocalHeaderMismatch
+    pub mismatches: Vec<HeaderMismatch>,
+
+    /// How the entry's checksum was verified if it passed, or why it failed:
+    /// typically [FormatError::WrongChecksum], [FormatError::WrongSize] or
+    /// [FormatError::LocalHeaderMismatch].
+    ///
+    /// [FormatError::WrongChecksum]: crate::error::FormatError::WrongChecksum
+    /// [FormatError::WrongSize]: crate::error::FormatError::WrongSize
+    /// [FormatError::LocalHeaderMismatch]: crate::error::FormatError::LocalHeaderMismatch
+    pub result: Result<VerificationStatus, Error>,
+}
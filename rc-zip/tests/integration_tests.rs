@@ -1,9 +1,22 @@
 use std::cmp;
 
 use rc_zip::{
+    apple_double::{apple_double_target, is_apple_double, AppleDouble, FinderInfo},
+    chrono::{Duration, TimeZone, Utc},
     corpus,
-    fsm::{ArchiveFsm, FsmResult},
+    encoding::{Encoding, EncodingDetection, EncodingPolicy, EncodingSource, NameDecoding},
+    error::{Error, FormatError, LimitExceeded, UnsupportedError, Warning},
+    fsm::{ArchiveFsm, ArchiveReadOptions, EntryFsm, FsmResult},
+    jar::{split_versioned_name, Manifest},
+    parse::{
+        normalize_name, AesStrength, Archive, CentralDirectoryFileHeader, DeflateOption,
+        EndOfCentralDirectory64Locator, EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord,
+        ExtraField, ExtraFields, FileType, HostSystem, LocalFileHeader, Method, MethodDetails,
+        MethodSpecific, Mode, MsdosMode, MsdosTimestamp, Overlap, OverlapKind, Strictness,
+        TimestampSource, UnixMode, Version,
+    },
 };
+use winnow::Partial;
 
 #[test]
 fn state_machine() {
@@ -49,3 +62,1475 @@ fn state_machine() {
     // cool, we have the archive
     let _ = archive;
 }
+
+/// Parses a record out of `bytes`, serializes it back, and checks that
+/// the result is byte-for-byte identical to what was parsed.
+fn round_trip<'a, T>(
+    bytes: &'a [u8],
+    parser: impl Fn(&mut Partial<&'a [u8]>) -> winnow::PResult<T>,
+    write: impl Fn(&T, &mut Vec<u8>) -> std::io::Result<()>,
+) -> (T, usize) {
+    let mut input = Partial::new(bytes);
+    let record = parser(&mut input).unwrap();
+    let consumed = bytes.len() - input.len();
+
+    let mut out = Vec::new();
+    write(&record, &mut out).unwrap();
+    assert_eq!(&out[..], &bytes[..consumed]);
+
+    (record, consumed)
+}
+
+#[test]
+fn serializers_round_trip() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let cases = cases
+        .iter()
+        .filter(|x| ["zip64.zip", "found-me-lzma.zip", "test.zip"].contains(&x.name));
+    for case in cases {
+        let bytes = case.bytes();
+
+        let eocd_offset = bytes.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+        let (eocd, _) = round_trip(
+            &bytes[eocd_offset..],
+            EndOfCentralDirectoryRecord::parser,
+            |r, w| r.write_to(w),
+        );
+
+        let mut directory_offset = eocd.directory_offset as u64;
+        if let Some(locator_offset) = eocd_offset.checked_sub(20) {
+            if bytes[locator_offset..].starts_with(b"PK\x06\x07") {
+                let (locator, _) = round_trip(
+                    &bytes[locator_offset..],
+                    EndOfCentralDirectory64Locator::parser,
+                    |r, w| r.write_to(w),
+                );
+                let (eocd64, _) = round_trip(
+                    &bytes[locator.directory_offset as usize..],
+                    EndOfCentralDirectory64Record::parser,
+                    |r, w| r.write_to(w),
+                );
+                directory_offset = eocd64.directory_offset;
+            }
+        }
+
+        let mut offset = directory_offset as usize;
+        while bytes[offset..].starts_with(b"PK\x01\x02") {
+            let (header, consumed) = round_trip(
+                &bytes[offset..],
+                CentralDirectoryFileHeader::parser,
+                |r, w| r.write_to(w),
+            );
+            offset += consumed;
+
+            round_trip(
+                &bytes[header.header_offset as usize..],
+                LocalFileHeader::parser,
+                |r, w| r.write_to(w),
+            );
+        }
+    }
+}
+
+fn read_archive(bytes: &[u8]) -> Archive {
+    read_archive_with_options(bytes, Default::default())
+}
+
+fn read_archive_with_options(bytes: &[u8], options: ArchiveReadOptions) -> Archive {
+    try_read_archive_with_options(bytes, options).unwrap()
+}
+
+fn try_read_archive_with_options(
+    bytes: &[u8],
+    options: ArchiveReadOptions,
+) -> Result<Archive, Error> {
+    let mut fsm = ArchiveFsm::with_options(bytes.len() as u64, options);
+    loop {
+        if let Some(offset) = fsm.wants_read() {
+            let slice = &bytes[offset as usize..];
+            let len = cmp::min(slice.len(), fsm.space().len());
+            fsm.space()[..len].copy_from_slice(&slice[..len]);
+            fsm.fill(len);
+        }
+
+        fsm = match fsm.process()? {
+            FsmResult::Continue(fsm) => fsm,
+            FsmResult::Done(archive) => return Ok(archive),
+        }
+    }
+}
+
+#[test]
+fn name_decoding() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    for (name, encoding, source) in [
+        ("test.zip", Encoding::Utf8, EncodingSource::Utf8Assumed),
+        ("utf8-winrar.zip", Encoding::Utf8, EncodingSource::Utf8Flag),
+        (
+            "shift-jis.zip",
+            Encoding::ShiftJis,
+            EncodingSource::Detected,
+        ),
+    ] {
+        let case = cases.iter().find(|x| x.name == name).unwrap();
+        let archive = read_archive(&case.bytes());
+        for entry in archive.entries() {
+            assert_eq!(entry.name_decoding.encoding, encoding, "{name}");
+            assert_eq!(entry.name_decoding.source, source, "{name}");
+        }
+    }
+}
+
+#[test]
+fn unicode_extra_fields() {
+    let unicode_field = |tag: u16, header_text: &[u8], text: &str| {
+        let mut field = tag.to_le_bytes().to_vec();
+        field.extend_from_slice(&(5 + text.len() as u16).to_le_bytes());
+        field.push(1);
+        field.extend_from_slice(&crc32fast::hash(header_text).to_le_bytes());
+        field.extend_from_slice(text.as_bytes());
+        field
+    };
+    let entry = |flags: u16, extra: Vec<u8>| {
+        let version = Version {
+            host_system: HostSystem::WindowsNtfs,
+            version: 20,
+        };
+        let header = CentralDirectoryFileHeader {
+            creator_version: version,
+            reader_version: version,
+            flags,
+            method: Method::Store,
+            modified: MsdosTimestamp { time: 0, date: 0 },
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            disk_nbr_start: 0,
+            internal_attrs: 0,
+            external_attrs: 0,
+            header_offset: 0,
+            name: b"na\x8bve.txt"[..].into(),
+            extra: extra.into(),
+            comment: b"caf\x82"[..].into(),
+        };
+        let decoding = NameDecoding {
+            encoding: Encoding::Cp437,
+            source: EncodingSource::Detected,
+            confident: false,
+        };
+        header.as_entry(decoding, 0).unwrap()
+    };
+
+    // the header is CP-437, where ï and é are single bytes too
+    let e = entry(0, vec![]);
+    assert_eq!((e.name.as_str(), e.comment.as_str()), ("naïve.txt", "café"));
+    assert_eq!(e.name_decoding.source, EncodingSource::Detected);
+
+    let extra = [
+        unicode_field(0x7075, b"na\x8bve.txt", "naïve 🙂.txt"),
+        unicode_field(0x6375, b"caf\x82", "café 🙂"),
+    ]
+    .concat();
+    let e = entry(0, extra.clone());
+    assert_eq!(
+        (e.name.as_str(), e.comment.as_str()),
+        ("naïve 🙂.txt", "café 🙂")
+    );
+    assert_eq!(e.name_decoding.encoding, Encoding::Utf8);
+    assert_eq!(e.name_decoding.source, EncodingSource::UnicodeExtraField);
+    // the header's text is kept as-is
+    assert_eq!(
+        (&e.raw_name[..], &e.raw_comment[..]),
+        (&b"na\x8bve.txt"[..], &b"caf\x82"[..])
+    );
+
+    // with the language encoding flag set, the header is used as-is
+    let e = entry(0x800, extra);
+    assert_eq!(e.name, "naïve.txt");
+
+    // fields made from another header text are stale: a tool that doesn't
+    // know about them renamed the entry
+    let extra = unicode_field(0x7075, b"old-name.txt", "old 🙂.txt");
+    let e = entry(0, extra);
+    assert_eq!(e.name, "naïve.txt");
+    assert_eq!(e.name_decoding.source, EncodingSource::Detected);
+}
+
+#[test]
+fn encoding_detection() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let bytes = cases
+        .iter()
+        .find(|x| x.name == "shift-jis.zip")
+        .unwrap()
+        .bytes();
+
+    for (detection, encoding, source) in [
+        (
+            EncodingDetection::default(),
+            Encoding::ShiftJis,
+            EncodingSource::Detected,
+        ),
+        (
+            EncodingDetection {
+                allowed: vec![Encoding::Utf8, Encoding::Cp437],
+                ..Default::default()
+            },
+            Encoding::Cp437,
+            EncodingSource::Fallback,
+        ),
+        (
+            EncodingDetection {
+                allowed: vec![Encoding::Utf8],
+                fallback: Encoding::ShiftJis,
+                ..Default::default()
+            },
+            Encoding::ShiftJis,
+            EncodingSource::Fallback,
+        ),
+    ] {
+        let archive = read_archive_with_options(
+            &bytes,
+            ArchiveReadOptions::default().with_encoding_detection(detection.clone()),
+        );
+        assert_eq!(archive.encoding(), encoding, "{detection:?}");
+        for entry in archive.entries() {
+            assert_eq!(entry.name_decoding.source, source, "{detection:?}");
+        }
+    }
+}
+
+#[test]
+fn encoding_policy() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    for (name, policy, encoding, source) in [
+        // a hint that doesn't fit the names is ignored
+        (
+            "shift-jis.zip",
+            EncodingPolicy::Hint(Encoding::Utf8),
+            Encoding::ShiftJis,
+            EncodingSource::Detected,
+        ),
+        (
+            "shift-jis.zip",
+            EncodingPolicy::Hint(Encoding::Cp437),
+            Encoding::Cp437,
+            EncodingSource::Given,
+        ),
+        (
+            "shift-jis.zip",
+            EncodingPolicy::Force(Encoding::Cp437),
+            Encoding::Cp437,
+            EncodingSource::Given,
+        ),
+        // a hint isn't needed for UTF-8 names, but forcing applies anyway
+        (
+            "test.zip",
+            EncodingPolicy::Hint(Encoding::Cp866),
+            Encoding::Utf8,
+            EncodingSource::Utf8Assumed,
+        ),
+        (
+            "test.zip",
+            EncodingPolicy::Force(Encoding::Cp866),
+            Encoding::Cp866,
+            EncodingSource::Given,
+        ),
+        // the language encoding flag wins
+        (
+            "utf8-winrar.zip",
+            EncodingPolicy::Force(Encoding::ShiftJis),
+            Encoding::Utf8,
+            EncodingSource::Utf8Flag,
+        ),
+    ] {
+        let case = cases.iter().find(|x| x.name == name).unwrap();
+        let archive = read_archive_with_options(
+            &case.bytes(),
+            ArchiveReadOptions::default().with_encoding(policy),
+        );
+        for entry in archive.entries() {
+            assert_eq!(entry.name_decoding.encoding, encoding, "{name} {policy:?}");
+            assert_eq!(entry.name_decoding.source, source, "{name} {policy:?}");
+        }
+    }
+}
+
+#[test]
+fn archive_stats() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let case = cases.iter().find(|x| x.name == "test.zip").unwrap();
+    let archive = read_archive(&case.bytes());
+    let stats = archive.stats();
+
+    assert_eq!(stats.entries, 2);
+    assert_eq!(
+        stats.uncompressed_size,
+        archive.entries().map(|e| e.uncompressed_size).sum::<u64>()
+    );
+    assert_eq!(
+        stats.compressed_size,
+        archive.entries().map(|e| e.compressed_size).sum::<u64>()
+    );
+    assert_eq!(stats.methods.values().sum::<usize>(), 2);
+    assert_eq!(stats.encrypted, 0);
+    let oldest = archive.by_name("test.txt").unwrap().modified;
+    let newest = archive.by_name("gophercolor16x16.png").unwrap().modified;
+    assert_eq!(stats.oldest, Some(oldest));
+    assert_eq!(stats.newest, Some(newest));
+
+    let empty = read_archive(&corpus::stored_zip(&[]));
+    assert_eq!(empty.stats(), Default::default());
+}
+
+#[test]
+fn raw_records() {
+    corpus::install_test_subscriber();
+
+    let bytes = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    assert!(read_archive(&bytes)
+        .entries()
+        .all(|e| e.raw_central_record.is_none()));
+
+    let options = ArchiveReadOptions::default().with_keep_raw_records(true);
+    let archive = read_archive_with_options(&bytes, options);
+    // records are contiguous, and the last one is followed by the eocd
+    let first = archive.entries().next().unwrap();
+    let mut offset = first.raw_central_record.as_ref().unwrap().offset;
+    for entry in archive.entries() {
+        let raw = entry.raw_central_record.as_ref().unwrap();
+        assert_eq!(raw.offset, offset);
+        assert_eq!(&bytes[offset as usize..][..raw.inner.len()], &raw.inner[..]);
+        offset += raw.inner.len() as u64;
+
+        let header = CentralDirectoryFileHeader::parser(&mut Partial::new(&raw.inner[..])).unwrap();
+        assert_eq!(header.name, entry.name.as_bytes());
+    }
+    assert_eq!(&bytes[offset as usize..][..4], b"PK\x05\x06");
+}
+
+#[test]
+fn negative_global_offset() {
+    corpus::install_test_subscriber();
+
+    // as if the archive was cut out of a larger file, without fixing up
+    // its offsets: they're all 1000 bytes too far.
+    let mut bytes = corpus::stored_zip(&[("hello.txt", b"hello"), ("world.txt", b"world")]);
+    let shift = |bytes: &mut [u8], at: usize| {
+        let field = at..at + 4;
+        let value = u32::from_le_bytes(bytes[field.clone()].try_into().unwrap());
+        bytes[field].copy_from_slice(&(value + 1000).to_le_bytes());
+        value as usize
+    };
+    let eocd_offset = bytes.len() - 22;
+    let mut offset = shift(&mut bytes, eocd_offset + 16);
+    while bytes[offset..].starts_with(b"PK\x01\x02") {
+        shift(&mut bytes, offset + 42);
+        let len = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize;
+        offset += 46 + len(offset + 28) + len(offset + 30) + len(offset + 32);
+    }
+
+    let archive = read_archive(&bytes);
+    let offsets: Vec<_> = archive.entries().map(|e| e.header_offset).collect();
+    assert_eq!(offsets[0], 0);
+    assert!(bytes[offsets[1] as usize..].starts_with(b"PK\x03\x04"));
+}
+
+#[test]
+fn directory_offset_overflow() {
+    corpus::install_test_subscriber();
+
+    // the zip64 record's directory offset can be anything: the distance from
+    // there to where the directory really is, or the entries' offsets moved
+    // by it, may not fit
+    let bytes = corpus::ZipBuilder::new()
+        .with_entry(corpus::ZipEntry::new("a.txt", "hello"))
+        .with_zip64_eocd(true)
+        .build();
+    let eocd64 = bytes.windows(4).rposition(|w| w == b"PK\x06\x06").unwrap();
+    for directory_offset in [1 << 63, u64::MAX] {
+        let mut bytes = bytes.clone();
+        bytes[eocd64 + 48..][..8].copy_from_slice(&u64::to_le_bytes(directory_offset));
+        assert!(matches!(
+            try_read_archive_with_options(&bytes, Default::default()),
+            Err(Error::Format(FormatError::InvalidOffsets))
+        ));
+    }
+
+    // a directory bigger than everything before the end of central directory
+    // record would start before the file does
+    let mut bytes = bytes.clone();
+    bytes[eocd64 + 40..][..8].copy_from_slice(&u64::to_le_bytes(u64::MAX));
+    assert!(matches!(
+        try_read_archive_with_options(&bytes, Default::default()),
+        Err(Error::Format(FormatError::InvalidOffsets))
+    ));
+}
+
+#[test]
+fn eocd_search() {
+    corpus::install_test_subscriber();
+
+    let zip = corpus::stored_zip(&[("hello.txt", b"hello")]);
+    let with_comment = |comment: &[u8], trailing: &[u8]| {
+        let mut bytes = zip.clone();
+        let len = bytes.len();
+        bytes[len - 2..].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(comment);
+        bytes.extend_from_slice(trailing);
+        bytes
+    };
+    let check = |bytes: &[u8], comment: &[u8]| {
+        let archive = read_archive(bytes);
+        assert_eq!(archive.raw_comment(), comment);
+        assert_eq!(archive.entries().count(), 1);
+        assert!(archive.by_name("hello.txt").is_some());
+    };
+
+    // the longest possible comment, with or without data after it
+    let comment = vec![b'x'; u16::MAX as usize];
+    check(&with_comment(&comment, &[]), &comment);
+    check(&with_comment(&comment, &[0xff; 4000]), &comment);
+
+    // an empty archive's record in the comment, that also reaches the end
+    let mut comment = Vec::new();
+    EndOfCentralDirectoryRecord {
+        disk_nbr: 0,
+        dir_disk_nbr: 0,
+        dir_records_this_disk: 0,
+        directory_records: 0,
+        directory_size: 0,
+        directory_offset: 0,
+        comment: Default::default(),
+    }
+    .write_to(&mut comment)
+    .unwrap();
+    check(&with_comment(&comment, &[]), &comment);
+
+    // the same record after the archive, without a comment
+    check(&with_comment(&[], &comment), &[]);
+
+    // a stored archive as the last entry, whose record is just as
+    // consistent, but further from the end
+    let inner = corpus::stored_zip(&[("a.txt", b"a")]);
+    let mut bytes = corpus::stored_zip(&[("hello.txt", b"hello"), ("inner.zip", &inner)]);
+    bytes.extend_from_slice(&[0; 4]);
+    let archive = read_archive(&bytes);
+    let names: Vec<_> = archive.entries().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["hello.txt", "inner.zip"]);
+}
+
+#[test]
+fn skip_invalid_entries() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = corpus::stored_zip(&[("a.txt", b"a"), ("broken.txt", b"b"), ("c.txt", b"c")]);
+    // the last 3 bytes of the second record's name become its extra field,
+    // which is too short to even hold a tag and a size.
+    let eocd_offset = bytes.len() - 22;
+    let directory_offset =
+        u32::from_le_bytes(bytes[eocd_offset + 16..][..4].try_into().unwrap()) as usize;
+    let record = directory_offset + 46 + "a.txt".len();
+    bytes[record + 28..][..2].copy_from_slice(&("broken.txt".len() as u16 - 3).to_le_bytes());
+    bytes[record + 30..][..2].copy_from_slice(&3u16.to_le_bytes());
+
+    assert!(try_read_archive_with_options(&bytes, Default::default()).is_err());
+
+    let options = ArchiveReadOptions::default().with_skip_invalid_entries(true);
+    let archive = read_archive_with_options(&bytes, options);
+    let names: Vec<_> = archive.entries().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["a.txt", "c.txt"]);
+
+    let results: Vec<_> = archive.entries_with_errors().collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].unwrap().name, "a.txt");
+    let Err(err) = results[1] else {
+        panic!("second record should be invalid")
+    };
+    assert_eq!(err.index, 1);
+    assert_eq!(err.offset, record as u64);
+    assert_eq!(err.name, "broken.");
+    assert!(matches!(
+        err.error,
+        Error::Format(FormatError::InvalidExtraField)
+    ));
+    assert_eq!(results[2].unwrap().name, "c.txt");
+}
+
+#[test]
+fn permissive_strictness() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = corpus::stored_zip(&[("a.txt", b"a"), ("broken.txt", b"b"), ("c.txt", b"c")]);
+    let eocd_offset = bytes.len() - 22;
+    let directory_offset =
+        u32::from_le_bytes(bytes[eocd_offset + 16..][..4].try_into().unwrap()) as usize;
+    // same invalid extra field as in skip_invalid_entries
+    let record = directory_offset + 46 + "a.txt".len();
+    bytes[record + 28..][..2].copy_from_slice(&("broken.txt".len() as u16 - 3).to_le_bytes());
+    bytes[record + 30..][..2].copy_from_slice(&3u16.to_le_bytes());
+    // no CRC-32 for the first entry
+    bytes[directory_offset + 16..][..4].copy_from_slice(&[0; 4]);
+
+    assert!(try_read_archive_with_options(&bytes, Default::default()).is_err());
+
+    let options = ArchiveReadOptions::default().with_strictness(Strictness::Permissive);
+    let archive = read_archive_with_options(&bytes, options.clone());
+    let names: Vec<_> = archive.entries().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["a.txt", "broken.", "c.txt"]);
+    assert_eq!(
+        archive.warnings(),
+        [
+            Warning::MissingChecksum {
+                name: "a.txt".into()
+            },
+            Warning::InvalidExtraField {
+                name: "broken.".into()
+            },
+        ]
+    );
+
+    // the end of central directory record claims one more entry than
+    // there is
+    for field in [8, 10] {
+        bytes[eocd_offset + field..][..2].copy_from_slice(&4u16.to_le_bytes());
+    }
+    assert!(matches!(
+        try_read_archive_with_options(&bytes, Default::default()),
+        Err(Error::Format(FormatError::InvalidCentralRecord { .. }))
+    ));
+    let archive = read_archive_with_options(&bytes, options);
+    assert_eq!(archive.entries().count(), 3);
+    assert_eq!(
+        archive.warnings()[0],
+        Warning::WrongEntryCount {
+            expected: 4,
+            actual: 3
+        }
+    );
+}
+
+#[test]
+fn zip64_masked_offsets() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = corpus::stored_zip_with_zip64_offsets(&[("a.txt", b"a"), ("b.txt", b"b")]);
+    let archive = read_archive(&bytes);
+    let offsets: Vec<_> = archive.entries().map(|e| e.header_offset).collect();
+    assert_eq!(offsets, [0, 30 + "a.txt".len() as u64 + 1]);
+    assert_eq!(archive.by_name("b.txt").unwrap().uncompressed_size, 1);
+
+    // without its zip64 extra field, the second entry's offset is unknown
+    let eocd_offset = bytes.len() - 22;
+    let directory_offset =
+        u32::from_le_bytes(bytes[eocd_offset + 16..][..4].try_into().unwrap()) as usize;
+    let record = directory_offset + 46 + "a.txt".len() + 12;
+    let extra = record + 46 + "b.txt".len();
+    assert_eq!(bytes[extra..][..2], [1, 0]);
+    bytes[extra..][..2].copy_from_slice(&0xcafe_u16.to_le_bytes());
+    assert!(matches!(
+        try_read_archive_with_options(&bytes, Default::default()),
+        Err(Error::Format(FormatError::InvalidExtraField))
+    ));
+
+    let options = ArchiveReadOptions::default().with_strictness(Strictness::Permissive);
+    let archive = read_archive_with_options(&bytes, options);
+    assert!(archive.warnings().contains(&Warning::InvalidExtraField {
+        name: "b.txt".into()
+    }));
+}
+
+#[test]
+fn central_directory_limits() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = corpus::stored_zip(&[("a.txt", b"a"), ("b.txt", b"b"), ("c.txt", b"c")]);
+    let directory_size = 3 * (46 + "a.txt".len()) as u64;
+
+    let read = |bytes: &[u8], max_entries, max_central_directory_size| {
+        let mut options = ArchiveReadOptions::default().with_strictness(Strictness::Permissive);
+        options.max_entries = max_entries;
+        options.max_central_directory_size = max_central_directory_size;
+        try_read_archive_with_options(bytes, options).map(|archive| archive.entries().count())
+    };
+    assert_eq!(read(&bytes, Some(3), Some(directory_size)).unwrap(), 3);
+    assert!(matches!(
+        read(&bytes, Some(2), None),
+        Err(Error::LimitExceeded(LimitExceeded::EntryCount { limit: 2 }))
+    ));
+    assert!(matches!(
+        read(&bytes, None, Some(directory_size - 1)),
+        Err(Error::LimitExceeded(
+            LimitExceeded::CentralDirectorySize { .. }
+        ))
+    ));
+
+    // the end of central directory record can't be trusted to enforce them
+    let eocd_offset = bytes.len() - 22;
+    for field in [8, 10] {
+        bytes[eocd_offset + field..][..2].copy_from_slice(&1u16.to_le_bytes());
+    }
+    assert!(matches!(
+        read(&bytes, Some(2), None),
+        Err(Error::LimitExceeded(LimitExceeded::EntryCount { limit: 2 }))
+    ));
+}
+
+#[test]
+fn method_details() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let case = cases.iter().find(|x| x.name == "test.zip").unwrap();
+    for entry in read_archive(&case.bytes()).entries() {
+        match entry.method {
+            Method::Deflate => assert!(matches!(entry.method_details, MethodDetails::Deflate(_))),
+            _ => assert_eq!(entry.method_details, MethodDetails::None),
+        }
+    }
+
+    // local file headers, with no name, that only differ in flags, method
+    // and extra field.
+    let local_header = |flags: u16, method: u16, extra: &[u8]| {
+        let mut header = b"PK\x03\x04\x14\x00".to_vec();
+        header.extend_from_slice(&flags.to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&[0u8; 16]); // timestamp, crc32, sizes
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        header.extend_from_slice(extra);
+        header
+    };
+    let details = |header: Vec<u8>| {
+        let header = LocalFileHeader::parser(&mut Partial::new(&header[..])).unwrap();
+        header.as_entry().unwrap().method_details
+    };
+
+    assert_eq!(
+        details(local_header(0b110, 8, &[])),
+        MethodDetails::Deflate(DeflateOption::SuperFast)
+    );
+    assert_eq!(
+        details(local_header(0b010, 6, &[])),
+        MethodDetails::Implode {
+            dictionary_size: 8192,
+            trees: 2
+        }
+    );
+
+    let mut lzma = local_header(0b010, 14, &[]);
+    lzma.extend_from_slice(&[2, 0, 5, 0]);
+    let MethodDetails::Lzma {
+        eos_marker,
+        properties: Some(properties),
+    } = details(lzma)
+    else {
+        panic!("expected LZMA properties")
+    };
+    assert!(eos_marker);
+    assert_eq!((properties.major, properties.minor), (2, 0));
+
+    // AE-2, 256-bit, deflated
+    let aes = [0x01, 0x99, 7, 0, 2, 0, b'A', b'E', 3, 8, 0];
+    assert_eq!(
+        details(local_header(0b001, 99, &aes)),
+        MethodDetails::Aes {
+            version: 2,
+            strength: AesStrength::Aes256,
+            method: Method::Deflate,
+        }
+    );
+    assert_eq!(AesStrength::Aes256.key_bits(), Some(256));
+}
+
+#[test]
+fn extra_fields() {
+    corpus::install_test_subscriber();
+
+    let mut extra = Vec::new();
+    // Info-ZIP New Unix: version 1, 4-byte uid 1000, 4-byte gid 100
+    extra.extend_from_slice(&[0x75, 0x78, 11, 0, 1, 4]);
+    extra.extend_from_slice(&1000u32.to_le_bytes());
+    extra.push(4);
+    extra.extend_from_slice(&100u32.to_le_bytes());
+    // something this crate doesn't know about
+    extra.extend_from_slice(&[0xfe, 0xca, 3, 0, 1, 2, 3]);
+
+    let header = |extra: &[u8]| {
+        LocalFileHeader {
+            reader_version: Version {
+                host_system: HostSystem::Unix,
+                version: 20,
+            },
+            flags: 0,
+            method: Method::Store,
+            modified: MsdosTimestamp { time: 0, date: 33 },
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            name: b"a.txt"[..].into(),
+            extra: extra.to_vec().into(),
+            method_specific: MethodSpecific::None,
+        }
+        .as_entry()
+        .unwrap()
+    };
+
+    let entry = header(&extra);
+    assert_eq!(entry.raw_extra, extra);
+    let fields: Vec<_> = entry.extra_fields().map(Result::unwrap).collect();
+    assert_eq!(fields.len(), 2);
+    assert!(matches!(
+        &fields[0],
+        ExtraField::NewUnix(field) if (field.uid, field.gid) == (1000, 100)
+    ));
+    assert!(matches!(
+        &fields[1],
+        ExtraField::Unknown { tag: 0xcafe, data } if data[..] == [1, 2, 3]
+    ));
+
+    // a field whose length goes past the end stops the iteration
+    let mut truncated = extra;
+    truncated.extend_from_slice(&[0xfe, 0xca, 9, 0, 1]);
+    let mut fields = ExtraFields::new(&truncated, Default::default());
+    assert!(fields.next().unwrap().is_ok());
+    assert!(fields.next().unwrap().is_ok());
+    assert!(matches!(
+        fields.next(),
+        Some(Err(Error::Format(FormatError::InvalidExtraField)))
+    ));
+    assert!(fields.next().is_none());
+}
+
+#[test]
+fn strong_encryption() {
+    corpus::install_test_subscriber();
+
+    // a central directory that's all noise, behind a zip64 end of central
+    // directory record that's either version 2, meaning the directory is
+    // encrypted, or version 1, meaning it's just invalid.
+    let encrypted_directory = |reader_version: u16| {
+        let directory = [0x5a; 64];
+        let mut bytes = directory.to_vec();
+        EndOfCentralDirectory64Record {
+            record_size: 44,
+            creator_version: reader_version,
+            reader_version,
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: 1,
+            directory_records: 1,
+            directory_size: directory.len() as u64,
+            directory_offset: 0,
+        }
+        .write_to(&mut bytes)
+        .unwrap();
+        EndOfCentralDirectory64Locator {
+            dir_disk_number: 0,
+            directory_offset: directory.len() as u64,
+            total_disks: 1,
+        }
+        .write_to(&mut bytes)
+        .unwrap();
+        EndOfCentralDirectoryRecord {
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: u16::MAX,
+            directory_records: u16::MAX,
+            directory_size: u32::MAX,
+            directory_offset: u32::MAX,
+            comment: Default::default(),
+        }
+        .write_to(&mut bytes)
+        .unwrap();
+        try_read_archive_with_options(&bytes, Default::default())
+    };
+    assert!(matches!(
+        encrypted_directory(62),
+        Err(Error::Unsupported(UnsupportedError::StrongEncryption {
+            name: None
+        }))
+    ));
+    assert!(matches!(
+        encrypted_directory(45),
+        Err(Error::Format(FormatError::InvalidCentralRecord { .. }))
+    ));
+
+    // an entry with bits 0 and 6 set, in both headers
+    let mut bytes = corpus::stored_zip(&[("secret.txt", b"hunter2")]);
+    bytes[6] |= 0x41;
+    let record_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[record_offset + 8] |= 0x41;
+
+    let archive = read_archive(&bytes);
+    let entry = archive.by_name("secret.txt").unwrap();
+    assert!(entry.is_encrypted() && entry.is_strongly_encrypted());
+
+    let mut fsm = EntryFsm::new(Some(entry.clone()), None);
+    let n = cmp::min(bytes.len(), fsm.space().len());
+    fsm.space()[..n].copy_from_slice(&bytes[..n]);
+    fsm.fill(n);
+    let res = fsm.process(&mut [0u8; 64]);
+    assert!(matches!(
+        res,
+        Err(Error::Unsupported(UnsupportedError::StrongEncryption { name: Some(name) }))
+            if name == "secret.txt"
+    ));
+}
+
+#[test]
+fn timestamp_precedence() {
+    let entry = |extra: &[&[u8]]| {
+        LocalFileHeader {
+            reader_version: Version {
+                host_system: HostSystem::Unix,
+                version: 20,
+            },
+            flags: 0,
+            method: Method::Store,
+            modified: MsdosTimestamp { time: 0, date: 0 },
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            name: b"file"[..].into(),
+            extra: extra.concat().into(),
+            method_specific: MethodSpecific::None,
+        }
+        .as_entry()
+        .unwrap()
+    };
+    let secs = |secs: i64| Utc.timestamp_opt(secs, 0).unwrap();
+
+    // 0x000d: atime, mtime, uid, gid
+    let unix: &[u8] = &[
+        0x0d, 0x00, 12, 0, //
+        10, 0, 0, 0, 11, 0, 0, 0, 0xe8, 0x03, 0xe8, 0x03,
+    ];
+    // 0x5455 with flags for mtime, atime and ctime
+    let timestamp: &[u8] = &[
+        0x55, 0x54, 13, 0, //
+        0b111, 20, 0, 0, 0, 21, 0, 0, 0, 22, 0, 0, 0,
+    ];
+    // 0x5455 with only mtime
+    let timestamp_mtime: &[u8] = &[0x55, 0x54, 5, 0, 0b1, 30, 0, 0, 0];
+    // 0x000a with a single attribute: mtime, atime, ctime, at 1s past the
+    // Unix epoch, 5µs apart
+    let mut ntfs = vec![0x0a, 0x00, 32, 0, 0, 0, 0, 0, 0x01, 0x00, 24, 0];
+    for i in 0..3u64 {
+        ntfs.extend_from_slice(&(116_444_736_010_000_000 + i * 50).to_le_bytes());
+    }
+
+    let e = entry(&[]);
+    assert_eq!(e.timestamp_source, TimestampSource::Msdos);
+
+    let e = entry(&[unix]);
+    assert_eq!((e.modified, e.accessed), (secs(11), Some(secs(10))));
+    assert_eq!(e.timestamp_source, TimestampSource::Unix);
+    assert_eq!((e.uid, e.gid), (Some(1000), Some(1000)));
+
+    for extra in [[unix, timestamp], [timestamp, unix]] {
+        let e = entry(&extra);
+        assert_eq!(e.modified, secs(20));
+        assert_eq!((e.accessed, e.created), (Some(secs(21)), Some(secs(22))));
+        assert_eq!(e.timestamp_source, TimestampSource::ExtendedTimestamp);
+    }
+
+    // timestamps missing from the more precise field come from the others
+    let e = entry(&[timestamp_mtime, unix]);
+    assert_eq!((e.modified, e.accessed), (secs(30), Some(secs(10))));
+
+    for extra in [[&ntfs[..], timestamp], [timestamp, &ntfs[..]]] {
+        let e = entry(&extra);
+        assert_eq!(e.modified, secs(1));
+        assert_eq!(e.accessed, Some(secs(1) + Duration::microseconds(5)));
+        assert_eq!(e.created, Some(secs(1) + Duration::microseconds(10)));
+        assert_eq!(e.timestamp_source, TimestampSource::Ntfs);
+    }
+}
+
+#[test]
+fn file_types() {
+    let cases = [
+        (UnixMode::IFREG | UnixMode(0o644), FileType::File, 0o644),
+        (
+            UnixMode::IFDIR | UnixMode(0o755),
+            FileType::Directory,
+            0o755,
+        ),
+        (UnixMode::IFLNK | UnixMode(0o777), FileType::Symlink, 0o777),
+        (
+            UnixMode::IFIFO | UnixMode(0o600),
+            FileType::NamedPipe,
+            0o600,
+        ),
+        (UnixMode::IFSOCK | UnixMode(0o600), FileType::Socket, 0o600),
+        (
+            UnixMode::IFBLK | UnixMode(0o660),
+            FileType::BlockDevice,
+            0o660,
+        ),
+        (
+            UnixMode::IFCHR | UnixMode(0o660),
+            FileType::CharDevice,
+            0o660,
+        ),
+    ];
+    for (unix, file_type, permissions) in cases {
+        let mode = Mode::from(unix);
+        assert_eq!(mode.file_type(), file_type, "{unix:?}");
+        assert_eq!(mode.permissions(), permissions, "{unix:?}");
+        assert_eq!(UnixMode::from(mode), unix);
+    }
+
+    let mode = Mode::from(MsdosMode(0));
+    assert_eq!(
+        (mode.file_type(), mode.permissions()),
+        (FileType::File, 0o666)
+    );
+    let mode = Mode::from(MsdosMode::READ_ONLY);
+    assert_eq!(
+        (mode.file_type(), mode.permissions()),
+        (FileType::File, 0o444)
+    );
+    let mode = Mode::from(MsdosMode::DIR);
+    assert_eq!(
+        (mode.file_type(), mode.permissions()),
+        (FileType::Directory, 0o777)
+    );
+}
+
+#[test]
+fn msdos_attributes() {
+    let mut bytes = corpus::stored_zip(&[("hidden.txt", b"boo"), ("plain.txt", b"hi")]);
+    // the first central directory record's external attributes
+    let record = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    let attrs =
+        (0o100444 << 16) | (MsdosMode::READ_ONLY | MsdosMode::HIDDEN | MsdosMode::ARCHIVE).0;
+    bytes[record + 38..record + 42].copy_from_slice(&u32::to_le_bytes(attrs));
+
+    let archive = read_archive(&bytes);
+    let hidden = archive.by_name("hidden.txt").unwrap();
+    assert_eq!(hidden.external_attrs, attrs);
+    let msdos = hidden.msdos_mode();
+    assert!(msdos.has(MsdosMode::READ_ONLY));
+    assert!(msdos.has(MsdosMode::HIDDEN));
+    assert!(msdos.has(MsdosMode::ARCHIVE));
+    assert!(!msdos.has(MsdosMode::SYSTEM));
+    assert_eq!(hidden.mode.permissions(), 0o444);
+
+    let plain = archive.by_name("plain.txt").unwrap();
+    assert_eq!(plain.msdos_mode(), MsdosMode(0));
+}
+
+#[test]
+fn state_machines_are_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ArchiveFsm>();
+    assert_send_sync::<EntryFsm>();
+}
+
+#[test]
+fn jar_manifest() {
+    let manifest = Manifest::parse(
+        b"Manifest-Version: 1.0\r\n\
+          Main-Class: com.example.Main\r\n\
+          Multi-Release: true\r\n\
+          Class-Path: lib/a.jar \r\n lib/b.jar\r\n\
+          \r\n\
+          Name: com/example/\r\n\
+          Sealed: true\r\n\
+          \r\n",
+    )
+    .unwrap();
+    let main = &manifest.main_attributes;
+    assert_eq!(main.len(), 4);
+    assert_eq!(main.get("main-class"), Some("com.example.Main"));
+    assert_eq!(main.get("Class-Path"), Some("lib/a.jar lib/b.jar"));
+    assert!(manifest.is_multi_release());
+    assert_eq!(manifest.sections.len(), 1);
+    let section = manifest.section("com/example/").unwrap();
+    assert_eq!(section.attributes.get("Sealed"), Some("true"));
+    assert_eq!(section.attributes.get("Name"), None);
+
+    for (bad, line) in [
+        (&b"Manifest-Version: 1.0\nno colon here\n"[..], 2),
+        (b"Manifest-Version: 1.0\n\nSealed: true\n", 3),
+        (b" continuation\n", 1),
+    ] {
+        match Manifest::parse(bad) {
+            Err(Error::Format(FormatError::InvalidManifest { line: actual })) => {
+                assert_eq!(actual, line)
+            }
+            res => panic!("expected invalid manifest, got {res:?}"),
+        }
+    }
+}
+
+#[test]
+fn jar_multi_release() {
+    assert_eq!(
+        split_versioned_name("META-INF/versions/11/a/B.class"),
+        Some((11, "a/B.class"))
+    );
+    assert_eq!(
+        split_versioned_name("META-INF/versions/+11/a/B.class"),
+        None
+    );
+    assert_eq!(split_versioned_name("a/B.class"), None);
+
+    let bytes = corpus::stored_zip(&[
+        ("META-INF/MANIFEST.MF", b"Multi-Release: true\n"),
+        ("a/B.class", b"base"),
+        ("a/C.class", b"base"),
+        ("META-INF/versions/9/a/B.class", b"9"),
+        ("META-INF/versions/17/a/B.class", b"17"),
+        ("META-INF/versions/11/a/D.class", b"11"),
+    ]);
+    let archive = read_archive(&bytes);
+    assert!(archive.manifest_entry().is_some());
+
+    let resolve = |name: &str, release: u32| {
+        archive
+            .by_name_for_release(name, release)
+            .map(|e| e.name.as_str())
+    };
+    assert_eq!(resolve("a/B.class", 8), Some("a/B.class"));
+    assert_eq!(
+        resolve("a/B.class", 11),
+        Some("META-INF/versions/9/a/B.class")
+    );
+    assert_eq!(
+        resolve("a/B.class", 21),
+        Some("META-INF/versions/17/a/B.class")
+    );
+    assert_eq!(resolve("a/C.class", 21), Some("a/C.class"));
+    assert_eq!(resolve("a/D.class", 9), None);
+
+    let listed: Vec<_> = archive
+        .entries_for_release(11)
+        .into_iter()
+        .map(|(name, entry)| (name, entry.name.as_str()))
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            ("META-INF/MANIFEST.MF", "META-INF/MANIFEST.MF"),
+            ("a/B.class", "META-INF/versions/9/a/B.class"),
+            ("a/C.class", "a/C.class"),
+            ("a/D.class", "META-INF/versions/11/a/D.class"),
+        ]
+    );
+}
+
+#[test]
+fn entry_index() {
+    let bytes = corpus::stored_zip(&[
+        ("docs\\README.md", b"readme"),
+        ("caf\u{e9}.txt", b"nfc"),
+        ("Config.toml", b"first"),
+        ("config.toml", b"second"),
+    ]);
+    let archive = read_archive(&bytes);
+    let index = archive.build_index();
+    assert_eq!(index.len(), 4);
+
+    fn name(entry: Option<&rc_zip::parse::Entry>) -> Option<&str> {
+        entry.map(|e| e.name.as_str())
+    }
+    assert_eq!(name(index.get("docs/README.md")), Some("docs\\README.md"));
+    assert_eq!(name(index.get("docs\\README.md")), Some("docs\\README.md"));
+    assert_eq!(name(index.get("docs/readme.md")), None);
+    assert_eq!(
+        name(index.get_ignore_case("DOCS/readme.md")),
+        Some("docs\\README.md")
+    );
+
+    // decomposed: `e` followed by a combining acute accent
+    assert_eq!(name(index.get("cafe\u{301}.txt")), Some("caf\u{e9}.txt"));
+    assert_eq!(normalize_name("cafe\u{301}.txt"), "caf\u{e9}.txt");
+
+    assert_eq!(name(index.get("config.toml")), Some("config.toml"));
+    assert_eq!(
+        name(index.get_ignore_case("CONFIG.TOML")),
+        Some("Config.toml")
+    );
+    assert_eq!(name(index.get("missing")), None);
+
+    // without an index
+    assert_eq!(
+        name(archive.by_name_ci("./Docs//readme.MD")),
+        Some("docs\\README.md")
+    );
+    assert_eq!(name(archive.by_name_ci("CONFIG.toml")), Some("Config.toml"));
+    assert_eq!(name(archive.by_name_ci("docs")), None);
+
+    assert_eq!(normalize_name("./.\\docs\\\\a.md"), "docs/a.md");
+    assert_eq!(normalize_name("docs/./a.md"), "docs/./a.md");
+    assert_eq!(normalize_name("//docs//"), "/docs/");
+    assert_eq!(normalize_name("./"), "");
+}
+
+#[test]
+fn entry_queries() {
+    let bytes = corpus::stored_zip(&[
+        ("assets/", b""),
+        ("assets/a.png", b""),
+        ("assets/ui/icons/b.png", b""),
+        ("assets/ui/c.txt", b""),
+        ("assets-old/d.png", b""),
+        ("e.png", b""),
+    ]);
+    let archive = read_archive(&bytes);
+    let names = |entries: &mut dyn Iterator<Item = &rc_zip::parse::Entry>| {
+        entries.map(|e| e.name.clone()).collect::<Vec<_>>()
+    };
+
+    let under = ["assets/a.png", "assets/ui/icons/b.png", "assets/ui/c.txt"];
+    assert_eq!(names(&mut archive.entries_under("assets")), under);
+    assert_eq!(names(&mut archive.entries_under("assets/")), under);
+    assert_eq!(
+        names(&mut archive.entries_under("assets/ui")),
+        ["assets/ui/icons/b.png", "assets/ui/c.txt"]
+    );
+    assert_eq!(archive.entries_under("").count(), 6);
+    assert_eq!(archive.entries_under("missing").count(), 0);
+
+    #[cfg(feature = "glob")]
+    {
+        assert_eq!(
+            names(&mut archive.glob("assets/**/*.png").unwrap()),
+            ["assets/a.png", "assets/ui/icons/b.png"]
+        );
+        assert_eq!(names(&mut archive.glob("*.png").unwrap()), ["e.png"]);
+        assert!(archive.glob("assets/[").is_err());
+    }
+}
+
+#[test]
+fn checksum_verification() {
+    use rc_zip::{
+        fsm::{Verification, VerificationStatus},
+        parse::Entry,
+        progress::Progress,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Status(Mutex<Option<VerificationStatus>>);
+
+    impl Progress for Status {
+        fn entry_done(&self, _entry: &Entry, status: VerificationStatus) {
+            *self.0.lock().unwrap() = Some(status);
+        }
+    }
+
+    // reads the only entry, with its CRC-32 replaced in both headers
+    let read = |crc32: Option<u32>, verification| -> Result<VerificationStatus, Error> {
+        let mut bytes = corpus::stored_zip(&[("hello.txt", b"hello")]);
+        if let Some(crc32) = crc32 {
+            let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+            bytes[14..18].copy_from_slice(&crc32.to_le_bytes());
+            bytes[central + 16..central + 20].copy_from_slice(&crc32.to_le_bytes());
+        }
+        let archive = read_archive(&bytes);
+        let entry = archive.by_name("hello.txt").unwrap();
+
+        let status = Arc::new(Status::default());
+        let mut fsm = EntryFsm::new(Some(entry.clone()), None)
+            .with_verification(verification)
+            .with_progress(status.clone());
+        let n = cmp::min(bytes.len(), fsm.space().len());
+        fsm.space()[..n].copy_from_slice(&bytes[..n]);
+        fsm.fill(n);
+        let mut out = [0u8; 64];
+        loop {
+            fsm = match fsm.process(&mut out)? {
+                FsmResult::Continue((fsm, _)) => fsm,
+                FsmResult::Done(_) => break,
+            }
+        }
+        let status = status.0.lock().unwrap().unwrap();
+        Ok(status)
+    };
+    let wrong_checksum = |res: Result<_, Error>| {
+        matches!(res, Err(Error::Format(FormatError::WrongChecksum { .. })))
+    };
+
+    for verification in [Verification::Require, Verification::IfPresent] {
+        assert_eq!(
+            read(None, verification).unwrap(),
+            VerificationStatus::Verified
+        );
+        assert!(wrong_checksum(read(Some(0xdeadbeef), verification)));
+    }
+    assert_eq!(
+        read(Some(0), Verification::IfPresent).unwrap(),
+        VerificationStatus::NoChecksum
+    );
+    assert!(wrong_checksum(read(Some(0), Verification::Require)));
+    assert_eq!(
+        read(Some(0xdeadbeef), Verification::Skip).unwrap(),
+        VerificationStatus::Skipped
+    );
+}
+
+#[test]
+#[cfg(feature = "aes")]
+fn aes_checksum_verification() {
+    use rc_zip::{
+        fsm::{Verification, VerificationStatus},
+        parse::Entry,
+        progress::Progress,
+    };
+    use std::sync::{Arc, Mutex};
+
+    corpus::install_test_subscriber();
+
+    #[derive(Default)]
+    struct Status(Mutex<Option<VerificationStatus>>);
+
+    impl Progress for Status {
+        fn entry_done(&self, _entry: &Entry, status: VerificationStatus) {
+            *self.0.lock().unwrap() = Some(status);
+        }
+    }
+
+    let bytes = std::fs::read(corpus::zips_dir().join("aes.zip")).unwrap();
+    let archive = read_archive(&bytes);
+    let mut ae2_entries = 0;
+    for entry in archive.entries() {
+        let MethodDetails::Aes { version, .. } = entry.method_details else {
+            panic!("{} isn't encrypted", entry.name);
+        };
+        if version == 2 {
+            // its data is authenticated by its HMAC instead
+            assert_eq!(entry.crc32, 0);
+            ae2_entries += 1;
+        }
+
+        let status = Arc::new(Status::default());
+        let mut fsm = EntryFsm::new(Some(entry.clone()), None)
+            .with_password(b"rc-zip")
+            .with_verification(Verification::Require)
+            .with_progress(status.clone());
+        let mut offset = entry.header_offset as usize;
+        let mut out = vec![0u8; 64 * 1024];
+        loop {
+            if fsm.wants_read() {
+                let n = cmp::min(bytes.len() - offset, fsm.space().len());
+                fsm.space()[..n].copy_from_slice(&bytes[offset..offset + n]);
+                fsm.fill(n);
+                offset += n;
+            }
+            fsm = match fsm.process(&mut out).unwrap() {
+                FsmResult::Continue((fsm, _)) => fsm,
+                FsmResult::Done(_) => break,
+            }
+        }
+        let status = status.0.lock().unwrap().unwrap();
+        assert_eq!(status, VerificationStatus::Verified, "{}", entry.name);
+    }
+    assert!(ae2_entries > 0);
+}
+
+#[test]
+fn apple_double() {
+    corpus::install_test_subscriber();
+
+    assert_eq!(
+        apple_double_target("__MACOSX/dir/._file.txt").as_deref(),
+        Some("dir/file.txt")
+    );
+    assert_eq!(
+        apple_double_target("._file.txt").as_deref(),
+        Some("file.txt")
+    );
+    assert_eq!(apple_double_target("dir/file.txt"), None);
+    assert!(is_apple_double("__MACOSX/"));
+    assert!(is_apple_double("__MACOSX/dir/"));
+    assert!(is_apple_double("dir/._file.txt"));
+    assert!(!is_apple_double("dir/file.txt"));
+
+    // Finder info for a TEXT file made by TextEdit, then a resource fork
+    let mut data = vec![0, 5, 0x16, 0x07, 0, 2, 0, 0];
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&2u16.to_be_bytes());
+    for (id, offset, len) in [(9u32, 50u32, 32u32), (2, 82, 3)] {
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(&offset.to_be_bytes());
+        data.extend_from_slice(&len.to_be_bytes());
+    }
+    let mut finder_info = [0u8; 32];
+    finder_info[..10].copy_from_slice(b"TEXTttxt\x40\x00");
+    data.extend_from_slice(&finder_info);
+    data.extend_from_slice(b"res");
+
+    let bytes = corpus::stored_zip(&[
+        ("dir/", b""),
+        ("dir/file.txt", b"hello"),
+        ("__MACOSX/", b""),
+        ("__MACOSX/dir/", b""),
+        ("__MACOSX/dir/._file.txt", &data),
+    ]);
+    let archive = read_archive(&bytes);
+    let names: Vec<_> = archive
+        .entries_without_apple_double()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    assert_eq!(names, ["dir/", "dir/file.txt"]);
+
+    let file = archive.by_name("dir/file.txt").unwrap();
+    let apple_double = archive.apple_double_for(file).unwrap();
+    assert_eq!(apple_double.name, "__MACOSX/dir/._file.txt");
+    assert!(archive
+        .apple_double_for(archive.by_name("dir/").unwrap())
+        .is_none());
+
+    let parsed = AppleDouble::parse(&data).unwrap();
+    assert_eq!(
+        parsed.finder_info,
+        Some(FinderInfo {
+            file_type: *b"TEXT",
+            creator: *b"ttxt",
+            flags: 0x4000,
+        })
+    );
+    assert_eq!(parsed.resource_fork, Some(&b"res"[..]));
+    assert_eq!(parsed.real_name, None);
+
+    assert!(matches!(
+        AppleDouble::parse(&data[..60]),
+        Err(Error::Format(FormatError::InvalidAppleDouble))
+    ));
+}
+
+#[test]
+fn zip_builder() {
+    use corpus::{extra_field, ZipBuilder, ZipEntry};
+
+    corpus::install_test_subscriber();
+
+    // without quirks, it's what the simpler helpers make
+    let plain = ZipBuilder::new()
+        .with_entry(ZipEntry::new("a.txt", "hello"))
+        .with_entry(ZipEntry::new("b.txt", "world"))
+        .build();
+    assert_eq!(
+        plain,
+        corpus::stored_zip(&[("a.txt", b"hello"), ("b.txt", b"world")])
+    );
+
+    let prefix = b"#!/bin/sh\n";
+    let bytes = ZipBuilder::new()
+        .with_prefix(&prefix[..])
+        .with_comment("quirky")
+        .with_zip64_eocd(true)
+        .with_entry(ZipEntry::new("descriptor.txt", "hello").with_data_descriptor(true))
+        .with_entry(
+            ZipEntry::new("zip64.txt", "hello")
+                .with_zip64(true)
+                .with_data_descriptor(true),
+        )
+        .with_entry(ZipEntry::new("extra.txt", "hello").with_extra(extra_field(0xcafe, b"hi")))
+        .with_entry(ZipEntry::new("wrong.txt", "hello").with_wrong_crc32(true))
+        .with_entry(ZipEntry::new("overlap.txt", "").with_overlaps(0))
+        .build();
+    let archive = read_archive(&bytes);
+    assert_eq!(archive.comment(), "quirky");
+    let entry = |name: &str| archive.by_name(name).unwrap();
+
+    let descriptor = entry("descriptor.txt");
+    assert_eq!(descriptor.header_offset, prefix.len() as u64);
+    assert_eq!(descriptor.flags & 0x8, 0x8);
+    assert_eq!(descriptor.crc32, crc32fast::hash(b"hello"));
+
+    let zip64 = entry("zip64.txt");
+    assert_eq!(zip64.reader_version.version, 45);
+    assert_eq!(zip64.uncompressed_size, 5);
+    assert!(matches!(
+        zip64.extra_fields().next(),
+        Some(Ok(ExtraField::Zip64(_)))
+    ));
+
+    assert!(matches!(
+        entry("extra.txt").extra_fields().next(),
+        Some(Ok(ExtraField::Unknown { tag: 0xcafe, data })) if data[..] == b"hi"[..]
+    ));
+    assert_eq!(entry("wrong.txt").crc32, crc32fast::hash(b"hello") ^ 1);
+
+    let overlap = entry("overlap.txt");
+    assert_eq!(overlap.header_offset, descriptor.header_offset);
+    assert_eq!(overlap.uncompressed_size, 5);
+
+    // any bytes make a builder, whose archive reads back
+    let mut state = 0x2545_f491_u32;
+    for len in 0..200 {
+        let input: Vec<u8> = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let builder = ZipBuilder::from_bytes(&input);
+        let archive = read_archive(&builder.build());
+        let names: Vec<_> = archive.entries().map(|entry| &entry.name).collect();
+        let expected: Vec<_> = builder.entries.iter().map(|entry| &entry.name).collect();
+        assert_eq!(names, expected, "{input:?}");
+    }
+}
+
+#[test]
+fn detect_overlaps() {
+    use corpus::{ZipBuilder, ZipEntry};
+
+    corpus::install_test_subscriber();
+
+    let builder = ZipBuilder::new()
+        .with_entry(ZipEntry::new("a.txt", "hello"))
+        .with_entry(ZipEntry::new("b.txt", "world"))
+        .with_entry(ZipEntry::new("c.txt", "!"));
+    assert_eq!(
+        read_archive(&builder.clone().build()).detect_overlaps(),
+        vec![]
+    );
+
+    // every entry pointing at the same local header
+    let bytes = builder
+        .clone()
+        .with_entry(ZipEntry::new("d.txt", "").with_overlaps(1))
+        .with_entry(ZipEntry::new("e.txt", "").with_overlaps(1))
+        .build();
+    assert_eq!(
+        read_archive(&bytes).detect_overlaps(),
+        [3, 4].map(|second| Overlap {
+            first: 1,
+            second,
+            kind: OverlapKind::SharedHeader,
+        })
+    );
+
+    // the first entry's data containing the others
+    let mut bytes = builder.build();
+    let record = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[record + 20..][..4].copy_from_slice(&u32::to_le_bytes(100));
+    assert_eq!(
+        read_archive(&bytes).detect_overlaps(),
+        [1, 2].map(|second| Overlap {
+            first: 0,
+            second,
+            kind: OverlapKind::Nested,
+        })
+    );
+}